@@ -3,12 +3,12 @@ use amethyst_assets::Source;
 use amethyst_error::Error;
 use amethyst_rendy::{
     formats::{mtl::MaterialPrefab, texture::TexturePrefab},
-    palette::{LinSrgba, Srgba},
+    palette::{LinSrgb, LinSrgba, Srgb, Srgba},
     rendy::{
         hal,
         texture::{
             image::{load_from_image, ImageFormat as DataFormat, ImageTextureConfig, Repr},
-            palette::{load_from_linear_rgba, load_from_srgba},
+            palette::load_from_srgba,
             MipLevels, TextureBuilder,
         },
     },
@@ -18,7 +18,48 @@ use amethyst_rendy::{
 use gltf::{self, material::AlphaMode};
 use std::sync::Arc;
 
-// Load a single material, and transform into a format usable by the engine
+/// Scalar properties of a glTF metallic-roughness material, all linear.
+#[derive(Clone, Debug, PartialEq)]
+struct MaterialFactors {
+    base_color: [f32; 4],
+    metallic: f32,
+    roughness: f32,
+    emissive: [f32; 3],
+}
+
+impl MaterialFactors {
+    fn from_material(material: &gltf::Material<'_>) -> Self {
+        let pbr = material.pbr_metallic_roughness();
+        MaterialFactors {
+            base_color: pbr.base_color_factor(),
+            metallic: pbr.metallic_factor(),
+            roughness: pbr.roughness_factor(),
+            emissive: material.emissive_factor(),
+        }
+    }
+
+    /// Set the factors of `prefab`.
+    ///
+    /// Returns whether the material emits light without an emission map, which then needs
+    /// a white map for the emissive factor to color.
+    fn apply(&self, prefab: &mut MaterialPrefab, emission_map: bool) -> bool {
+        let [r, g, b, a] = self.base_color;
+        prefab.base_color = Srgba::from_linear(LinSrgba::new(r, g, b, a));
+        prefab.metallic_factor = Some(self.metallic);
+        prefab.roughness_factor = Some(self.roughness);
+
+        let [r, g, b] = self.emissive;
+        prefab.emission_factor = Srgb::from_linear(LinSrgb::new(r, g, b));
+        !emission_map && r.max(g).max(b) > 0.0
+    }
+}
+
+/// Convert a glTF material into a `MaterialPrefab`.
+///
+/// Maps missing from the file fall back to `MaterialDefaults`, except a missing
+/// metallic-roughness map which is white so the factors apply alone, as glTF specifies.
+/// The factors of the material are kept on the prefab. glTF packs metallic into the blue channel and roughness into the
+/// green channel of a single map, the layout `Material` expects.
 pub fn load_material(
     material: &gltf::Material<'_>,
     buffers: &Buffers,
@@ -28,46 +69,38 @@ pub fn load_material(
     let mut prefab = MaterialPrefab::default();
 
     let pbr = material.pbr_metallic_roughness();
-
-    prefab.albedo = Some(
-        load_texture_with_factor(
-            pbr.base_color_texture(),
-            pbr.base_color_factor(),
-            buffers,
-            source.clone(),
-            name,
-            true,
-        )
-        .map(|(texture, _)| TexturePrefab::Data(texture.into()))?,
-    );
-
-    // metallic from B channel
-    // roughness from G channel
-    let metallic_roughness = load_texture_with_factor(
+    prefab.albedo = load_texture_info(
+        pbr.base_color_texture(),
+        buffers,
+        source.clone(),
+        name,
+        true,
+    )?;
+    prefab.metallic_roughness = load_texture_info(
         pbr.metallic_roughness_texture(),
-        [1.0, pbr.roughness_factor(), pbr.metallic_factor(), 1.0],
         buffers,
         source.clone(),
         name,
         false,
-    )?
-    .0;
-
-    prefab.metallic_roughness = Some(TexturePrefab::Data(metallic_roughness.into()));
-
-    let em_factor = material.emissive_factor();
-    prefab.emission = Some(TexturePrefab::Data(
-        load_texture_with_factor(
-            material.emissive_texture(),
-            [em_factor[0], em_factor[1], em_factor[2], 1.0],
-            buffers,
-            source.clone(),
-            name,
-            true,
-        )?
-        .0
-        .into(),
-    ));
+    )?;
+    if prefab.metallic_roughness.is_none() {
+        // The factors alone describe the surface, which a white map leaves untouched.
+        let white = Srgba::new(1.0, 1.0, 1.0, 1.0);
+        prefab.metallic_roughness = Some(TexturePrefab::Data(load_from_srgba(white).into()));
+    }
+    prefab.emission = load_texture_info(
+        material.emissive_texture(),
+        buffers,
+        source.clone(),
+        name,
+        true,
+    )?;
+
+    let emission_map = prefab.emission.is_some();
+    if MaterialFactors::from_material(material).apply(&mut prefab, emission_map) {
+        let white = Srgba::new(1.0, 1.0, 1.0, 1.0);
+        prefab.emission = Some(TexturePrefab::Data(load_from_srgba(white).into()));
+    }
 
     // Can't use map/and_then because of Result returning from the load_texture function
     prefab.normal = match material.normal_texture() {
@@ -112,32 +145,24 @@ pub fn load_material(
             prefab.alpha_cutoff = 0.0;
         }
     }
+    prefab.double_sided = material.double_sided();
     Ok(prefab)
 }
 
-fn load_texture_with_factor(
+fn load_texture_info(
     texture: Option<gltf::texture::Info<'_>>,
-    factor: [f32; 4],
     buffers: &Buffers,
     source: Arc<dyn Source>,
     name: &str,
     srgb: bool,
-) -> Result<(TextureBuilder<'static>, [f32; 4]), Error> {
-    match texture {
-        Some(info) => Ok((
-            load_texture(&info.texture(), buffers, source, name, srgb)?
-                .with_mip_levels(MipLevels::GenerateAuto),
-            factor,
-        )),
-        None => Ok((
-            if srgb {
-                load_from_srgba(Srgba::new(factor[0], factor[1], factor[2], factor[3]))
-            } else {
-                load_from_linear_rgba(LinSrgba::new(factor[0], factor[1], factor[2], factor[3]))
-            },
-            [1.0, 1.0, 1.0, 1.0],
-        )),
-    }
+) -> Result<Option<TexturePrefab>, Error> {
+    texture
+        .map(|info| {
+            load_texture(&info.texture(), buffers, source, name, srgb).map(|texture| {
+                TexturePrefab::Data(texture.with_mip_levels(MipLevels::GenerateAuto).into())
+            })
+        })
+        .transpose()
 }
 
 fn load_texture(
@@ -202,3 +227,52 @@ fn map_wrapping(gltf_wrap: gltf::texture::WrappingMode) -> hal::image::WrapMode
         gltf::texture::WrappingMode::Repeat => hal::image::WrapMode::Tile,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::MaterialFactors;
+    use amethyst_rendy::formats::mtl::MaterialPrefab;
+
+    const FACTORS: MaterialFactors = MaterialFactors {
+        base_color: [1.0, 1.0, 1.0, 0.5],
+        metallic: 0.25,
+        roughness: 0.75,
+        emissive: [0.5, 0.0, 0.0],
+    };
+
+    #[test]
+    fn test_factors_with_emission_map() {
+        let mut prefab = MaterialPrefab::default();
+        assert!(!FACTORS.apply(&mut prefab, true));
+        assert_eq!(prefab.metallic_factor, Some(0.25));
+        assert_eq!(prefab.roughness_factor, Some(0.75));
+        assert_eq!(prefab.emission_intensity, 1.0);
+        assert_eq!(prefab.base_color.alpha, 0.5);
+
+        // Colored factors tint the map instead of collapsing to a brightness.
+        let colored = MaterialFactors {
+            emissive: [1.0, 0.5, 0.0],
+            ..FACTORS
+        };
+        assert!(!colored.apply(&mut prefab, true));
+        let factor = prefab.emission_factor.into_linear();
+        assert!((factor.red - 1.0).abs() < 1e-4);
+        assert!((factor.green - 0.5).abs() < 1e-4);
+        assert!(factor.blue.abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_factors_without_emission_map() {
+        let mut prefab = MaterialPrefab::default();
+        assert!(FACTORS.apply(&mut prefab, false));
+        let factor = prefab.emission_factor;
+        assert!(factor.red > 0.5 && factor.green == 0.0);
+        assert_eq!(prefab.emission_intensity, 1.0);
+
+        let black = MaterialFactors {
+            emissive: [0.0; 3],
+            ..FACTORS
+        };
+        assert!(!black.apply(&mut prefab, false));
+    }
+}
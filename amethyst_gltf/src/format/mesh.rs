@@ -5,8 +5,9 @@ use amethyst_error::Error;
 use amethyst_rendy::{
     rendy::mesh::{Color, MeshBuilder, Normal, Position, Tangent, TexCoord},
     skinning::JointCombined,
+    types::MeshData,
 };
-use log::trace;
+use log::{trace, warn};
 use std::{iter::repeat, ops::Range};
 
 fn compute_if<T, F: Fn() -> T>(predicate: bool, func: F) -> Option<T> {
@@ -53,7 +54,7 @@ pub fn load_mesh(
     mesh: &gltf::Mesh<'_>,
     buffers: &Buffers,
     options: &GltfSceneOptions,
-) -> Result<Vec<(MeshBuilder<'static>, Option<usize>, Range<[f32; 3]>)>, Error> {
+) -> Result<Vec<(MeshData, Option<usize>, Range<[f32; 3]>)>, Error> {
     trace!("Loading mesh");
     let mut primitives = vec![];
 
@@ -109,9 +110,18 @@ pub fn load_mesh(
 
         let tangents = compute_if(options.load_tangents, || {
             trace!("Loading tangents");
-            let tangents = reader.read_tangents();
+            let tangents = reader
+                .read_tangents()
+                .map(|tangents| tangents.map(Tangent).collect::<Vec<_>>())
+                .filter(|tangents| {
+                    let valid = tangents.iter().all(is_valid_tangent);
+                    if !valid {
+                        warn!("Mesh has zero or invalid tangents, calculating them instead");
+                    }
+                    valid
+                });
             match tangents {
-                Some(tangents) => tangents.map(Tangent).collect::<Vec<_>>(),
+                Some(tangents) => tangents,
                 None => {
                     trace!("Calculating tangents");
                     calculate_tangents(
@@ -169,7 +179,11 @@ pub fn load_mesh(
         let bounds = bounds.min..bounds.max;
         let material = primitive.material().index();
 
-        primitives.push((builder, material, bounds));
+        let data = MeshData {
+            builder,
+            bounds: Some((Vector3::from(bounds.start), Vector3::from(bounds.end))),
+        };
+        primitives.push((data, material, bounds));
     }
     trace!("Loaded mesh");
     Ok(primitives)
@@ -196,6 +210,13 @@ fn calculate_normals(positions: &[Position], indices: &Indices) -> Vec<Normal> {
         .collect::<Vec<_>>()
 }
 
+/// Whether a tangent read from a file can be used to build a normal mapping basis.
+fn is_valid_tangent(tangent: &Tangent) -> bool {
+    let [x, y, z, w] = tangent.0;
+    let length2 = x * x + y * y + z * z;
+    length2.is_finite() && length2 > std::f32::EPSILON && w.is_finite()
+}
+
 fn calculate_tangents(
     positions: &[Position],
     normals: &[Normal],
@@ -220,7 +241,7 @@ fn calculate_tangents(
 
 #[cfg(test)]
 mod tests {
-    use super::{calculate_tangents, Indices};
+    use super::{calculate_tangents, is_valid_tangent, Indices};
     use amethyst_rendy::rendy::mesh::{Normal, Position, Tangent, TexCoord};
 
     const POSITIONS: &[Position] = &[
@@ -264,6 +285,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_tangent_validation() {
+        assert!(is_valid_tangent(&Tangent([1.0, 0.0, 0.0, 1.0])));
+        assert!(is_valid_tangent(&Tangent([0.0, 0.5, 0.0, -1.0])));
+        assert!(!is_valid_tangent(&Tangent([0.0, 0.0, 0.0, 1.0])));
+        assert!(!is_valid_tangent(&Tangent([std::f32::NAN, 0.0, 0.0, 1.0])));
+    }
+
     #[test]
     fn test_indexed_tangent_calc() {
         let tangents = calculate_tangents(
@@ -14,7 +14,9 @@ use amethyst_core::{
 };
 use amethyst_error::Error;
 use amethyst_rendy::{
-    formats::mtl::MaterialPrefab, rendy::mesh::MeshBuilder, types::Mesh, visibility::BoundingSphere,
+    formats::mtl::MaterialPrefab,
+    types::{Mesh, MeshData},
+    visibility::BoundingSphere,
 };
 use derivative::Derivative;
 use serde::{Deserialize, Serialize};
@@ -38,7 +40,7 @@ pub struct GltfPrefab {
     /// certain scenarios (based on the data in the Gltf file)
     pub transform: Option<Transform>,
     /// `MeshData` is placed on all `Entity`s with graphics primitives
-    pub mesh: Option<MeshBuilder<'static>>,
+    pub mesh: Option<MeshData>,
     /// Mesh handle after sub asset loading is done
     pub mesh_handle: Option<Handle<Mesh>>,
     /// `Material` is placed on all `Entity`s with graphics primitives with material
@@ -269,7 +271,7 @@ impl<'a> PrefabData<'a> for GltfPrefab {
         }
         if let Some(mesh) = self.mesh.take() {
             self.mesh_handle =
-                Some(loader.load_from_data(mesh.clone().into(), &mut *progress, meshes_storage));
+                Some(loader.load_from_data(mesh.clone(), &mut *progress, meshes_storage));
             ret = true;
         }
         if let Some(animatable) = &mut self.animatable {
@@ -55,7 +55,7 @@ impl<'a, T: BindingTypes> System<'a> for InputSystem<T> {
                 event,
                 &mut *handler,
                 &mut *output,
-                screen_dimensions.hidpi_factor(),
+                screen_dimensions.window_hidpi_factor(),
             );
         }
     }
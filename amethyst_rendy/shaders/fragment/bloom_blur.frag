@@ -0,0 +1,33 @@
+#version 450
+
+layout(std140, set = 0, binding = 0) uniform BloomArgs {
+    vec2 direction;
+    float threshold;
+    float radius;
+    float intensity;
+};
+
+layout(set = 1, binding = 0) uniform sampler2D bright;
+
+layout(location = 0) in vec2 tex_coord;
+
+layout(location = 0) out vec4 out_color;
+
+const int MAX_RADIUS = 16;
+
+// One direction of a separable gaussian blur, `radius` texels wide.
+void main() {
+    vec2 texel_step = direction / vec2(textureSize(bright, 0));
+    int taps = min(int(ceil(radius)), MAX_RADIUS);
+    float sigma = max(radius * 0.5, 0.0001);
+
+    vec3 result = texture(bright, tex_coord).rgb;
+    float total = 1.0;
+    for (int i = 1; i <= taps; i++) {
+        float weight = exp(-float(i * i) / (2.0 * sigma * sigma));
+        result += texture(bright, tex_coord + texel_step * float(i)).rgb * weight;
+        result += texture(bright, tex_coord - texel_step * float(i)).rgb * weight;
+        total += 2.0 * weight;
+    }
+    out_color = vec4(result / total, 1.0);
+}
@@ -0,0 +1,19 @@
+#version 450
+
+layout(std140, set = 0, binding = 0) uniform BloomArgs {
+    vec2 direction;
+    float threshold;
+    float radius;
+    float intensity;
+};
+
+layout(set = 1, binding = 0) uniform sampler2D bloom;
+
+layout(location = 0) in vec2 tex_coord;
+
+layout(location = 0) out vec4 out_color;
+
+// Added onto the target by the pipeline's blend state.
+void main() {
+    out_color = vec4(texture(bloom, tex_coord).rgb * intensity, 0.0);
+}
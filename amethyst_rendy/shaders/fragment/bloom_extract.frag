@@ -0,0 +1,22 @@
+#version 450
+
+layout(std140, set = 0, binding = 0) uniform BloomArgs {
+    vec2 direction;
+    float threshold;
+    float radius;
+    float intensity;
+};
+
+layout(set = 1, binding = 0) uniform sampler2D hdr_color;
+
+layout(location = 0) in vec2 tex_coord;
+
+layout(location = 0) out vec4 out_color;
+
+// Keep only the part of each pixel brighter than the threshold, keeping its hue.
+void main() {
+    vec3 color = texture(hdr_color, tex_coord).rgb;
+    float brightness = max(color.r, max(color.g, color.b));
+    float contribution = max(brightness - threshold, 0.0) / max(brightness, 0.0001);
+    out_color = vec4(color * contribution, 1.0);
+}
@@ -1,4 +1,5 @@
 #version 450
+#extension GL_GOOGLE_include_directive : require
 
 struct UvOffset {
     vec2 u_offset;
@@ -7,6 +8,9 @@ struct UvOffset {
 
 layout(std140, set = 1, binding = 0) uniform Material {
     UvOffset uv_offset;
+    vec2 uv_scroll;
+    vec2 uv_scale;
+    vec4 base_color;
     float alpha_cutoff;
 };
 
@@ -16,9 +20,16 @@ layout(location = 0) in VertexData {
     vec3 position;
     vec2 tex_coord;
     vec4 color;
+    float fade;
 } vertex;
 
+layout(location = 7) flat in uint vertex_object_id;
+
 layout(location = 0) out vec4 out_color;
+// Only stored when the pass is built with object ids.
+layout(location = 1) out uint out_object_id;
+
+#include "../header/dither.glsl"
 
 float tex_coord(float coord, vec2 offset) {
     return offset.x + coord * (offset.y - offset.x);
@@ -29,7 +40,10 @@ vec2 tex_coords(vec2 coord, vec2 u, vec2 v) {
 }
 
 void main() {
-    vec4 albedo = texture(albedo, tex_coords(vertex.tex_coord, uv_offset.u_offset, uv_offset.v_offset));
+    if(vertex.fade < dither_threshold()) discard;
+
+    vec4 albedo = texture(albedo, tex_coords(vertex.tex_coord * uv_scale + uv_scroll, uv_offset.u_offset, uv_offset.v_offset)) * base_color;
     if(albedo.w < alpha_cutoff) discard;
     out_color = albedo * vertex.color;
+    out_object_id = vertex_object_id;
 }
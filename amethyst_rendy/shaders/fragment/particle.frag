@@ -0,0 +1,36 @@
+#version 450
+
+layout(std140, set = 1, binding = 0) uniform ParticleArgs {
+    mat4 inv_proj;
+    vec2 framebuffer_size;
+    float fade_distance;
+};
+
+layout(set = 2, binding = 0) uniform sampler2D depth;
+
+layout(location = 0) in VertexData {
+    vec4 color;
+    float view_depth;
+} vertex;
+
+layout(location = 0) out vec4 out_color;
+
+// Fade out particles close to the scene behind them instead of clipping them.
+float soft_fade() {
+    if (fade_distance <= 0.0) {
+        return 1.0;
+    }
+    vec2 uv = gl_FragCoord.xy / framebuffer_size;
+    vec4 ndc = vec4(uv * 2.0 - 1.0, texture(depth, uv).r, 1.0);
+    vec4 scene = inv_proj * ndc;
+    float scene_depth = -scene.z / scene.w;
+    return clamp((scene_depth - vertex.view_depth) / fade_distance, 0.0, 1.0);
+}
+
+void main() {
+    float alpha = vertex.color.a * soft_fade();
+    if (alpha <= 0.0) {
+        discard;
+    }
+    out_color = vec4(vertex.color.rgb, alpha);
+}
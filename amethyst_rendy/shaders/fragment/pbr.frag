@@ -1,4 +1,11 @@
 #version 450
+#extension GL_GOOGLE_include_directive : require
+
+// Light array sizes, specialized to the limits of the environment at pipeline creation.
+layout(constant_id = 0) const int MAX_POINT_LIGHTS = 128;
+layout(constant_id = 1) const int MAX_DIR_LIGHTS = 16;
+layout(constant_id = 2) const int MAX_SPOT_LIGHTS = 128;
+layout(constant_id = 3) const int MAX_AREA_LIGHTS = 16;
 
 // layout(early_fragment_tests) in;
 
@@ -6,6 +13,8 @@ struct PointLight {
     vec3 position;
     vec3 color;
     float intensity;
+    float radius;
+    float smoothness;
 };
 
 struct DirectionalLight {
@@ -14,14 +23,22 @@ struct DirectionalLight {
     vec3 direction;
 };
 
+struct AreaLight {
+    vec3 position;
+    vec3 color;
+    vec3 half_width;
+    float intensity;
+    vec3 half_height;
+};
+
 struct SpotLight {
     vec3 position;
     vec3 color;
     vec3 direction;
     float angle;
+    float inner_angle;
     float intensity;
     float range;
-    float smoothness;
 };
 
 layout(std140, set = 0, binding = 1) uniform Environment {
@@ -30,20 +47,53 @@ layout(std140, set = 0, binding = 1) uniform Environment {
     int point_light_count;
     int directional_light_count;
     int spot_light_count;
+    int area_light_count;
+    vec3 fog_color;
+    float fog_start;
+    float fog_density;
+    // Grid of light tiles, no columns when every point light lights every fragment.
+    int light_tile_columns;
+    int light_tile_rows;
+    int lights_per_tile;
+    // Scale of the lit color, applied before tonemapping.
+    float exposure;
 };
 
 layout(std140, set = 0, binding = 2) uniform PointLights {
-    PointLight plight[128];
+    PointLight plight[MAX_POINT_LIGHTS];
 };
 
 layout(std140, set = 0, binding = 3) uniform DirectionalLights {
-    DirectionalLight dlight[16];
+    DirectionalLight dlight[MAX_DIR_LIGHTS];
 };
 
 layout(std140, set = 0, binding = 4) uniform SpotLights {
-    SpotLight slight[128];
+    SpotLight slight[MAX_SPOT_LIGHTS];
 };
 
+layout(std140, set = 0, binding = 10) uniform AreaLights {
+    AreaLight alight[MAX_AREA_LIGHTS];
+};
+
+// For every tile, the number of point lights reaching it followed by their indices.
+layout(std430, set = 0, binding = 9) readonly buffer LightTiles {
+    uint light_tiles[];
+};
+
+layout(std140, set = 0, binding = 5) uniform Shadow {
+    mat4 light_view_proj[4];
+    vec4 cascade_splits;
+    vec3 camera_forward;
+    float shadow_bias;
+    int cascade_count;
+    int shadow_enabled;
+};
+
+// Image based lighting, black textures when there is no environment map.
+layout(set = 0, binding = 6) uniform samplerCube irradiance_map;
+layout(set = 0, binding = 7) uniform samplerCube specular_map;
+layout(set = 0, binding = 8) uniform sampler2D brdf_lut;
+
 struct UvOffset {
     vec2 u_offset;
     vec2 v_offset;
@@ -51,7 +101,15 @@ struct UvOffset {
 
 layout(std140, set = 1, binding = 0) uniform Material {
     UvOffset uv_offset;
+    vec2 uv_scroll;
+    vec2 uv_scale;
+    vec4 base_color;
     float alpha_cutoff;
+    vec3 emission_factor;
+    float emission_intensity;
+    float metallic_factor;
+    float roughness_factor;
+    bool double_sided;
 };
 
 layout(set = 1, binding = 1) uniform sampler2D albedo;
@@ -61,6 +119,12 @@ layout(set = 1, binding = 4) uniform sampler2D metallic_roughness;
 layout(set = 1, binding = 5) uniform sampler2D ambient_occlusion;
 layout(set = 1, binding = 6) uniform sampler2D cavity;
 
+// Screen space ambient occlusion, a single white texel when disabled.
+layout(set = 3, binding = 0) uniform sampler2D screen_ambient_occlusion;
+
+// Depth seen by the primary directional light, a single white texel when disabled.
+layout(set = 4, binding = 0) uniform sampler2D shadow_map;
+
 layout(location = 0) in VertexData {
     vec3 position;
     vec3 normal;
@@ -68,9 +132,16 @@ layout(location = 0) in VertexData {
     float tang_handedness;
     vec2 tex_coord;
     vec4 color;
+    float fade;
 } vertex;
 
+layout(location = 7) flat in uint vertex_object_id;
+
 layout(location = 0) out vec4 out_color;
+// Only stored when the pass is built with object ids.
+layout(location = 1) out uint out_object_id;
+
+#include "../header/dither.glsl"
 
 const float PI = 3.14159265359;
 
@@ -107,6 +178,32 @@ vec3 fresnel(float HdotV, vec3 fresnel_base) {
     return fresnel_base + (1.0 - fresnel_base) * pow(1.0 - HdotV, 5.0);
 }
 
+vec3 fresnel_roughness(float NdotV, vec3 fresnel_base, float roughness) {
+    return fresnel_base + (max(vec3(1.0 - roughness), fresnel_base) - fresnel_base) * pow(1.0 - NdotV, 5.0);
+}
+
+// Split sum approximation of the light reflected from the environment map.
+vec3 image_based_light(vec3 view_direction,
+                       vec3 albedo,
+                       vec3 normal,
+                       float roughness,
+                       float metallic,
+                       vec3 fresnel_base) {
+    float NdotV = max(dot(normal, view_direction), 0.0);
+    vec3 fresnel = fresnel_roughness(NdotV, fresnel_base, roughness);
+
+    vec3 diffuse = (vec3(1.0) - fresnel) * (1.0 - metallic) * albedo
+        * texture(irradiance_map, normal).rgb;
+
+    vec3 reflection = reflect(-view_direction, normal);
+    float max_lod = float(textureQueryLevels(specular_map) - 1);
+    vec3 prefiltered = textureLod(specular_map, reflection, roughness * max_lod).rgb;
+    vec2 brdf = texture(brdf_lut, vec2(NdotV, roughness)).rg;
+    vec3 specular = prefiltered * (fresnel * brdf.x + brdf.y);
+
+    return diffuse + specular;
+}
+
 vec3 compute_light(vec3 attenuation,
                    vec3 light_color,
                    vec3 view_direction,
@@ -138,21 +235,106 @@ vec3 compute_light(vec3 attenuation,
     return resulting_light;
 }
 
+// Side in pixels of the square light tiles, matching `LIGHT_TILE_SIZE`.
+const int LIGHT_TILE_SIZE = 16;
+
+// Offset of the light list of the fragment's tile, -1 when point lights are not tiled.
+int light_tile() {
+    if (light_tile_columns <= 0) {
+        return -1;
+    }
+    ivec2 tile = min(ivec2(gl_FragCoord.xy) / LIGHT_TILE_SIZE,
+                     ivec2(light_tile_columns, light_tile_rows) - 1);
+    return (tile.y * light_tile_columns + tile.x) * (lights_per_tile + 1);
+}
+
+// Number of point lights to iterate over for the fragment.
+int point_light_iterations(int tile) {
+    return tile < 0 ? point_light_count : int(light_tiles[tile]);
+}
+
+// Index of the `n`th point light iterated over for the fragment.
+int point_light_index(int tile, int n) {
+    return tile < 0 ? n : int(light_tiles[tile + 1 + n]);
+}
+
+// Frostbite style window reaching zero at `radius`, disabled for non-positive radii.
+float range_falloff(float distance, float radius, float smoothness) {
+    if (radius <= 0.0) {
+        return 1.0;
+    }
+    float window = clamp(1.0 - pow(distance / radius, max(smoothness, 0.001)), 0.0, 1.0);
+    return window * window;
+}
+
+// Visibility of the fragment from the primary directional light, 1.0 when not shadowed.
+float primary_shadow(vec3 position) {
+    if (shadow_enabled == 0) {
+        return 1.0;
+    }
+    // Cascades are picked by view depth and laid out side by side in the shadow map.
+    float depth = dot(position - camera_position, camera_forward);
+    int cascade = 0;
+    for (int i = 0; i < cascade_count - 1; i++) {
+        if (depth > cascade_splits[i]) {
+            cascade = i + 1;
+        }
+    }
+    vec4 light_space = light_view_proj[cascade] * vec4(position, 1.0);
+    vec3 coord = light_space.xyz / light_space.w;
+    vec2 uv = coord.xy * 0.5 + 0.5;
+    if (any(lessThan(uv, vec2(0.0))) || any(greaterThan(uv, vec2(1.0))) || coord.z > 1.0) {
+        return 1.0;
+    }
+    uv.x = (float(cascade) + uv.x) / float(cascade_count);
+    return coord.z - shadow_bias > texture(shadow_map, uv).r ? 0.0 : 1.0;
+}
+
+// Point of the rectangle of an area light closest to `position`.
+vec3 area_light_point(int i, vec3 position) {
+    vec3 offset = position - alight[i].position;
+    float half_width = length(alight[i].half_width);
+    float half_height = length(alight[i].half_height);
+    vec3 right = alight[i].half_width / max(half_width, 1e-6);
+    vec3 up = alight[i].half_height / max(half_height, 1e-6);
+    return alight[i].position
+        + right * clamp(dot(offset, right), -half_width, half_width)
+        + up * clamp(dot(offset, up), -half_height, half_height);
+}
+
+// Intensity of an area light reaching a fragment `light_vector` away from its closest point,
+// fading out as the rectangle is seen at a grazing angle and dark behind it.
+float area_light_attenuation(int i, vec3 light_vector) {
+    vec3 facing = normalize(cross(alight[i].half_height, alight[i].half_width));
+    float distance2 = max(dot(light_vector, light_vector), 1e-4);
+    float emission = max(dot(facing, -normalize(light_vector)), 0.0);
+    return alight[i].intensity * emission / distance2;
+}
+
+// Exponential distance fog, starting `fog_start` units away from the camera.
+vec3 apply_fog(vec3 color, vec3 position) {
+    float distance = max(length(camera_position - position) - fog_start, 0.0);
+    float fog = 1.0 - exp(-fog_density * distance);
+    return mix(color, fog_color, fog);
+}
+
 void main() {
-    vec2 final_tex_coords   = tex_coords(vertex.tex_coord, uv_offset.u_offset, uv_offset.v_offset);
-    vec4 albedo_alpha       = texture(albedo, final_tex_coords);
+    if(vertex.fade < dither_threshold()) discard;
+
+    vec2 final_tex_coords   = tex_coords(vertex.tex_coord * uv_scale + uv_scroll, uv_offset.u_offset, uv_offset.v_offset);
+    vec4 albedo_alpha       = texture(albedo, final_tex_coords) * base_color;
     float alpha             = albedo_alpha.a;
     if(alpha < alpha_cutoff) discard;
 
     vec3 albedo             = albedo_alpha.rgb;
-    vec3 emission           = texture(emission, final_tex_coords).rgb;
+    vec3 emission           = texture(emission, final_tex_coords).rgb * emission_factor * emission_intensity;
     vec3 normal             = texture(normal, final_tex_coords).rgb;
     vec2 metallic_roughness = texture(metallic_roughness, final_tex_coords).bg;
     float ambient_occlusion = texture(ambient_occlusion, final_tex_coords).r;
     // TODO: Use cavity
     // float cavity            = texture(cavity, tex_coords(vertex.tex_coord, final_tex_coords).r;
-    float metallic          = metallic_roughness.r;
-    float roughness         = metallic_roughness.g;
+    float metallic          = metallic_roughness.r * metallic_factor;
+    float roughness         = metallic_roughness.g * roughness_factor;
 
     // normal conversion
     normal = normal * 2 - 1;
@@ -161,16 +343,35 @@ void main() {
     vec3 fresnel_base = mix(vec3(0.04), albedo, metallic);
 
     vec3 vertex_normal = normalize(vertex.normal);
-    vec3 vertex_tangent = normalize(vertex.tangent - vertex_normal * dot(vertex_normal, vertex.tangent));
-    vec3 vertex_bitangent = normalize(cross(vertex_normal, vertex_tangent) * vertex.tang_handedness);
-    mat3 vertex_basis = mat3(vertex_tangent, vertex_bitangent, vertex_normal);
-    normal = normalize(vertex_basis * normal);
+    vec3 vertex_tangent = vertex.tangent - vertex_normal * dot(vertex_normal, vertex.tangent);
+    // Without a usable tangent the normal map is ignored, rather than producing NaNs.
+    if (dot(vertex_tangent, vertex_tangent) > 1e-12) {
+        vertex_tangent = normalize(vertex_tangent);
+        vec3 vertex_bitangent = cross(vertex_normal, vertex_tangent) * (vertex.tang_handedness < 0.0 ? -1.0 : 1.0);
+        mat3 vertex_basis = mat3(vertex_tangent, vertex_bitangent, vertex_normal);
+        normal = normalize(vertex_basis * normal);
+    } else {
+        normal = vertex_normal;
+    }
+    // Back faces of double sided materials are lit as if facing the other way.
+    if (double_sided && !gl_FrontFacing) {
+        normal = -normal;
+    }
 
     vec3 view_direction = normalize(camera_position - vertex.position);
     vec3 lighted = vec3(0.0);
-    for (int i = 0; i < point_light_count; i++) {
-        vec3 light_direction = normalize(plight[i].position - vertex.position);
-        float attenuation = plight[i].intensity / dot(light_direction, light_direction);
+    int tile = light_tile();
+    int point_lights = point_light_iterations(tile);
+    for (int n = 0; n < point_lights; n++) {
+        int i = point_light_index(tile, n);
+        if (i >= point_light_count) {
+            continue;
+        }
+        vec3 light_vector = plight[i].position - vertex.position;
+        float distance2 = dot(light_vector, light_vector);
+        vec3 light_direction = normalize(light_vector);
+        float attenuation = plight[i].intensity / distance2
+            * range_falloff(sqrt(distance2), plight[i].radius, plight[i].smoothness);
 
         vec3 light = compute_light(vec3(attenuation),
                                    plight[i].color,
@@ -188,6 +389,9 @@ void main() {
     for (int i = 0; i < directional_light_count; i++) {
         vec3 light_direction = -normalize(dlight[i].direction);
         float attenuation = dlight[i].intensity;
+        if (i == 0) {
+            attenuation *= primary_shadow(vertex.position);
+        }
 
         vec3 light = compute_light(vec3(attenuation),
                                    dlight[i].color,
@@ -220,24 +424,17 @@ void main() {
         // point light instead, so we need to also check for the spot angle and direction.
         float range_attenuation = max(0.0, 1.0 - normalized_range);
 
-        // this is actually the cosine of the angle, so it can be compared with the
-        // "dotted" frag_angle below a lot cheaper.
-        float spot_angle = max(slight[i].angle, 0.00001);
+        // These are actually the cosines of the outer and inner cone angles, so they
+        // can be compared with the "dotted" frag_angle below a lot cheaper.
+        float spot_angle = slight[i].angle;
+        float inner_angle = max(slight[i].inner_angle, spot_angle + 0.00001);
         vec3 spot_direction = normalize(slight[i].direction);
-        float smoothness = 1.0 - slight[i].smoothness;
 
-        // Here we check if the current fragment is within the "ring" of the spotlight.
+        // Here we check if the current fragment is within the cone of the spotlight.
         float frag_angle = dot(spot_direction, -normalized_light_vec);
 
-        // so that the ring_attenuation won't be > 1
-        frag_angle = max(frag_angle, spot_angle);
-
-        // How much is this outside of the ring? (let's call it "rim")
-        // Also smooth this out.
-        float rim_attenuation = pow(max((1.0 - frag_angle) / (1.0 - spot_angle), 0.00001), smoothness);
-
-        // How much is this inside the "ring"?
-        float ring_attenuation = 1.0 - rim_attenuation;
+        // Fully lit inside the inner cone, fading out smoothly towards the outer one.
+        float ring_attenuation = smoothstep(spot_angle, inner_angle, frag_angle);
 
         // combine the attenuations and intensity
         float attenuation = range_attenuation * ring_attenuation * slight[i].intensity;
@@ -254,8 +451,32 @@ void main() {
         lighted += light;
     }
 
-    vec3 ambient = ambient_color * albedo * ambient_occlusion;
+    for (int i = 0; i < area_light_count; i++) {
+        vec3 light_vector = area_light_point(i, vertex.position) - vertex.position;
+        float attenuation = area_light_attenuation(i, light_vector);
+
+        vec3 light = compute_light(vec3(attenuation),
+                                   alight[i].color,
+                                   view_direction,
+                                   normalize(light_vector),
+                                   albedo,
+                                   normal,
+                                   roughness2,
+                                   metallic,
+                                   fresnel_base);
+
+        lighted += light;
+    }
+
+    vec2 screen_coord = gl_FragCoord.xy / vec2(textureSize(screen_ambient_occlusion, 0));
+    float screen_occlusion = texture(screen_ambient_occlusion, screen_coord).r;
+
+    vec3 ambient = ambient_color * albedo;
+    ambient += image_based_light(view_direction, albedo, normal, roughness, metallic, fresnel_base);
+    ambient *= ambient_occlusion * screen_occlusion;
     vec3 color = ambient + lighted + emission;
 
-    out_color = vec4(color, alpha) * vertex.color;
+    color = color * vertex.color.rgb;
+    out_color = vec4(apply_fog(color, vertex.position) * exposure, alpha * vertex.color.a);
+    out_object_id = vertex_object_id;
 }
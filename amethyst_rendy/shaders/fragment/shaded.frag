@@ -1,9 +1,18 @@
 #version 450
+#extension GL_GOOGLE_include_directive : require
+
+// Light array sizes, specialized to the limits of the environment at pipeline creation.
+layout(constant_id = 0) const int MAX_POINT_LIGHTS = 128;
+layout(constant_id = 1) const int MAX_DIR_LIGHTS = 16;
+layout(constant_id = 2) const int MAX_SPOT_LIGHTS = 128;
+layout(constant_id = 3) const int MAX_AREA_LIGHTS = 16;
 
 struct PointLight {
     vec3 position;
     vec3 color;
     float intensity;
+    float radius;
+    float smoothness;
 };
 
 struct DirectionalLight {
@@ -12,19 +21,56 @@ struct DirectionalLight {
     vec3 direction;
 };
 
+struct AreaLight {
+    vec3 position;
+    vec3 color;
+    vec3 half_width;
+    float intensity;
+    vec3 half_height;
+};
+
 layout(set = 0, binding = 1) uniform Environment {
     vec3 ambient_color;
     vec3 camera_position; 
     int point_light_count;
     int directional_light_count;
+    int spot_light_count;
+    int area_light_count;
+    vec3 fog_color;
+    float fog_start;
+    float fog_density;
+    // Grid of light tiles, no columns when every point light lights every fragment.
+    int light_tile_columns;
+    int light_tile_rows;
+    int lights_per_tile;
+    // Scale of the lit color, applied before tonemapping.
+    float exposure;
 };
 
 layout(set = 0, binding = 2) uniform PointLights {
-    PointLight plight[128];
+    PointLight plight[MAX_POINT_LIGHTS];
 };
 
 layout(set = 0, binding = 3) uniform DirectionalLights {
-    DirectionalLight dlight[16];
+    DirectionalLight dlight[MAX_DIR_LIGHTS];
+};
+
+layout(std140, set = 0, binding = 10) uniform AreaLights {
+    AreaLight alight[MAX_AREA_LIGHTS];
+};
+
+// For every tile, the number of point lights reaching it followed by their indices.
+layout(std430, set = 0, binding = 9) readonly buffer LightTiles {
+    uint light_tiles[];
+};
+
+layout(std140, set = 0, binding = 5) uniform Shadow {
+    mat4 light_view_proj[4];
+    vec4 cascade_splits;
+    vec3 camera_forward;
+    float shadow_bias;
+    int cascade_count;
+    int shadow_enabled;
 };
 
 struct UvOffset {
@@ -34,20 +80,41 @@ struct UvOffset {
 
 layout(set = 1, binding = 0) uniform Material {
     UvOffset uv_offset;
+    vec2 uv_scroll;
+    vec2 uv_scale;
+    vec4 base_color;
     float alpha_cutoff;
+    vec3 emission_factor;
+    float emission_intensity;
+    float metallic_factor;
+    float roughness_factor;
+    bool double_sided;
 };
 
 layout(set = 1, binding = 1) uniform sampler2D albedo;
 layout(set = 1, binding = 2) uniform sampler2D emission;
 
+// Screen space ambient occlusion, a single white texel when disabled.
+layout(set = 3, binding = 0) uniform sampler2D screen_ambient_occlusion;
+
+// Depth seen by the primary directional light, a single white texel when disabled.
+layout(set = 4, binding = 0) uniform sampler2D shadow_map;
+
 layout(location = 0) in VertexData {
     vec3 position;
     vec3 normal;
     vec2 tex_coord;
     vec4 color;
+    float fade;
 } vertex;
 
+layout(location = 7) flat in uint vertex_object_id;
+
 layout(location = 0) out vec4 out_color;
+// Only stored when the pass is built with object ids.
+layout(location = 1) out uint out_object_id;
+
+#include "../header/dither.glsl"
 
 float tex_coord(float coord, vec2 offset) {
     return offset.x + coord * (offset.y - offset.x);
@@ -57,18 +124,113 @@ vec2 tex_coords(vec2 coord, vec2 u, vec2 v) {
     return vec2(tex_coord(coord.x, u), tex_coord(coord.y, v));
 }
 
+// Side in pixels of the square light tiles, matching `LIGHT_TILE_SIZE`.
+const int LIGHT_TILE_SIZE = 16;
+
+// Offset of the light list of the fragment's tile, -1 when point lights are not tiled.
+int light_tile() {
+    if (light_tile_columns <= 0) {
+        return -1;
+    }
+    ivec2 tile = min(ivec2(gl_FragCoord.xy) / LIGHT_TILE_SIZE,
+                     ivec2(light_tile_columns, light_tile_rows) - 1);
+    return (tile.y * light_tile_columns + tile.x) * (lights_per_tile + 1);
+}
+
+// Number of point lights to iterate over for the fragment.
+int point_light_iterations(int tile) {
+    return tile < 0 ? point_light_count : int(light_tiles[tile]);
+}
+
+// Index of the `n`th point light iterated over for the fragment.
+int point_light_index(int tile, int n) {
+    return tile < 0 ? n : int(light_tiles[tile + 1 + n]);
+}
+
+// Frostbite style window reaching zero at `radius`, disabled for non-positive radii.
+float range_falloff(float distance, float radius, float smoothness) {
+    if (radius <= 0.0) {
+        return 1.0;
+    }
+    float window = clamp(1.0 - pow(distance / radius, max(smoothness, 0.001)), 0.0, 1.0);
+    return window * window;
+}
+
+// Visibility of the fragment from the primary directional light, 1.0 when not shadowed.
+float primary_shadow(vec3 position) {
+    if (shadow_enabled == 0) {
+        return 1.0;
+    }
+    // Cascades are picked by view depth and laid out side by side in the shadow map.
+    float depth = dot(position - camera_position, camera_forward);
+    int cascade = 0;
+    for (int i = 0; i < cascade_count - 1; i++) {
+        if (depth > cascade_splits[i]) {
+            cascade = i + 1;
+        }
+    }
+    vec4 light_space = light_view_proj[cascade] * vec4(position, 1.0);
+    vec3 coord = light_space.xyz / light_space.w;
+    vec2 uv = coord.xy * 0.5 + 0.5;
+    if (any(lessThan(uv, vec2(0.0))) || any(greaterThan(uv, vec2(1.0))) || coord.z > 1.0) {
+        return 1.0;
+    }
+    uv.x = (float(cascade) + uv.x) / float(cascade_count);
+    return coord.z - shadow_bias > texture(shadow_map, uv).r ? 0.0 : 1.0;
+}
+
+// Point of the rectangle of an area light closest to `position`.
+vec3 area_light_point(int i, vec3 position) {
+    vec3 offset = position - alight[i].position;
+    float half_width = length(alight[i].half_width);
+    float half_height = length(alight[i].half_height);
+    vec3 right = alight[i].half_width / max(half_width, 1e-6);
+    vec3 up = alight[i].half_height / max(half_height, 1e-6);
+    return alight[i].position
+        + right * clamp(dot(offset, right), -half_width, half_width)
+        + up * clamp(dot(offset, up), -half_height, half_height);
+}
+
+// Intensity of an area light reaching a fragment `light_vector` away from its closest point,
+// fading out as the rectangle is seen at a grazing angle and dark behind it.
+float area_light_attenuation(int i, vec3 light_vector) {
+    vec3 facing = normalize(cross(alight[i].half_height, alight[i].half_width));
+    float distance2 = max(dot(light_vector, light_vector), 1e-4);
+    float emission = max(dot(facing, -normalize(light_vector)), 0.0);
+    return alight[i].intensity * emission / distance2;
+}
+
+// Exponential distance fog, starting `fog_start` units away from the camera.
+vec3 apply_fog(vec3 color, vec3 position) {
+    float distance = max(length(camera_position - position) - fog_start, 0.0);
+    float fog = 1.0 - exp(-fog_density * distance);
+    return mix(color, fog_color, fog);
+}
+
 void main() {
-    vec2 final_tex_coords   = tex_coords(vertex.tex_coord, uv_offset.u_offset, uv_offset.v_offset);
-    vec4 albedo_alpha       = texture(albedo, final_tex_coords);
+    if(vertex.fade < dither_threshold()) discard;
+
+    vec2 final_tex_coords   = tex_coords(vertex.tex_coord * uv_scale + uv_scroll, uv_offset.u_offset, uv_offset.v_offset);
+    vec4 albedo_alpha       = texture(albedo, final_tex_coords) * base_color;
     float alpha             = albedo_alpha.a;
     if(alpha < alpha_cutoff) discard;
 
     vec3 albedo = albedo_alpha.rgb;
-    vec3 emission = texture(emission, final_tex_coords).rgb;
+    vec3 emission = texture(emission, final_tex_coords).rgb * emission_factor * emission_intensity;
 
     vec3 lighting = vec3(0.0);
     vec3 normal = normalize(vertex.normal);
-    for (uint i = 0u; i < point_light_count; i++) {
+    // Back faces of double sided materials are lit as if facing the other way.
+    if (double_sided && !gl_FrontFacing) {
+        normal = -normal;
+    }
+    int tile = light_tile();
+    int point_lights = point_light_iterations(tile);
+    for (int n = 0; n < point_lights; n++) {
+        int i = point_light_index(tile, n);
+        if (i >= point_light_count) {
+            continue;
+        }
         // Calculate diffuse light
         vec3 light_dir = normalize(plight[i].position - vertex.position);
         float diff = max(dot(light_dir, normal), 0.0);
@@ -76,15 +238,27 @@ void main() {
         // Calculate attenuation
         vec3 dist = plight[i].position - vertex.position;
         float dist2 = dot(dist, dist);
-        float attenuation = (plight[i].intensity / dist2);
+        float attenuation = (plight[i].intensity / dist2)
+            * range_falloff(sqrt(dist2), plight[i].radius, plight[i].smoothness);
         lighting += diffuse * attenuation;
     }
     for (uint i = 0u; i < directional_light_count; i++) {
         vec3 dir = dlight[i].direction;
         float diff = max(dot(-dir, normal), 0.0);
         vec3 diffuse = diff * dlight[i].color;
+        if (i == 0u) {
+            diffuse *= primary_shadow(vertex.position);
+        }
         lighting += diffuse * dlight[i].intensity;
     }
-    lighting += ambient_color;
-    out_color = vec4(lighting * albedo + emission, alpha) * vertex.color;
+    for (int i = 0; i < area_light_count; i++) {
+        vec3 light_vec = area_light_point(i, vertex.position) - vertex.position;
+        float diff = max(dot(normalize(light_vec), normal), 0.0);
+        lighting += diff * alight[i].color * area_light_attenuation(i, light_vec);
+    }
+    vec2 screen_coord = gl_FragCoord.xy / vec2(textureSize(screen_ambient_occlusion, 0));
+    lighting += ambient_color * texture(screen_ambient_occlusion, screen_coord).r;
+    vec3 color = (lighting * albedo + emission) * vertex.color.rgb;
+    out_color = vec4(apply_fog(color, vertex.position) * exposure, alpha * vertex.color.a);
+    out_object_id = vertex_object_id;
 }
@@ -10,12 +10,19 @@ layout(location = 0) in VertexData {
 layout(location = 0) out vec4 out_color;
 
 layout(std140, set = 1, binding = 0) uniform _ {
-    vec3 zenith_color;
     vec3 nadir_color;
+    vec3 zenith_color;
+    bool use_cubemap;
 };
 
+layout(set = 2, binding = 0) uniform samplerCube cubemap;
+
 void main() {
     vec3 normalized_position = normalize(vertex.position.xyz);
+    if (use_cubemap) {
+        out_color = vec4(texture(cubemap, normalized_position).rgb, 1.0f);
+        return;
+    }
     vec3 horizon_color = mix(nadir_color, zenith_color, smoothstep(-1., 1., normalized_position.y));
     out_color = vec4(horizon_color, 1.0f);
 }
@@ -3,12 +3,13 @@
 layout(set = 1, binding = 0) uniform sampler2D albedo;
 
 layout(location = 0) in vec2 tex_uv;
+layout(location = 1) in vec4 color;
 layout(location = 0) out vec4 out_color;
 
 void main() {
-    vec4 color = texture(albedo, tex_uv);
-    if (color.a == 0.0) {
+    vec4 tex_color = texture(albedo, tex_uv) * color;
+    if (tex_color.a == 0.0) {
         discard;
     }
-    out_color = color;
+    out_color = tex_color;
 }
@@ -0,0 +1,74 @@
+#version 450
+
+layout(std140, set = 0, binding = 0) uniform SsaoArgs {
+    mat4 proj;
+    mat4 inv_proj;
+    int sample_count;
+    float radius;
+    float intensity;
+    float bias;
+};
+
+layout(set = 1, binding = 0) uniform sampler2D depth;
+
+layout(location = 0) in vec2 tex_coord;
+
+layout(location = 0) out vec4 out_color;
+
+const float PI = 3.14159265359;
+
+vec3 view_position(vec2 uv) {
+    vec4 ndc = vec4(uv * 2.0 - 1.0, texture(depth, uv).r, 1.0);
+    vec4 position = inv_proj * ndc;
+    return position.xyz / position.w;
+}
+
+float radical_inverse(uint bits) {
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return float(bits) * 2.3283064365386963e-10;
+}
+
+// Hemisphere sample around +Z, denser close to the origin.
+vec3 kernel_sample(int i) {
+    float u = (float(i) + 0.5) / float(sample_count);
+    float v = radical_inverse(uint(i));
+    float phi = 2.0 * PI * v;
+    float cos_theta = sqrt(1.0 - u);
+    float sin_theta = sqrt(u);
+    float scale = mix(0.1, 1.0, u * u);
+    return vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta) * scale;
+}
+
+// Per pixel rotation of the kernel, trading banding for noise removed by the blur.
+float interleaved_gradient_noise(vec2 position) {
+    return fract(52.9829189 * fract(dot(position, vec2(0.06711056, 0.00583715))));
+}
+
+void main() {
+    vec3 position = view_position(tex_coord);
+    vec3 normal = normalize(cross(dFdx(position), dFdy(position)));
+
+    float angle = 2.0 * PI * interleaved_gradient_noise(gl_FragCoord.xy);
+    vec3 random = vec3(cos(angle), sin(angle), 0.0);
+    vec3 tangent = normalize(random - normal * dot(random, normal));
+    mat3 tbn = mat3(tangent, cross(normal, tangent), normal);
+
+    float occlusion = 0.0;
+    for (int i = 0; i < sample_count; i++) {
+        vec3 sample_position = position + tbn * kernel_sample(i) * radius;
+
+        vec4 offset = proj * vec4(sample_position, 1.0);
+        vec2 sample_uv = offset.xy / offset.w * 0.5 + 0.5;
+
+        float sample_depth = view_position(sample_uv).z;
+        float range_check = smoothstep(0.0, 1.0, radius / abs(position.z - sample_depth));
+        occlusion += (sample_depth >= sample_position.z + bias ? 1.0 : 0.0) * range_check;
+    }
+
+    float ao = 1.0 - intensity * occlusion / float(max(sample_count, 1));
+    out_color = vec4(clamp(ao, 0.0, 1.0));
+}
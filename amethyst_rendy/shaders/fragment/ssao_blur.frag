@@ -0,0 +1,20 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform sampler2D ambient_occlusion;
+
+layout(location = 0) in vec2 tex_coord;
+
+layout(location = 0) out vec4 out_color;
+
+// 4x4 box blur smoothing out the per pixel kernel rotation noise of ssao.frag.
+void main() {
+    vec2 texel_size = 1.0 / vec2(textureSize(ambient_occlusion, 0));
+    float result = 0.0;
+    for (int x = -2; x < 2; x++) {
+        for (int y = -2; y < 2; y++) {
+            vec2 offset = (vec2(x, y) + 0.5) * texel_size;
+            result += texture(ambient_occlusion, tex_coord + offset).r;
+        }
+    }
+    out_color = vec4(result / 16.0);
+}
@@ -0,0 +1,36 @@
+#version 450
+
+layout(std140, set = 0, binding = 0) uniform TonemapArgs {
+    float exposure;
+    int operator;
+};
+
+layout(set = 1, binding = 0) uniform sampler2D hdr_color;
+
+layout(location = 0) in vec2 tex_coord;
+
+layout(location = 0) out vec4 out_color;
+
+const int OPERATOR_CLAMP = 0;
+const int OPERATOR_REINHARD = 1;
+const int OPERATOR_ACES_FILMIC = 2;
+
+// Narkowicz's fit of the ACES filmic curve.
+vec3 aces_filmic(vec3 x) {
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+void main() {
+    vec4 hdr = texture(hdr_color, tex_coord);
+    vec3 color = hdr.rgb * exposure;
+
+    if (operator == OPERATOR_REINHARD) {
+        color = color / (color + 1.0);
+    } else if (operator == OPERATOR_ACES_FILMIC) {
+        color = aces_filmic(color);
+    } else {
+        color = clamp(color, 0.0, 1.0);
+    }
+
+    out_color = vec4(color, hdr.a);
+}
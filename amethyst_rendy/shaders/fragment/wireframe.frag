@@ -0,0 +1,19 @@
+#version 450
+
+layout(location = 0) in VertexData {
+    vec3 position;
+    vec2 tex_coord;
+    vec4 color;
+    float fade;
+} vertex;
+
+layout(location = 7) flat in uint vertex_object_id;
+
+layout(location = 0) out vec4 out_color;
+// Only stored when the pass is built with object ids.
+layout(location = 1) out uint out_object_id;
+
+void main() {
+    out_color = vertex.color;
+    out_object_id = vertex_object_id;
+}
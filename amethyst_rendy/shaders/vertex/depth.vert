@@ -0,0 +1,15 @@
+#version 450
+
+layout(std140, set = 0, binding = 0) uniform ViewArgs {
+    uniform mat4 proj;
+    uniform mat4 view;
+};
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in mat4 model; // instance rate
+
+void main() {
+    // Same operations as the lit vertex shaders, so equal depth tests pass.
+    vec4 vertex_position = model * vec4(position, 1.0);
+    gl_Position = proj * view * vertex_position;
+}
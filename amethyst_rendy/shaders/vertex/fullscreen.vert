@@ -0,0 +1,9 @@
+#version 450
+
+layout(location = 0) out vec2 tex_coord;
+
+// Single triangle covering the whole screen, generated from the vertex index.
+void main() {
+    tex_coord = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+    gl_Position = vec4(tex_coord * 2.0 - 1.0, 0.0, 1.0);
+}
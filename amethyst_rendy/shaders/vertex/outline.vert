@@ -0,0 +1,20 @@
+#version 450
+
+layout(std140, set = 0, binding = 0) uniform ViewArgs {
+    uniform mat4 proj;
+    uniform mat4 view;
+};
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+layout(location = 2) in mat4 model; // instance rate
+layout(location = 6) in vec4 outline_color; // instance rate
+layout(location = 7) in float outline_thickness; // instance rate
+
+layout(location = 0) out vec4 color;
+
+void main() {
+    vec3 expanded = position + normalize(normal) * outline_thickness;
+    color = outline_color;
+    gl_Position = proj * view * model * vec4(expanded, 1.0);
+}
@@ -0,0 +1,38 @@
+#version 450
+
+layout(std140, set = 0, binding = 0) uniform ViewArgs {
+    uniform mat4 proj;
+    uniform mat4 view;
+};
+
+// Particle instance.
+layout(location = 0) in vec3 position;
+layout(location = 1) in float size;
+layout(location = 2) in vec4 color;
+layout(location = 3) in float rotation;
+
+layout(location = 0) out VertexData {
+    vec4 color;
+    float view_depth;
+} vertex;
+
+const vec2 corners[4] = vec2[](
+    vec2(0.5, -0.5), // Right bottom
+    vec2(-0.5, -0.5), // Left bottom
+    vec2(0.5, 0.5), // Right top
+    vec2(-0.5, 0.5) // Left top
+);
+
+void main() {
+    float s = sin(rotation);
+    float c = cos(rotation);
+    vec2 corner = mat2(c, s, -s, c) * corners[gl_VertexIndex] * size;
+
+    // Offsetting in view space keeps the quad facing the camera.
+    vec4 view_position = view * vec4(position, 1.0);
+    view_position.xy += corner;
+
+    vertex.color = color;
+    vertex.view_depth = -view_position.z;
+    gl_Position = proj * view_position;
+}
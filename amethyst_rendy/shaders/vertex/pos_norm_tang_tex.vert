@@ -11,6 +11,8 @@ layout(location = 2) in vec4 tangent;
 layout(location = 3) in vec2 tex_coord;
 layout(location = 4) in mat4 model; // instance rate
 layout(location = 8) in vec4 tint; // instance rate
+layout(location = 9) in float fade; // instance rate
+layout(location = 10) in uint object_id; // instance rate
 
 layout(location = 0) out VertexData {
     vec3 position;
@@ -19,8 +21,11 @@ layout(location = 0) out VertexData {
     float tang_handedness;
     vec2 tex_coord;
     vec4 color;
+    float fade;
 } vertex;
 
+layout(location = 7) flat out uint vertex_object_id;
+
 void main() {
     vec4 vertex_position = model * vec4(position, 1.0);
     vertex.position = vertex_position.xyz;
@@ -29,5 +34,7 @@ void main() {
     vertex.tang_handedness = tangent.w;
     vertex.tex_coord = tex_coord;
     vertex.color = tint;
+    vertex.fade = fade;
+    vertex_object_id = object_id;
     gl_Position = proj * view * vertex_position;
 }
@@ -10,19 +10,26 @@ layout(location = 1) in vec3 normal;
 layout(location = 2) in vec2 tex_coord;
 layout(location = 3) in mat4 model; // instance rate
 layout(location = 7) in vec4 tint; // instance rate
+layout(location = 8) in float fade; // instance rate
+layout(location = 9) in uint object_id; // instance rate
 
 layout(location = 0) out VertexData {
     vec3 position;
     vec3 normal;
     vec2 tex_coord;
     vec4 color;
+    float fade;
 } vertex;
 
+layout(location = 7) flat out uint vertex_object_id;
+
 void main() {
     vec4 vertex_position = model * vec4(position, 1.0);
     vertex.position = vertex_position.xyz;
     vertex.normal = mat3(model) * normal;
     vertex.tex_coord = tex_coord;
     vertex.color = tint;
+    vertex.fade = fade;
+    vertex_object_id = object_id;
     gl_Position = proj * view * vertex_position;
 }
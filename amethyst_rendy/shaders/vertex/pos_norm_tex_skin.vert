@@ -16,15 +16,20 @@ layout(location = 3) in uvec4 joint_ids;
 layout(location = 4) in vec4 joint_weights;
 layout(location = 5) in mat4 model; // instance rate
 layout(location = 9) in vec4 tint; // instance rate
-layout(location = 10) in uint joints_offset; // instance rate
+layout(location = 10) in float fade; // instance rate
+layout(location = 11) in uint joints_offset; // instance rate
+layout(location = 12) in uint object_id; // instance rate
 
 layout(location = 0) out VertexData {
     vec3 position;
     vec3 normal;
     vec2 tex_coord;
     vec4 color;
+    float fade;
 } vertex;
 
+layout(location = 7) flat out uint vertex_object_id;
+
 void main() {
     mat4 joint_transform =
         joint_weights.x * joints[int(joints_offset + joint_ids.x)] +
@@ -38,6 +43,8 @@ void main() {
     vertex.normal = mat3_transform * normal;
     vertex.tex_coord = tex_coord;
     vertex.color = tint;
+    vertex.fade = fade;
+    vertex_object_id = object_id;
     gl_Position = proj * view * vertex_position;
 
 }
@@ -0,0 +1,33 @@
+#version 450
+
+layout(std140, set = 0, binding = 0) uniform Projview {
+    mat4 proj;
+    mat4 view;
+};
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec2 tex_coord;
+layout(location = 2) in vec4 color;
+layout(location = 3) in mat4 model; // instance rate
+layout(location = 7) in vec4 tint; // instance rate
+layout(location = 8) in float fade; // instance rate
+layout(location = 9) in uint object_id; // instance rate
+
+layout(location = 0) out VertexData {
+    vec3 position;
+    vec2 tex_coord;
+    vec4 color;
+    float fade;
+} vertex;
+
+layout(location = 7) flat out uint vertex_object_id;
+
+void main() {
+    vec4 vertex_position = model * vec4(position, 1.0);
+    vertex.position = vertex_position.xyz;
+    vertex.tex_coord = tex_coord;
+    vertex.color = tint * color;
+    vertex.fade = fade;
+    vertex_object_id = object_id;
+    gl_Position = proj * view * vertex_position;
+}
@@ -15,14 +15,19 @@ layout(location = 2) in uvec4 joint_ids;
 layout(location = 3) in vec4 joint_weights;
 layout(location = 4) in mat4 model; // instance rate
 layout(location = 8) in vec4 tint; // instance rate
-layout(location = 9) in uint joints_offset; // instance rate
+layout(location = 9) in float fade; // instance rate
+layout(location = 10) in uint joints_offset; // instance rate
+layout(location = 11) in uint object_id; // instance rate
 
 layout(location = 0) out VertexData {
     vec3 position;
     vec2 tex_coord;
     vec4 color;
+    float fade;
 } vertex;
 
+layout(location = 7) flat out uint vertex_object_id;
+
 void main() {
     mat4 joint_transform =
         joint_weights.x * joints[int(joints_offset + joint_ids.x)] +
@@ -34,5 +39,7 @@ void main() {
     vertex.position = vertex_position.xyz;
     vertex.tex_coord = tex_coord;
     vertex.color = tint;
+    vertex.fade = fade;
+    vertex_object_id = object_id;
     gl_Position = proj * view * vertex_position;
 }
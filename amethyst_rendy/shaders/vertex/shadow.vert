@@ -0,0 +1,21 @@
+#version 450
+
+layout(std140, set = 0, binding = 0) uniform Shadow {
+    mat4 light_view_proj[4];
+    vec4 cascade_splits;
+    vec3 camera_forward;
+    float bias;
+    int cascade_count;
+    int enabled;
+};
+
+layout(push_constant) uniform Cascade {
+    int cascade;
+};
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in mat4 model; // instance rate
+
+void main() {
+    gl_Position = light_view_proj[cascade] * model * vec4(position, 1.0);
+}
@@ -1,5 +1,8 @@
 #version 450
 
+// Set for `DepthMode::Reversed`, where the far plane is at depth 0.
+layout(constant_id = 0) const bool reversed_depth = false;
+
 layout(std140, set = 0, binding = 0) uniform ViewArgs {
     uniform mat4 proj;
     uniform mat4 view;
@@ -20,5 +23,7 @@ void main() {
     vertex.position = position.xyz;
     vertex.tex_coord = tex_coord;
 
-    gl_Position = (proj * view_without_translation * vec4(position, 1.0)).xyww;
+    vec4 clip = proj * view_without_translation * vec4(position, 1.0);
+    // Drawn on the far plane, behind everything else.
+    gl_Position = reversed_depth ? vec4(clip.xy, 0.0, clip.w) : clip.xyww;
 }
@@ -12,8 +12,10 @@ layout(location = 2) in vec2 pos;
 layout(location = 3) in vec2 u_offset;
 layout(location = 4) in vec2 v_offset;
 layout(location = 5) in float depth;
+layout(location = 6) in vec4 tint;
 
 layout(location = 0) out vec2 tex_uv;
+layout(location = 1) out vec4 color;
 
 const vec2 positions[4] = vec2[](
     vec2(0.5, -0.5), // Right bottom
@@ -32,6 +34,7 @@ void main() {
     float tex_v = positions[gl_VertexIndex][1];
 
     tex_uv = texture_coords(vec2(tex_u, tex_v), u_offset, v_offset);
+    color = tint;
     vec2 final_pos = pos + tex_u * dir_x + tex_v * dir_y;
     vec4 vertex = vec4(final_pos, depth, 1.0);
     gl_Position = proj * view * vertex;
@@ -20,6 +20,14 @@ where
     fn for_each_group<F>(self, on_group: F)
     where
         F: FnMut(K, &mut Vec<V>);
+
+    /// Perform grouping by the key `key` maps every group identifier to, such as a part of
+    /// it. Evaluates `on_group` on every next contiguous list of data with the same key.
+    fn for_each_group_by<G, KF, F>(self, key: KF, on_group: F)
+    where
+        G: PartialEq,
+        KF: FnMut(K) -> G,
+        F: FnMut(G, &mut Vec<V>);
 }
 
 // This would be an iterator adaptor if `Item` type would allow a borrow on iterator itself.
@@ -29,16 +37,26 @@ where
     K: PartialEq,
     I: Iterator<Item = (K, V)>,
 {
-    fn for_each_group<F>(self, mut on_group: F)
+    fn for_each_group<F>(self, on_group: F)
     where
         F: FnMut(K, &mut Vec<V>),
+    {
+        self.for_each_group_by(|group_id| group_id, on_group);
+    }
+
+    fn for_each_group_by<G, KF, F>(self, mut key: KF, mut on_group: F)
+    where
+        G: PartialEq,
+        KF: FnMut(K) -> G,
+        F: FnMut(G, &mut Vec<V>),
     {
         #[cfg(feature = "profiler")]
         profile_scope!("for_each_group");
 
-        let mut block: Option<(K, Vec<V>)> = None;
+        let mut block: Option<(G, Vec<V>)> = None;
 
-        for (next_group_id, value) in self {
+        for (group_id, value) in self {
+            let next_group_id = key(group_id);
             match &mut block {
                 slot @ None => {
                     let mut group_buffer = Vec::with_capacity(64);
@@ -63,20 +81,43 @@ where
     }
 }
 
+/// Insert `key` in the ascending `keys`, which don't contain it yet.
+fn insert_sorted<K: Ord + Clone>(keys: &mut Vec<K>, key: &K) {
+    let index = keys.binary_search(key).unwrap_or_else(|index| index);
+    keys.insert(index, key.clone());
+}
+
+/// Batches sharing a primary key, with the position of each secondary key among them.
+#[derive(Derivative, Debug)]
+#[derivative(Default(bound = ""))]
+struct SecondaryBatches<SK, C>
+where
+    SK: Eq + std::hash::Hash,
+{
+    batches: SmallVec<[(SK, C); 1]>,
+    positions: fnv::FnvHashMap<SK, usize>,
+}
+
+/// Instance data batched by a primary and a secondary key, such as material and mesh.
+///
+/// Primary keys are iterated in ascending order, so descriptor sets are bound in the same
+/// order every frame. Secondary keys keep their insertion order.
 #[derive(Derivative, Debug)]
 #[derivative(Default(bound = ""))]
 pub struct TwoLevelBatch<PK, SK, C>
 where
     PK: Eq + std::hash::Hash,
+    SK: Eq + std::hash::Hash,
 {
-    map: fnv::FnvHashMap<PK, SmallVec<[(SK, C); 1]>>,
+    map: fnv::FnvHashMap<PK, SecondaryBatches<SK, C>>,
+    keys: Vec<PK>,
     data_count: usize,
 }
 
 impl<PK, SK, C> TwoLevelBatch<PK, SK, C>
 where
-    PK: Eq + std::hash::Hash,
-    SK: PartialEq,
+    PK: Ord + Eq + std::hash::Hash + Clone,
+    SK: Eq + std::hash::Hash + Clone,
     C: IntoIterator,
     C: FromIterator<<C as IntoIterator>::Item>,
     C: Extend<<C as IntoIterator>::Item>,
@@ -84,50 +125,59 @@ where
     pub fn clear_inner(&mut self) {
         self.data_count = 0;
         for (_, data) in self.map.iter_mut() {
-            data.clear();
+            data.batches.clear();
+            data.positions.clear();
         }
     }
 
     pub fn prune(&mut self) {
-        self.map.retain(|_, b| b.len() > 0);
+        self.map.retain(|_, b| !b.batches.is_empty());
+        let map = &self.map;
+        self.keys.retain(|pk| map.contains_key(pk));
     }
 
+    /// Appends instance data to the batch of `sk` under `pk`, creating it if needed.
     pub fn insert(&mut self, pk: PK, sk: SK, data: impl IntoIterator<Item = C::Item>) {
         #[cfg(feature = "profiler")]
         profile_scope!("twolevel_insert");
 
         let instance_data = data.into_iter().tap_count(&mut self.data_count);
-
-        match self.map.entry(pk) {
-            Entry::Occupied(mut e) => {
-                let e = e.get_mut();
-                // scan for the same key to try to combine batches.
-                // Scanning limited slots to limit complexity.
-                if let Some(batch) = e.iter_mut().take(8).find(|(k, _)| k == &sk) {
-                    batch.1.extend(instance_data);
-                } else {
-                    e.push((sk, instance_data.collect()));
-                }
+        let SecondaryBatches { batches, positions } = match self.map.entry(pk) {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => {
+                insert_sorted(&mut self.keys, e.key());
+                e.insert(Default::default())
             }
+        };
+
+        match positions.entry(sk) {
+            Entry::Occupied(e) => batches[*e.get()].1.extend(instance_data),
             Entry::Vacant(e) => {
-                e.insert(smallvec![(sk, instance_data.collect())]);
+                batches.push((e.key().clone(), instance_data.collect()));
+                e.insert(batches.len() - 1);
             }
         }
     }
 
     pub fn data<'a>(&'a self) -> impl Iterator<Item = &'a C> {
-        self.map
+        self.keys
             .iter()
-            .flat_map(|(_, batch)| batch.iter().map(|data| &data.1))
+            .flat_map(move |pk| self.map[pk].batches.iter().map(|data| &data.1))
     }
 
     pub fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a PK, impl Iterator<Item = &'a (SK, C)>)> {
-        self.map.iter().map(|(pk, batch)| (pk, batch.iter()))
+        self.keys
+            .iter()
+            .map(move |pk| (pk, self.map[pk].batches.iter()))
     }
 
     pub fn count(&self) -> usize {
         self.data_count
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.data_count == 0
+    }
 }
 
 #[derive(Derivative, Debug)]
@@ -203,8 +253,13 @@ where
     pub fn count(&self) -> usize {
         self.data_list.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.data_list.is_empty()
+    }
 }
 
+/// Instance data batched by a single key, iterated in ascending key order.
 #[derive(Derivative, Debug)]
 #[derivative(Default(bound = ""))]
 pub struct OneLevelBatch<PK, D>
@@ -212,12 +267,13 @@ where
     PK: Eq + std::hash::Hash,
 {
     map: fnv::FnvHashMap<PK, Vec<D>>,
+    keys: Vec<PK>,
     data_count: usize,
 }
 
 impl<PK, D> OneLevelBatch<PK, D>
 where
-    PK: Eq + std::hash::Hash,
+    PK: Ord + Eq + std::hash::Hash + Clone,
 {
     pub fn clear_inner(&mut self) {
         self.data_count = 0;
@@ -228,6 +284,8 @@ where
 
     pub fn prune(&mut self) {
         self.map.retain(|_, b| b.len() > 0);
+        let map = &self.map;
+        self.keys.retain(|pk| map.contains_key(pk));
     }
 
     pub fn insert(&mut self, pk: PK, data: impl IntoIterator<Item = D>) {
@@ -246,18 +304,20 @@ where
             Entry::Vacant(e) => {
                 let collected = instance_data.collect::<Vec<_>>();
                 self.data_count += collected.len();
+                insert_sorted(&mut self.keys, e.key());
                 e.insert(collected);
             }
         }
     }
 
     pub fn data<'a>(&'a self) -> impl Iterator<Item = &'a Vec<D>> {
-        self.map.values()
+        self.keys.iter().map(move |pk| &self.map[pk])
     }
 
     pub fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a PK, Range<u32>)> {
         let mut offset = 0;
-        self.map.iter().map(move |(pk, data)| {
+        self.keys.iter().map(move |pk| {
+            let data = &self.map[pk];
             let range = offset..offset + data.len() as u32;
             offset = range.end;
             (pk, range)
@@ -330,3 +390,122 @@ where
         self.data_list.len()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_mesh_instances_keep_submission_order() {
+        let mut batch = OrderedTwoLevelBatch::<u32, u32, char>::default();
+        batch.insert(0, 0, vec!['a', 'b', 'c', 'd']);
+
+        assert_eq!(batch.data(), &vec!['a', 'b', 'c', 'd']);
+        assert_eq!(batch.iter().count(), 1);
+        assert_eq!(batch.iter().next().unwrap().1, &[(0, 0..4)]);
+    }
+
+    #[test]
+    fn same_mesh_batches_merge_past_many_meshes() {
+        let mut batch = TwoLevelBatch::<u32, u32, Vec<u32>>::default();
+        for mesh in 0..20 {
+            batch.insert(0, mesh, vec![mesh]);
+        }
+        batch.insert(0, 15, vec![100]);
+
+        let (_, batches) = batch.iter().next().unwrap();
+        let batches = batches.collect::<Vec<_>>();
+        assert_eq!(batches.len(), 20);
+        assert_eq!(batches[15], &(15, vec![15, 100]));
+        assert_eq!(batch.count(), 21);
+    }
+
+    #[test]
+    fn groups_by_extracted_key() {
+        let items = vec![(0, 'a'), (1, 'b'), (0, 'c'), (2, 'd'), (1, 'e')];
+
+        let mut contiguous = Vec::new();
+        items
+            .clone()
+            .into_iter()
+            .for_each_group(|key, data| contiguous.push((key, data.clone())));
+        assert_eq!(contiguous.len(), 5);
+
+        let mut groups = Vec::new();
+        items
+            .into_iter()
+            .for_each_group_by(|key| key % 2, |key, data| groups.push((key, data.clone())));
+        assert_eq!(
+            groups,
+            vec![
+                (0, vec!['a']),
+                (1, vec!['b']),
+                (0, vec!['c', 'd']),
+                (1, vec!['e'])
+            ]
+        );
+    }
+
+    #[test]
+    fn cleared_batches_are_empty() {
+        let mut batch = TwoLevelBatch::<u32, u32, Vec<u32>>::default();
+        assert!(batch.is_empty());
+        batch.insert(0, 0, vec![1, 2, 3]);
+        assert!(!batch.is_empty());
+        batch.clear_inner();
+        batch.prune();
+        assert!(batch.is_empty());
+        assert_eq!(batch.iter().count(), 0);
+
+        batch.insert(0, 0, vec![1, 2, 3]);
+        batch.insert(1, 0, vec![9]);
+        batch.clear_inner();
+        batch.insert(0, 1, vec![4]);
+        assert_eq!(batch.count(), 1);
+        let contents = batch
+            .iter()
+            .flat_map(|(&pk, batches)| batches.map(move |(sk, data)| (pk, *sk, data.clone())))
+            .collect::<Vec<_>>();
+        assert_eq!(contents, vec![(0, 1, vec![4])]);
+
+        let mut ordered = OrderedTwoLevelBatch::<u32, u32, u32>::default();
+        ordered.insert(0, 0, vec![1, 2, 3]);
+        assert!(!ordered.is_empty());
+        ordered.swap_clear();
+        assert!(ordered.is_empty());
+        assert_eq!(ordered.iter().count(), 0);
+
+        ordered.insert(2, 0, vec![5]);
+        let contents = ordered
+            .iter()
+            .map(|(&pk, batches)| (pk, batches.to_vec()))
+            .collect::<Vec<_>>();
+        assert_eq!(contents, vec![(2, vec![(0, 0..1)])]);
+        assert_eq!(ordered.data(), &vec![5]);
+    }
+
+    #[test]
+    fn primary_keys_iterate_in_order() {
+        let mut batch = TwoLevelBatch::<u32, u32, Vec<u32>>::default();
+        batch.insert(7, 0, vec![70]);
+        batch.insert(2, 1, vec![20]);
+        batch.insert(5, 0, vec![50]);
+        batch.insert(2, 0, vec![21]);
+
+        let keys = batch.iter().map(|(&pk, _)| pk).collect::<Vec<_>>();
+        assert_eq!(keys, vec![2, 5, 7]);
+        let data = batch.data().flatten().cloned().collect::<Vec<_>>();
+        assert_eq!(data, vec![20, 21, 50, 70]);
+
+        batch.clear_inner();
+        batch.insert(7, 0, vec![71]);
+        batch.prune();
+        assert_eq!(batch.iter().map(|(&pk, _)| pk).collect::<Vec<_>>(), vec![7]);
+
+        let mut single = OneLevelBatch::<u32, u32>::default();
+        single.insert(3, vec![30, 31]);
+        single.insert(1, vec![10]);
+        let ranges = single.iter().collect::<Vec<_>>();
+        assert_eq!(ranges, vec![(&1, 0..1), (&3, 1..3)]);
+    }
+}
@@ -0,0 +1,407 @@
+//! Copying images between render targets, for post-processing chains.
+//!
+//! Add a `BlitDesc` node with the source image first and the destination second, such as
+//! to downsample the scene before blurring it:
+//!
+//! ```ignore
+//! let half = graph_builder.create_image(
+//!     hal::image::Kind::D2(width / 2, height / 2, 1, 1),
+//!     1,
+//!     hal::format::Format::Rgba16Sfloat,
+//!     None,
+//! );
+//! let downsample = graph_builder.add_node(
+//!     BlitDesc::new()
+//!         .with_scale(0.5)
+//!         .builder()
+//!         .with_image(scene)
+//!         .with_image(half)
+//!         .with_dependency(pass),
+//! );
+//! ```
+//!
+//! Multisampled images are resolved instead, which needs a single sampled destination of
+//! the same format and a scale of `1.0`. This is how a scene drawn with multisample
+//! anti-aliasing is turned into an image that can be presented.
+
+use crate::types::Backend;
+use amethyst_core::ecs::Resources;
+use derivative::Derivative;
+use rendy::{
+    command::{
+        CommandBuffer, CommandPool, ExecutableState, Family, Fence, Graphics, MultiShot,
+        PendingState, Queue, SimultaneousUse, Submission, Submit,
+    },
+    factory::Factory,
+    frame::Frames,
+    graph::{
+        gfx_acquire_barriers, gfx_release_barriers, GraphContext, ImageAccess, Node, NodeBuffer,
+        NodeDesc, NodeImage,
+    },
+    hal::{
+        self,
+        format::{Aspects, ChannelType, Format, ImageFeature},
+        image::{Extent, Filter, Kind},
+        pso,
+    },
+};
+
+/// Copies its first image into its second, scaled and filtered, or resolves it when it is
+/// multisampled. See the module documentation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlitDesc {
+    scale: f32,
+    filter: Filter,
+}
+
+impl Default for BlitDesc {
+    fn default() -> Self {
+        BlitDesc {
+            scale: 1.0,
+            filter: Filter::Linear,
+        }
+    }
+}
+
+impl BlitDesc {
+    /// Copy the source image into the top left corner of the destination, at its size.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Scale of the copy relative to the source, such as `0.5` to downsample it.
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Filter used when the copy is scaled, `Filter::Linear` by default.
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+}
+
+/// Size of the area of `destination` a blit of `source` scaled by `scale` covers.
+///
+/// Fails with a description of the mismatch when the images can't be blitted or resolved.
+pub fn blit_extent(
+    (source_format, source_kind): (Format, Kind),
+    (destination_format, destination_kind): (Format, Kind),
+    scale: f32,
+    filter: Filter,
+) -> Result<Extent, failure::Error> {
+    if destination_kind.num_samples() > 1 {
+        return Err(failure::format_err!(
+            "Can't blit or resolve into multisampled images"
+        ));
+    }
+    let source_aspects = source_format.surface_desc().aspects;
+    if source_kind.num_samples() > 1 {
+        if source_format != destination_format || (scale - 1.0).abs() > std::f32::EPSILON {
+            return Err(failure::format_err!(
+                "Multisampled {:?} images can only be resolved into images of the same \
+                 format and size",
+                source_format
+            ));
+        }
+        if !source_aspects.contains(Aspects::COLOR) {
+            return Err(failure::format_err!(
+                "Multisampled depth and stencil images can't be resolved"
+            ));
+        }
+    }
+    if source_aspects != destination_format.surface_desc().aspects {
+        return Err(failure::format_err!(
+            "Can't blit {:?} images into {:?} images",
+            source_format,
+            destination_format
+        ));
+    }
+    if source_aspects.contains(Aspects::COLOR) {
+        // Integer formats are only blitted into integer formats of the same signedness.
+        let integer = |format: Format| match format.base_format().1 {
+            channel @ ChannelType::Uint | channel @ ChannelType::Sint => Some(channel),
+            _ => None,
+        };
+        if integer(source_format) != integer(destination_format) {
+            return Err(failure::format_err!(
+                "Can't blit {:?} images into {:?} images, integer and non-integer formats \
+                 don't mix",
+                source_format,
+                destination_format
+            ));
+        }
+        if integer(source_format).is_some() && filter != Filter::Nearest {
+            return Err(failure::format_err!(
+                "Integer images of format {:?} can only be blitted with `Filter::Nearest`",
+                source_format
+            ));
+        }
+    } else if filter != Filter::Nearest {
+        return Err(failure::format_err!(
+            "Depth and stencil images of format {:?} can only be blitted with `Filter::Nearest`",
+            source_format
+        ));
+    }
+    if !(scale > 0.0 && scale.is_finite()) {
+        return Err(failure::format_err!("Invalid blit scale {}", scale));
+    }
+
+    let source = source_kind.extent();
+    let destination = destination_kind.extent();
+    let scaled = |size: u32| ((size as f32 * scale).round() as u32).max(1);
+    let extent = Extent {
+        width: scaled(source.width),
+        height: scaled(source.height),
+        depth: 1,
+    };
+    if extent.width > destination.width || extent.height > destination.height {
+        return Err(failure::format_err!(
+            "Can't blit {}x{} pixels into a {}x{} image",
+            extent.width,
+            extent.height,
+            destination.width,
+            destination.height
+        ));
+    }
+    Ok(extent)
+}
+
+impl<B: Backend> NodeDesc<B, Resources> for BlitDesc {
+    type Node = BlitNode<B>;
+
+    fn images(&self) -> Vec<ImageAccess> {
+        vec![
+            ImageAccess {
+                access: hal::image::Access::TRANSFER_READ,
+                usage: hal::image::Usage::TRANSFER_SRC,
+                layout: hal::image::Layout::TransferSrcOptimal,
+                stages: pso::PipelineStage::TRANSFER,
+            },
+            ImageAccess {
+                access: hal::image::Access::TRANSFER_WRITE,
+                usage: hal::image::Usage::TRANSFER_DST,
+                layout: hal::image::Layout::TransferDstOptimal,
+                stages: pso::PipelineStage::TRANSFER,
+            },
+        ]
+    }
+
+    fn build<'a>(
+        self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        family: &mut Family<B>,
+        _queue: usize,
+        _aux: &Resources,
+        _buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<Self::Node, failure::Error> {
+        assert_eq!(images.len(), 2);
+        let source = ctx
+            .get_image(images[0].id)
+            .expect("Blit source is not created by the graph");
+        let destination = ctx
+            .get_image(images[1].id)
+            .expect("Blit destination is not created by the graph");
+        let extent = blit_extent(
+            (source.format(), source.kind()),
+            (destination.format(), destination.kind()),
+            self.scale,
+            self.filter,
+        )?;
+        let source_extent = source.kind().extent();
+        let resolve = source.kind().num_samples() > 1;
+        if !resolve {
+            let features = |format: Format| {
+                factory
+                    .physical()
+                    .format_properties(Some(format))
+                    .optimal_tiling
+            };
+            let (source_features, destination_features) =
+                (features(source.format()), features(destination.format()));
+            if !source_features.contains(ImageFeature::BLIT_SRC)
+                || !destination_features.contains(ImageFeature::BLIT_DST)
+            {
+                return Err(failure::format_err!(
+                    "The device can't blit {:?} images into {:?} images",
+                    source.format(),
+                    destination.format()
+                ));
+            }
+            if self.filter == Filter::Linear
+                && !source_features.contains(ImageFeature::SAMPLED_LINEAR)
+            {
+                return Err(failure::format_err!(
+                    "The device can't filter {:?} images linearly, blit them with \
+                     `Filter::Nearest`",
+                    source.format()
+                ));
+            }
+        }
+        let aspects = source.format().surface_desc().aspects;
+        let layers = hal::image::SubresourceLayers {
+            aspects,
+            level: 0,
+            layers: 0..1,
+        };
+
+        let mut pool = factory
+            .create_command_pool(family)?
+            .with_capability::<Graphics>()
+            .expect("Graph builder must provide a family with graphics capability");
+
+        let mut recording = pool
+            .allocate_buffers(1)
+            .pop()
+            .unwrap()
+            .begin(MultiShot(SimultaneousUse), ());
+        unsafe {
+            let mut encoder = recording.encoder();
+            let (stages, barriers) = gfx_acquire_barriers(ctx, None, &images);
+            if !barriers.is_empty() {
+                encoder.pipeline_barrier(stages, hal::memory::Dependencies::empty(), barriers);
+            }
+            if resolve {
+                hal::command::RawCommandBuffer::resolve_image(
+                    encoder.raw(),
+                    source.raw(),
+                    hal::image::Layout::TransferSrcOptimal,
+                    destination.raw(),
+                    hal::image::Layout::TransferDstOptimal,
+                    Some(hal::command::ImageResolve {
+                        src_subresource: layers.clone(),
+                        src_offset: hal::image::Offset::ZERO,
+                        dst_subresource: layers,
+                        dst_offset: hal::image::Offset::ZERO,
+                        extent,
+                    }),
+                );
+            } else {
+                encoder.blit_image(
+                    source.raw(),
+                    hal::image::Layout::TransferSrcOptimal,
+                    destination.raw(),
+                    hal::image::Layout::TransferDstOptimal,
+                    self.filter,
+                    Some(hal::command::ImageBlit {
+                        src_subresource: layers.clone(),
+                        src_bounds: hal::image::Offset::ZERO.into_bounds(&source_extent),
+                        dst_subresource: layers,
+                        dst_bounds: hal::image::Offset::ZERO.into_bounds(&extent),
+                    }),
+                );
+            }
+            let (stages, barriers) = gfx_release_barriers(ctx, None, &images);
+            if !barriers.is_empty() {
+                encoder.pipeline_barrier(stages, hal::memory::Dependencies::empty(), barriers);
+            }
+        }
+
+        Ok(BlitNode {
+            pool,
+            commands: recording.finish().submit(),
+        })
+    }
+}
+
+/// Node built from `BlitDesc`.
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""))]
+pub struct BlitNode<B: Backend> {
+    #[derivative(Debug = "ignore")]
+    pool: CommandPool<B, Graphics>,
+    #[derivative(Debug = "ignore")]
+    commands: (
+        Submit<B, SimultaneousUse>,
+        CommandBuffer<B, Graphics, PendingState<ExecutableState<MultiShot<SimultaneousUse>>>>,
+    ),
+}
+
+impl<B: Backend> Node<B, Resources> for BlitNode<B> {
+    type Capability = Graphics;
+    type Desc = BlitDesc;
+
+    unsafe fn run<'a>(
+        &mut self,
+        _ctx: &GraphContext<B>,
+        _factory: &Factory<B>,
+        queue: &mut Queue<B>,
+        _aux: &Resources,
+        _frames: &Frames<B>,
+        waits: &[(&'a B::Semaphore, pso::PipelineStage)],
+        signals: &[&'a B::Semaphore],
+        fence: Option<&mut Fence<B>>,
+    ) {
+        queue.submit(
+            Some(
+                Submission::new()
+                    .submits(Some(&self.commands.0))
+                    .wait(waits.iter().cloned())
+                    .signal(signals.iter()),
+            ),
+            fence,
+        );
+    }
+
+    unsafe fn dispose(mut self, factory: &mut Factory<B>, _aux: &Resources) {
+        self.pool
+            .free_buffers(Some(self.commands.1.mark_complete()));
+        factory.destroy_command_pool(self.pool);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blit_checks_destination_size() {
+        let full = (Format::Rgba16Sfloat, Kind::D2(1920, 1080, 1, 1));
+        let half = (Format::Rgba8Unorm, Kind::D2(960, 540, 1, 1));
+
+        let extent = blit_extent(full, half, 0.5, Filter::Linear).unwrap();
+        assert_eq!((extent.width, extent.height), (960, 540));
+        assert!(blit_extent(full, half, 1.0, Filter::Linear).is_err());
+        assert!(blit_extent(full, (Format::D32Sfloat, half.1), 0.5, Filter::Nearest).is_err());
+    }
+
+    #[test]
+    fn blit_keeps_integer_formats_apart() {
+        let ids = (Format::R32Uint, Kind::D2(64, 64, 1, 1));
+        let colors = (Format::R32Sfloat, Kind::D2(64, 64, 1, 1));
+
+        assert!(blit_extent(ids, (Format::R16Uint, ids.1), 1.0, Filter::Nearest).is_ok());
+        assert!(blit_extent(ids, (Format::R32Sint, ids.1), 1.0, Filter::Nearest).is_err());
+        assert!(blit_extent(ids, colors, 1.0, Filter::Nearest).is_err());
+        assert!(blit_extent(colors, ids, 1.0, Filter::Nearest).is_err());
+        assert!(blit_extent(ids, ids, 1.0, Filter::Linear).is_err());
+    }
+
+    #[test]
+    fn multisampled_images_are_resolved_at_their_size() {
+        let multisampled = (Format::Rgba16Sfloat, Kind::D2(1920, 1080, 1, 4));
+        let full = (Format::Rgba16Sfloat, Kind::D2(1920, 1080, 1, 1));
+
+        let extent = blit_extent(multisampled, full, 1.0, Filter::Linear).unwrap();
+        assert_eq!((extent.width, extent.height), (1920, 1080));
+        assert!(blit_extent(multisampled, full, 0.5, Filter::Linear).is_err());
+        assert!(blit_extent(
+            multisampled,
+            (Format::Rgba8Unorm, full.1),
+            1.0,
+            Filter::Linear
+        )
+        .is_err());
+        assert!(blit_extent(full, multisampled, 1.0, Filter::Linear).is_err());
+        assert!(blit_extent(
+            (Format::D32Sfloat, multisampled.1),
+            (Format::D32Sfloat, full.1),
+            1.0,
+            Filter::Nearest
+        )
+        .is_err());
+    }
+}
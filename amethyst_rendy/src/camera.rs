@@ -3,8 +3,11 @@
 use amethyst_assets::PrefabData;
 use amethyst_core::{
     ecs::prelude::{Component, Entity, HashMapStorage, Write, WriteStorage},
-    math::Matrix4,
+    math::{convert, Matrix4, Point3, Vector2, Vector3},
+    transform::Transform,
 };
+use amethyst_window::ScreenDimensions;
+use rendy::hal::pso;
 
 use amethyst_error::Error;
 
@@ -316,6 +319,22 @@ impl From<Projection> for Camera {
     }
 }
 
+/// Half-line in world space, such as the ray under the cursor returned by `Camera::screen_ray`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ray {
+    /// Starting point of the ray.
+    pub origin: Point3<f32>,
+    /// Normalized direction of the ray.
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    /// Point `distance` units along the ray.
+    pub fn at(&self, distance: f32) -> Point3<f32> {
+        self.origin + self.direction * distance
+    }
+}
+
 /// Camera struct.
 ///
 /// Contains a projection matrix to convert from world/eye-space
@@ -373,6 +392,46 @@ impl Camera {
         ))
     }
 
+    /// Create a camera with a perspective projection.
+    ///
+    /// `fov_y` is the vertical field of view in radians and `aspect` the width of the
+    /// view divided by its height. The camera looks down its local -z axis with +y up,
+    /// and the projection maps `z_near` to depth 0 and `z_far` to depth 1 as expected by
+    /// the render passes.
+    pub fn perspective(aspect: f32, fov_y: f32, z_near: f32, z_far: f32) -> Self {
+        Self::from(Projection::perspective(aspect, fov_y, z_near, z_far))
+    }
+
+    /// Create a camera with a perspective projection matching the aspect ratio of the window.
+    ///
+    /// See `Camera::perspective` for the other parameters.
+    pub fn perspective_for(
+        dimensions: &ScreenDimensions,
+        fov_y: f32,
+        z_near: f32,
+        z_far: f32,
+    ) -> Self {
+        Self::perspective(dimensions.aspect_ratio(), fov_y, z_near, z_far)
+    }
+
+    /// Create a camera with an orthographic projection.
+    ///
+    /// The planes are distances in the local space of the camera, which looks down its
+    /// -z axis with +y up. The projection maps `z_near` to depth 0 and `z_far` to depth 1
+    /// as expected by the render passes.
+    pub fn orthographic(
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+        z_near: f32,
+        z_far: f32,
+    ) -> Self {
+        Self::from(Projection::orthographic(
+            left, right, bottom, top, z_near, z_far,
+        ))
+    }
+
     pub fn as_matrix(&self) -> &Matrix4<f32> {
         match self.inner {
             Projection::Orthographic(ref p) => p.as_matrix(),
@@ -398,12 +457,63 @@ impl Camera {
     pub fn set_projection(&mut self, new: Projection) {
         self.inner = new;
     }
+
+    /// World space ray through a point on the screen, for picking with the mouse.
+    ///
+    /// `screen_pos` is measured in pixels from the top left corner of the window and is
+    /// clamped to its edges. The ray starts on the near plane, its direction points away
+    /// from the camera for both perspective and orthographic projections.
+    pub fn screen_ray(
+        &self,
+        screen_pos: Vector2<f32>,
+        screen_dims: &ScreenDimensions,
+        camera_transform: &Transform,
+    ) -> Ray {
+        let x = (screen_pos.x / screen_dims.width()).max(0.0).min(1.0) * 2.0 - 1.0;
+        let y = (screen_pos.y / screen_dims.height()).max(0.0).min(1.0) * 2.0 - 1.0;
+
+        let view = convert::<_, Matrix4<f32>>(camera_transform.view_matrix());
+        let inverse = (self.as_matrix() * view)
+            .try_inverse()
+            .unwrap_or_else(Matrix4::identity);
+        let near = inverse.transform_point(&Point3::new(x, y, 0.0));
+        let far = inverse.transform_point(&Point3::new(x, y, 1.0));
+
+        Ray {
+            origin: near,
+            direction: (far - near).normalize(),
+        }
+    }
 }
 
 impl Component for Camera {
     type Storage = HashMapStorage<Self>;
 }
 
+/// Exposure of a camera, a linear scale applied to the lit colors of the 3D passes
+/// before tonemapping. Cameras without one use `1.0`.
+///
+/// Animate it for eye adaptation, such as walking out of a dark cave into daylight.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Exposure(pub f32);
+
+impl Default for Exposure {
+    fn default() -> Self {
+        Exposure(1.0)
+    }
+}
+
+impl Exposure {
+    /// Exposure brightening the image by `stops` powers of two, or darkening it when negative.
+    pub fn from_stops(stops: f32) -> Self {
+        Exposure(stops.exp2())
+    }
+}
+
+impl Component for Exposure {
+    type Storage = HashMapStorage<Self>;
+}
+
 /// Active camera resource, used by the renderer to choose which camera to get the view matrix from.
 /// If no active camera is found, the first camera will be used as a fallback.
 #[derive(Clone, Debug, PartialEq)]
@@ -412,6 +522,93 @@ pub struct ActiveCamera {
     pub entity: Entity,
 }
 
+/// Area of the framebuffer drawn through a camera, for split-screen.
+///
+/// The projection of the camera should match the aspect ratio of its area.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CameraViewport {
+    /// Camera entity
+    pub entity: Entity,
+    /// Left and top edges, as fractions of the framebuffer size
+    pub origin: Vector2<f32>,
+    /// Width and height, as fractions of the framebuffer size
+    pub size: Vector2<f32>,
+}
+
+impl CameraViewport {
+    /// Draw `entity` into the area at `origin` of `size`, as fractions of the framebuffer size.
+    pub fn new(entity: Entity, origin: Vector2<f32>, size: Vector2<f32>) -> Self {
+        CameraViewport {
+            entity,
+            origin,
+            size,
+        }
+    }
+
+    /// Pixel rectangle covered in a `width` by `height` framebuffer.
+    pub fn rect(&self, width: u32, height: u32) -> pso::Rect {
+        let clamp = |v: f32| v.max(0.0).min(1.0);
+        let (left, top) = (clamp(self.origin.x), clamp(self.origin.y));
+        let right = clamp(self.origin.x + self.size.x);
+        let bottom = clamp(self.origin.y + self.size.y);
+        let (x, y) = ((left * width as f32).round(), (top * height as f32).round());
+        pso::Rect {
+            x: x as i16,
+            y: y as i16,
+            w: ((right * width as f32).round() - x) as i16,
+            h: ((bottom * height as f32).round() - y) as i16,
+        }
+    }
+}
+
+/// Split-screen layout of the opaque 3D passes, which draw the scene once per viewport.
+///
+/// Without this resource, or with no viewports, the active camera fills the framebuffer.
+/// Transparent passes always draw the active camera, as they depend on the `Visibility`
+/// ordering computed for it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CameraViewports(pub Vec<CameraViewport>);
+
+impl CameraViewports {
+    /// Side by side columns of equal width, one per camera from left to right.
+    pub fn columns(cameras: impl IntoIterator<Item = Entity>) -> Self {
+        let cameras = cameras.into_iter().collect::<Vec<_>>();
+        let width = 1.0 / cameras.len().max(1) as f32;
+        CameraViewports(
+            cameras
+                .into_iter()
+                .enumerate()
+                .map(|(i, entity)| {
+                    CameraViewport::new(
+                        entity,
+                        Vector2::new(i as f32 * width, 0.0),
+                        Vector2::new(width, 1.0),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    /// Stacked rows of equal height, one per camera from top to bottom.
+    pub fn rows(cameras: impl IntoIterator<Item = Entity>) -> Self {
+        let cameras = cameras.into_iter().collect::<Vec<_>>();
+        let height = 1.0 / cameras.len().max(1) as f32;
+        CameraViewports(
+            cameras
+                .into_iter()
+                .enumerate()
+                .map(|(i, entity)| {
+                    CameraViewport::new(
+                        entity,
+                        Vector2::new(0.0, i as f32 * height),
+                        Vector2::new(1.0, height),
+                    )
+                })
+                .collect(),
+        )
+    }
+}
+
 /// Projection prefab
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 pub enum CameraPrefab {
@@ -537,6 +734,30 @@ mod tests {
         assert_eq!(test_persp, de);
     }
 
+    #[test]
+    fn screen_ray_through_center_points_forward() {
+        let camera = Camera::standard_3d(800.0, 600.0);
+        let dims = ScreenDimensions::new(800, 600, 1.0);
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(1.0, 2.0, 3.0);
+
+        let ray = camera.screen_ray(Vector2::new(400.0, 300.0), &dims, &transform);
+        assert_abs_diff_eq!(ray.direction, -Vector3::z(), epsilon = 1e-4);
+        assert_abs_diff_eq!(ray.origin, Point3::new(1.0, 2.0, 2.9), epsilon = 1e-4);
+    }
+
+    #[test]
+    fn screen_ray_orthographic_corner() {
+        let camera = Camera::standard_2d(800.0, 600.0);
+        let dims = ScreenDimensions::new(800, 600, 1.0);
+        let transform = Transform::default();
+
+        let ray = camera.screen_ray(Vector2::new(-10.0, 0.0), &dims, &transform);
+        assert_abs_diff_eq!(ray.direction, -Vector3::z(), epsilon = 1e-4);
+        assert_abs_diff_eq!(ray.origin.x, -400.0, epsilon = 1e-2);
+        assert_abs_diff_eq!(ray.origin.y, 300.0, epsilon = 1e-2);
+    }
+
     #[test]
     fn extract_perspective_values() {
         let proj = Perspective::new(1280.0 / 720.0, std::f32::consts::FRAC_PI_3, 0.1, 100.0);
@@ -722,6 +943,32 @@ mod tests {
         assert_ulps_eq!(our_proj.as_matrix(), proj.as_matrix());
     }
 
+    #[test]
+    fn perspective_for_window() {
+        let dimensions = ScreenDimensions::new(1280, 720, 1.0);
+        let camera = Camera::perspective_for(&dimensions, std::f32::consts::FRAC_PI_3, 0.1, 2000.0);
+
+        assert_ulps_eq!(
+            camera.as_matrix(),
+            Camera::standard_3d(1280.0, 720.0).as_matrix()
+        );
+        let perspective = camera.projection().as_perspective().unwrap();
+        assert_ulps_eq!(perspective.aspect(), 1280.0 / 720.0);
+        assert_ulps_eq!(perspective.near(), 0.1);
+    }
+
+    #[test]
+    fn orthographic_depth_range() {
+        let camera = Camera::orthographic(-4.0, 4.0, -3.0, 3.0, 1.0, 11.0);
+
+        let near = camera.as_matrix() * Point3::new(4.0, 3.0, -1.0).to_homogeneous();
+        let far = camera.as_matrix() * Point3::new(-4.0, -3.0, -11.0).to_homogeneous();
+        assert_abs_diff_eq!(near.z / near.w, 0.0);
+        assert_abs_diff_eq!(far.z / far.w, 1.0);
+        assert_abs_diff_eq!(near.x / near.w, 1.0);
+        assert_abs_diff_eq!(far.x / far.w, -1.0);
+    }
+
     #[test]
     fn perspective_orientation() {
         // -w_c <= x_c <= w_c
@@ -925,4 +1172,31 @@ mod tests {
     fn orthographic_project_cube_off_centered_rotated() {
         unimplemented!()
     }
+
+    #[test]
+    fn viewport_columns_cover_framebuffer() {
+        use amethyst_core::ecs::{Builder, World};
+
+        let mut world = World::new();
+        let first = world.create_entity().build();
+        let second = world.create_entity().build();
+        let viewports = CameraViewports::columns(vec![first, second]);
+
+        let rects = viewports
+            .0
+            .iter()
+            .map(|viewport| viewport.rect(801, 600))
+            .collect::<Vec<_>>();
+        assert_eq!(viewports.0[1].entity, second);
+        assert_eq!((rects[0].x, rects[0].y, rects[0].h), (0, 0, 600));
+        assert_eq!(rects[0].x + rects[0].w, rects[1].x);
+        assert_eq!(rects[1].x + rects[1].w, 801);
+    }
+
+    #[test]
+    fn exposure_stops_are_powers_of_two() {
+        assert_eq!(Exposure::from_stops(0.0), Exposure::default());
+        assert!((Exposure::from_stops(2.0).0 - 4.0).abs() < 1e-6);
+        assert!((Exposure::from_stops(-1.0).0 - 0.5).abs() < 1e-6);
+    }
 }
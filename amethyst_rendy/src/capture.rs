@@ -0,0 +1,369 @@
+//! Reading rendered frames back to the CPU, for screenshots and visual regression tests.
+//!
+//! Add a `CaptureFrameDesc` node reading the color image right before it is presented:
+//!
+//! ```ignore
+//! let capture = graph_builder.add_node(
+//!     CaptureFrameDesc::new()
+//!         .builder()
+//!         .with_image(color)
+//!         .with_dependency(pass),
+//! );
+//! graph_builder.add_node(PresentNode::builder(factory, surface, color).with_dependency(capture));
+//! ```
+//!
+//! Then call `CaptureFrame::request` and read the `CapturedFrame` event sent through the
+//! `EventChannel<CapturedFrame>` resource once the GPU is done with the frame, usually a
+//! couple of frames later.
+
+use crate::types::Backend;
+use amethyst_core::{
+    ecs::{Resources, SystemData, Write},
+    shrev::EventChannel,
+};
+use derivative::Derivative;
+use rendy::{
+    command::{
+        CommandBuffer, CommandPool, ExecutableState, Family, Fence, MultiShot, PendingState, Queue,
+        SimultaneousUse, Submission, Submit, Transfer,
+    },
+    factory::Factory,
+    frame::Frames,
+    graph::{
+        gfx_acquire_barriers, gfx_release_barriers, GraphContext, ImageAccess, Node, NodeBuffer,
+        NodeDesc, NodeImage,
+    },
+    hal::{self, format::Format, pso},
+    memory::Download,
+    resource::{Buffer, BufferInfo, Escape},
+};
+
+/// Request a capture of the next frame drawn by `CaptureFrameDesc` nodes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CaptureFrame {
+    requested: bool,
+}
+
+impl CaptureFrame {
+    /// Capture the next frame. Several requests before it is drawn capture it once.
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+
+    /// Whether a capture is requested and not started yet.
+    pub fn is_requested(&self) -> bool {
+        self.requested
+    }
+}
+
+/// Pixels of a captured frame, as displayed.
+#[derive(Clone, Debug)]
+pub struct CapturedFrame {
+    /// Width of the frame in pixels.
+    pub width: u32,
+    /// Height of the frame in pixels.
+    pub height: u32,
+    /// Rows of sRGB encoded RGBA pixels from top to bottom, 4 bytes per pixel.
+    pub rgba: Vec<u8>,
+}
+
+/// Convert the texels of an image of `format`, tightly packed, into RGBA bytes.
+///
+/// 8 bit images are returned as stored, which is how the swapchain displays an image of its
+/// own format: sRGB images hold encoded colors, and the colors of linear images are shown
+/// as written. `None` for other formats.
+pub fn rgba_from_texels(format: Format, texels: &[u8]) -> Option<Vec<u8>> {
+    match format {
+        Format::Rgba8Unorm | Format::Rgba8Srgb => Some(texels.to_vec()),
+        Format::Bgra8Unorm | Format::Bgra8Srgb => Some(
+            texels
+                .chunks_exact(4)
+                .flat_map(|bgra| vec![bgra[2], bgra[1], bgra[0], bgra[3]])
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// Copies its image into host memory when a `CaptureFrame` is requested, sending the
+/// pixels as a `CapturedFrame` event. See the module documentation.
+#[derive(Clone, Debug, Default)]
+pub struct CaptureFrameDesc;
+
+impl CaptureFrameDesc {
+    /// Capture the image passed to the node builder.
+    pub fn new() -> Self {
+        CaptureFrameDesc
+    }
+}
+
+impl<B: Backend> NodeDesc<B, Resources> for CaptureFrameDesc {
+    type Node = CaptureFrameNode<B>;
+
+    fn images(&self) -> Vec<ImageAccess> {
+        vec![ImageAccess {
+            access: hal::image::Access::TRANSFER_READ,
+            usage: hal::image::Usage::TRANSFER_SRC,
+            layout: hal::image::Layout::TransferSrcOptimal,
+            stages: pso::PipelineStage::TRANSFER,
+        }]
+    }
+
+    fn build<'a>(
+        self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        family: &mut Family<B>,
+        _queue: usize,
+        _aux: &Resources,
+        _buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<Self::Node, failure::Error> {
+        assert_eq!(images.len(), 1);
+        let image = ctx
+            .get_image(images[0].id)
+            .expect("Captured image is not created by the graph");
+        let format = image.format();
+        if rgba_from_texels(format, &[]).is_none() {
+            return Err(failure::format_err!(
+                "Frames of format {:?} can not be captured",
+                format
+            ));
+        }
+        let extent = image.kind().extent();
+
+        let mut pool = factory
+            .create_command_pool(family)?
+            .with_capability::<Transfer>()
+            .expect("Graph builder must provide a family with transfer capability");
+
+        let frames = ctx.frames_in_flight() as usize;
+        let mut per_frame = Vec::with_capacity(frames);
+        let mut command_buffers = pool.allocate_buffers(frames * 2).into_iter();
+        for _ in 0..frames {
+            let buffer = factory.create_buffer(
+                BufferInfo {
+                    size: u64::from(extent.width) * u64::from(extent.height) * 4,
+                    usage: hal::buffer::Usage::TRANSFER_DST,
+                },
+                Download,
+            )?;
+
+            // Without a capture, only the layout transitions expected by the graph are done.
+            let mut record = |copy: bool| {
+                let mut recording = command_buffers
+                    .next()
+                    .unwrap()
+                    .begin(MultiShot(SimultaneousUse), ());
+                unsafe {
+                    let mut encoder = recording.encoder();
+                    let (stages, barriers) = gfx_acquire_barriers(ctx, None, &images);
+                    if !barriers.is_empty() {
+                        encoder.pipeline_barrier(
+                            stages,
+                            hal::memory::Dependencies::empty(),
+                            barriers,
+                        );
+                    }
+                    if copy {
+                        encoder.copy_image_to_buffer(
+                            image.raw(),
+                            hal::image::Layout::TransferSrcOptimal,
+                            buffer.raw(),
+                            Some(hal::command::BufferImageCopy {
+                                buffer_offset: 0,
+                                buffer_width: 0,
+                                buffer_height: 0,
+                                image_layers: hal::image::SubresourceLayers {
+                                    aspects: hal::format::Aspects::COLOR,
+                                    level: 0,
+                                    layers: 0..1,
+                                },
+                                image_offset: hal::image::Offset::ZERO,
+                                image_extent: extent,
+                            }),
+                        );
+                        encoder.pipeline_barrier(
+                            pso::PipelineStage::TRANSFER..pso::PipelineStage::HOST,
+                            hal::memory::Dependencies::empty(),
+                            Some(hal::memory::Barrier::Buffer {
+                                states: hal::buffer::Access::TRANSFER_WRITE
+                                    ..hal::buffer::Access::HOST_READ,
+                                target: buffer.raw(),
+                                families: None,
+                                range: None..None,
+                            }),
+                        );
+                    }
+                    let (stages, barriers) = gfx_release_barriers(ctx, None, &images);
+                    if !barriers.is_empty() {
+                        encoder.pipeline_barrier(
+                            stages,
+                            hal::memory::Dependencies::empty(),
+                            barriers,
+                        );
+                    }
+                }
+                recording.finish().submit()
+            };
+            let skip = record(false);
+            let copy = record(true);
+
+            per_frame.push(PerFrameCapture {
+                buffer,
+                skip,
+                copy,
+                pending: None,
+            });
+        }
+
+        Ok(CaptureFrameNode {
+            format,
+            width: extent.width,
+            height: extent.height,
+            pool,
+            per_frame,
+        })
+    }
+}
+
+type CaptureCommands<B> = (
+    Submit<B, SimultaneousUse>,
+    CommandBuffer<B, Transfer, PendingState<ExecutableState<MultiShot<SimultaneousUse>>>>,
+);
+
+/// Node built from `CaptureFrameDesc`.
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""))]
+pub struct CaptureFrameNode<B: Backend> {
+    format: Format,
+    width: u32,
+    height: u32,
+    #[derivative(Debug = "ignore")]
+    pool: CommandPool<B, Transfer>,
+    per_frame: Vec<PerFrameCapture<B>>,
+}
+
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""))]
+struct PerFrameCapture<B: Backend> {
+    buffer: Escape<Buffer<B>>,
+    #[derivative(Debug = "ignore")]
+    skip: CaptureCommands<B>,
+    #[derivative(Debug = "ignore")]
+    copy: CaptureCommands<B>,
+    // Index of the frame copied into the buffer, until it is read back.
+    pending: Option<u64>,
+}
+
+impl<B: Backend> CaptureFrameNode<B> {
+    /// Send the captures of the frames the GPU is done with.
+    fn read_back(&mut self, factory: &Factory<B>, res: &Resources, complete: u64) {
+        let size = u64::from(self.width) * u64::from(self.height) * 4;
+        for frame in &mut self.per_frame {
+            match frame.pending {
+                Some(index) if index < complete => frame.pending = None,
+                _ => continue,
+            }
+
+            let mut mapped = match frame.buffer.map(factory, 0..size) {
+                Ok(mapped) => mapped,
+                Err(e) => {
+                    log::error!("Failed to read back the captured frame: {}", e);
+                    continue;
+                }
+            };
+            let rgba = unsafe {
+                mapped
+                    .read::<u8>(factory, 0..size)
+                    .ok()
+                    .and_then(|texels| rgba_from_texels(self.format, texels))
+            };
+            if let (Some(rgba), Some(mut channel)) = (
+                rgba,
+                <Option<Write<'_, EventChannel<CapturedFrame>>>>::fetch(res),
+            ) {
+                channel.single_write(CapturedFrame {
+                    width: self.width,
+                    height: self.height,
+                    rgba,
+                });
+            }
+        }
+    }
+}
+
+impl<B: Backend> Node<B, Resources> for CaptureFrameNode<B> {
+    type Capability = Transfer;
+    type Desc = CaptureFrameDesc;
+
+    unsafe fn run<'a>(
+        &mut self,
+        _ctx: &GraphContext<B>,
+        factory: &Factory<B>,
+        queue: &mut Queue<B>,
+        aux: &Resources,
+        frames: &Frames<B>,
+        waits: &[(&'a B::Semaphore, pso::PipelineStage)],
+        signals: &[&'a B::Semaphore],
+        fence: Option<&mut Fence<B>>,
+    ) {
+        self.read_back(factory, aux, frames.complete_upper_bound());
+
+        let index = frames.next().index();
+        let requested = <Option<Write<'_, CaptureFrame>>>::fetch(aux)
+            .map_or(false, |mut capture| {
+                std::mem::replace(&mut capture.requested, false)
+            });
+
+        // The graph waits for the frame that last used this slot before running again.
+        let slot = index as usize % self.per_frame.len();
+        let this_frame = &mut self.per_frame[slot];
+        let submit = if requested {
+            this_frame.pending = Some(index);
+            &this_frame.copy.0
+        } else {
+            &this_frame.skip.0
+        };
+
+        queue.submit(
+            Some(
+                Submission::new()
+                    .submits(Some(submit))
+                    .wait(waits.iter().cloned())
+                    .signal(signals.iter()),
+            ),
+            fence,
+        );
+    }
+
+    unsafe fn dispose(mut self, factory: &mut Factory<B>, _aux: &Resources) {
+        let pool = &mut self.pool;
+        for frame in self.per_frame.drain(..) {
+            pool.free_buffers(vec![
+                frame.skip.1.mark_complete(),
+                frame.copy.1.mark_complete(),
+            ]);
+        }
+        factory.destroy_command_pool(self.pool);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bgra_is_swizzled() {
+        let texels = [1, 2, 3, 4, 5, 6, 7, 8];
+        assert_eq!(
+            rgba_from_texels(Format::Bgra8Srgb, &texels),
+            Some(vec![3, 2, 1, 4, 7, 6, 5, 8])
+        );
+        assert_eq!(
+            rgba_from_texels(Format::Rgba8Unorm, &texels),
+            Some(texels.to_vec())
+        );
+        assert_eq!(rgba_from_texels(Format::Rgba16Sfloat, &texels), None);
+    }
+}
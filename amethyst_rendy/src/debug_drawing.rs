@@ -1,7 +1,9 @@
-use crate::pod::IntoPod;
+use crate::{pod::IntoPod, types::Mesh, visibility::Visibility};
+use amethyst_assets::{AssetStorage, Handle};
 use amethyst_core::{
-    ecs::{Component, DenseVecStorage},
-    math::{Point3, Vector3},
+    ecs::{Component, DenseVecStorage, Entities, Join, Read, ReadStorage, System, Write},
+    math::{convert, Matrix4, Point3, Vector3},
+    Hidden, HiddenPropagate, Transform,
 };
 use palette::Srgba;
 use rendy::mesh::{AsVertex, Color, PosColor, VertexFormat};
@@ -94,6 +96,22 @@ impl DebugLinesComponent {
         self.lines.push(vertex);
     }
 
+    /// Adds the twelve edges of the axis aligned box spanning from `min` to `max`.
+    pub fn add_box(&mut self, min: Point3<f32>, max: Point3<f32>, color: Srgba) {
+        let corner = |x: bool, y: bool, z: bool| {
+            Point3::new(
+                if x { max.x } else { min.x },
+                if y { max.y } else { min.y },
+                if z { max.z } else { min.z },
+            )
+        };
+        for &(a, b) in &[(false, false), (true, false), (false, true), (true, true)] {
+            self.add_line(corner(false, a, b), corner(true, a, b), color);
+            self.add_line(corner(a, false, b), corner(a, true, b), color);
+            self.add_line(corner(a, b, false), corner(a, b, true), color);
+        }
+    }
+
     /// Clears lines buffer.
     ///
     /// As lines are persistent, it's necessary to use this function for updating or deleting lines.
@@ -144,7 +162,160 @@ impl DebugLines {
         self.inner.add_line(start, end, color);
     }
 
+    /// Submits the twelve edges of the axis aligned box spanning from `min` to `max`.
+    pub fn draw_box(&mut self, min: Point3<f32>, max: Point3<f32>, color: Srgba) {
+        self.inner.add_box(min, max, color);
+    }
+
     pub(crate) fn drain<'a>(&'a mut self) -> impl Iterator<Item = DebugLine> + 'a {
         self.inner.lines.drain(..)
     }
 }
+
+/// Resource drawing the bounds of every visible mesh with `DrawMeshBoundsSystem` when set,
+/// showing the volumes tested by visibility culling. Off by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrawMeshBounds(pub bool);
+
+/// World space axis aligned box enclosing the model space box from `min` to `max`
+/// transformed by `model`, as `(min, max)`.
+pub fn world_bounds(
+    model: &Matrix4<f32>,
+    min: Vector3<f32>,
+    max: Vector3<f32>,
+) -> (Point3<f32>, Point3<f32>) {
+    let mut world_min = Point3::new(std::f32::MAX, std::f32::MAX, std::f32::MAX);
+    let mut world_max = Point3::new(std::f32::MIN, std::f32::MIN, std::f32::MIN);
+    for corner in 0..8 {
+        let local = Point3::new(
+            if corner & 1 == 0 { min.x } else { max.x },
+            if corner & 2 == 0 { min.y } else { max.y },
+            if corner & 4 == 0 { min.z } else { max.z },
+        );
+        let world = model.transform_point(&local);
+        world_min = world_min.inf(&world);
+        world_max = world_max.sup(&world);
+    }
+    (world_min, world_max)
+}
+
+/// Submits the world space bounds of visible meshes to `DebugLines` while `DrawMeshBounds`
+/// is set, doing nothing otherwise.
+///
+/// Meshes are visible when the `Visibility` resource lists them, or when they are not hidden
+/// if there is none. Run it after `VisibilitySortingSystem`.
+#[derive(Debug)]
+pub struct DrawMeshBoundsSystem {
+    color: Srgba,
+}
+
+impl Default for DrawMeshBoundsSystem {
+    fn default() -> Self {
+        DrawMeshBoundsSystem {
+            color: Srgba::new(0.0, 1.0, 0.0, 1.0),
+        }
+    }
+}
+
+impl DrawMeshBoundsSystem {
+    /// Creates a new `DrawMeshBoundsSystem` drawing green boxes.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Draw the boxes with `color` instead.
+    pub fn with_color(mut self, color: Srgba) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+impl<'a> System<'a> for DrawMeshBoundsSystem {
+    type SystemData = (
+        Read<'a, DrawMeshBounds>,
+        Entities<'a>,
+        Option<Read<'a, Visibility>>,
+        ReadStorage<'a, Hidden>,
+        ReadStorage<'a, HiddenPropagate>,
+        ReadStorage<'a, Handle<Mesh>>,
+        ReadStorage<'a, Transform>,
+        Read<'a, AssetStorage<Mesh>>,
+        Write<'a, DebugLines>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            enabled,
+            entities,
+            visibility,
+            hidden,
+            hidden_prop,
+            handles,
+            transforms,
+            meshes,
+            mut lines,
+        ): Self::SystemData,
+    ) {
+        if !enabled.0 {
+            return;
+        }
+
+        for (entity, handle, transform, _, _) in
+            (&entities, &handles, &transforms, !&hidden, !&hidden_prop).join()
+        {
+            let visible = visibility.as_ref().map_or(true, |visibility| {
+                visibility.visible_unordered.contains(entity.id())
+                    || visibility.visible_ordered.contains(&entity)
+            });
+            if !visible {
+                continue;
+            }
+            if let Some((min, max)) = meshes.get(handle).and_then(Mesh::bounds) {
+                let (min, max) = world_bounds(&convert(*transform.global_matrix()), min, max);
+                lines.draw_box(min, max, self.color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn box_has_twelve_distinct_edges() {
+        let mut lines = DebugLinesComponent::new();
+        lines.add_box(
+            Point3::new(-1.0, 0.0, 2.0),
+            Point3::new(1.0, 3.0, 4.0),
+            Srgba::new(1.0, 1.0, 1.0, 1.0),
+        );
+
+        let edges = lines.lines();
+        assert_eq!(edges.len(), 12);
+        for (i, edge) in edges.iter().enumerate() {
+            let [sx, sy, sz] = edge.start.position.0;
+            let [ex, ey, ez] = edge.end.position.0;
+            let changed = [sx != ex, sy != ey, sz != ez];
+            assert_eq!(changed.iter().filter(|&&c| c).count(), 1);
+            assert!(edges[..i].iter().all(|other| other != edge));
+        }
+    }
+
+    #[test]
+    fn world_bounds_enclose_rotated_box() {
+        let model = Matrix4::new_translation(&Vector3::new(10.0, 0.0, 0.0))
+            * Matrix4::from_euler_angles(0.0, 0.0, std::f32::consts::FRAC_PI_4);
+        let (min, max) = world_bounds(
+            &model,
+            Vector3::new(-1.0, -1.0, -2.0),
+            Vector3::new(1.0, 1.0, 2.0),
+        );
+
+        let half_diagonal = 2.0f32.sqrt();
+        assert!((min - Point3::new(10.0 - half_diagonal, -half_diagonal, -2.0)).norm() < 1e-5);
+        assert!((max - Point3::new(10.0 + half_diagonal, half_diagonal, 2.0)).norm() < 1e-5);
+    }
+}
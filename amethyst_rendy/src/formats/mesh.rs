@@ -29,12 +29,30 @@ impl Format<MeshData> for ObjFormat {
                 if iter.next().is_some() {
                     log::warn!("OBJ file contains more than one object, only loading the first");
                 }
-                builder.0.into()
+                MeshData::with_positions(builder.0, obj_positions(&bytes))
             })
             .map_err(|e| e.compat().into())
     }
 }
 
+/// Positions of all vertices declared in an OBJ file.
+///
+/// Vertices are shared by every object in the file, so bounds of the first
+/// object computed from these may be larger than necessary.
+fn obj_positions(bytes: &[u8]) -> Vec<[f32; 3]> {
+    String::from_utf8_lossy(bytes)
+        .lines()
+        .filter_map(|line| {
+            let mut tokens = line.split_whitespace();
+            if tokens.next() != Some("v") {
+                return None;
+            }
+            let mut coord = || tokens.next()?.parse::<f32>().ok();
+            Some([coord()?, coord()?, coord()?])
+        })
+        .collect()
+}
+
 /// Internal mesh loading
 ///
 /// ### Type parameters:
@@ -7,6 +7,7 @@ use crate::{
 use amethyst_assets::{AssetStorage, Handle, Loader, PrefabData, ProgressCounter};
 use amethyst_core::ecs::prelude::{Entity, Read, ReadExpect, WriteStorage};
 use amethyst_error::Error;
+use palette::{Srgb, Srgba};
 use serde::{Deserialize, Serialize};
 
 /// `PrefabData` for loading `Material`s
@@ -15,22 +16,40 @@ use serde::{Deserialize, Serialize};
 pub struct MaterialPrefab {
     /// Diffuse map.
     pub albedo: Option<TexturePrefab>,
+    /// Color multiplied with the diffuse map.
+    #[serde(with = "crate::serde_shim::srgba")]
+    pub base_color: Srgba,
     /// Emission map.
     pub emission: Option<TexturePrefab>,
+    /// Color multiplied with the emission map.
+    #[serde(with = "crate::serde_shim::srgb")]
+    pub emission_factor: Srgb,
+    /// Multiplier of the emission map.
+    pub emission_intensity: f32,
     /// Normal map.
     pub normal: Option<TexturePrefab>,
     /// Metallic-roughness map. (B channel metallic, G channel roughness)
     pub metallic_roughness: Option<TexturePrefab>,
+    /// Multiplier of the metallic channel, the factor of `MaterialDefaults` when `None`.
+    pub metallic_factor: Option<f32>,
+    /// Multiplier of the roughness channel, the factor of `MaterialDefaults` when `None`.
+    pub roughness_factor: Option<f32>,
     /// Ambient occlusion map.
     pub ambient_occlusion: Option<TexturePrefab>,
     /// Cavity map.
     pub cavity: Option<TexturePrefab>,
     /// Texture offset.
     pub uv_offset: TextureOffset,
+    /// Offset added to the texture coordinates.
+    pub uv_scroll: [f32; 2],
+    /// Factor the texture coordinates are multiplied by.
+    pub uv_scale: [f32; 2],
     /// Set material as `Transparent`
     pub transparent: bool,
     /// Alpha cutoff: the value below which we do not draw the pixel
     pub alpha_cutoff: f32,
+    /// Light back faces as if they were facing the other way.
+    pub double_sided: bool,
     /// Clone handle only
     #[serde(skip)]
     handle: Option<Handle<Material>>,
@@ -52,14 +71,22 @@ impl Default for MaterialPrefab {
     fn default() -> Self {
         MaterialPrefab {
             albedo: None,
+            base_color: Srgba::new(1.0, 1.0, 1.0, 1.0),
             emission: None,
+            emission_factor: Srgb::new(1.0, 1.0, 1.0),
+            emission_intensity: 1.0,
             normal: None,
             metallic_roughness: None,
+            metallic_factor: None,
+            roughness_factor: None,
             ambient_occlusion: None,
             cavity: None,
             uv_offset: TextureOffset::default(),
+            uv_scroll: [0.0, 0.0],
+            uv_scale: [1.0, 1.0],
             transparent: false,
             alpha_cutoff: std::f32::MIN_POSITIVE,
+            double_sided: false,
             handle: None,
         }
     }
@@ -142,19 +169,31 @@ impl<'a> PrefabData<'a> for MaterialPrefab {
         if self.handle.is_none() {
             let mtl = Material {
                 albedo: load_handle(&self.albedo, &mat_default.0.albedo),
+                base_color: self.base_color,
                 emission: load_handle(&self.emission, &mat_default.0.emission),
+                emission_factor: self.emission_factor,
+                emission_intensity: self.emission_intensity,
                 normal: load_handle(&self.normal, &mat_default.0.normal),
                 metallic_roughness: load_handle(
                     &self.metallic_roughness,
                     &mat_default.0.metallic_roughness,
                 ),
+                metallic_factor: self
+                    .metallic_factor
+                    .unwrap_or(mat_default.0.metallic_factor),
+                roughness_factor: self
+                    .roughness_factor
+                    .unwrap_or(mat_default.0.roughness_factor),
                 ambient_occlusion: load_handle(
                     &self.ambient_occlusion,
                     &mat_default.0.ambient_occlusion,
                 ),
                 cavity: load_handle(&self.cavity, &mat_default.0.cavity),
                 uv_offset: self.uv_offset.clone(),
+                uv_scroll: self.uv_scroll,
+                uv_scale: self.uv_scale,
                 alpha_cutoff: self.alpha_cutoff,
+                double_sided: self.double_sided,
             };
 
             self.handle
@@ -5,7 +5,7 @@ use amethyst_error::Error;
 use rendy::{
     hal::{
         self,
-        image::{Filter, Kind, Size, ViewKind},
+        image::{Filter, Kind, Size, ViewKind, WrapMode},
     },
     texture::{
         image::{load_from_image, ImageTextureConfig},
@@ -15,6 +15,10 @@ use rendy::{
 };
 use serde::{Deserialize, Serialize};
 
+/// Image file format, loading the texture with the options of `ImageTextureConfig`.
+///
+/// The default config samples the nearest pixel of a single mip level and tiles the
+/// texture. The `with_*` methods change the sampler the texture is bound with.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(transparent)]
 pub struct ImageFormat(pub ImageTextureConfig);
@@ -22,7 +26,7 @@ pub struct ImageFormat(pub ImageTextureConfig);
 impl Default for ImageFormat {
     fn default() -> Self {
         use rendy::{
-            hal::image::{Anisotropic, PackedColor, SamplerInfo, WrapMode},
+            hal::image::{Anisotropic, PackedColor, SamplerInfo},
             texture::image::{Repr, TextureKind},
         };
 
@@ -49,6 +53,45 @@ impl Default for ImageFormat {
     }
 }
 
+impl ImageFormat {
+    /// Sample the texture with `filter` when it is minified or magnified, instead of
+    /// picking the nearest pixel.
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.0.sampler_info.min_filter = filter;
+        self.0.sampler_info.mag_filter = filter;
+        self
+    }
+
+    /// Generate the mip levels of the texture when it is loaded, and blend between
+    /// them with `filter`. Removes the shimmering of distant, detailed surfaces.
+    pub fn with_mipmaps(mut self, filter: Filter) -> Self {
+        self.0.generate_mips = true;
+        self.0.sampler_info.mip_filter = filter;
+        self
+    }
+
+    /// Filter the texture anisotropically with up to `samples` samples, which keeps
+    /// surfaces viewed at grazing angles sharp. `0` or `1` disables it.
+    ///
+    /// Requires a device supporting anisotropic filtering.
+    pub fn with_anisotropy(mut self, samples: u8) -> Self {
+        use rendy::hal::image::Anisotropic;
+
+        self.0.sampler_info.anisotropic = if samples > 1 {
+            Anisotropic::On(samples)
+        } else {
+            Anisotropic::Off
+        };
+        self
+    }
+
+    /// How texture coordinates outside of `[0, 1]` are handled, `WrapMode::Tile` by default.
+    pub fn with_wrap_mode(mut self, wrap_mode: WrapMode) -> Self {
+        self.0.sampler_info.wrap_mode = (wrap_mode, wrap_mode, wrap_mode);
+        self
+    }
+}
+
 amethyst_assets::register_format_type!(TextureData);
 
 amethyst_assets::register_format!("IMAGE", ImageFormat as TextureData);
@@ -0,0 +1,424 @@
+//! GPU timing of render groups, enabled with the `gpu-profiler` feature.
+//!
+//! Wrap a group description with `GpuProfiledDesc` to bracket its drawing with timestamp
+//! queries. `RenderingSystem` collects the timestamps once the GPU is done with them and
+//! stores the time spent by every wrapped group in the `GpuProfilerStats` resource:
+//!
+//! ```ignore
+//! SubpassBuilder::new()
+//!     .with_group(GpuProfiledDesc::new("pbr", DrawPbrDesc::new()).builder())
+//!     .with_group(GpuProfiledDesc::new("skybox", DrawSkyboxDesc::new()).builder())
+//! ```
+//!
+//! Timestamps are taken at the top and bottom of the pipeline around the draw calls of the
+//! group, so groups sharing a subpass may overlap on the GPU.
+
+use crate::types::Backend;
+use amethyst_core::ecs::Resources;
+use fnv::FnvHashMap;
+use rendy::{
+    command::{Families, QueueId, RenderPassEncoder},
+    factory::Factory,
+    graph::{
+        render::{PrepareResult, RenderGroup, RenderGroupDesc},
+        BufferAccess, GraphContext, ImageAccess, NodeBuffer, NodeImage,
+    },
+    hal::{self, command::RawCommandBuffer, device::Device, pool::RawCommandPool, pso, query},
+};
+
+/// Largest number of profiled render groups.
+pub const MAX_PROFILED_GROUPS: usize = 64;
+
+/// Number of frames whose timestamps can be in flight at once, by framebuffer index.
+const FRAMES: usize = 4;
+
+/// Time spent by the GPU drawing each profiled render group, in milliseconds.
+#[derive(Clone, Debug, Default)]
+pub struct GpuProfilerStats {
+    groups: FnvHashMap<String, f32>,
+}
+
+impl GpuProfilerStats {
+    /// Latest time measured for the group profiled as `name`.
+    pub fn get(&self, name: &str) -> Option<f32> {
+        self.groups.get(name).cloned()
+    }
+
+    /// Iterate over the names of the profiled groups and their latest times.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, f32)> {
+        self.groups
+            .iter()
+            .map(|(name, &time)| (name.as_str(), time))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SlotState {
+    /// The queries have to be reset before being written.
+    Stale,
+    /// A reset was submitted, the queries can be written.
+    Ready,
+    /// The queries were written by a frame that may still be running.
+    Written,
+}
+
+/// Timestamp queries shared by the profiled render groups.
+///
+/// Inserted by `RenderingSystem`, which resets the queries between frames.
+#[derive(Debug)]
+pub struct GpuTimestamps<B: Backend> {
+    pool: B::QueryPool,
+    names: Vec<String>,
+    slots: Vec<SlotState>,
+    period: f32,
+}
+
+impl<B: Backend> GpuTimestamps<B> {
+    pub(crate) fn new(factory: &Factory<B>) -> Result<Self, failure::Error> {
+        let count = (FRAMES * MAX_PROFILED_GROUPS * 2) as query::Id;
+        let pool = unsafe {
+            factory
+                .device()
+                .create_query_pool(query::Type::Timestamp, count)
+        }
+        .map_err(|e| failure::format_err!("Failed to create timestamp queries: {:?}", e))?;
+
+        Ok(GpuTimestamps {
+            pool,
+            names: Vec::new(),
+            slots: vec![SlotState::Stale; FRAMES * MAX_PROFILED_GROUPS],
+            period: 1.0,
+        })
+    }
+
+    /// Nanoseconds between two ticks of the timestamp counter, `1.0` by default.
+    ///
+    /// Matches most desktop GPUs. Check the timestamp period reported by the driver
+    /// when times look off.
+    pub fn set_timestamp_period(&mut self, period: f32) {
+        self.period = period;
+    }
+
+    /// Scope of the group profiled as `name`, `None` once `MAX_PROFILED_GROUPS` are in use.
+    fn register(&mut self, name: &str) -> Option<usize> {
+        if let Some(scope) = self.names.iter().position(|n| n == name) {
+            return Some(scope);
+        }
+        if self.names.len() == MAX_PROFILED_GROUPS {
+            return None;
+        }
+        self.names.push(name.to_owned());
+        Some(self.names.len() - 1)
+    }
+
+    /// Forget the registered scopes and mark every query stale, keeping the query pool.
+    pub(crate) fn clear(&mut self) {
+        self.names.clear();
+        for state in &mut self.slots {
+            *state = SlotState::Stale;
+        }
+    }
+
+    fn slot(scope: usize, index: usize) -> usize {
+        (index % FRAMES) * MAX_PROFILED_GROUPS + scope
+    }
+
+    fn queries(slot: usize) -> std::ops::Range<query::Id> {
+        (slot * 2) as query::Id..(slot * 2 + 2) as query::Id
+    }
+
+    /// Read the timestamps of finished frames into `stats` and mark their queries stale.
+    fn collect(&mut self, factory: &Factory<B>, stats: &mut GpuProfilerStats) {
+        for (slot, state) in self.slots.iter_mut().enumerate() {
+            if *state != SlotState::Written {
+                continue;
+            }
+            let mut data = [0u64; 2];
+            let available = unsafe {
+                factory.device().get_query_pool_results(
+                    &self.pool,
+                    Self::queries(slot),
+                    std::slice::from_raw_parts_mut(data.as_mut_ptr() as *mut u8, 16),
+                    8,
+                    query::ResultFlags::BITS_64,
+                )
+            };
+            if let Ok(true) = available {
+                let ticks = data[1].saturating_sub(data[0]);
+                let name = &self.names[slot % MAX_PROFILED_GROUPS];
+                stats
+                    .groups
+                    .insert(name.clone(), ticks as f32 * self.period / 1_000_000.0);
+                *state = SlotState::Stale;
+            }
+        }
+    }
+
+    /// Record the reset of stale queries, which are ready once the commands are submitted.
+    ///
+    /// Returns `false` when no query needed a reset.
+    unsafe fn reset(&mut self, buffer: &mut B::CommandBuffer) -> bool {
+        let mut any = false;
+        for (slot, state) in self.slots.iter_mut().enumerate() {
+            if *state == SlotState::Stale {
+                buffer.reset_query_pool(&self.pool, Self::queries(slot));
+                *state = SlotState::Ready;
+                any = true;
+            }
+        }
+        any
+    }
+
+    pub(crate) fn dispose(self, factory: &Factory<B>) {
+        unsafe {
+            factory.device().destroy_query_pool(self.pool);
+        }
+    }
+}
+
+/// Resets the timestamp queries outside of the render passes, ahead of every frame.
+#[derive(Debug)]
+pub(crate) struct GpuProfiler<B: Backend> {
+    pool: B::CommandPool,
+    buffer: B::CommandBuffer,
+    fence: B::Fence,
+    submitted: bool,
+}
+
+impl<B: Backend> GpuProfiler<B> {
+    pub(crate) fn new(factory: &Factory<B>, queue: QueueId) -> Result<Self, failure::Error> {
+        unsafe {
+            let mut pool = factory.device().create_command_pool(
+                queue.family,
+                hal::pool::CommandPoolCreateFlags::RESET_INDIVIDUAL,
+            )?;
+            let buffer = pool.allocate_one(hal::command::RawLevel::Primary);
+            let fence = factory.device().create_fence(false)?;
+            Ok(GpuProfiler {
+                pool,
+                buffer,
+                fence,
+                submitted: false,
+            })
+        }
+    }
+
+    /// Collect the timestamps of finished frames and reset their queries before the
+    /// next frame is submitted on `queue`.
+    pub(crate) fn maintain(
+        &mut self,
+        factory: &Factory<B>,
+        families: &mut Families<B>,
+        queue: QueueId,
+        res: &Resources,
+    ) {
+        let mut timestamps = res.fetch_mut::<GpuTimestamps<B>>();
+        timestamps.collect(factory, &mut res.fetch_mut::<GpuProfilerStats>());
+
+        unsafe {
+            if self.submitted {
+                // Only waits for the reset submitted before the previous frame.
+                if let Err(e) = factory.device().wait_for_fence(&self.fence, !0) {
+                    log::error!("Failed to wait for timestamp query reset: {:?}", e);
+                    return;
+                }
+                let _ = factory.device().reset_fence(&self.fence);
+                self.buffer.reset(false);
+                self.submitted = false;
+            }
+
+            self.buffer.begin(
+                hal::command::CommandBufferFlags::ONE_TIME_SUBMIT,
+                hal::command::CommandBufferInheritanceInfo::default(),
+            );
+            let any = timestamps.reset(&mut self.buffer);
+            self.buffer.finish();
+            if !any {
+                self.buffer.reset(false);
+                return;
+            }
+
+            let queue = families
+                .family_by_id_mut(queue.family)
+                .queue_mut(queue.index);
+            queue.raw().submit(
+                hal::queue::Submission {
+                    command_buffers: Some(&self.buffer),
+                    wait_semaphores: std::iter::empty::<(&B::Semaphore, pso::PipelineStage)>(),
+                    signal_semaphores: std::iter::empty::<&B::Semaphore>(),
+                },
+                Some(&self.fence),
+            );
+            self.submitted = true;
+        }
+    }
+
+    pub(crate) fn dispose(mut self, factory: &Factory<B>) {
+        unsafe {
+            if self.submitted {
+                let _ = factory.device().wait_for_fence(&self.fence, !0);
+            }
+            self.pool.free(Some(self.buffer));
+            factory.device().destroy_command_pool(self.pool);
+            factory.device().destroy_fence(self.fence);
+        }
+    }
+}
+
+/// Profile the GPU time of the group described by `D` as `name`.
+#[derive(Clone, Debug)]
+pub struct GpuProfiledDesc<D> {
+    name: String,
+    inner: D,
+}
+
+impl<D> GpuProfiledDesc<D> {
+    /// Report the time spent drawing the group described by `inner` as `name`.
+    pub fn new(name: impl Into<String>, inner: D) -> Self {
+        GpuProfiledDesc {
+            name: name.into(),
+            inner,
+        }
+    }
+}
+
+impl<B, D> RenderGroupDesc<B, Resources> for GpuProfiledDesc<D>
+where
+    B: Backend,
+    D: RenderGroupDesc<B, Resources>,
+{
+    fn buffers(&self) -> Vec<BufferAccess> {
+        self.inner.buffers()
+    }
+
+    fn images(&self) -> Vec<ImageAccess> {
+        self.inner.images()
+    }
+
+    fn depth(&self) -> bool {
+        self.inner.depth()
+    }
+
+    fn colors(&self) -> usize {
+        self.inner.colors()
+    }
+
+    fn build(
+        self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        queue: QueueId,
+        aux: &Resources,
+        framebuffer_width: u32,
+        framebuffer_height: u32,
+        subpass: hal::pass::Subpass<'_, B>,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<Box<dyn RenderGroup<B, Resources>>, failure::Error> {
+        // Missing when the device can't create timestamp queries.
+        let scope = aux
+            .try_fetch_mut::<GpuTimestamps<B>>()
+            .and_then(|mut timestamps| {
+                let scope = timestamps.register(&self.name);
+                if scope.is_none() {
+                    log::warn!(
+                        "More than {} render groups are profiled, `{}` is not",
+                        MAX_PROFILED_GROUPS,
+                        self.name
+                    );
+                }
+                scope
+            });
+
+        let inner = self.inner.build(
+            ctx,
+            factory,
+            queue,
+            aux,
+            framebuffer_width,
+            framebuffer_height,
+            subpass,
+            buffers,
+            images,
+        )?;
+
+        Ok(Box::new(GpuProfiled { scope, inner }))
+    }
+}
+
+/// Render group timing the drawing of the group it wraps.
+#[derive(Debug)]
+pub struct GpuProfiled<B: Backend> {
+    scope: Option<usize>,
+    inner: Box<dyn RenderGroup<B, Resources>>,
+}
+
+impl<B: Backend> RenderGroup<B, Resources> for GpuProfiled<B> {
+    fn prepare(
+        &mut self,
+        factory: &Factory<B>,
+        queue: QueueId,
+        index: usize,
+        subpass: hal::pass::Subpass<'_, B>,
+        resources: &Resources,
+    ) -> PrepareResult {
+        // Timestamps are written every frame, so the commands are always recorded again.
+        match self
+            .inner
+            .prepare(factory, queue, index, subpass, resources)
+        {
+            PrepareResult::DrawReuse => PrepareResult::DrawRecord,
+            result => result,
+        }
+    }
+
+    fn draw_inline(
+        &mut self,
+        mut encoder: RenderPassEncoder<'_, B>,
+        index: usize,
+        subpass: hal::pass::Subpass<'_, B>,
+        resources: &Resources,
+    ) {
+        let slot = self.scope.and_then(|scope| {
+            let mut timestamps = resources.fetch_mut::<GpuTimestamps<B>>();
+            let slot = GpuTimestamps::<B>::slot(scope, index);
+            if timestamps.slots[slot] != SlotState::Ready {
+                return None;
+            }
+            timestamps.slots[slot] = SlotState::Written;
+            Some(slot)
+        });
+
+        match slot {
+            None => self.inner.draw_inline(encoder, index, subpass, resources),
+            Some(slot) => {
+                let timestamps = resources.fetch::<GpuTimestamps<B>>();
+                let queries = GpuTimestamps::<B>::queries(slot);
+                unsafe {
+                    encoder.raw().write_timestamp(
+                        pso::PipelineStage::TOP_OF_PIPE,
+                        query::Query {
+                            pool: &timestamps.pool,
+                            id: queries.start,
+                        },
+                    );
+                }
+                self.inner
+                    .draw_inline(encoder.reborrow(), index, subpass, resources);
+                unsafe {
+                    encoder.raw().write_timestamp(
+                        pso::PipelineStage::BOTTOM_OF_PIPE,
+                        query::Query {
+                            pool: &timestamps.pool,
+                            id: queries.start + 1,
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    fn dispose(self: Box<Self>, factory: &mut Factory<B>, aux: &Resources) {
+        self.inner.dispose(factory, aux);
+    }
+}
@@ -0,0 +1,235 @@
+//! Wrapper render group that records user commands around another group's draw.
+
+use crate::types::Backend;
+use amethyst_core::ecs::Resources;
+use derivative::Derivative;
+use rendy::{
+    command::{QueueId, RenderPassEncoder},
+    factory::Factory,
+    graph::{
+        render::{PrepareResult, RenderGroup, RenderGroupDesc},
+        BufferAccess, GraphContext, ImageAccess, NodeBuffer, NodeImage,
+    },
+    hal,
+};
+use std::sync::Arc;
+
+/// Callback recording extra commands into the render pass of a hooked group.
+///
+/// Receives the encoder, the frame index and the world resources.
+pub type DrawHook<B> = Arc<dyn Fn(&mut RenderPassEncoder<'_, B>, usize, &Resources) + Send + Sync>;
+
+/// Wraps a render group description, running hooks right before and after its draw.
+///
+/// Hooks are recorded together with the wrapped group, so they only run again
+/// when the group asks for its commands to be recorded.
+/// When no hook is registered the wrapped group is built as-is.
+#[derive(Derivative)]
+#[derivative(Debug(bound = "G: std::fmt::Debug"))]
+pub struct HookedGroupDesc<B: Backend, G> {
+    inner: G,
+    #[derivative(Debug = "ignore")]
+    before: Option<DrawHook<B>>,
+    #[derivative(Debug = "ignore")]
+    after: Option<DrawHook<B>>,
+}
+
+impl<B: Backend, G> HookedGroupDesc<B, G> {
+    /// Wrap a render group description without any hooks.
+    pub fn new(inner: G) -> Self {
+        Self {
+            inner,
+            before: None,
+            after: None,
+        }
+    }
+
+    /// Record commands before the wrapped group draws.
+    pub fn with_before_draw<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&mut RenderPassEncoder<'_, B>, usize, &Resources) + Send + Sync + 'static,
+    {
+        self.before = Some(Arc::new(hook));
+        self
+    }
+
+    /// Record commands after the wrapped group draws.
+    pub fn with_after_draw<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&mut RenderPassEncoder<'_, B>, usize, &Resources) + Send + Sync + 'static,
+    {
+        self.after = Some(Arc::new(hook));
+        self
+    }
+
+    /// Whether any hook is registered.
+    pub fn has_hooks(&self) -> bool {
+        self.before.is_some() || self.after.is_some()
+    }
+}
+
+impl<B, G> RenderGroupDesc<B, Resources> for HookedGroupDesc<B, G>
+where
+    B: Backend,
+    G: RenderGroupDesc<B, Resources>,
+{
+    fn buffers(&self) -> Vec<BufferAccess> {
+        self.inner.buffers()
+    }
+
+    fn images(&self) -> Vec<ImageAccess> {
+        self.inner.images()
+    }
+
+    fn depth(&self) -> bool {
+        self.inner.depth()
+    }
+
+    fn colors(&self) -> usize {
+        self.inner.colors()
+    }
+
+    fn build(
+        self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        queue: QueueId,
+        aux: &Resources,
+        framebuffer_width: u32,
+        framebuffer_height: u32,
+        subpass: hal::pass::Subpass<'_, B>,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<Box<dyn RenderGroup<B, Resources>>, failure::Error> {
+        let has_hooks = self.has_hooks();
+        let inner = self.inner.build(
+            ctx,
+            factory,
+            queue,
+            aux,
+            framebuffer_width,
+            framebuffer_height,
+            subpass,
+            buffers,
+            images,
+        )?;
+
+        if !has_hooks {
+            return Ok(inner);
+        }
+
+        Ok(Box::new(HookedGroup {
+            inner,
+            before: self.before,
+            after: self.after,
+        }))
+    }
+}
+
+/// Render group built from `HookedGroupDesc`.
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""))]
+pub struct HookedGroup<B: Backend> {
+    inner: Box<dyn RenderGroup<B, Resources>>,
+    #[derivative(Debug = "ignore")]
+    before: Option<DrawHook<B>>,
+    #[derivative(Debug = "ignore")]
+    after: Option<DrawHook<B>>,
+}
+
+impl<B: Backend> RenderGroup<B, Resources> for HookedGroup<B> {
+    fn prepare(
+        &mut self,
+        factory: &Factory<B>,
+        queue: QueueId,
+        index: usize,
+        subpass: hal::pass::Subpass<'_, B>,
+        resources: &Resources,
+    ) -> PrepareResult {
+        self.inner
+            .prepare(factory, queue, index, subpass, resources)
+    }
+
+    fn draw_inline(
+        &mut self,
+        mut encoder: RenderPassEncoder<'_, B>,
+        index: usize,
+        subpass: hal::pass::Subpass<'_, B>,
+        resources: &Resources,
+    ) {
+        let inner = &mut self.inner;
+        draw_with_hooks(
+            &mut encoder,
+            (self.before.as_ref(), self.after.as_ref()),
+            |hook, encoder| hook(encoder, index, resources),
+            |encoder| inner.draw_inline(encoder.reborrow(), index, subpass, resources),
+        );
+    }
+
+    fn dispose(self: Box<Self>, factory: &mut Factory<B>, aux: &Resources) {
+        self.inner.dispose(factory, aux);
+    }
+}
+
+/// Run the `before` hook, then `draw`, then the `after` hook, on the same encoder.
+fn draw_with_hooks<E, H>(
+    encoder: &mut E,
+    (before, after): (Option<&H>, Option<&H>),
+    run_hook: impl Fn(&H, &mut E),
+    draw: impl FnOnce(&mut E),
+) {
+    if let Some(before) = before {
+        run_hook(before, encoder);
+    }
+    draw(encoder);
+    if let Some(after) = after {
+        run_hook(after, encoder);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pass::DrawFlatDesc;
+    use rendy::empty::Backend as EmptyBackend;
+
+    #[test]
+    fn no_hooks_by_default() {
+        let desc = HookedGroupDesc::<EmptyBackend, _>::new(DrawFlatDesc::<EmptyBackend>::new());
+        assert!(!desc.has_hooks());
+    }
+
+    #[test]
+    fn noop_marker_keeps_attachments() {
+        let inner = DrawFlatDesc::<EmptyBackend>::new();
+        let (depth, colors) = (inner.depth(), inner.colors());
+
+        let desc = HookedGroupDesc::<EmptyBackend, _>::new(inner)
+            .with_before_draw(|_encoder, _index, _res| {})
+            .with_after_draw(|_encoder, _index, _res| {});
+
+        assert!(desc.has_hooks());
+        assert_eq!(desc.depth(), depth);
+        assert_eq!(desc.colors(), colors);
+    }
+
+    #[test]
+    fn registered_hooks_run_around_the_draw() {
+        let record = |hook: &&'static str, calls: &mut Vec<&'static str>| calls.push(*hook);
+
+        let mut calls = Vec::new();
+        draw_with_hooks(
+            &mut calls,
+            (Some(&"before"), Some(&"after")),
+            record,
+            |calls| calls.push("draw"),
+        );
+        assert_eq!(calls, ["before", "draw", "after"]);
+
+        let mut calls = Vec::new();
+        draw_with_hooks(&mut calls, (None, Some(&"after")), record, |calls| {
+            calls.push("draw")
+        });
+        assert_eq!(calls, ["draw", "after"]);
+    }
+}
@@ -17,15 +17,24 @@ pub use rendy;
 pub mod pass;
 
 pub mod batch;
+pub mod blit;
 pub mod camera;
+pub mod capture;
 pub mod debug_drawing;
 pub mod error;
 pub mod formats;
+#[cfg(feature = "gpu-profiler")]
+pub mod gpu_profiler;
+pub mod hook;
 pub mod light;
 pub mod mtl;
+pub mod particle;
 pub mod pipeline;
+pub mod render_target;
 pub mod resources;
 pub mod serde_shim;
+#[cfg(feature = "shader-hot-reload")]
+pub mod shader_reload;
 pub mod shape;
 pub mod skinning;
 pub mod sprite;
@@ -42,9 +51,9 @@ pub mod util;
 pub use formats::{mesh::MeshPrefab, texture::TexturePrefab};
 pub use mtl::{Material, MaterialDefaults};
 pub use sprite::{Sprite, SpriteRender, SpriteSheet};
-pub use system::{GraphCreator, RenderingSystem};
+pub use system::{GraphCreator, RendererRecovered, RenderingSystem};
 pub use types::{Backend, Mesh, Texture};
-pub use util::{simple_shader_set, ChangeDetection};
+pub use util::{simple_shader_modules, simple_shader_set, ChangeDetection};
 
 pub mod loaders {
     pub use rendy::texture::palette::{load_from_linear_rgba, load_from_srgb, load_from_srgba};
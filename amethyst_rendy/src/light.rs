@@ -2,10 +2,10 @@
 //!
 //! TODO: Remove redundant padding once `#[repr(align(...))]` stabilizes.
 
-use crate::resources::AmbientColor;
+use crate::resources::{AmbientColor, Fog};
 use amethyst_assets::{PrefabData, ProgressCounter};
 use amethyst_core::{
-    ecs::prelude::{Component, DenseVecStorage, Entity, WriteStorage},
+    ecs::prelude::{Component, DenseVecStorage, Entity, NullStorage, WriteStorage},
     math::Vector3,
 };
 use amethyst_error::Error;
@@ -14,9 +14,8 @@ use amethyst_error::Error;
 #[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize, PrefabData)]
 #[prefab(Component)]
 pub enum Light {
-    /// An area light.
-    /// FIXME: Missing implementation!
-    Area,
+    /// A rectangular area light.
+    Area(AreaLight),
     /// A directional light.
     Directional(DirectionalLight),
     /// A point light.
@@ -87,6 +86,12 @@ pub struct PointLight {
     /// Brightness of the light source, in lumens.
     pub intensity: f32,
     /// Maximum radius of the point light's affected area.
+    ///
+    /// Zero or less leaves the light unbounded, only falling off with the inverse square
+    /// of the distance. Defaults to `0.0`.
+    ///
+    /// The radius used to be ignored by the shaders and defaulted to `10.0`. Lights that set
+    /// it explicitly now go dark past it, clear it to keep the unbounded falloff.
     pub radius: f32,
     /// Smoothness of the light-to-dark transition from the center to the
     /// radius.
@@ -98,7 +103,7 @@ impl Default for PointLight {
         PointLight {
             color: Default::default(),
             intensity: 10.0,
-            radius: 10.0,
+            radius: 0.0,
             smoothness: 4.0,
         }
     }
@@ -111,12 +116,19 @@ impl From<PointLight> for Light {
 }
 
 /// A spot light source.
+///
+/// The light is at full brightness inside the cone of `inner_angle` and fades out
+/// smoothly towards the edge of the cone of `angle`.
 #[repr(C)]
 #[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
 #[serde(default)]
 pub struct SpotLight {
     /// Opening angle of the light cone in radians.
     pub angle: f32,
+    /// Opening angle of the fully lit inner cone in radians, at most `angle`.
+    ///
+    /// Defaults to `None`, using `DEFAULT_SPOT_INNER_RATIO` of `angle`.
+    pub inner_angle: Option<f32>,
     /// Color of the light in SRGB format.
     #[serde(with = "crate::serde_shim::srgb")]
     pub color: palette::Srgb,
@@ -126,30 +138,81 @@ pub struct SpotLight {
     pub intensity: f32,
     /// Range/length of the light source.
     pub range: f32,
-    /// Smoothness of the light-to-dark transition from the center to the
-    /// radius.
-    pub smoothness: f32,
 }
 
+/// Fraction of a spot light's `angle` used for its inner cone when none is set.
+pub const DEFAULT_SPOT_INNER_RATIO: f32 = 0.75;
+
 impl Default for SpotLight {
     fn default() -> Self {
         SpotLight {
             angle: std::f32::consts::FRAC_PI_3,
+            inner_angle: None,
             color: Default::default(),
             direction: [0.0, -1.0, 0.0].into(),
             intensity: 10.0,
             range: 10.0,
-            smoothness: 4.0,
         }
     }
 }
 
+impl SpotLight {
+    /// Opening angle of the fully lit inner cone, clamped to the outer `angle`.
+    pub fn inner_cone_angle(&self) -> f32 {
+        self.inner_angle
+            .unwrap_or(self.angle * DEFAULT_SPOT_INNER_RATIO)
+            .max(0.0)
+            .min(self.angle)
+    }
+}
+
 impl From<SpotLight> for Light {
     fn from(sp: SpotLight) -> Self {
         Light::Spot(sp)
     }
 }
 
+/// A rectangular light source, such as a window or a studio softbox. Uses the `Transform`
+/// set of components for positioning and orientation.
+///
+/// The rectangle is centered on the entity, spans its local X and Y axes and lights the
+/// side its local -Z axis points to, like a camera looks. It is scaled along with the entity.
+///
+/// Each fragment is lit as if by a point light at the closest point of the rectangle,
+/// dimmed as that point is seen at a grazing angle. This keeps the soft falloff of large
+/// lights without the cost of integrating over the rectangle.
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct AreaLight {
+    /// Color of the light in SRGB format.
+    #[serde(with = "crate::serde_shim::srgb")]
+    pub color: palette::Srgb,
+    /// Brightness of the light source, in lumens.
+    pub intensity: f32,
+    /// Size of the rectangle along the local X axis.
+    pub width: f32,
+    /// Size of the rectangle along the local Y axis.
+    pub height: f32,
+}
+
+impl Default for AreaLight {
+    fn default() -> Self {
+        AreaLight {
+            color: Default::default(),
+            intensity: 10.0,
+            width: 1.0,
+            height: 1.0,
+        }
+    }
+}
+
+impl From<AreaLight> for Light {
+    fn from(area: AreaLight) -> Self {
+        Light::Area(area)
+    }
+}
+
 /// A realistic disk-shaped sun light source.
 #[repr(C)]
 #[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
@@ -187,10 +250,48 @@ impl Component for Light {
     type Storage = DenseVecStorage<Self>;
 }
 
+/// Turns off the `Light` of its entity while keeping its configuration.
+///
+/// Disabled lights are not uploaded to the shaders and do not count towards the light limits.
+#[derive(Clone, Debug, Default)]
+pub struct LightDisabled;
+
+impl Component for LightDisabled {
+    type Storage = NullStorage<Self>;
+}
+
+/// Marks the directional `Light` of its entity as the primary one, the sun of the scene.
+///
+/// The primary light is uploaded first and is the one casting the `ShadowMap`, the other
+/// directional lights only add fill light. Without a marked light, the first directional
+/// light found is the primary one. When several are marked, one of them is picked.
+#[derive(Clone, Debug, Default)]
+pub struct PrimaryDirectionalLight;
+
+impl Component for PrimaryDirectionalLight {
+    type Storage = NullStorage<Self>;
+}
+
 /// Prefab for lighting
 #[derive(Default, Clone, serde::Serialize, serde::Deserialize, PrefabData)]
 #[serde(default)]
 pub struct LightPrefab {
     light: Option<Light>,
     ambient_color: Option<AmbientColor>,
+    fog: Option<Fog>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spot_inner_cone_defaults_inside_outer_cone() {
+        let mut light = SpotLight::default();
+        assert!(light.inner_cone_angle() > 0.0);
+        assert!(light.inner_cone_angle() < light.angle);
+
+        light.inner_angle = Some(light.angle * 2.0);
+        assert_eq!(light.inner_cone_angle(), light.angle);
+    }
 }
@@ -3,6 +3,7 @@
 use crate::types::Texture;
 use amethyst_assets::{Asset, Handle};
 use amethyst_core::ecs::prelude::DenseVecStorage;
+use palette::{Srgb, Srgba};
 
 /// Material reference this part of the texture
 #[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
@@ -22,6 +23,17 @@ impl Default for TextureOffset {
     }
 }
 
+impl TextureOffset {
+    /// Show the rectangle `[min_u, min_v, max_u, max_v]` of the textures over the whole
+    /// mesh, such as a slice of a texture atlas.
+    pub fn from_rect(rect: [f32; 4]) -> Self {
+        TextureOffset {
+            u: (rect[0], rect[2]),
+            v: (rect[1], rect[3]),
+        }
+    }
+}
+
 /// A physically based Material with metallic workflow, fully utilized in PBR render pass.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Material {
@@ -29,18 +41,41 @@ pub struct Material {
     pub alpha_cutoff: f32,
     /// Diffuse map.
     pub albedo: Handle<Texture>,
+    /// Color multiplied with the diffuse map, white by default.
+    /// Leaving the default white diffuse map makes a material of this solid color.
+    pub base_color: Srgba,
     /// Emission map.
     pub emission: Handle<Texture>,
+    /// Color multiplied with the emission map, white by default.
+    pub emission_factor: Srgb,
+    /// Multiplier of the emission map, `1.0` uses its values as-is.
+    pub emission_intensity: f32,
     /// Normal map.
     pub normal: Handle<Texture>,
     /// Metallic-roughness map. (B channel metallic, G channel roughness)
     pub metallic_roughness: Handle<Texture>,
+    /// Multiplier of the metallic channel, `1.0` uses the map as-is.
+    pub metallic_factor: f32,
+    /// Multiplier of the roughness channel, `1.0` uses the map as-is.
+    pub roughness_factor: f32,
     /// Ambient occlusion map.
     pub ambient_occlusion: Handle<Texture>,
     /// Cavity map.
     pub cavity: Handle<Texture>,
-    /// Texture offset
+    /// Part of the textures shown, with texture coordinates remapped from `0.0..1.0` into
+    /// its ranges after `uv_scale` and `uv_scroll` are applied. Use `TextureOffset::from_rect`
+    /// to show a slice of a texture atlas.
     pub uv_offset: TextureOffset,
+    /// Offset added to the texture coordinates of meshes before `uv_offset` is applied,
+    /// `[0.0, 0.0]` by default. Change it over time to scroll the textures, for water or
+    /// conveyor belts.
+    pub uv_scroll: [f32; 2],
+    /// Factor the texture coordinates of meshes are multiplied by before adding
+    /// `uv_scroll`, `[1.0, 1.0]` by default.
+    pub uv_scale: [f32; 2],
+    /// Light back faces as if they were facing the other way, for cloth or leaves.
+    /// Only visible when the pass drawing the material culls no faces.
+    pub double_sided: bool,
 }
 
 impl Asset for Material {
@@ -128,3 +163,19 @@ impl_texture_set_tuple!(A, B, C);
 impl_texture_set_tuple!(A, B, C, D);
 impl_texture_set_tuple!(A, B, C, D, E);
 impl_texture_set_tuple!(A, B, C, D, E, F);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn texture_offset_from_rect() {
+        let offset = TextureOffset::from_rect([0.25, 0.5, 0.5, 1.0]);
+        assert_eq!(offset.u, (0.25, 0.5));
+        assert_eq!(offset.v, (0.5, 1.0));
+        assert_eq!(
+            TextureOffset::from_rect([0.0, 0.0, 1.0, 1.0]),
+            TextureOffset::default()
+        );
+    }
+}
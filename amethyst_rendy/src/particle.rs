@@ -0,0 +1,85 @@
+//! GPU instanced particles.
+
+use amethyst_core::ecs::prelude::{Component, DenseVecStorage};
+use rendy::{
+    hal::format::Format,
+    mesh::{AsVertex, VertexFormat},
+};
+
+/// A single particle, laid out as the instance data read by `DrawParticles`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Particle {
+    /// World space position of the particle center.
+    pub position: [f32; 3],
+    /// Width and height of the particle quad, in world units.
+    pub size: f32,
+    /// Linear color multiplied by alpha when blending.
+    pub color: [f32; 4],
+    /// Rotation of the quad around the view direction, in radians.
+    pub rotation: f32,
+}
+
+impl Default for Particle {
+    fn default() -> Self {
+        Particle {
+            position: [0.0; 3],
+            size: 1.0,
+            color: [1.0; 4],
+            rotation: 0.0,
+        }
+    }
+}
+
+impl AsVertex for Particle {
+    fn vertex() -> VertexFormat {
+        VertexFormat::new((
+            (Format::Rgb32Sfloat, "position"),
+            (Format::R32Sfloat, "size"),
+            (Format::Rgba32Sfloat, "color"),
+            (Format::R32Sfloat, "rotation"),
+        ))
+    }
+}
+
+/// Particles drawn by `DrawParticles` in a single instanced draw call.
+///
+/// Particles are uploaded as they are stored, so systems simulating them write their
+/// state directly into `particles` every frame. Positions are in world space and the
+/// emitter's `Transform` is not applied. Alpha blended particles are drawn in storage
+/// order, sort them back to front when their overlap matters.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParticleEmitter {
+    /// Live particles of the emitter.
+    pub particles: Vec<Particle>,
+}
+
+impl ParticleEmitter {
+    /// Create an emitter without particles.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Create an emitter with room for `capacity` particles.
+    pub fn with_capacity(capacity: usize) -> Self {
+        ParticleEmitter {
+            particles: Vec::with_capacity(capacity),
+        }
+    }
+}
+
+impl Component for ParticleEmitter {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn particle_matches_its_vertex_format() {
+        let (_, stride, _) =
+            Particle::vertex().gfx_vertex_input_desc(rendy::hal::pso::VertexInputRate::Instance(1));
+        assert_eq!(stride as usize, std::mem::size_of::<Particle>());
+    }
+}
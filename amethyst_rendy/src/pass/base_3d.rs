@@ -1,19 +1,24 @@
 use crate::{
     batch::{GroupIterator, OrderedTwoLevelBatch, TwoLevelBatch},
+    camera::CameraViewports,
     mtl::{FullTextureSet, Material, StaticTextureSet},
     pipeline::{PipelineDescBuilder, PipelinesBuilder},
     pod::{SkinnedVertexArgs, VertexArgs},
-    resources::Tint,
+    resources::{DepthMode, DitheredFade, RenderStats, Tint},
     skinning::JointTransforms,
-    submodules::{DynamicVertex, EnvironmentSub, MaterialId, MaterialSub, SkinningSub},
-    transparent::Transparent,
+    submodules::{
+        DynamicVertex, EnvironmentSub, LightLimits, LightTiling, MaterialId, MaterialSub,
+        SampledImageSub, SkinningSub, LIGHT_LIMIT_CONSTANTS,
+    },
+    transparent::{RenderOrder, Transparent},
     types::{Backend, Mesh},
-    util,
-    visibility::Visibility,
+    util::{self, BufferGrowth, SpecConstants},
+    visibility::{self, BoundingSphere, Visibility},
 };
 use amethyst_assets::{AssetStorage, Handle};
 use amethyst_core::{
-    ecs::{Join, Read, ReadExpect, ReadStorage, Resources, SystemData},
+    ecs::{Entities, Entity, Join, Read, ReadExpect, ReadStorage, Resources, SystemData, Write},
+    math::Matrix4,
     transform::Transform,
     Hidden, HiddenPropagate,
 };
@@ -23,14 +28,14 @@ use rendy::{
     factory::Factory,
     graph::{
         render::{PrepareResult, RenderGroup, RenderGroupDesc},
-        GraphContext, NodeBuffer, NodeImage,
+        BufferAccess, GraphContext, ImageAccess, NodeBuffer, NodeImage,
     },
-    hal::{self, device::Device, pso},
-    mesh::{AsVertex, VertexFormat},
+    hal::{self, device::Device, image::Filter, pso},
+    mesh::{AsVertex, Color, VertexFormat},
     shader::{Shader, SpirvShader},
 };
 use smallvec::SmallVec;
-use std::marker::PhantomData;
+use std::{marker::PhantomData, ops::Range};
 
 macro_rules! profile_scope_impl {
     ($string:expr) => {
@@ -52,6 +57,29 @@ pub trait Base3DPassDef<B: Backend>: 'static + std::fmt::Debug + Send + Sync {
     fn fragment_shader() -> &'static SpirvShader;
     fn base_format() -> Vec<VertexFormat>;
     fn skinned_format() -> Vec<VertexFormat>;
+
+    /// Sibling of `vertex_shader` reading a per-vertex `Color` right after the
+    /// `base_format` attributes and multiplying it into the tint.
+    /// `None` when the pass can not draw vertex colors.
+    fn vertex_color_shader() -> Option<&'static SpirvShader> {
+        None
+    }
+
+    /// Sibling of `vertex_skinned_shader` reading a per-vertex `Color` right after the
+    /// `skinned_format` attributes.
+    fn vertex_color_skinned_shader() -> Option<&'static SpirvShader> {
+        None
+    }
+
+    /// How polygons are rasterized, filled by default.
+    fn polygon_mode() -> pso::PolygonMode {
+        pso::PolygonMode::Fill
+    }
+
+    /// Whether the fragment shader lights surfaces and applies the ambient occlusion image.
+    fn lit() -> bool {
+        true
+    }
 }
 
 /// Draw opaque 3d mesh with specified shaders and texture set
@@ -59,6 +87,26 @@ pub trait Base3DPassDef<B: Backend>: 'static + std::fmt::Debug + Send + Sync {
 #[derivative(Debug(bound = ""), Default(bound = ""))]
 pub struct DrawBase3DDesc<B: Backend, T: Base3DPassDef<B>> {
     skinning: bool,
+    attribute_base: pso::Location,
+    environment: Option<EnvironmentSub<B>>,
+    light_limits: LightLimits,
+    light_tiles: Option<LightTiling>,
+    ambient_occlusion: bool,
+    shadow_map: bool,
+    #[derivative(Default(value = "pso::Face::BACK"))]
+    cull_face: pso::Face,
+    depth_test: Option<pso::DepthTest>,
+    stencil_test: pso::StencilTest,
+    #[derivative(Default(value = "1"))]
+    samples: hal::image::NumSamples,
+    alpha_coverage: bool,
+    vertex_colors: bool,
+    instance_growth: BufferGrowth,
+    instance_shrink_after: Option<u32>,
+    spec_constants: SpecConstants,
+    depth_prepass: bool,
+    camera: Option<Entity>,
+    color_targets: Vec<pso::ColorBlendDesc>,
     marker: PhantomData<(B, T)>,
 }
 
@@ -67,6 +115,24 @@ impl<B: Backend, T: Base3DPassDef<B>> DrawBase3DDesc<B, T> {
     pub fn new() -> Self {
         Self {
             skinning: false,
+            attribute_base: 0,
+            environment: None,
+            light_limits: LightLimits::default(),
+            light_tiles: None,
+            ambient_occlusion: false,
+            shadow_map: false,
+            cull_face: pso::Face::BACK,
+            depth_test: None,
+            stencil_test: pso::StencilTest::Off,
+            samples: 1,
+            alpha_coverage: false,
+            vertex_colors: false,
+            instance_growth: BufferGrowth::PowerOfTwo,
+            instance_shrink_after: None,
+            spec_constants: SpecConstants::new(),
+            depth_prepass: false,
+            camera: None,
+            color_targets: Vec::new(),
             marker: PhantomData,
         }
     }
@@ -75,65 +141,316 @@ impl<B: Backend, T: Base3DPassDef<B>> DrawBase3DDesc<B, T> {
     pub fn skinned() -> Self {
         Self {
             skinning: true,
-            marker: PhantomData,
+            ..Self::new()
+        }
+    }
+
+    /// Place vertex attributes starting at the given shader location,
+    /// for custom shaders that reserve the lower locations.
+    pub fn with_attribute_base(mut self, location: pso::Location) -> Self {
+        self.attribute_base = location;
+        self
+    }
+
+    /// Bind an environment shared with other passes instead of uploading a private copy.
+    pub fn with_environment(mut self, environment: EnvironmentSub<B>) -> Self {
+        self.environment = Some(environment);
+        self
+    }
+
+    /// Upload at most `count` point lights, 128 by default.
+    ///
+    /// Ignored when a shared environment is set, which carries its own limits.
+    pub fn with_max_point_lights(mut self, count: usize) -> Self {
+        self.light_limits.point = count.max(1);
+        self
+    }
+
+    /// Only light fragments with the point lights listed for their screen tile by a
+    /// `LightCulling` node, in the buffer passed to the group builder.
+    ///
+    /// The point light limit of the environment has to match the one of the node.
+    pub fn with_light_tiles(mut self, tiling: LightTiling) -> Self {
+        self.light_tiles = Some(tiling);
+        self
+    }
+
+    /// Upload at most `count` directional lights, 16 by default.
+    ///
+    /// Ignored when a shared environment is set, which carries its own limits.
+    pub fn with_max_directional_lights(mut self, count: usize) -> Self {
+        self.light_limits.directional = count.max(1);
+        self
+    }
+
+    /// Upload at most `count` spot lights, 128 by default.
+    ///
+    /// Ignored when a shared environment is set, which carries its own limits.
+    pub fn with_max_spot_lights(mut self, count: usize) -> Self {
+        self.light_limits.spot = count.max(1);
+        self
+    }
+
+    /// Upload at most `count` area lights, 16 by default.
+    ///
+    /// Ignored when a shared environment is set, which carries its own limits.
+    pub fn with_max_area_lights(mut self, count: usize) -> Self {
+        self.light_limits.area = count.max(1);
+        self
+    }
+
+    /// Cull faces facing this way, `Face::BACK` by default.
+    /// `Face::NONE` draws both sides of two-sided materials.
+    pub fn with_cull_face(mut self, cull_face: pso::Face) -> Self {
+        self.cull_face = cull_face;
+        self
+    }
+
+    /// Replace the default depth test, which writes depth for opaque passes
+    /// and passes fragments closer than the stored depth.
+    ///
+    /// The comparison is written for `DepthMode::Standard` and flipped along with it.
+    pub fn with_depth_test(mut self, depth_test: pso::DepthTest) -> Self {
+        self.depth_test = Some(depth_test);
+        self
+    }
+
+    /// Test and update the stencil buffer, off by default. `util::stencil_write` marks the
+    /// drawn meshes for a later group to test against with `util::stencil_compare`.
+    ///
+    /// The depth image needs a stencil aspect, see `util::select_depth_format`.
+    pub fn with_stencil_test(mut self, stencil_test: pso::StencilTest) -> Self {
+        self.stencil_test = stencil_test;
+        self
+    }
+
+    /// Number of samples per pixel of the color and depth attachments, 1 by default.
+    pub fn with_samples(mut self, samples: hal::image::NumSamples) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+
+    /// Derive multisample coverage from fragment alpha, so cutout materials such as
+    /// foliage get smooth edges without going through the transparent pass.
+    /// Without multisampling materials are alpha tested against their `alpha_cutoff`.
+    pub fn with_alpha_coverage(mut self, alpha_coverage: bool) -> Self {
+        self.alpha_coverage = alpha_coverage;
+        self
+    }
+
+    /// Multiply the tint of meshes having a `Color` vertex attribute by their vertex colors.
+    /// Meshes without one are drawn as usual. Ignored by passes without vertex color shaders.
+    pub fn with_vertex_colors(mut self) -> Self {
+        self.vertex_colors = true;
+        self
+    }
+
+    /// Specialize the shaders of the pass with `constants`, selecting variants such as
+    /// feature toggles. Ids 0 to 3 hold the light limits and are ignored with a warning,
+    /// set them with `with_max_point_lights` and its siblings instead.
+    pub fn with_spec_constants(mut self, constants: SpecConstants) -> Self {
+        warn_light_limit_constants(&constants);
+        self.spec_constants = constants;
+        self
+    }
+
+    /// Size the per instance vertex buffers with `growth`, powers of two by default.
+    /// `BufferGrowth::Exact` leaves no unused space, reallocating whenever more entities
+    /// are drawn.
+    pub fn with_instance_growth(mut self, growth: BufferGrowth) -> Self {
+        self.instance_growth = growth;
+        self
+    }
+
+    /// Reallocate the per instance vertex buffers smaller after `frames` frames in a row
+    /// using at most half of them, freeing memory after a spike of drawn entities.
+    pub fn with_instance_shrink_after(mut self, frames: u32) -> Self {
+        self.instance_shrink_after = Some(frames);
+        self
+    }
+
+    /// Only shade fragments whose depth equals the depth already stored by a
+    /// `DrawDepthPrepass` group drawn earlier into the same depth buffer.
+    ///
+    /// Skinned meshes are not part of the prepass and keep the default depth test.
+    pub fn with_depth_prepass(mut self) -> Self {
+        self.depth_prepass = true;
+        self
+    }
+
+    /// Darken the ambient term with screen space ambient occlusion,
+    /// read from the image passed to the group builder.
+    /// The PBR and shaded passes apply it, unlit passes such as flat ignore it.
+    pub fn with_ambient_occlusion(mut self) -> Self {
+        if !T::lit() {
+            log::warn!(
+                "{} pass is unlit and ignores the ambient occlusion image",
+                T::NAME
+            );
         }
+        self.ambient_occlusion = true;
+        self
+    }
+
+    /// Shadow the primary directional light with the shadow map read from the image
+    /// passed to the group builder, after the ambient occlusion image if there is one.
+    /// Only the shaded and PBR fragment shaders apply it.
+    pub fn with_shadow_map(mut self) -> Self {
+        self.shadow_map = true;
+        self
+    }
+
+    /// Draw the view of `camera` instead of the active camera, such as the view
+    /// shown by a `RenderTarget`.
+    ///
+    /// The `Visibility` resource is culled for the active camera, so every mesh that
+    /// is not hidden gets drawn. Ignored when a shared environment is set, which
+    /// carries its own camera.
+    pub fn with_camera(mut self, camera: Entity) -> Self {
+        self.camera = Some(camera);
+        self
+    }
+
+    /// Add a color attachment after the ones already set, written by the next output
+    /// of the fragment shader. The first attachment is always set, opaque.
+    ///
+    /// Every group of a subpass has to write the same attachments.
+    pub fn with_color_target(mut self, target: pso::ColorBlendDesc) -> Self {
+        self.color_targets.push(target);
+        self
+    }
+
+    /// Write object ids into the second color attachment, an `R32Uint` image cleared to `0`,
+    /// for picking meshes under the cursor. `pod::ObjectId` maps ids back to entities.
+    ///
+    /// The vertex shaders of the built-in passes hand the id over at location 7, and their
+    /// fragment shaders write it into their second output.
+    pub fn with_object_ids(self) -> Self {
+        self.with_color_target(pso::ColorBlendDesc(
+            pso::ColorMask::ALL,
+            pso::BlendState::Off,
+        ))
     }
 }
 
 impl<B: Backend, T: Base3DPassDef<B>> RenderGroupDesc<B, Resources> for DrawBase3DDesc<B, T> {
+    fn buffers(&self) -> Vec<BufferAccess> {
+        light_tiles_access(self.light_tiles)
+    }
+
+    fn images(&self) -> Vec<ImageAccess> {
+        let count = self.ambient_occlusion as usize + self.shadow_map as usize;
+        vec![util::sampled_image_access(); count]
+    }
+
     fn build(
         self,
-        _ctx: &GraphContext<B>,
+        ctx: &GraphContext<B>,
         factory: &mut Factory<B>,
-        _queue: QueueId,
-        _aux: &Resources,
+        queue: QueueId,
+        aux: &Resources,
         framebuffer_width: u32,
         framebuffer_height: u32,
         subpass: hal::pass::Subpass<'_, B>,
-        _buffers: Vec<NodeBuffer>,
-        _images: Vec<NodeImage>,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
     ) -> Result<Box<dyn RenderGroup<B, Resources>>, failure::Error> {
         profile_scope_impl!("build");
 
-        let env = EnvironmentSub::new(factory)?;
+        let env = match self.environment {
+            Some(env) => env,
+            None => EnvironmentSub::with_limits(factory, queue, self.light_limits)?
+                .with_camera(self.camera),
+        };
+        let env = with_light_tiles(env, ctx, &buffers, self.light_tiles);
         let materials = MaterialSub::new(factory)?;
         let skinning = SkinningSub::new(factory)?;
+        let mut images = images.iter();
+        let ambient_occlusion = if self.ambient_occlusion {
+            SampledImageSub::new(ctx, factory, images.next().unwrap(), Filter::Linear)?
+        } else {
+            SampledImageSub::white(factory, queue)?
+        };
+        let shadow_map = if self.shadow_map {
+            SampledImageSub::new(ctx, factory, images.next().unwrap(), Filter::Nearest)?
+        } else {
+            SampledImageSub::white(factory, queue)?
+        };
 
         let mut vertex_format_base = T::base_format();
         let mut vertex_format_skinned = T::skinned_format();
+        let mut vertex_format_color = with_vertex_color(&vertex_format_base);
+        let mut vertex_format_color_skinned = with_vertex_color(&vertex_format_skinned);
+        let vertex_colors = self.vertex_colors && T::vertex_color_shader().is_some();
 
-        let (mut pipelines, pipeline_layout) = build_pipelines::<B, T>(
+        let (pipelines, pipeline_layout) = build_pipelines::<B, T>(
             factory,
             subpass,
-            framebuffer_width,
-            framebuffer_height,
             &vertex_format_base,
             &vertex_format_skinned,
+            &vertex_format_color,
+            &vertex_format_color_skinned,
             self.skinning,
-            false,
+            vertex_colors,
+            true,
+            pso::BlendState::Off,
+            &self.color_targets,
+            self.depth_prepass,
+            self.attribute_base,
+            self.cull_face,
+            self.depth_test,
+            self.stencil_test,
+            aux,
+            util::multisampling(self.samples, self.alpha_coverage),
+            env.limits(),
+            &self.spec_constants,
             vec![
                 env.raw_layout(),
                 materials.raw_layout(),
                 skinning.raw_layout(),
+                ambient_occlusion.raw_layout(),
+                shadow_map.raw_layout(),
             ],
         )?;
 
         vertex_format_base.sort();
         vertex_format_skinned.sort();
+        vertex_format_color.sort();
+        vertex_format_color_skinned.sort();
+
+        let mut pipelines = pipelines.into_iter();
 
         Ok(Box::new(DrawBase3D::<B, T> {
-            pipeline_basic: pipelines.remove(0),
-            pipeline_skinned: pipelines.pop(),
+            camera: env.camera(),
+            pipeline_basic: pipelines.next().unwrap(),
+            pipeline_skinned: if self.skinning {
+                pipelines.next()
+            } else {
+                None
+            },
+            pipeline_color: pipelines.next(),
+            pipeline_color_skinned: pipelines.next(),
             pipeline_layout,
             static_batches: Default::default(),
             skinned_batches: Default::default(),
             vertex_format_base,
             vertex_format_skinned,
+            vertex_format_color,
+            vertex_format_color_skinned,
             env,
             materials,
             skinning,
-            models: DynamicVertex::new(),
-            skinned_models: DynamicVertex::new(),
+            ambient_occlusion,
+            shadow_map,
+            models: DynamicVertex::new()
+                .with_growth(self.instance_growth)
+                .with_shrink_after(self.instance_shrink_after),
+            skinned_models: DynamicVertex::new()
+                .with_growth(self.instance_growth)
+                .with_shrink_after(self.instance_shrink_after),
+            viewport_envs: Vec::new(),
+            viewports: Vec::new(),
+            viewport_rect: util::viewport_rect(aux, framebuffer_width, framebuffer_height),
             marker: PhantomData,
         }))
     }
@@ -144,16 +461,26 @@ impl<B: Backend, T: Base3DPassDef<B>> RenderGroupDesc<B, Resources> for DrawBase
 pub struct DrawBase3D<B: Backend, T: Base3DPassDef<B>> {
     pipeline_basic: B::GraphicsPipeline,
     pipeline_skinned: Option<B::GraphicsPipeline>,
+    pipeline_color: Option<B::GraphicsPipeline>,
+    pipeline_color_skinned: Option<B::GraphicsPipeline>,
     pipeline_layout: B::PipelineLayout,
     static_batches: TwoLevelBatch<MaterialId, u32, SmallVec<[VertexArgs; 4]>>,
     skinned_batches: TwoLevelBatch<MaterialId, u32, SmallVec<[SkinnedVertexArgs; 4]>>,
     vertex_format_base: Vec<VertexFormat>,
     vertex_format_skinned: Vec<VertexFormat>,
+    vertex_format_color: Vec<VertexFormat>,
+    vertex_format_color_skinned: Vec<VertexFormat>,
     env: EnvironmentSub<B>,
     materials: MaterialSub<B, T::TextureSet>,
     skinning: SkinningSub<B>,
+    ambient_occlusion: SampledImageSub<B>,
+    shadow_map: SampledImageSub<B>,
     models: DynamicVertex<B, VertexArgs>,
     skinned_models: DynamicVertex<B, SkinnedVertexArgs>,
+    camera: Option<Entity>,
+    viewport_envs: Vec<EnvironmentSub<B>>,
+    viewports: Vec<pso::Rect>,
+    viewport_rect: pso::Rect,
     marker: PhantomData<T>,
 }
 
@@ -169,8 +496,10 @@ impl<B: Backend, T: Base3DPassDef<B>> RenderGroup<B, Resources> for DrawBase3D<B
         profile_scope_impl!("prepare");
 
         let (
+            entities,
             mesh_storage,
             visibility,
+            viewports,
             transparent,
             hiddens,
             hiddens_prop,
@@ -179,9 +508,12 @@ impl<B: Backend, T: Base3DPassDef<B>> RenderGroup<B, Resources> for DrawBase3D<B
             transforms,
             joints,
             tints,
+            fades,
         ) = <(
+            Entities,
             Read<AssetStorage<Mesh>>,
             Option<Read<Visibility>>,
+            Option<Read<CameraViewports>>,
             ReadStorage<Transparent>,
             ReadStorage<Hidden>,
             ReadStorage<HiddenPropagate>,
@@ -190,11 +522,42 @@ impl<B: Backend, T: Base3DPassDef<B>> RenderGroup<B, Resources> for DrawBase3D<B
             ReadStorage<Transform>,
             ReadStorage<JointTransforms>,
             ReadStorage<Tint>,
+            ReadStorage<DitheredFade>,
         )>::fetch(resources);
 
+        // A camera set on the group takes precedence over split-screen.
+        let split_screen = viewports
+            .as_ref()
+            .filter(|viewports| self.camera.is_none() && !viewports.0.is_empty());
+        // Culling only applies to the view of the active camera.
+        let visibility = visibility.filter(|_| self.camera.is_none() && split_screen.is_none());
+
         // Prepare environment
-        self.env.process(factory, index, resources);
-        self.materials.maintain();
+        self.viewports.clear();
+        match split_screen {
+            None => {
+                self.env.process(factory, index, resources);
+            }
+            Some(split_screen) => {
+                for (i, viewport) in split_screen.0.iter().enumerate() {
+                    if self.viewport_envs.len() == i {
+                        self.viewport_envs.push(self.env.for_camera(None));
+                    }
+                    let env = &mut self.viewport_envs[i];
+                    env.set_camera(Some(viewport.entity));
+                    env.process(factory, index, resources);
+                    // Split-screen areas divide the area kept by an `AspectRatioLock`.
+                    let area = self.viewport_rect;
+                    let rect = viewport.rect(area.w as u32, area.h as u32);
+                    self.viewports.push(pso::Rect {
+                        x: area.x + rect.x,
+                        y: area.y + rect.y,
+                        ..rect
+                    });
+                }
+            }
+        }
+        self.materials.maintain(factory, resources);
 
         self.static_batches.clear_inner();
         self.skinned_batches.clear_inner();
@@ -204,9 +567,31 @@ impl<B: Backend, T: Base3DPassDef<B>> RenderGroup<B, Resources> for DrawBase3D<B
         let statics_ref = &mut self.static_batches;
         let skinned_ref = &mut self.skinned_batches;
 
-        let static_input = || ((&materials, &meshes, &transforms, tints.maybe()), !&joints);
+        let static_input = || {
+            (
+                (
+                    &entities,
+                    &materials,
+                    &meshes,
+                    &transforms,
+                    tints.maybe(),
+                    fades.maybe(),
+                ),
+                !&joints,
+            )
+        };
 
-        let skinned_input = || (&materials, &meshes, &transforms, tints.maybe(), &joints);
+        let skinned_input = || {
+            (
+                &entities,
+                &materials,
+                &meshes,
+                &transforms,
+                tints.maybe(),
+                fades.maybe(),
+                &joints,
+            )
+        };
 
         match &visibility {
             None => {
@@ -214,8 +599,11 @@ impl<B: Backend, T: Base3DPassDef<B>> RenderGroup<B, Resources> for DrawBase3D<B
 
                 (static_input(), (!&hiddens, !&hiddens_prop, !&transparent))
                     .join()
-                    .map(|(((mat, mesh, tform, tint), _), _)| {
-                        ((mat, mesh.id()), VertexArgs::from_object_data(tform, tint))
+                    .map(|(((entity, mat, mesh, tform, tint, fade), _), _)| {
+                        (
+                            (mat, mesh.id()),
+                            VertexArgs::from_object_data(tform, tint, fade).with_object_id(entity),
+                        )
                     })
                     .for_each_group(|(mat, mesh_id), data| {
                         if mesh_storage.contains_id(mesh_id) {
@@ -230,14 +618,16 @@ impl<B: Backend, T: Base3DPassDef<B>> RenderGroup<B, Resources> for DrawBase3D<B
 
                     (skinned_input(), (!&hiddens, !&hiddens_prop))
                         .join()
-                        .map(|((mat, mesh, tform, tint, joints), _)| {
+                        .map(|((entity, mat, mesh, tform, tint, fade, joints), _)| {
                             (
                                 (mat, mesh.id()),
                                 SkinnedVertexArgs::from_object_data(
                                     tform,
                                     tint,
+                                    fade,
                                     skinning_ref.insert(joints),
-                                ),
+                                )
+                                .with_object_id(entity),
                             )
                         })
                         .for_each_group(|(mat, mesh_id), data| {
@@ -256,8 +646,11 @@ impl<B: Backend, T: Base3DPassDef<B>> RenderGroup<B, Resources> for DrawBase3D<B
 
                 (static_input(), &visibility.visible_unordered)
                     .join()
-                    .map(|(((mat, mesh, tform, tint), _), _)| {
-                        ((mat, mesh.id()), VertexArgs::from_object_data(tform, tint))
+                    .map(|(((entity, mat, mesh, tform, tint, fade), _), _)| {
+                        (
+                            (mat, mesh.id()),
+                            VertexArgs::from_object_data(tform, tint, fade).with_object_id(entity),
+                        )
                     })
                     .for_each_group(|(mat, mesh_id), data| {
                         if mesh_storage.contains_id(mesh_id) {
@@ -272,14 +665,16 @@ impl<B: Backend, T: Base3DPassDef<B>> RenderGroup<B, Resources> for DrawBase3D<B
 
                     (skinned_input(), &visibility.visible_unordered)
                         .join()
-                        .map(|((mat, mesh, tform, tint, joints), _)| {
+                        .map(|((entity, mat, mesh, tform, tint, fade, joints), _)| {
                             (
                                 (mat, mesh.id()),
                                 SkinnedVertexArgs::from_object_data(
                                     tform,
                                     tint,
+                                    fade,
                                     skinning_ref.insert(joints),
-                                ),
+                                )
+                                .with_object_id(entity),
                             )
                         })
                         .for_each_group(|(mat, mesh_id), data| {
@@ -316,6 +711,31 @@ impl<B: Backend, T: Base3DPassDef<B>> RenderGroup<B, Resources> for DrawBase3D<B
             );
             self.skinning.commit(factory, index);
         }
+
+        if let Some(mut stats) = <Option<Write<'_, RenderStats>>>::fetch(resources) {
+            let materials = &self.materials;
+            let views = self.viewports.len().max(1);
+            record_draws::<B>(
+                &mut stats,
+                &mesh_storage,
+                views,
+                self.static_batches
+                    .iter()
+                    .filter(|&(&mat, _)| materials.loaded(mat))
+                    .flat_map(|(_, batches)| batches)
+                    .map(|(mesh_id, data)| (*mesh_id, data.len() as u32)),
+            );
+            record_draws::<B>(
+                &mut stats,
+                &mesh_storage,
+                views,
+                self.skinned_batches
+                    .iter()
+                    .filter(|&(&mat, _)| materials.loaded(mat))
+                    .flat_map(|(_, batches)| batches)
+                    .map(|(mesh_id, data)| (*mesh_id, data.len() as u32)),
+            );
+        }
         PrepareResult::DrawRecord
     }
 
@@ -328,31 +748,101 @@ impl<B: Backend, T: Base3DPassDef<B>> RenderGroup<B, Resources> for DrawBase3D<B
     ) {
         profile_scope_impl!("draw");
 
+        if self.static_batches.is_empty() && self.skinned_batches.is_empty() {
+            return;
+        }
+
         let mesh_storage = <Read<'_, AssetStorage<Mesh>>>::fetch(resources);
+
+        if self.viewports.is_empty() {
+            self.draw_view(
+                &mut encoder,
+                index,
+                &self.env,
+                self.viewport_rect,
+                &mesh_storage,
+            );
+        } else {
+            for (&rect, env) in self.viewports.iter().zip(&self.viewport_envs) {
+                self.draw_view(&mut encoder, index, env, rect, &mesh_storage);
+            }
+        }
+    }
+
+    fn dispose(mut self: Box<Self>, factory: &mut Factory<B>, _aux: &Resources) {
+        profile_scope_impl!("dispose");
+        unsafe {
+            factory
+                .device()
+                .destroy_graphics_pipeline(self.pipeline_basic);
+            self.pipeline_skinned.take().map(|pipeline| {
+                factory.device().destroy_graphics_pipeline(pipeline);
+            });
+            self.pipeline_color.take().map(|pipeline| {
+                factory.device().destroy_graphics_pipeline(pipeline);
+            });
+            self.pipeline_color_skinned.take().map(|pipeline| {
+                factory.device().destroy_graphics_pipeline(pipeline);
+            });
+            factory
+                .device()
+                .destroy_pipeline_layout(self.pipeline_layout);
+        }
+    }
+}
+
+impl<B: Backend, T: Base3DPassDef<B>> DrawBase3D<B, T> {
+    /// Draw the gathered batches into `rect`, as seen through the camera of `env`.
+    fn draw_view(
+        &self,
+        encoder: &mut RenderPassEncoder<'_, B>,
+        index: usize,
+        env: &EnvironmentSub<B>,
+        rect: pso::Rect,
+        mesh_storage: &AssetStorage<Mesh>,
+    ) {
+        encoder.set_viewports(
+            0,
+            &[pso::Viewport {
+                rect,
+                depth: 0.0..1.0,
+            }],
+        );
+        encoder.set_scissors(0, &[rect]);
+
         let models_loc = self.vertex_format_base.len() as u32;
         let skin_models_loc = self.vertex_format_skinned.len() as u32;
 
         encoder.bind_graphics_pipeline(&self.pipeline_basic);
-        self.env.bind(index, &self.pipeline_layout, 0, &mut encoder);
+        env.bind(index, &self.pipeline_layout, 0, encoder);
+        self.ambient_occlusion
+            .bind(&self.pipeline_layout, 3, encoder);
+        self.shadow_map.bind(&self.pipeline_layout, 4, encoder);
 
-        if self.models.bind(index, models_loc, &mut encoder) {
+        if self.models.bind(index, models_loc, encoder) {
+            let mut drawer = MeshDrawer::new(
+                (&self.pipeline_basic, &self.vertex_format_base[..]),
+                self.pipeline_color
+                    .as_ref()
+                    .map(|pipeline| (pipeline, &self.vertex_format_color[..])),
+                &self.models,
+                index,
+            );
             let mut instances_drawn = 0;
             for (&mat_id, batches) in self.static_batches.iter() {
                 if self.materials.loaded(mat_id) {
                     self.materials
-                        .bind(&self.pipeline_layout, 1, mat_id, &mut encoder);
+                        .bind(&self.pipeline_layout, 1, mat_id, encoder);
                     for (mesh_id, batch_data) in batches {
                         debug_assert!(mesh_storage.contains_id(*mesh_id));
                         if let Some(mesh) =
                             B::unwrap_mesh(unsafe { mesh_storage.get_by_id_unchecked(*mesh_id) })
                         {
-                            mesh.bind_and_draw(
-                                0,
-                                &self.vertex_format_base,
+                            drawer.draw(
+                                mesh,
                                 instances_drawn..instances_drawn + batch_data.len() as u32,
-                                &mut encoder,
-                            )
-                            .unwrap();
+                                encoder,
+                            );
                         }
                         instances_drawn += batch_data.len() as u32;
                     }
@@ -363,30 +853,32 @@ impl<B: Backend, T: Base3DPassDef<B>> RenderGroup<B, Resources> for DrawBase3D<B
         if let Some(pipeline_skinned) = self.pipeline_skinned.as_ref() {
             encoder.bind_graphics_pipeline(pipeline_skinned);
 
-            if self
-                .skinned_models
-                .bind(index, skin_models_loc, &mut encoder)
-            {
-                self.skinning
-                    .bind(index, &self.pipeline_layout, 2, &mut encoder);
-
+            if self.skinned_models.bind(index, skin_models_loc, encoder) {
+                self.skinning.bind(index, &self.pipeline_layout, 2, encoder);
+
+                let mut drawer = MeshDrawer::new(
+                    (pipeline_skinned, &self.vertex_format_skinned[..]),
+                    self.pipeline_color_skinned
+                        .as_ref()
+                        .map(|pipeline| (pipeline, &self.vertex_format_color_skinned[..])),
+                    &self.skinned_models,
+                    index,
+                );
                 let mut instances_drawn = 0;
                 for (&mat_id, batches) in self.skinned_batches.iter() {
                     if self.materials.loaded(mat_id) {
                         self.materials
-                            .bind(&self.pipeline_layout, 1, mat_id, &mut encoder);
+                            .bind(&self.pipeline_layout, 1, mat_id, encoder);
                         for (mesh_id, batch_data) in batches {
                             debug_assert!(mesh_storage.contains_id(*mesh_id));
                             if let Some(mesh) = B::unwrap_mesh(unsafe {
                                 mesh_storage.get_by_id_unchecked(*mesh_id)
                             }) {
-                                mesh.bind_and_draw(
-                                    0,
-                                    &self.vertex_format_skinned,
+                                drawer.draw(
+                                    mesh,
                                     instances_drawn..instances_drawn + batch_data.len() as u32,
-                                    &mut encoder,
-                                )
-                                .unwrap();
+                                    encoder,
+                                );
                             }
                             instances_drawn += batch_data.len() as u32;
                         }
@@ -395,21 +887,6 @@ impl<B: Backend, T: Base3DPassDef<B>> RenderGroup<B, Resources> for DrawBase3D<B
             }
         }
     }
-
-    fn dispose(mut self: Box<Self>, factory: &mut Factory<B>, _aux: &Resources) {
-        profile_scope_impl!("dispose");
-        unsafe {
-            factory
-                .device()
-                .destroy_graphics_pipeline(self.pipeline_basic);
-            self.pipeline_skinned.take().map(|pipeline| {
-                factory.device().destroy_graphics_pipeline(pipeline);
-            });
-            factory
-                .device()
-                .destroy_pipeline_layout(self.pipeline_layout);
-        }
-    }
 }
 
 /// Draw transparent mesh with physically based lighting
@@ -417,6 +894,24 @@ impl<B: Backend, T: Base3DPassDef<B>> RenderGroup<B, Resources> for DrawBase3D<B
 #[derivative(Debug(bound = ""), Default(bound = ""))]
 pub struct DrawBase3DTransparentDesc<B: Backend, T: Base3DPassDef<B>> {
     skinning: bool,
+    attribute_base: pso::Location,
+    environment: Option<EnvironmentSub<B>>,
+    light_limits: LightLimits,
+    light_tiles: Option<LightTiling>,
+    #[derivative(Default(value = "pso::Face::BACK"))]
+    cull_face: pso::Face,
+    depth_test: Option<pso::DepthTest>,
+    depth_write: bool,
+    stencil_test: pso::StencilTest,
+    #[derivative(Default(value = "1"))]
+    samples: hal::image::NumSamples,
+    vertex_colors: bool,
+    instance_growth: BufferGrowth,
+    instance_shrink_after: Option<u32>,
+    spec_constants: SpecConstants,
+    #[derivative(Default(value = "pso::BlendState::ALPHA"))]
+    blend: pso::BlendState,
+    color_targets: Vec<pso::ColorBlendDesc>,
     marker: PhantomData<(B, T)>,
 }
 
@@ -425,6 +920,21 @@ impl<B: Backend, T: Base3DPassDef<B>> DrawBase3DTransparentDesc<B, T> {
     pub fn new() -> Self {
         Self {
             skinning: false,
+            attribute_base: 0,
+            environment: None,
+            light_limits: LightLimits::default(),
+            light_tiles: None,
+            cull_face: pso::Face::BACK,
+            depth_test: None,
+            depth_write: false,
+            stencil_test: pso::StencilTest::Off,
+            samples: 1,
+            vertex_colors: false,
+            instance_growth: BufferGrowth::PowerOfTwo,
+            instance_shrink_after: None,
+            spec_constants: SpecConstants::new(),
+            blend: pso::BlendState::ALPHA,
+            color_targets: Vec::new(),
             marker: PhantomData,
         }
     }
@@ -433,66 +943,268 @@ impl<B: Backend, T: Base3DPassDef<B>> DrawBase3DTransparentDesc<B, T> {
     pub fn skinned() -> Self {
         Self {
             skinning: true,
-            marker: PhantomData,
+            ..Self::new()
         }
     }
+
+    /// Place vertex attributes starting at the given shader location,
+    /// for custom shaders that reserve the lower locations.
+    pub fn with_attribute_base(mut self, location: pso::Location) -> Self {
+        self.attribute_base = location;
+        self
+    }
+
+    /// Bind an environment shared with other passes instead of uploading a private copy.
+    pub fn with_environment(mut self, environment: EnvironmentSub<B>) -> Self {
+        self.environment = Some(environment);
+        self
+    }
+
+    /// Upload at most `count` point lights, 128 by default.
+    ///
+    /// Ignored when a shared environment is set, which carries its own limits.
+    pub fn with_max_point_lights(mut self, count: usize) -> Self {
+        self.light_limits.point = count.max(1);
+        self
+    }
+
+    /// Only light fragments with the point lights listed for their screen tile by a
+    /// `LightCulling` node, in the buffer passed to the group builder.
+    ///
+    /// The point light limit of the environment has to match the one of the node.
+    pub fn with_light_tiles(mut self, tiling: LightTiling) -> Self {
+        self.light_tiles = Some(tiling);
+        self
+    }
+
+    /// Upload at most `count` directional lights, 16 by default.
+    ///
+    /// Ignored when a shared environment is set, which carries its own limits.
+    pub fn with_max_directional_lights(mut self, count: usize) -> Self {
+        self.light_limits.directional = count.max(1);
+        self
+    }
+
+    /// Upload at most `count` spot lights, 128 by default.
+    ///
+    /// Ignored when a shared environment is set, which carries its own limits.
+    pub fn with_max_spot_lights(mut self, count: usize) -> Self {
+        self.light_limits.spot = count.max(1);
+        self
+    }
+
+    /// Upload at most `count` area lights, 16 by default.
+    ///
+    /// Ignored when a shared environment is set, which carries its own limits.
+    pub fn with_max_area_lights(mut self, count: usize) -> Self {
+        self.light_limits.area = count.max(1);
+        self
+    }
+
+    /// Cull faces facing this way, `Face::BACK` by default.
+    /// `Face::NONE` draws both sides of two-sided materials.
+    pub fn with_cull_face(mut self, cull_face: pso::Face) -> Self {
+        self.cull_face = cull_face;
+        self
+    }
+
+    /// Replace the default depth test, which writes depth for opaque passes
+    /// and passes fragments closer than the stored depth.
+    ///
+    /// The comparison is written for `DepthMode::Standard` and flipped along with it.
+    pub fn with_depth_test(mut self, depth_test: pso::DepthTest) -> Self {
+        self.depth_test = Some(depth_test);
+        self
+    }
+
+    /// Write the depth of transparent meshes while still blending them, so that meshes
+    /// drawn later are hidden behind them. Suits mostly opaque materials overlapping
+    /// themselves, such as glass panes in opaque frames. Meshes are drawn back to front,
+    /// so nearer parts of a single mesh can still be drawn before farther ones.
+    ///
+    /// Ignored when a depth test is set with `with_depth_test`.
+    pub fn with_depth_write(mut self) -> Self {
+        self.depth_write = true;
+        self
+    }
+
+    /// Test and update the stencil buffer, off by default. `util::stencil_write` marks the
+    /// drawn meshes for a later group to test against with `util::stencil_compare`.
+    ///
+    /// The depth image needs a stencil aspect, see `util::select_depth_format`.
+    pub fn with_stencil_test(mut self, stencil_test: pso::StencilTest) -> Self {
+        self.stencil_test = stencil_test;
+        self
+    }
+
+    /// Number of samples per pixel of the color and depth attachments, 1 by default.
+    pub fn with_samples(mut self, samples: hal::image::NumSamples) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+
+    /// Multiply the tint of meshes having a `Color` vertex attribute by their vertex colors.
+    /// Meshes without one are drawn as usual. Ignored by passes without vertex color shaders.
+    pub fn with_vertex_colors(mut self) -> Self {
+        self.vertex_colors = true;
+        self
+    }
+
+    /// Specialize the shaders of the pass with `constants`, selecting variants such as
+    /// feature toggles. Ids 0 to 3 hold the light limits and are ignored with a warning,
+    /// set them with `with_max_point_lights` and its siblings instead.
+    pub fn with_spec_constants(mut self, constants: SpecConstants) -> Self {
+        warn_light_limit_constants(&constants);
+        self.spec_constants = constants;
+        self
+    }
+
+    /// Size the per instance vertex buffers with `growth`, powers of two by default.
+    /// `BufferGrowth::Exact` leaves no unused space, reallocating whenever more entities
+    /// are drawn.
+    pub fn with_instance_growth(mut self, growth: BufferGrowth) -> Self {
+        self.instance_growth = growth;
+        self
+    }
+
+    /// Reallocate the per instance vertex buffers smaller after `frames` frames in a row
+    /// using at most half of them, freeing memory after a spike of drawn entities.
+    pub fn with_instance_shrink_after(mut self, frames: u32) -> Self {
+        self.instance_shrink_after = Some(frames);
+        self
+    }
+
+    /// Blend state of the color target, `BlendState::ALPHA` by default.
+    /// `BlendState::ADD` suits glowing particles, `BlendState::PREMULTIPLIED_ALPHA`
+    /// textures with premultiplied alpha.
+    pub fn with_blend(mut self, blend: pso::BlendState) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    /// Add a color attachment after the ones already set, written by the next output
+    /// of the fragment shader. The first attachment is always set, blended with `with_blend`.
+    ///
+    /// Every group of a subpass has to write the same attachments.
+    pub fn with_color_target(mut self, target: pso::ColorBlendDesc) -> Self {
+        self.color_targets.push(target);
+        self
+    }
+
+    /// Write object ids into the second color attachment, matching an opaque pass
+    /// built with `with_object_ids` in the same subpass.
+    pub fn with_object_ids(self) -> Self {
+        self.with_color_target(pso::ColorBlendDesc(
+            pso::ColorMask::ALL,
+            pso::BlendState::Off,
+        ))
+    }
 }
 
 impl<B: Backend, T: Base3DPassDef<B>> RenderGroupDesc<B, Resources>
     for DrawBase3DTransparentDesc<B, T>
 {
+    fn buffers(&self) -> Vec<BufferAccess> {
+        light_tiles_access(self.light_tiles)
+    }
+
     fn build(
         self,
-        _ctx: &GraphContext<B>,
+        ctx: &GraphContext<B>,
         factory: &mut Factory<B>,
-        _queue: QueueId,
-        _aux: &Resources,
+        queue: QueueId,
+        aux: &Resources,
         framebuffer_width: u32,
         framebuffer_height: u32,
         subpass: hal::pass::Subpass<'_, B>,
-        _buffers: Vec<NodeBuffer>,
+        buffers: Vec<NodeBuffer>,
         _images: Vec<NodeImage>,
     ) -> Result<Box<dyn RenderGroup<B, Resources>>, failure::Error> {
-        let env = EnvironmentSub::new(factory)?;
+        let env = match self.environment {
+            Some(env) => env,
+            None => EnvironmentSub::with_limits(factory, queue, self.light_limits)?,
+        };
+        let env = with_light_tiles(env, ctx, &buffers, self.light_tiles);
         let materials = MaterialSub::new(factory)?;
         let skinning = SkinningSub::new(factory)?;
+        // Occlusion computed from opaque depth does not apply to transparent surfaces.
+        let ambient_occlusion = SampledImageSub::white(factory, queue)?;
+        let shadow_map = SampledImageSub::white(factory, queue)?;
 
         let mut vertex_format_base = T::base_format();
         let mut vertex_format_skinned = T::skinned_format();
+        let mut vertex_format_color = with_vertex_color(&vertex_format_base);
+        let mut vertex_format_color_skinned = with_vertex_color(&vertex_format_skinned);
+        let vertex_colors = self.vertex_colors && T::vertex_color_shader().is_some();
 
-        let (mut pipelines, pipeline_layout) = build_pipelines::<B, T>(
+        let (pipelines, pipeline_layout) = build_pipelines::<B, T>(
             factory,
             subpass,
-            framebuffer_width,
-            framebuffer_height,
             &vertex_format_base,
             &vertex_format_skinned,
+            &vertex_format_color,
+            &vertex_format_color_skinned,
             self.skinning,
-            true,
+            vertex_colors,
+            self.depth_write,
+            self.blend,
+            &self.color_targets,
+            false,
+            self.attribute_base,
+            self.cull_face,
+            self.depth_test,
+            self.stencil_test,
+            aux,
+            util::multisampling(self.samples, false),
+            env.limits(),
+            &self.spec_constants,
             vec![
                 env.raw_layout(),
                 materials.raw_layout(),
                 skinning.raw_layout(),
+                ambient_occlusion.raw_layout(),
+                shadow_map.raw_layout(),
             ],
         )?;
 
         vertex_format_base.sort();
         vertex_format_skinned.sort();
+        vertex_format_color.sort();
+        vertex_format_color_skinned.sort();
+
+        let mut pipelines = pipelines.into_iter();
 
         Ok(Box::new(DrawBase3DTransparent::<B, T> {
-            pipeline_basic: pipelines.remove(0),
-            pipeline_skinned: pipelines.pop(),
+            pipeline_basic: pipelines.next().unwrap(),
+            pipeline_skinned: if self.skinning {
+                pipelines.next()
+            } else {
+                None
+            },
+            pipeline_color: pipelines.next(),
+            pipeline_color_skinned: pipelines.next(),
             pipeline_layout,
             static_batches: Default::default(),
             skinned_batches: Default::default(),
             vertex_format_base,
             vertex_format_skinned,
+            vertex_format_color,
+            vertex_format_color_skinned,
             env,
             materials,
             skinning,
-            models: DynamicVertex::new(),
-            skinned_models: DynamicVertex::new(),
+            ambient_occlusion,
+            shadow_map,
+            models: DynamicVertex::new()
+                .with_growth(self.instance_growth)
+                .with_shrink_after(self.instance_shrink_after),
+            skinned_models: DynamicVertex::new()
+                .with_growth(self.instance_growth)
+                .with_shrink_after(self.instance_shrink_after),
             change: Default::default(),
+            viewport_envs: Vec::new(),
+            viewports: Vec::new(),
+            viewport_rect: util::viewport_rect(aux, framebuffer_width, framebuffer_height),
             marker: PhantomData,
         }))
     }
@@ -503,17 +1215,27 @@ impl<B: Backend, T: Base3DPassDef<B>> RenderGroupDesc<B, Resources>
 pub struct DrawBase3DTransparent<B: Backend, T: Base3DPassDef<B>> {
     pipeline_basic: B::GraphicsPipeline,
     pipeline_skinned: Option<B::GraphicsPipeline>,
+    pipeline_color: Option<B::GraphicsPipeline>,
+    pipeline_color_skinned: Option<B::GraphicsPipeline>,
     pipeline_layout: B::PipelineLayout,
-    static_batches: OrderedTwoLevelBatch<MaterialId, u32, VertexArgs>,
-    skinned_batches: OrderedTwoLevelBatch<MaterialId, u32, SkinnedVertexArgs>,
+    /// Batches keyed by the index of the view drawing them, each view has its own order.
+    static_batches: OrderedTwoLevelBatch<(usize, MaterialId), u32, VertexArgs>,
+    skinned_batches: OrderedTwoLevelBatch<(usize, MaterialId), u32, SkinnedVertexArgs>,
     vertex_format_base: Vec<VertexFormat>,
     vertex_format_skinned: Vec<VertexFormat>,
+    vertex_format_color: Vec<VertexFormat>,
+    vertex_format_color_skinned: Vec<VertexFormat>,
     env: EnvironmentSub<B>,
     materials: MaterialSub<B, FullTextureSet>,
     skinning: SkinningSub<B>,
+    ambient_occlusion: SampledImageSub<B>,
+    shadow_map: SampledImageSub<B>,
     models: DynamicVertex<B, VertexArgs>,
     skinned_models: DynamicVertex<B, SkinnedVertexArgs>,
     change: util::ChangeDetection,
+    viewport_envs: Vec<EnvironmentSub<B>>,
+    viewports: Vec<pso::Rect>,
+    viewport_rect: pso::Rect,
     marker: PhantomData<(T)>,
 }
 
@@ -526,20 +1248,101 @@ impl<B: Backend, T: Base3DPassDef<B>> RenderGroup<B, Resources> for DrawBase3DTr
         _subpass: hal::pass::Subpass<'_, B>,
         resources: &Resources,
     ) -> PrepareResult {
-        let (mesh_storage, visibility, meshes, materials, transforms, joints, tints) =
-            <(
-                Read<AssetStorage<Mesh>>,
-                ReadExpect<Visibility>,
-                ReadStorage<Handle<Mesh>>,
-                ReadStorage<Handle<Material>>,
-                ReadStorage<Transform>,
-                ReadStorage<JointTransforms>,
-                ReadStorage<Tint>,
-            )>::fetch(resources);
+        let (
+            entities,
+            mesh_storage,
+            visibility,
+            viewports,
+            transparent,
+            hiddens,
+            hiddens_prop,
+            bounds,
+            meshes,
+            materials,
+            transforms,
+            joints,
+            tints,
+            orders,
+        ) = <(
+            Entities,
+            Read<AssetStorage<Mesh>>,
+            ReadExpect<Visibility>,
+            Option<Read<CameraViewports>>,
+            ReadStorage<Transparent>,
+            ReadStorage<Hidden>,
+            ReadStorage<HiddenPropagate>,
+            ReadStorage<BoundingSphere>,
+            ReadStorage<Handle<Mesh>>,
+            ReadStorage<Handle<Material>>,
+            ReadStorage<Transform>,
+            ReadStorage<JointTransforms>,
+            ReadStorage<Tint>,
+            ReadStorage<RenderOrder>,
+        )>::fetch(resources);
 
-        // Prepare environment
-        self.env.process(factory, index, resources);
-        self.materials.maintain();
+        // A camera set on the group takes precedence over split-screen.
+        let split_screen = viewports
+            .as_ref()
+            .filter(|viewports| self.env.camera().is_none() && !viewports.0.is_empty());
+
+        // Prepare environment and the drawing order of every view
+        self.viewports.clear();
+        let mut split_orders = Vec::new();
+        match split_screen {
+            None => {
+                self.env.process(factory, index, resources);
+            }
+            Some(split_screen) => {
+                // `Visibility` is sorted for the active camera only, and split-screen views
+                // aren't culled.
+                let objects = (
+                    &entities,
+                    &transparent,
+                    &transforms,
+                    bounds.maybe(),
+                    !&hiddens,
+                    !&hiddens_prop,
+                )
+                    .join()
+                    .map(|(entity, _, transform, sphere, _, _)| {
+                        (
+                            entity,
+                            orders.get(entity).cloned().unwrap_or_default(),
+                            visibility::centroid(transform, sphere),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                for (i, viewport) in split_screen.0.iter().enumerate() {
+                    if self.viewport_envs.len() == i {
+                        self.viewport_envs.push(self.env.for_camera(None));
+                    }
+                    let env = &mut self.viewport_envs[i];
+                    env.set_camera(Some(viewport.entity));
+                    env.process(factory, index, resources);
+                    // Split-screen areas divide the area kept by an `AspectRatioLock`.
+                    let area = self.viewport_rect;
+                    let rect = viewport.rect(area.w as u32, area.h as u32);
+                    self.viewports.push(pso::Rect {
+                        x: area.x + rect.x,
+                        y: area.y + rect.y,
+                        ..rect
+                    });
+
+                    let view = transforms
+                        .get(viewport.entity)
+                        .and_then(|transform| transform.global_matrix().try_inverse())
+                        .unwrap_or_else(Matrix4::identity);
+                    split_orders.push(visibility::sort_for_view(&view, &objects));
+                }
+            }
+        }
+        let view_orders = if split_orders.is_empty() {
+            vec![&visibility.visible_ordered[..]]
+        } else {
+            split_orders.iter().map(Vec::as_slice).collect()
+        };
+        self.materials.maintain(factory, resources);
 
         self.static_batches.swap_clear();
         self.skinned_batches.swap_clear();
@@ -550,53 +1353,84 @@ impl<B: Backend, T: Base3DPassDef<B>> RenderGroup<B, Resources> for DrawBase3DTr
         let skinned_ref = &mut self.skinned_batches;
         let mut changed = false;
 
-        let mut joined = ((&materials, &meshes, &transforms, tints.maybe()), !&joints).join();
-        visibility
-            .visible_ordered
-            .iter()
-            .filter_map(|e| joined.get_unchecked(e.id()))
-            .map(|((mat, mesh, tform, tint), _)| {
-                ((mat, mesh.id()), VertexArgs::from_object_data(tform, tint))
-            })
-            .for_each_group(|(mat, mesh_id), data| {
-                if mesh_storage.contains_id(mesh_id) {
-                    if let Some((mat, this_changed)) = materials_ref.insert(factory, resources, mat)
-                    {
-                        changed = changed || this_changed;
-                        statics_ref.insert(mat, mesh_id, data.drain(..));
-                    }
-                }
-            });
-
-        if self.pipeline_skinned.is_some() {
-            let mut joined = (&materials, &meshes, &transforms, tints.maybe(), &joints).join();
-
-            visibility
-                .visible_ordered
+        // Blended meshes are faded through their alpha, `DitheredFade` only applies to opaque ones.
+        let mut joined = (
+            (
+                &entities,
+                &materials,
+                &meshes,
+                &transforms,
+                tints.maybe(),
+                orders.maybe(),
+            ),
+            !&joints,
+        )
+            .join();
+        // Every view orders the instances by render order, then back to front, so every
+        // run of the same mesh keeps that order.
+        for (view, ordered) in view_orders.iter().enumerate() {
+            ordered
                 .iter()
                 .filter_map(|e| joined.get_unchecked(e.id()))
-                .map(|(mat, mesh, tform, tint, joints)| {
+                .map(|((entity, mat, mesh, tform, tint, order), _)| {
                     (
-                        (mat, mesh.id()),
-                        SkinnedVertexArgs::from_object_data(
-                            tform,
-                            tint,
-                            skinning_ref.insert(joints),
-                        ),
+                        (mat, mesh.id(), order.cloned().unwrap_or_default()),
+                        VertexArgs::from_object_data(tform, tint, None).with_object_id(entity),
                     )
                 })
-                .for_each_group(|(mat, mesh_id), data| {
+                .for_each_group(|(mat, mesh_id, _), data| {
                     if mesh_storage.contains_id(mesh_id) {
                         if let Some((mat, this_changed)) =
                             materials_ref.insert(factory, resources, mat)
                         {
                             changed = changed || this_changed;
-                            skinned_ref.insert(mat, mesh_id, data.drain(..));
+                            statics_ref.insert((view, mat), mesh_id, data.drain(..));
                         }
                     }
                 });
         }
 
+        if self.pipeline_skinned.is_some() {
+            let mut joined = (
+                &entities,
+                &materials,
+                &meshes,
+                &transforms,
+                tints.maybe(),
+                orders.maybe(),
+                &joints,
+            )
+                .join();
+
+            for (view, ordered) in view_orders.iter().enumerate() {
+                ordered
+                    .iter()
+                    .filter_map(|e| joined.get_unchecked(e.id()))
+                    .map(|(entity, mat, mesh, tform, tint, order, joints)| {
+                        (
+                            (mat, mesh.id(), order.cloned().unwrap_or_default()),
+                            SkinnedVertexArgs::from_object_data(
+                                tform,
+                                tint,
+                                None,
+                                skinning_ref.insert(joints),
+                            )
+                            .with_object_id(entity),
+                        )
+                    })
+                    .for_each_group(|(mat, mesh_id, _), data| {
+                        if mesh_storage.contains_id(mesh_id) {
+                            if let Some((mat, this_changed)) =
+                                materials_ref.insert(factory, resources, mat)
+                            {
+                                changed = changed || this_changed;
+                                skinned_ref.insert((view, mat), mesh_id, data.drain(..));
+                            }
+                        }
+                    });
+            }
+        }
+
         self.models.write(
             factory,
             index,
@@ -616,6 +1450,30 @@ impl<B: Backend, T: Base3DPassDef<B>> RenderGroup<B, Resources> for DrawBase3DTr
         changed = changed || self.static_batches.changed();
         changed = changed || self.skinned_batches.changed();
 
+        if let Some(mut stats) = <Option<Write<'_, RenderStats>>>::fetch(resources) {
+            let materials = &self.materials;
+            record_draws::<B>(
+                &mut stats,
+                &mesh_storage,
+                1,
+                self.static_batches
+                    .iter()
+                    .filter(|&(&(_, mat), _)| materials.loaded(mat))
+                    .flat_map(|(_, batches)| batches)
+                    .map(|(mesh_id, range)| (*mesh_id, range.end - range.start)),
+            );
+            record_draws::<B>(
+                &mut stats,
+                &mesh_storage,
+                1,
+                self.skinned_batches
+                    .iter()
+                    .filter(|&(&(_, mat), _)| materials.loaded(mat))
+                    .flat_map(|(_, batches)| batches)
+                    .map(|(mesh_id, range)| (*mesh_id, range.end - range.start)),
+            );
+        }
+
         self.change.prepare_result(index, changed)
     }
 
@@ -626,18 +1484,93 @@ impl<B: Backend, T: Base3DPassDef<B>> RenderGroup<B, Resources> for DrawBase3DTr
         _subpass: hal::pass::Subpass<'_, B>,
         resources: &Resources,
     ) {
+        if self.static_batches.is_empty() && self.skinned_batches.is_empty() {
+            return;
+        }
+
         let mesh_storage = <Read<'_, AssetStorage<Mesh>>>::fetch(resources);
+
+        if self.viewports.is_empty() {
+            self.draw_view(
+                &mut encoder,
+                index,
+                0,
+                &self.env,
+                self.viewport_rect,
+                &mesh_storage,
+            );
+        } else {
+            for (view, (&rect, env)) in self.viewports.iter().zip(&self.viewport_envs).enumerate() {
+                self.draw_view(&mut encoder, index, view, env, rect, &mesh_storage);
+            }
+        }
+    }
+
+    fn dispose(mut self: Box<Self>, factory: &mut Factory<B>, _aux: &Resources) {
+        unsafe {
+            factory
+                .device()
+                .destroy_graphics_pipeline(self.pipeline_basic);
+            self.pipeline_skinned.take().map(|pipeline| {
+                factory.device().destroy_graphics_pipeline(pipeline);
+            });
+            self.pipeline_color.take().map(|pipeline| {
+                factory.device().destroy_graphics_pipeline(pipeline);
+            });
+            self.pipeline_color_skinned.take().map(|pipeline| {
+                factory.device().destroy_graphics_pipeline(pipeline);
+            });
+            factory
+                .device()
+                .destroy_pipeline_layout(self.pipeline_layout);
+        }
+    }
+}
+
+impl<B: Backend, T: Base3DPassDef<B>> DrawBase3DTransparent<B, T> {
+    /// Draw the batches gathered for `view` into `rect`, as seen through the camera of `env`.
+    fn draw_view(
+        &self,
+        encoder: &mut RenderPassEncoder<'_, B>,
+        index: usize,
+        view: usize,
+        env: &EnvironmentSub<B>,
+        rect: pso::Rect,
+        mesh_storage: &AssetStorage<Mesh>,
+    ) {
         let layout = &self.pipeline_layout;
-        let encoder = &mut encoder;
+
+        encoder.set_viewports(
+            0,
+            &[pso::Viewport {
+                rect,
+                depth: 0.0..1.0,
+            }],
+        );
+        encoder.set_scissors(0, &[rect]);
 
         let models_loc = self.vertex_format_base.len() as u32;
         let skin_models_loc = self.vertex_format_skinned.len() as u32;
 
         encoder.bind_graphics_pipeline(&self.pipeline_basic);
-        self.env.bind(index, layout, 0, encoder);
+        env.bind(index, layout, 0, encoder);
+        self.ambient_occlusion.bind(layout, 3, encoder);
+        self.shadow_map.bind(layout, 4, encoder);
 
         if self.models.bind(index, models_loc, encoder) {
-            for (&mat, batches) in self.static_batches.iter() {
+            let mut drawer = MeshDrawer::new(
+                (&self.pipeline_basic, &self.vertex_format_base[..]),
+                self.pipeline_color
+                    .as_ref()
+                    .map(|pipeline| (pipeline, &self.vertex_format_color[..])),
+                &self.models,
+                index,
+            );
+            for (&(_, mat), batches) in self
+                .static_batches
+                .iter()
+                .filter(|&(&(batch_view, _), _)| batch_view == view)
+            {
                 if self.materials.loaded(mat) {
                     self.materials.bind(layout, 1, mat, encoder);
                     for (mesh, range) in batches {
@@ -645,8 +1578,7 @@ impl<B: Backend, T: Base3DPassDef<B>> RenderGroup<B, Resources> for DrawBase3DTr
                         if let Some(mesh) =
                             B::unwrap_mesh(unsafe { mesh_storage.get_by_id_unchecked(*mesh) })
                         {
-                            mesh.bind_and_draw(0, &self.vertex_format_base, range.clone(), encoder)
-                                .unwrap();
+                            drawer.draw(mesh, range.clone(), encoder);
                         }
                     }
                 }
@@ -658,7 +1590,19 @@ impl<B: Backend, T: Base3DPassDef<B>> RenderGroup<B, Resources> for DrawBase3DTr
 
             if self.skinned_models.bind(index, skin_models_loc, encoder) {
                 self.skinning.bind(index, layout, 2, encoder);
-                for (&mat, batches) in self.skinned_batches.iter() {
+                let mut drawer = MeshDrawer::new(
+                    (pipeline_skinned, &self.vertex_format_skinned[..]),
+                    self.pipeline_color_skinned
+                        .as_ref()
+                        .map(|pipeline| (pipeline, &self.vertex_format_color_skinned[..])),
+                    &self.skinned_models,
+                    index,
+                );
+                for (&(_, mat), batches) in self
+                    .skinned_batches
+                    .iter()
+                    .filter(|&(&(batch_view, _), _)| batch_view == view)
+                {
                     if self.materials.loaded(mat) {
                         self.materials.bind(layout, 1, mat, encoder);
                         for (mesh, range) in batches {
@@ -666,13 +1610,7 @@ impl<B: Backend, T: Base3DPassDef<B>> RenderGroup<B, Resources> for DrawBase3DTr
                             if let Some(mesh) =
                                 B::unwrap_mesh(unsafe { mesh_storage.get_by_id_unchecked(*mesh) })
                             {
-                                mesh.bind_and_draw(
-                                    0,
-                                    &self.vertex_format_skinned,
-                                    range.clone(),
-                                    encoder,
-                                )
-                                .unwrap();
+                                drawer.draw(mesh, range.clone(), encoder);
                             }
                         }
                     }
@@ -680,31 +1618,137 @@ impl<B: Backend, T: Base3DPassDef<B>> RenderGroup<B, Resources> for DrawBase3DTr
             }
         }
     }
+}
 
-    fn dispose(mut self: Box<Self>, factory: &mut Factory<B>, _aux: &Resources) {
-        unsafe {
-            factory
-                .device()
-                .destroy_graphics_pipeline(self.pipeline_basic);
-            self.pipeline_skinned.take().map(|pipeline| {
-                factory.device().destroy_graphics_pipeline(pipeline);
-            });
-            factory
-                .device()
-                .destroy_pipeline_layout(self.pipeline_layout);
+/// Count the draws of `(mesh id, instance count)` pairs into `stats`, once per view.
+fn record_draws<B: Backend>(
+    stats: &mut RenderStats,
+    mesh_storage: &AssetStorage<Mesh>,
+    views: usize,
+    draws: impl IntoIterator<Item = (u32, u32)>,
+) {
+    for (mesh_id, instances) in draws {
+        // Batches only hold meshes present in the storage.
+        if let Some(mesh) = B::unwrap_mesh(unsafe { mesh_storage.get_by_id_unchecked(mesh_id) }) {
+            for _ in 0..views {
+                stats.record_draw(mesh.len(), instances);
+            }
+        }
+    }
+}
+
+/// Draws meshes with the vertex color pipeline of a pass when they have a `Color`
+/// attribute and with its regular pipeline otherwise, switching only when needed.
+struct MeshDrawer<'a, B: Backend, V: 'static> {
+    regular: (&'a B::GraphicsPipeline, &'a [VertexFormat]),
+    colored: Option<(&'a B::GraphicsPipeline, &'a [VertexFormat])>,
+    models: &'a DynamicVertex<B, V>,
+    index: usize,
+    bound_colored: bool,
+}
+
+impl<'a, B: Backend, V: 'static> MeshDrawer<'a, B, V> {
+    /// Starts with the regular pipeline and its instance data already bound.
+    fn new(
+        regular: (&'a B::GraphicsPipeline, &'a [VertexFormat]),
+        colored: Option<(&'a B::GraphicsPipeline, &'a [VertexFormat])>,
+        models: &'a DynamicVertex<B, V>,
+        index: usize,
+    ) -> Self {
+        Self {
+            regular,
+            colored,
+            models,
+            index,
+            bound_colored: false,
+        }
+    }
+
+    fn draw(
+        &mut self,
+        mesh: &rendy::mesh::Mesh<B>,
+        instances: Range<u32>,
+        encoder: &mut RenderPassEncoder<'_, B>,
+    ) {
+        let colored = self
+            .colored
+            .filter(|&(_, format)| mesh.bind(0, format, encoder).is_ok());
+        let (pipeline, format) = colored.unwrap_or(self.regular);
+
+        if colored.is_some() != self.bound_colored {
+            // Instance data follows the vertex attributes, so it moves with them.
+            encoder.bind_graphics_pipeline(pipeline);
+            self.models.bind(self.index, format.len() as u32, encoder);
+            self.bound_colored = colored.is_some();
+        }
+
+        mesh.bind_and_draw(0, format, instances, encoder).unwrap();
+    }
+}
+
+fn light_tiles_access(tiling: Option<LightTiling>) -> Vec<BufferAccess> {
+    tiling
+        .map(|_| BufferAccess {
+            access: hal::buffer::Access::SHADER_READ,
+            usage: hal::buffer::Usage::STORAGE,
+            stages: pso::PipelineStage::FRAGMENT_SHADER,
+        })
+        .into_iter()
+        .collect()
+}
+
+/// Bind the light tiles read from the first buffer of the group, when it has one.
+fn with_light_tiles<B: Backend>(
+    env: EnvironmentSub<B>,
+    ctx: &GraphContext<B>,
+    buffers: &[NodeBuffer],
+    tiling: Option<LightTiling>,
+) -> EnvironmentSub<B> {
+    match (tiling, buffers.first()) {
+        (Some(tiling), Some(buffer)) => {
+            let buffer = ctx
+                .get_buffer(buffer.id)
+                .expect("Light tile buffer is not created by the graph");
+            env.with_light_tiles(buffer.clone(), tiling)
         }
+        _ => env,
+    }
+}
+
+fn warn_light_limit_constants(constants: &SpecConstants) {
+    for constant in LIGHT_LIMIT_CONSTANTS
+        .iter()
+        .filter(|constant| constants.contains(constant.id))
+    {
+        log::warn!(
+            "Specialization constant {} holds a light limit and is ignored, use \
+             `with_max_point_lights` and its siblings to set the light limits",
+            constant.id
+        );
     }
 }
 
 fn build_pipelines<B: Backend, T: Base3DPassDef<B>>(
     factory: &Factory<B>,
     subpass: hal::pass::Subpass<'_, B>,
-    framebuffer_width: u32,
-    framebuffer_height: u32,
     vertex_format_base: &[VertexFormat],
     vertex_format_skinned: &[VertexFormat],
+    vertex_format_color: &[VertexFormat],
+    vertex_format_color_skinned: &[VertexFormat],
     skinning: bool,
-    transparent: bool,
+    vertex_colors: bool,
+    depth_write: bool,
+    blend: pso::BlendState,
+    color_targets: &[pso::ColorBlendDesc],
+    depth_prepass: bool,
+    attribute_base: pso::Location,
+    cull_face: pso::Face,
+    depth_test: Option<pso::DepthTest>,
+    stencil_test: pso::StencilTest,
+    res: &Resources,
+    multisampling: Option<pso::Multisampling>,
+    light_limits: LightLimits,
+    spec_constants: &SpecConstants,
     layouts: Vec<&B::DescriptorSetLayout>,
 ) -> Result<(Vec<B::GraphicsPipeline>, B::PipelineLayout), failure::Error> {
     let pipeline_layout = unsafe {
@@ -713,88 +1757,186 @@ fn build_pipelines<B: Backend, T: Base3DPassDef<B>>(
             .create_pipeline_layout(layouts, None as Option<(_, _)>)
     }?;
 
-    let vertex_desc = vertex_format_base
-        .iter()
-        .map(|f| (f.clone(), pso::VertexInputRate::Vertex))
-        .chain(Some((
-            VertexArgs::vertex(),
-            pso::VertexInputRate::Instance(1),
-        )))
-        .collect::<Vec<_>>();
-
-    let shader_vertex_basic = unsafe { T::vertex_shader().module(factory).unwrap() };
-    let shader_fragment = unsafe { T::fragment_shader().module(factory).unwrap() };
-    let pipe_desc = PipelineDescBuilder::new()
-        .with_vertex_desc(&vertex_desc)
-        .with_shaders(util::simple_shader_set(
-            &shader_vertex_basic,
-            Some(&shader_fragment),
-        ))
-        .with_layout(&pipeline_layout)
-        .with_subpass(subpass)
-        .with_framebuffer_size(framebuffer_width, framebuffer_height)
-        .with_face_culling(pso::Face::BACK)
-        .with_depth_test(pso::DepthTest::On {
-            fun: pso::Comparison::Less,
-            write: !transparent,
-        })
-        .with_blend_targets(vec![pso::ColorBlendDesc(
-            pso::ColorMask::ALL,
-            if transparent {
-                pso::BlendState::ALPHA
-            } else {
-                pso::BlendState::Off
-            },
-        )]);
+    // The regular pipelines come first, followed by the vertex color ones.
+    let mut variants = vec![(
+        T::vertex_shader(),
+        Some(T::vertex_skinned_shader()),
+        vertex_format_base,
+        vertex_format_skinned,
+    )];
+    if let (true, Some(shader)) = (vertex_colors, T::vertex_color_shader()) {
+        variants.push((
+            shader,
+            T::vertex_color_skinned_shader(),
+            vertex_format_color,
+            vertex_format_color_skinned,
+        ));
+    }
 
-    let pipelines = if skinning {
-        let shader_vertex_skinned = unsafe { T::vertex_skinned_shader().module(factory).unwrap() };
+    let depth_mode = *res.fetch::<DepthMode>();
+    let shader_fragment = match unsafe { util::shader_module(factory, res, T::fragment_shader()) } {
+        Ok(module) => module,
+        Err(e) => {
+            unsafe {
+                factory.device().destroy_pipeline_layout(pipeline_layout);
+            }
+            return Err(e.into());
+        }
+    };
+    // Light limits are set on the fragment shader, the constants of the pass on both shaders.
+    let (fragment_constants, fragment_data) =
+        spec_constants.append_to(&LIGHT_LIMIT_CONSTANTS, &light_limits.specialization_data());
+    let (vertex_constants, vertex_data) = spec_constants.append_to(&[], &[]);
+    let polygon_mode = T::polygon_mode();
+    // Depth written by a prepass is matched exactly, unless a depth test was set explicitly.
+    let equal_depth = depth_prepass && depth_test.is_none();
+    let depth_test = depth_mode.depth_test(depth_test.unwrap_or_else(|| pso::DepthTest::On {
+        // Edges drawn over a filled pass sharing the depth buffer land on equal depths.
+        fun: match polygon_mode {
+            pso::PolygonMode::Fill => pso::Comparison::Less,
+            _ => pso::Comparison::LessEqual,
+        },
+        write: depth_write,
+    }));
+    let basic_depth_test = if equal_depth {
+        pso::DepthTest::On {
+            fun: pso::Comparison::Equal,
+            write: false,
+        }
+    } else {
+        depth_test
+    };
+    let specialization = || pso::Specialization {
+        constants: &fragment_constants,
+        data: &fragment_data,
+    };
+    let vertex_specialization = || pso::Specialization {
+        constants: &vertex_constants,
+        data: &vertex_data,
+    };
 
-        let vertex_desc = vertex_format_skinned
+    let mut pipelines = Vec::new();
+    let mut error = None;
+    for (shader_basic, shader_skinned, format_base, format_skinned) in variants {
+        let vertex_desc = format_base
             .iter()
             .map(|f| (f.clone(), pso::VertexInputRate::Vertex))
             .chain(Some((
-                SkinnedVertexArgs::vertex(),
+                VertexArgs::vertex(),
                 pso::VertexInputRate::Instance(1),
             )))
             .collect::<Vec<_>>();
 
-        let pipe = PipelinesBuilder::new()
-            .with_pipeline(pipe_desc.clone())
-            .with_child_pipeline(
-                0,
-                pipe_desc
-                    .with_vertex_desc(&vertex_desc)
-                    .with_shaders(util::simple_shader_set(
-                        &shader_vertex_skinned,
-                        Some(&shader_fragment),
-                    )),
-            )
-            .build(factory, None);
+        let shader_vertex_basic = match unsafe { util::shader_module(factory, res, shader_basic) } {
+            Ok(module) => module,
+            Err(e) => {
+                error = Some(e.into());
+                break;
+            }
+        };
+        let pipe_desc = PipelineDescBuilder::new()
+            .with_vertex_desc_at(&vertex_desc, attribute_base)
+            .with_shaders(util::specialized_shader_set(
+                &shader_vertex_basic,
+                Some(&shader_fragment),
+                vertex_specialization(),
+                specialization(),
+            ))
+            .with_layout(&pipeline_layout)
+            .with_subpass(subpass)
+            // Viewports are set while drawing, for split-screen.
+            .with_face_culling(cull_face)
+            .with_polygon_mode(polygon_mode)
+            .with_depth_test(basic_depth_test)
+            .with_stencil_test(stencil_test)
+            .with_multisampling(multisampling)
+            .with_blend_targets(
+                Some(pso::ColorBlendDesc(pso::ColorMask::ALL, blend))
+                    .into_iter()
+                    .chain(color_targets.iter().cloned())
+                    .collect(),
+            );
+
+        let pipes = match shader_skinned.filter(|_| skinning) {
+            Some(shader_skinned) => {
+                match unsafe { util::shader_module(factory, res, shader_skinned) } {
+                    Err(e) => Err(e.into()),
+                    Ok(shader_vertex_skinned) => {
+                        let vertex_desc = format_skinned
+                            .iter()
+                            .map(|f| (f.clone(), pso::VertexInputRate::Vertex))
+                            .chain(Some((
+                                SkinnedVertexArgs::vertex(),
+                                pso::VertexInputRate::Instance(1),
+                            )))
+                            .collect::<Vec<_>>();
+
+                        let pipe = PipelinesBuilder::new()
+                            .with_pipeline(pipe_desc.clone())
+                            .with_child_pipeline(
+                                0,
+                                pipe_desc
+                                    .with_vertex_desc_at(&vertex_desc, attribute_base)
+                                    .with_depth_test(depth_test)
+                                    .with_stencil_test(stencil_test)
+                                    .with_shaders(util::specialized_shader_set(
+                                        &shader_vertex_skinned,
+                                        Some(&shader_fragment),
+                                        vertex_specialization(),
+                                        specialization(),
+                                    )),
+                            )
+                            .build(factory, None);
+
+                        unsafe {
+                            factory.destroy_shader_module(shader_vertex_skinned);
+                        }
+
+                        pipe
+                    }
+                }
+            }
+            None => PipelinesBuilder::new()
+                .with_pipeline(pipe_desc)
+                .build(factory, None),
+        };
 
         unsafe {
-            factory.destroy_shader_module(shader_vertex_skinned);
+            factory.destroy_shader_module(shader_vertex_basic);
         }
 
-        pipe
-    } else {
-        PipelinesBuilder::new()
-            .with_pipeline(pipe_desc)
-            .build(factory, None)
-    };
+        match pipes {
+            Ok(pipes) => pipelines.extend(pipes),
+            Err(e) => {
+                error = Some(e);
+                break;
+            }
+        }
+    }
 
     unsafe {
-        factory.destroy_shader_module(shader_vertex_basic);
         factory.destroy_shader_module(shader_fragment);
     }
 
-    match pipelines {
-        Err(e) => {
+    match error {
+        Some(e) => {
             unsafe {
+                for pipeline in pipelines {
+                    factory.device().destroy_graphics_pipeline(pipeline);
+                }
                 factory.device().destroy_pipeline_layout(pipeline_layout);
             }
             Err(e)
         }
-        Ok(pipelines) => Ok((pipelines, pipeline_layout)),
+        None => Ok((pipelines, pipeline_layout)),
     }
 }
+
+/// Vertex formats of a pass with a `Color` attribute appended, for the vertex color shaders.
+fn with_vertex_color(formats: &[VertexFormat]) -> Vec<VertexFormat> {
+    formats
+        .iter()
+        .cloned()
+        .chain(Some(Color::vertex()))
+        .collect()
+}
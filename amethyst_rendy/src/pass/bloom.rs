@@ -0,0 +1,278 @@
+//! Bloom of the bright parts of a high dynamic range color image.
+//!
+//! The chain is made of render groups each reading the image written by the
+//! previous one through the group builder. Drawing into images smaller than the
+//! source downsamples them, so a few levels can be blurred cheaply and composited back:
+//!
+//! ```ignore
+//! DrawBloomExtractDesc::new().builder().with_image(hdr_color) // into a half size image
+//! DrawBloomBlurDesc::horizontal().builder().with_image(bright)
+//! DrawBloomBlurDesc::vertical().builder().with_image(blurred_horizontally)
+//! DrawBloomDesc::new().builder().with_image(blurred) // into hdr_color, loaded
+//! ```
+//!
+//! Settings are read from the `BloomSettings` resource every frame, or its
+//! defaults when there is none.
+
+use super::fullscreen::build_fullscreen_pipeline;
+use crate::{
+    pod::BloomArgs,
+    resources::BloomSettings,
+    submodules::{DynamicUniform, SampledImageSub},
+    types::Backend,
+    util,
+};
+use amethyst_core::ecs::{Read, Resources, SystemData};
+use derivative::Derivative;
+use glsl_layout::*;
+use rendy::{
+    command::{QueueId, RenderPassEncoder},
+    factory::Factory,
+    graph::{
+        render::{PrepareResult, RenderGroup, RenderGroupDesc},
+        GraphContext, ImageAccess, NodeBuffer, NodeImage,
+    },
+    hal::{self, device::Device, image::Filter, pso},
+    shader::SpirvShader,
+};
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BloomStage {
+    Extract,
+    Blur([f32; 2]),
+    Composite,
+}
+
+impl BloomStage {
+    fn fragment(self) -> &'static SpirvShader {
+        match self {
+            BloomStage::Extract => &super::BLOOM_EXTRACT_FRAGMENT,
+            BloomStage::Blur(_) => &super::BLOOM_BLUR_FRAGMENT,
+            BloomStage::Composite => &super::BLOOM_COMPOSITE_FRAGMENT,
+        }
+    }
+
+    fn blend(self) -> pso::BlendState {
+        match self {
+            BloomStage::Composite => pso::BlendState::On {
+                color: pso::BlendOp::Add {
+                    src: pso::Factor::One,
+                    dst: pso::Factor::One,
+                },
+                alpha: pso::BlendOp::Add {
+                    src: pso::Factor::Zero,
+                    dst: pso::Factor::One,
+                },
+            },
+            _ => pso::BlendState::Off,
+        }
+    }
+
+    fn direction(self) -> [f32; 2] {
+        match self {
+            BloomStage::Blur(direction) => direction,
+            _ => [0.0, 0.0],
+        }
+    }
+}
+
+/// Extract the parts of a high dynamic range image brighter than `BloomSettings::threshold`.
+#[derive(Clone, Debug, PartialEq, Derivative)]
+#[derivative(Default(bound = ""))]
+pub struct DrawBloomExtractDesc;
+
+impl DrawBloomExtractDesc {
+    /// Create instance of the bright pass render group
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+/// Blur an image in one direction over `BloomSettings::radius` texels.
+///
+/// A horizontal and a vertical blur together give a gaussian blur.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DrawBloomBlurDesc {
+    direction: [f32; 2],
+}
+
+impl DrawBloomBlurDesc {
+    /// Create instance of a render group blurring along the x axis
+    pub fn horizontal() -> Self {
+        DrawBloomBlurDesc {
+            direction: [1.0, 0.0],
+        }
+    }
+
+    /// Create instance of a render group blurring along the y axis
+    pub fn vertical() -> Self {
+        DrawBloomBlurDesc {
+            direction: [0.0, 1.0],
+        }
+    }
+}
+
+/// Add a blurred bright image onto its color attachment, scaled by `BloomSettings::intensity`.
+///
+/// The color attachment has to keep its contents, so the scene is not cleared.
+#[derive(Clone, Debug, PartialEq, Derivative)]
+#[derivative(Default(bound = ""))]
+pub struct DrawBloomDesc;
+
+impl DrawBloomDesc {
+    /// Create instance of `DrawBloom` render group
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+macro_rules! impl_bloom_desc {
+    ($desc:ty, |$this:ident| $stage:expr) => {
+        impl<B: Backend> RenderGroupDesc<B, Resources> for $desc {
+            fn images(&self) -> Vec<ImageAccess> {
+                vec![util::sampled_image_access()]
+            }
+
+            fn depth(&self) -> bool {
+                false
+            }
+
+            fn build(
+                self,
+                ctx: &GraphContext<B>,
+                factory: &mut Factory<B>,
+                _queue: QueueId,
+                _aux: &Resources,
+                framebuffer_width: u32,
+                framebuffer_height: u32,
+                subpass: hal::pass::Subpass<'_, B>,
+                _buffers: Vec<NodeBuffer>,
+                images: Vec<NodeImage>,
+            ) -> Result<Box<dyn RenderGroup<B, Resources>>, failure::Error> {
+                let $this = self;
+                DrawBloom::build(
+                    ctx,
+                    factory,
+                    framebuffer_width,
+                    framebuffer_height,
+                    subpass,
+                    &images[0],
+                    $stage,
+                )
+            }
+        }
+    };
+}
+
+impl_bloom_desc!(DrawBloomExtractDesc, |_this| BloomStage::Extract);
+impl_bloom_desc!(DrawBloomBlurDesc, |this| BloomStage::Blur(this.direction));
+impl_bloom_desc!(DrawBloomDesc, |_this| BloomStage::Composite);
+
+/// Render group drawing one stage of the bloom chain.
+#[derive(Debug)]
+pub struct DrawBloom<B: Backend> {
+    pipeline: B::GraphicsPipeline,
+    pipeline_layout: B::PipelineLayout,
+    args: DynamicUniform<B, BloomArgs>,
+    input: SampledImageSub<B>,
+    stage: BloomStage,
+    change: util::ChangeDetection,
+}
+
+impl<B: Backend> DrawBloom<B> {
+    fn build(
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        framebuffer_width: u32,
+        framebuffer_height: u32,
+        subpass: hal::pass::Subpass<'_, B>,
+        image: &NodeImage,
+        stage: BloomStage,
+    ) -> Result<Box<dyn RenderGroup<B, Resources>>, failure::Error> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("build");
+
+        let args = DynamicUniform::new(factory, pso::ShaderStageFlags::FRAGMENT)?;
+        let input = SampledImageSub::new(ctx, factory, image, Filter::Linear)?;
+
+        let (pipeline, pipeline_layout) = build_fullscreen_pipeline(
+            factory,
+            subpass,
+            framebuffer_width,
+            framebuffer_height,
+            stage.fragment(),
+            stage.blend(),
+            vec![args.raw_layout(), input.raw_layout()],
+        )?;
+
+        Ok(Box::new(DrawBloom::<B> {
+            pipeline,
+            pipeline_layout,
+            args,
+            input,
+            stage,
+            change: Default::default(),
+        }))
+    }
+}
+
+impl<B: Backend> RenderGroup<B, Resources> for DrawBloom<B> {
+    fn prepare(
+        &mut self,
+        factory: &Factory<B>,
+        _queue: QueueId,
+        index: usize,
+        _subpass: hal::pass::Subpass<'_, B>,
+        resources: &Resources,
+    ) -> PrepareResult {
+        #[cfg(feature = "profiler")]
+        profile_scope!("prepare");
+
+        let settings = <Option<Read<'_, BloomSettings>>>::fetch(resources)
+            .map(|settings| settings.clone())
+            .unwrap_or_default();
+
+        let changed = self.args.write(
+            factory,
+            index,
+            BloomArgs {
+                direction: self.stage.direction().into(),
+                threshold: settings.threshold,
+                radius: settings.radius,
+                intensity: settings.intensity,
+            }
+            .std140(),
+        );
+
+        self.change.prepare_result(index, changed)
+    }
+
+    fn draw_inline(
+        &mut self,
+        mut encoder: RenderPassEncoder<'_, B>,
+        index: usize,
+        _subpass: hal::pass::Subpass<'_, B>,
+        _resources: &Resources,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("draw");
+
+        encoder.bind_graphics_pipeline(&self.pipeline);
+        self.args
+            .bind(index, &self.pipeline_layout, 0, &mut encoder);
+        self.input.bind(&self.pipeline_layout, 1, &mut encoder);
+        encoder.draw(0..3, 0..1);
+    }
+
+    fn dispose(self: Box<Self>, factory: &mut Factory<B>, _aux: &Resources) {
+        unsafe {
+            factory.device().destroy_graphics_pipeline(self.pipeline);
+            factory
+                .device()
+                .destroy_pipeline_layout(self.pipeline_layout);
+        }
+    }
+}
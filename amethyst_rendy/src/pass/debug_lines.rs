@@ -2,6 +2,7 @@ use crate::{
     debug_drawing::{DebugLine, DebugLines, DebugLinesComponent, DebugLinesParams},
     pipeline::{PipelineDescBuilder, PipelinesBuilder},
     pod::ViewArgs,
+    resources::DepthMode,
     submodules::{gather::CameraGatherer, DynamicUniform, DynamicVertex},
     types::Backend,
     util,
@@ -18,7 +19,6 @@ use rendy::{
     },
     hal::{self, device::Device, pso},
     mesh::AsVertex,
-    shader::Shader,
 };
 
 #[cfg(feature = "profiler")]
@@ -29,16 +29,31 @@ struct DebugLinesArgs {
     screen_space_thickness: vec2,
 }
 
-/// Draw opaque sprites without lighting.
+/// Draw the lines of the `DebugLines` resource and `DebugLinesComponent`s without lighting.
+///
+/// Lines are expanded into screen space quads `DebugLinesParams::line_width` pixels wide,
+/// drawn without face culling.
 #[derive(Clone, Debug, PartialEq, Derivative)]
 #[derivative(Default(bound = ""))]
-pub struct DrawDebugLinesDesc;
+pub struct DrawDebugLinesDesc {
+    #[derivative(Default(value = "true"))]
+    depth_test: bool,
+}
 
 impl DrawDebugLinesDesc {
     /// Create instance of `DrawDebugLines` render group
     pub fn new() -> Self {
         Default::default()
     }
+
+    /// Whether lines are hidden behind the geometry drawn before them, `true` by default.
+    ///
+    /// Without depth test, lines are drawn over everything and write no depth, which
+    /// suits gizmos that must stay visible.
+    pub fn with_depth_test(mut self, depth_test: bool) -> Self {
+        self.depth_test = depth_test;
+        self
+    }
 }
 
 impl<B: Backend> RenderGroupDesc<B, Resources> for DrawDebugLinesDesc {
@@ -47,7 +62,7 @@ impl<B: Backend> RenderGroupDesc<B, Resources> for DrawDebugLinesDesc {
         _ctx: &GraphContext<B>,
         factory: &mut Factory<B>,
         _queue: QueueId,
-        _aux: &Resources,
+        aux: &Resources,
         framebuffer_width: u32,
         framebuffer_height: u32,
         subpass: hal::pass::Subpass<'_, B>,
@@ -61,11 +76,14 @@ impl<B: Backend> RenderGroupDesc<B, Resources> for DrawDebugLinesDesc {
         let args = DynamicUniform::new(factory, pso::ShaderStageFlags::VERTEX)?;
         let vertex = DynamicVertex::new();
 
+        let depth_mode = *aux.fetch::<DepthMode>();
+        let viewport = util::viewport_rect(aux, framebuffer_width, framebuffer_height);
         let (pipeline, pipeline_layout) = build_lines_pipeline(
             factory,
             subpass,
-            framebuffer_width,
-            framebuffer_height,
+            viewport,
+            depth_mode,
+            self.depth_test,
             vec![env.raw_layout(), args.raw_layout()],
         )?;
 
@@ -75,8 +93,8 @@ impl<B: Backend> RenderGroupDesc<B, Resources> for DrawDebugLinesDesc {
             env,
             args,
             vertex,
-            framebuffer_width: framebuffer_width as f32,
-            framebuffer_height: framebuffer_height as f32,
+            framebuffer_width: viewport.w as f32,
+            framebuffer_height: viewport.h as f32,
             lines: Vec::new(),
             change: Default::default(),
         }))
@@ -189,8 +207,9 @@ impl<B: Backend> RenderGroup<B, Resources> for DrawDebugLines<B> {
 fn build_lines_pipeline<B: Backend>(
     factory: &Factory<B>,
     subpass: hal::pass::Subpass<'_, B>,
-    framebuffer_width: u32,
-    framebuffer_height: u32,
+    viewport: pso::Rect,
+    depth_mode: DepthMode,
+    depth_test: bool,
     layouts: Vec<&B::DescriptorSetLayout>,
 ) -> Result<(B::GraphicsPipeline, B::PipelineLayout), failure::Error> {
     let pipeline_layout = unsafe {
@@ -199,8 +218,22 @@ fn build_lines_pipeline<B: Backend>(
             .create_pipeline_layout(layouts, None as Option<(_, _)>)
     }?;
 
-    let shader_vertex = unsafe { super::DEBUG_LINES_VERTEX.module(factory).unwrap() };
-    let shader_fragment = unsafe { super::DEBUG_LINES_FRAGMENT.module(factory).unwrap() };
+    let modules = unsafe {
+        util::simple_shader_modules(
+            factory,
+            &super::DEBUG_LINES_VERTEX,
+            &super::DEBUG_LINES_FRAGMENT,
+        )
+    };
+    let (shader_vertex, shader_fragment) = match modules {
+        Ok(modules) => modules,
+        Err(e) => {
+            unsafe {
+                factory.device().destroy_pipeline_layout(pipeline_layout);
+            }
+            return Err(e);
+        }
+    };
 
     let pipes = PipelinesBuilder::new()
         .with_pipeline(
@@ -213,14 +246,18 @@ fn build_lines_pipeline<B: Backend>(
                 ))
                 .with_layout(&pipeline_layout)
                 .with_subpass(subpass)
-                .with_framebuffer_size(framebuffer_width, framebuffer_height)
+                .with_viewport(viewport)
                 .with_blend_targets(vec![pso::ColorBlendDesc(
                     pso::ColorMask::ALL,
                     pso::BlendState::ALPHA,
                 )])
-                .with_depth_test(pso::DepthTest::On {
-                    fun: pso::Comparison::LessEqual,
-                    write: true,
+                .with_depth_test(if depth_test {
+                    depth_mode.depth_test(pso::DepthTest::On {
+                        fun: pso::Comparison::LessEqual,
+                        write: true,
+                    })
+                } else {
+                    pso::DepthTest::Off
                 }),
         )
         .build(factory, None);
@@ -0,0 +1,321 @@
+//! Depth prepass for the 3D passes.
+//!
+//! `DrawDepthPrepassDesc` fills the depth buffer with the opaque static meshes drawn by
+//! `DrawBase3D`, so that a lit pass built with `with_depth_prepass` only shades the
+//! visible fragment of every pixel. Both groups share a subpass, prepass first:
+//!
+//! ```ignore
+//! SubpassBuilder::new()
+//!     .with_group(DrawDepthPrepassDesc::new().builder())
+//!     .with_group(DrawPbrDesc::new().with_depth_prepass().builder())
+//! ```
+//!
+//! Depth is written without running any fragment shader, so materials discarding
+//! fragments through `alpha_cutoff` or `DitheredFade` should not be drawn with a prepass.
+
+use crate::{
+    batch::{GroupIterator, OneLevelBatch},
+    mtl::Material,
+    pipeline::{PipelineDescBuilder, PipelinesBuilder},
+    pod::VertexArgs,
+    resources::DepthMode,
+    skinning::JointTransforms,
+    submodules::{DynamicVertex, FlatEnvironmentSub},
+    transparent::Transparent,
+    types::{Backend, Mesh},
+    util,
+    visibility::Visibility,
+};
+use amethyst_assets::{AssetStorage, Handle};
+use amethyst_core::{
+    ecs::{Join, Read, ReadStorage, Resources, SystemData},
+    transform::Transform,
+    Hidden, HiddenPropagate,
+};
+use derivative::Derivative;
+use rendy::{
+    command::{QueueId, RenderPassEncoder},
+    factory::Factory,
+    graph::{
+        render::{PrepareResult, RenderGroup, RenderGroupDesc},
+        GraphContext, NodeBuffer, NodeImage,
+    },
+    hal::{self, device::Device, pso},
+    mesh::{AsVertex, Position, VertexFormat},
+    shader::Shader,
+};
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+/// Draw the depth of opaque static meshes ahead of a lit pass.
+#[derive(Clone, Debug, PartialEq, Derivative)]
+#[derivative(Default(bound = ""))]
+pub struct DrawDepthPrepassDesc {
+    #[derivative(Default(value = "pso::Face::BACK"))]
+    cull_face: pso::Face,
+    #[derivative(Default(value = "1"))]
+    samples: hal::image::NumSamples,
+}
+
+impl DrawDepthPrepassDesc {
+    /// Create instance of `DrawDepthPrepass` render group
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Cull faces facing this way, matching the lit pass. `Face::BACK` by default.
+    pub fn with_cull_face(mut self, cull_face: pso::Face) -> Self {
+        self.cull_face = cull_face;
+        self
+    }
+
+    /// Number of samples per pixel of the attachments, matching the lit pass. 1 by default.
+    pub fn with_samples(mut self, samples: hal::image::NumSamples) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+}
+
+impl<B: Backend> RenderGroupDesc<B, Resources> for DrawDepthPrepassDesc {
+    fn build(
+        self,
+        _ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        _queue: QueueId,
+        aux: &Resources,
+        framebuffer_width: u32,
+        framebuffer_height: u32,
+        subpass: hal::pass::Subpass<'_, B>,
+        _buffers: Vec<NodeBuffer>,
+        _images: Vec<NodeImage>,
+    ) -> Result<Box<dyn RenderGroup<B, Resources>>, failure::Error> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("build");
+
+        let env = FlatEnvironmentSub::new(factory)?;
+        let mut vertex_format = vec![Position::vertex()];
+
+        let depth_mode = *aux.fetch::<DepthMode>();
+        let viewport = util::viewport_rect(aux, framebuffer_width, framebuffer_height);
+        let (pipeline, pipeline_layout) = build_prepass_pipeline(
+            factory,
+            subpass,
+            viewport,
+            depth_mode,
+            &vertex_format,
+            self.cull_face,
+            util::multisampling(self.samples, false),
+            vec![env.raw_layout()],
+        )?;
+
+        vertex_format.sort();
+
+        Ok(Box::new(DrawDepthPrepass::<B> {
+            pipeline,
+            pipeline_layout,
+            env,
+            models: DynamicVertex::new(),
+            batches: Default::default(),
+            vertex_format,
+        }))
+    }
+}
+
+/// Render group drawing the depth of opaque static meshes.
+#[derive(Debug)]
+pub struct DrawDepthPrepass<B: Backend> {
+    pipeline: B::GraphicsPipeline,
+    pipeline_layout: B::PipelineLayout,
+    env: FlatEnvironmentSub<B>,
+    models: DynamicVertex<B, VertexArgs>,
+    batches: OneLevelBatch<u32, VertexArgs>,
+    vertex_format: Vec<VertexFormat>,
+}
+
+impl<B: Backend> RenderGroup<B, Resources> for DrawDepthPrepass<B> {
+    fn prepare(
+        &mut self,
+        factory: &Factory<B>,
+        _queue: QueueId,
+        index: usize,
+        _subpass: hal::pass::Subpass<'_, B>,
+        resources: &Resources,
+    ) -> PrepareResult {
+        #[cfg(feature = "profiler")]
+        profile_scope!("prepare");
+
+        let (
+            mesh_storage,
+            visibility,
+            transparent,
+            hiddens,
+            hiddens_prop,
+            meshes,
+            materials,
+            transforms,
+            joints,
+        ) = <(
+            Read<AssetStorage<Mesh>>,
+            Option<Read<Visibility>>,
+            ReadStorage<Transparent>,
+            ReadStorage<Hidden>,
+            ReadStorage<HiddenPropagate>,
+            ReadStorage<Handle<Mesh>>,
+            ReadStorage<Handle<Material>>,
+            ReadStorage<Transform>,
+            ReadStorage<JointTransforms>,
+        )>::fetch(resources);
+
+        self.env.process(factory, index, resources);
+        self.batches.clear_inner();
+
+        let batches_ref = &mut self.batches;
+        let mut insert = |mesh_id: u32, data: &mut Vec<VertexArgs>| {
+            if mesh_storage.contains_id(mesh_id) {
+                batches_ref.insert(mesh_id, data.drain(..));
+            }
+        };
+
+        // Matches the static meshes gathered by `DrawBase3D`.
+        let input = (&meshes, &materials, &transforms, !&joints);
+        match &visibility {
+            None => (input, !&hiddens, !&hiddens_prop, !&transparent)
+                .join()
+                .map(|((mesh, _, tform, _), _, _, _)| {
+                    (mesh.id(), VertexArgs::from_object_data(tform, None, None))
+                })
+                .for_each_group(&mut insert),
+            Some(visibility) => (input, &visibility.visible_unordered)
+                .join()
+                .map(|((mesh, _, tform, _), _)| {
+                    (mesh.id(), VertexArgs::from_object_data(tform, None, None))
+                })
+                .for_each_group(&mut insert),
+        }
+
+        self.batches.prune();
+        self.models.write(
+            factory,
+            index,
+            self.batches.count() as u64,
+            self.batches.data(),
+        );
+
+        PrepareResult::DrawRecord
+    }
+
+    fn draw_inline(
+        &mut self,
+        mut encoder: RenderPassEncoder<'_, B>,
+        index: usize,
+        _subpass: hal::pass::Subpass<'_, B>,
+        resources: &Resources,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("draw");
+
+        if self.batches.count() == 0 {
+            return;
+        }
+
+        let mesh_storage = <Read<'_, AssetStorage<Mesh>>>::fetch(resources);
+        let models_loc = self.vertex_format.len() as u32;
+
+        encoder.bind_graphics_pipeline(&self.pipeline);
+        self.env.bind(index, &self.pipeline_layout, 0, &mut encoder);
+
+        if self.models.bind(index, models_loc, &mut encoder) {
+            for (mesh_id, range) in self.batches.iter() {
+                debug_assert!(mesh_storage.contains_id(*mesh_id));
+                if let Some(mesh) =
+                    B::unwrap_mesh(unsafe { mesh_storage.get_by_id_unchecked(*mesh_id) })
+                {
+                    mesh.bind_and_draw(0, &self.vertex_format, range, &mut encoder)
+                        .unwrap();
+                }
+            }
+        }
+    }
+
+    fn dispose(self: Box<Self>, factory: &mut Factory<B>, _aux: &Resources) {
+        unsafe {
+            factory.device().destroy_graphics_pipeline(self.pipeline);
+            factory
+                .device()
+                .destroy_pipeline_layout(self.pipeline_layout);
+        }
+    }
+}
+
+fn build_prepass_pipeline<B: Backend>(
+    factory: &Factory<B>,
+    subpass: hal::pass::Subpass<'_, B>,
+    viewport: pso::Rect,
+    depth_mode: DepthMode,
+    vertex_format: &[VertexFormat],
+    cull_face: pso::Face,
+    multisampling: Option<pso::Multisampling>,
+    layouts: Vec<&B::DescriptorSetLayout>,
+) -> Result<(B::GraphicsPipeline, B::PipelineLayout), failure::Error> {
+    let pipeline_layout = unsafe {
+        factory
+            .device()
+            .create_pipeline_layout(layouts, None as Option<(_, _)>)
+    }?;
+
+    let vertex_desc = vertex_format
+        .iter()
+        .map(|f| (f.clone(), pso::VertexInputRate::Vertex))
+        .chain(Some((
+            VertexArgs::vertex(),
+            pso::VertexInputRate::Instance(1),
+        )))
+        .collect::<Vec<_>>();
+
+    let shader_vertex = match unsafe { super::DEPTH_VERTEX.module(factory) } {
+        Ok(module) => module,
+        Err(e) => {
+            unsafe {
+                factory.device().destroy_pipeline_layout(pipeline_layout);
+            }
+            return Err(e.into());
+        }
+    };
+
+    let pipes = PipelinesBuilder::new()
+        .with_pipeline(
+            PipelineDescBuilder::new()
+                .with_vertex_desc(&vertex_desc)
+                .with_shaders(util::simple_shader_set(&shader_vertex, None))
+                .with_layout(&pipeline_layout)
+                .with_subpass(subpass)
+                .with_viewport(viewport)
+                .with_face_culling(cull_face)
+                .with_depth_test(depth_mode.depth_test(pso::DepthTest::On {
+                    fun: pso::Comparison::Less,
+                    write: true,
+                }))
+                .with_multisampling(multisampling)
+                // The color attachment shared with the lit pass is left untouched.
+                .with_blend_targets(vec![pso::ColorBlendDesc(
+                    pso::ColorMask::empty(),
+                    pso::BlendState::Off,
+                )]),
+        )
+        .build(factory, None);
+
+    unsafe {
+        factory.destroy_shader_module(shader_vertex);
+    }
+
+    match pipes {
+        Err(e) => {
+            unsafe {
+                factory.device().destroy_pipeline_layout(pipeline_layout);
+            }
+            Err(e)
+        }
+        Ok(mut pipes) => Ok((pipes.remove(0), pipeline_layout)),
+    }
+}
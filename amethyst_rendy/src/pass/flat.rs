@@ -5,6 +5,10 @@ use rendy::{
     shader::SpirvShader,
 };
 
+/// Unlit pass drawing the albedo of materials, multiplied by `Tint` and vertex colors.
+///
+/// Ignores every light, for billboards, holograms and other objects that should not
+/// react to the lighting of the scene.
 #[derive(Debug)]
 pub struct FlatPassDef;
 impl<B: Backend> Base3DPassDef<B> for FlatPassDef {
@@ -16,6 +20,12 @@ impl<B: Backend> Base3DPassDef<B> for FlatPassDef {
     fn vertex_skinned_shader() -> &'static SpirvShader {
         &super::POS_TEX_SKIN_VERTEX
     }
+    fn vertex_color_shader() -> Option<&'static SpirvShader> {
+        Some(&super::POS_TEX_COLOR_VERTEX)
+    }
+    fn vertex_color_skinned_shader() -> Option<&'static SpirvShader> {
+        Some(&super::POS_TEX_COLOR_SKIN_VERTEX)
+    }
     fn fragment_shader() -> &'static SpirvShader {
         &super::FLAT_FRAGMENT
     }
@@ -29,6 +39,9 @@ impl<B: Backend> Base3DPassDef<B> for FlatPassDef {
             JointCombined::vertex(),
         ]
     }
+    fn lit() -> bool {
+        false
+    }
 }
 
 pub type DrawFlatDesc<B> = DrawBase3DDesc<B, FlatPassDef>;
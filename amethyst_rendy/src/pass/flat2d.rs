@@ -2,6 +2,7 @@ use crate::{
     batch::{GroupIterator, OneLevelBatch, OrderedOneLevelBatch},
     pipeline::{PipelineDescBuilder, PipelinesBuilder},
     pod::SpriteArgs,
+    resources::{DepthMode, Tint},
     sprite::{SpriteRender, SpriteSheet},
     sprite_visibility::SpriteVisibility,
     submodules::{DynamicVertex, FlatEnvironmentSub, TextureId, TextureSub},
@@ -24,13 +25,15 @@ use rendy::{
     },
     hal::{self, device::Device, pso},
     mesh::AsVertex,
-    shader::Shader,
 };
 
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 
 /// Draw opaque sprites without lighting.
+///
+/// Sprites are batched by texture, layered through depth testing on their `Transform` z,
+/// and multiplied by their `Tint` if they have one.
 #[derive(Clone, Debug, PartialEq, Derivative)]
 #[derivative(Default(bound = ""))]
 pub struct DrawFlat2DDesc;
@@ -48,7 +51,7 @@ impl<B: Backend> RenderGroupDesc<B, Resources> for DrawFlat2DDesc {
         _ctx: &GraphContext<B>,
         factory: &mut Factory<B>,
         _queue: QueueId,
-        _aux: &Resources,
+        aux: &Resources,
         framebuffer_width: u32,
         framebuffer_height: u32,
         subpass: hal::pass::Subpass<'_, B>,
@@ -62,11 +65,13 @@ impl<B: Backend> RenderGroupDesc<B, Resources> for DrawFlat2DDesc {
         let textures = TextureSub::new(factory)?;
         let vertex = DynamicVertex::new();
 
+        let depth_mode = *aux.fetch::<DepthMode>();
+        let viewport = util::viewport_rect(aux, framebuffer_width, framebuffer_height);
         let (pipeline, pipeline_layout) = build_sprite_pipeline(
             factory,
             subpass,
-            framebuffer_width,
-            framebuffer_height,
+            viewport,
+            depth_mode,
             false,
             vec![env.raw_layout(), textures.raw_layout()],
         )?;
@@ -112,6 +117,7 @@ impl<B: Backend> RenderGroup<B, Resources> for DrawFlat2D<B> {
             hidden_props,
             sprite_renders,
             transforms,
+            tints,
         ) = <(
             Read<'_, AssetStorage<SpriteSheet>>,
             Read<'_, AssetStorage<Texture>>,
@@ -120,6 +126,7 @@ impl<B: Backend> RenderGroup<B, Resources> for DrawFlat2D<B> {
             ReadStorage<'_, HiddenPropagate>,
             ReadStorage<'_, SpriteRender>,
             ReadStorage<'_, Transform>,
+            ReadStorage<'_, Tint>,
         )>::fetch(resources);
 
         self.env.process(factory, index, resources);
@@ -134,14 +141,21 @@ impl<B: Backend> RenderGroup<B, Resources> for DrawFlat2D<B> {
                 #[cfg(feature = "profiler")]
                 profile_scope!("gather_novisibility");
 
-                (&sprite_renders, &transforms, !&hiddens, !&hidden_props)
+                (
+                    &sprite_renders,
+                    &transforms,
+                    tints.maybe(),
+                    !&hiddens,
+                    !&hidden_props,
+                )
                     .join()
-                    .filter_map(|(sprite_render, global, _, _)| {
+                    .filter_map(|(sprite_render, global, tint, _, _)| {
                         let (batch_data, texture) = SpriteArgs::from_data(
                             &tex_storage,
                             &sprite_sheet_storage,
                             &sprite_render,
                             &global,
+                            tint,
                         )?;
                         let (tex_id, _) = textures_ref.insert(
                             factory,
@@ -159,14 +173,20 @@ impl<B: Backend> RenderGroup<B, Resources> for DrawFlat2D<B> {
                 #[cfg(feature = "profiler")]
                 profile_scope!("gather_visibility");
 
-                (&sprite_renders, &transforms, &visibility.visible_unordered)
+                (
+                    &sprite_renders,
+                    &transforms,
+                    tints.maybe(),
+                    &visibility.visible_unordered,
+                )
                     .join()
-                    .filter_map(|(sprite_render, global, _)| {
+                    .filter_map(|(sprite_render, global, tint, _)| {
                         let (batch_data, texture) = SpriteArgs::from_data(
                             &tex_storage,
                             &sprite_sheet_storage,
                             &sprite_render,
                             &global,
+                            tint,
                         )?;
                         let (tex_id, _) = textures_ref.insert(
                             factory,
@@ -231,7 +251,8 @@ impl<B: Backend> RenderGroup<B, Resources> for DrawFlat2D<B> {
         }
     }
 }
-/// Draw transparent sprites without lighting.
+/// Draw transparent sprites without lighting, back to front,
+/// multiplied by their `Tint` if they have one.
 #[derive(Clone, Debug, PartialEq, Derivative)]
 #[derivative(Default(bound = ""))]
 pub struct DrawFlat2DTransparentDesc;
@@ -249,7 +270,7 @@ impl<B: Backend> RenderGroupDesc<B, Resources> for DrawFlat2DTransparentDesc {
         _ctx: &GraphContext<B>,
         factory: &mut Factory<B>,
         _queue: QueueId,
-        _aux: &Resources,
+        aux: &Resources,
         framebuffer_width: u32,
         framebuffer_height: u32,
         subpass: hal::pass::Subpass<'_, B>,
@@ -263,11 +284,13 @@ impl<B: Backend> RenderGroupDesc<B, Resources> for DrawFlat2DTransparentDesc {
         let textures = TextureSub::new(factory)?;
         let vertex = DynamicVertex::new();
 
+        let depth_mode = *aux.fetch::<DepthMode>();
+        let viewport = util::viewport_rect(aux, framebuffer_width, framebuffer_height);
         let (pipeline, pipeline_layout) = build_sprite_pipeline(
             factory,
             subpass,
-            framebuffer_width,
-            framebuffer_height,
+            viewport,
+            depth_mode,
             true,
             vec![env.raw_layout(), textures.raw_layout()],
         )?;
@@ -307,13 +330,14 @@ impl<B: Backend> RenderGroup<B, Resources> for DrawFlat2DTransparent<B> {
         #[cfg(feature = "profiler")]
         profile_scope!("prepare_trans");
 
-        let (sprite_sheet_storage, tex_storage, visibility, sprite_renders, transforms) =
+        let (sprite_sheet_storage, tex_storage, visibility, sprite_renders, transforms, tints) =
             <(
                 Read<'_, AssetStorage<SpriteSheet>>,
                 Read<'_, AssetStorage<Texture>>,
                 ReadExpect<'_, SpriteVisibility>,
                 ReadStorage<'_, SpriteRender>,
                 ReadStorage<'_, Transform>,
+                ReadStorage<'_, Tint>,
             )>::fetch(resources);
 
         self.env.process(factory, index, resources);
@@ -327,17 +351,18 @@ impl<B: Backend> RenderGroup<B, Resources> for DrawFlat2DTransparent<B> {
             #[cfg(feature = "profiler")]
             profile_scope!("gather_sprites_trans");
 
-            let mut joined = (&sprite_renders, &transforms).join();
+            let mut joined = (&sprite_renders, &transforms, tints.maybe()).join();
             visibility
                 .visible_ordered
                 .iter()
                 .filter_map(|e| joined.get_unchecked(e.id()))
-                .filter_map(|(sprite_render, global)| {
+                .filter_map(|(sprite_render, global, tint)| {
                     let (batch_data, texture) = SpriteArgs::from_data(
                         &tex_storage,
                         &sprite_sheet_storage,
                         &sprite_render,
                         &global,
+                        tint,
                     )?;
                     let (tex_id, this_changed) = textures_ref.insert(
                         factory,
@@ -405,8 +430,8 @@ impl<B: Backend> RenderGroup<B, Resources> for DrawFlat2DTransparent<B> {
 fn build_sprite_pipeline<B: Backend>(
     factory: &Factory<B>,
     subpass: hal::pass::Subpass<'_, B>,
-    framebuffer_width: u32,
-    framebuffer_height: u32,
+    viewport: pso::Rect,
+    depth_mode: DepthMode,
     transparent: bool,
     layouts: Vec<&B::DescriptorSetLayout>,
 ) -> Result<(B::GraphicsPipeline, B::PipelineLayout), failure::Error> {
@@ -416,8 +441,18 @@ fn build_sprite_pipeline<B: Backend>(
             .create_pipeline_layout(layouts, None as Option<(_, _)>)
     }?;
 
-    let shader_vertex = unsafe { super::SPRITE_VERTEX.module(factory).unwrap() };
-    let shader_fragment = unsafe { super::SPRITE_FRAGMENT.module(factory).unwrap() };
+    let modules = unsafe {
+        util::simple_shader_modules(factory, &super::SPRITE_VERTEX, &super::SPRITE_FRAGMENT)
+    };
+    let (shader_vertex, shader_fragment) = match modules {
+        Ok(modules) => modules,
+        Err(e) => {
+            unsafe {
+                factory.device().destroy_pipeline_layout(pipeline_layout);
+            }
+            return Err(e);
+        }
+    };
 
     let pipes = PipelinesBuilder::new()
         .with_pipeline(
@@ -430,7 +465,7 @@ fn build_sprite_pipeline<B: Backend>(
                 ))
                 .with_layout(&pipeline_layout)
                 .with_subpass(subpass)
-                .with_framebuffer_size(framebuffer_width, framebuffer_height)
+                .with_viewport(viewport)
                 .with_blend_targets(vec![pso::ColorBlendDesc(
                     pso::ColorMask::ALL,
                     if transparent {
@@ -439,10 +474,10 @@ fn build_sprite_pipeline<B: Backend>(
                         pso::BlendState::Off
                     },
                 )])
-                .with_depth_test(pso::DepthTest::On {
+                .with_depth_test(depth_mode.depth_test(pso::DepthTest::On {
                     fun: pso::Comparison::Less,
                     write: !transparent,
-                }),
+                })),
         )
         .build(factory, None);
 
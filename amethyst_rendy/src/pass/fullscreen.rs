@@ -0,0 +1,71 @@
+//! Helpers for render groups drawing a single triangle covering the whole target.
+
+use crate::{
+    pipeline::{PipelineDescBuilder, PipelinesBuilder},
+    types::Backend,
+    util,
+};
+use rendy::{
+    factory::Factory,
+    hal::{self, device::Device, pso},
+    shader::SpirvShader,
+};
+
+/// Build a pipeline drawing `fragment` over the whole framebuffer with `draw(0..3, 0..1)`,
+/// blending its output into the color attachment with `blend`.
+pub(crate) fn build_fullscreen_pipeline<B: Backend>(
+    factory: &Factory<B>,
+    subpass: hal::pass::Subpass<'_, B>,
+    framebuffer_width: u32,
+    framebuffer_height: u32,
+    fragment: &SpirvShader,
+    blend: pso::BlendState,
+    layouts: Vec<&B::DescriptorSetLayout>,
+) -> Result<(B::GraphicsPipeline, B::PipelineLayout), failure::Error> {
+    let pipeline_layout = unsafe {
+        factory
+            .device()
+            .create_pipeline_layout(layouts, None as Option<(_, _)>)
+    }?;
+
+    let modules =
+        unsafe { util::simple_shader_modules(factory, &super::FULLSCREEN_VERTEX, fragment) };
+    let (shader_vertex, shader_fragment) = match modules {
+        Ok(modules) => modules,
+        Err(e) => {
+            unsafe {
+                factory.device().destroy_pipeline_layout(pipeline_layout);
+            }
+            return Err(e);
+        }
+    };
+
+    let pipes = PipelinesBuilder::new()
+        .with_pipeline(
+            PipelineDescBuilder::new()
+                .with_shaders(util::simple_shader_set(
+                    &shader_vertex,
+                    Some(&shader_fragment),
+                ))
+                .with_layout(&pipeline_layout)
+                .with_subpass(subpass)
+                .with_framebuffer_size(framebuffer_width, framebuffer_height)
+                .with_blend_targets(vec![pso::ColorBlendDesc(pso::ColorMask::ALL, blend)]),
+        )
+        .build(factory, None);
+
+    unsafe {
+        factory.destroy_shader_module(shader_vertex);
+        factory.destroy_shader_module(shader_fragment);
+    }
+
+    match pipes {
+        Err(e) => {
+            unsafe {
+                factory.device().destroy_pipeline_layout(pipeline_layout);
+            }
+            Err(e)
+        }
+        Ok(mut pipes) => Ok((pipes.remove(0), pipeline_layout)),
+    }
+}
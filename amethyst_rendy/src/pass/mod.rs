@@ -1,16 +1,47 @@
+//! Render groups drawing the scene.
+//!
+//! Fragment shaders write linear colors, lit and blended in linear space. Swapchain
+//! images get an sRGB format from `util::select_surface_format`, which encodes those
+//! colors for display. On a linear swapchain they are presented as written and look too
+//! dark, unless the game applies gamma correction itself.
+
 mod base_3d;
+mod bloom;
 mod debug_lines;
+mod depth_prepass;
 mod flat;
 mod flat2d;
+mod fullscreen;
+mod outline;
+mod particles;
 mod pbr;
 mod shaded;
+mod shadow;
 mod skybox;
+mod ssao;
+mod tonemap;
+mod wireframe;
 
-pub use self::{base_3d::*, debug_lines::*, flat::*, flat2d::*, pbr::*, shaded::*, skybox::*};
+pub use self::{
+    base_3d::*, bloom::*, debug_lines::*, depth_prepass::*, flat::*, flat2d::*, outline::*,
+    particles::*, pbr::*, shaded::*, shadow::*, skybox::*, ssao::*, tonemap::*, wireframe::*,
+};
 
 use rendy::{hal::pso::ShaderStageFlags, shader::SpirvShader};
 
 lazy_static::lazy_static! {
+    static ref PARTICLE_VERTEX: SpirvShader = SpirvShader::new(
+        include_bytes!("../../compiled/vertex/particle.vert.spv").to_vec(),
+        ShaderStageFlags::VERTEX,
+        "main",
+    );
+
+    static ref PARTICLE_FRAGMENT: SpirvShader = SpirvShader::new(
+        include_bytes!("../../compiled/fragment/particle.frag.spv").to_vec(),
+        ShaderStageFlags::FRAGMENT,
+        "main",
+    );
+
     static ref POS_TEX_VERTEX: SpirvShader = SpirvShader::new(
         include_bytes!("../../compiled/vertex/pos_tex.vert.spv").to_vec(),
         ShaderStageFlags::VERTEX,
@@ -47,6 +78,42 @@ lazy_static::lazy_static! {
         "main",
     );
 
+    static ref POS_TEX_COLOR_VERTEX: SpirvShader = SpirvShader::new(
+        include_bytes!("../../compiled/vertex/pos_tex_color.vert.spv").to_vec(),
+        ShaderStageFlags::VERTEX,
+        "main",
+    );
+
+    static ref POS_TEX_COLOR_SKIN_VERTEX: SpirvShader = SpirvShader::new(
+        include_bytes!("../../compiled/vertex/pos_tex_color_skin.vert.spv").to_vec(),
+        ShaderStageFlags::VERTEX,
+        "main",
+    );
+
+    static ref POS_NORM_TEX_COLOR_VERTEX: SpirvShader = SpirvShader::new(
+        include_bytes!("../../compiled/vertex/pos_norm_tex_color.vert.spv").to_vec(),
+        ShaderStageFlags::VERTEX,
+        "main",
+    );
+
+    static ref POS_NORM_TEX_COLOR_SKIN_VERTEX: SpirvShader = SpirvShader::new(
+        include_bytes!("../../compiled/vertex/pos_norm_tex_color_skin.vert.spv").to_vec(),
+        ShaderStageFlags::VERTEX,
+        "main",
+    );
+
+    static ref POS_NORM_TANG_TEX_COLOR_VERTEX: SpirvShader = SpirvShader::new(
+        include_bytes!("../../compiled/vertex/pos_norm_tang_tex_color.vert.spv").to_vec(),
+        ShaderStageFlags::VERTEX,
+        "main",
+    );
+
+    static ref POS_NORM_TANG_TEX_COLOR_SKIN_VERTEX: SpirvShader = SpirvShader::new(
+        include_bytes!("../../compiled/vertex/pos_norm_tang_tex_color_skin.vert.spv").to_vec(),
+        ShaderStageFlags::VERTEX,
+        "main",
+    );
+
     static ref FLAT_FRAGMENT: SpirvShader = SpirvShader::new(
         include_bytes!("../../compiled/fragment/flat.frag.spv").to_vec(),
         ShaderStageFlags::FRAGMENT,
@@ -100,4 +167,76 @@ lazy_static::lazy_static! {
         ShaderStageFlags::FRAGMENT,
         "main",
     );
+
+    static ref OUTLINE_VERTEX: SpirvShader = SpirvShader::new(
+        include_bytes!("../../compiled/vertex/outline.vert.spv").to_vec(),
+        ShaderStageFlags::VERTEX,
+        "main",
+    );
+
+    static ref OUTLINE_FRAGMENT: SpirvShader = SpirvShader::new(
+        include_bytes!("../../compiled/fragment/outline.frag.spv").to_vec(),
+        ShaderStageFlags::FRAGMENT,
+        "main",
+    );
+
+    static ref FULLSCREEN_VERTEX: SpirvShader = SpirvShader::new(
+        include_bytes!("../../compiled/vertex/fullscreen.vert.spv").to_vec(),
+        ShaderStageFlags::VERTEX,
+        "main",
+    );
+
+    static ref SSAO_FRAGMENT: SpirvShader = SpirvShader::new(
+        include_bytes!("../../compiled/fragment/ssao.frag.spv").to_vec(),
+        ShaderStageFlags::FRAGMENT,
+        "main",
+    );
+
+    static ref SSAO_BLUR_FRAGMENT: SpirvShader = SpirvShader::new(
+        include_bytes!("../../compiled/fragment/ssao_blur.frag.spv").to_vec(),
+        ShaderStageFlags::FRAGMENT,
+        "main",
+    );
+
+    static ref DEPTH_VERTEX: SpirvShader = SpirvShader::new(
+        include_bytes!("../../compiled/vertex/depth.vert.spv").to_vec(),
+        ShaderStageFlags::VERTEX,
+        "main",
+    );
+
+    static ref SHADOW_VERTEX: SpirvShader = SpirvShader::new(
+        include_bytes!("../../compiled/vertex/shadow.vert.spv").to_vec(),
+        ShaderStageFlags::VERTEX,
+        "main",
+    );
+
+    static ref BLOOM_EXTRACT_FRAGMENT: SpirvShader = SpirvShader::new(
+        include_bytes!("../../compiled/fragment/bloom_extract.frag.spv").to_vec(),
+        ShaderStageFlags::FRAGMENT,
+        "main",
+    );
+
+    static ref BLOOM_BLUR_FRAGMENT: SpirvShader = SpirvShader::new(
+        include_bytes!("../../compiled/fragment/bloom_blur.frag.spv").to_vec(),
+        ShaderStageFlags::FRAGMENT,
+        "main",
+    );
+
+    static ref BLOOM_COMPOSITE_FRAGMENT: SpirvShader = SpirvShader::new(
+        include_bytes!("../../compiled/fragment/bloom_composite.frag.spv").to_vec(),
+        ShaderStageFlags::FRAGMENT,
+        "main",
+    );
+
+    static ref TONEMAP_FRAGMENT: SpirvShader = SpirvShader::new(
+        include_bytes!("../../compiled/fragment/tonemap.frag.spv").to_vec(),
+        ShaderStageFlags::FRAGMENT,
+        "main",
+    );
+
+    static ref WIREFRAME_FRAGMENT: SpirvShader = SpirvShader::new(
+        include_bytes!("../../compiled/fragment/wireframe.frag.spv").to_vec(),
+        ShaderStageFlags::FRAGMENT,
+        "main",
+    );
 }
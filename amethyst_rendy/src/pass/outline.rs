@@ -0,0 +1,291 @@
+use crate::{
+    batch::{GroupIterator, OneLevelBatch},
+    pipeline::{PipelineDescBuilder, PipelinesBuilder},
+    pod::OutlineArgs,
+    resources::{DepthMode, Outline},
+    submodules::{DynamicVertex, FlatEnvironmentSub},
+    types::{Backend, Mesh},
+    util,
+    visibility::Visibility,
+};
+use amethyst_assets::{AssetStorage, Handle};
+use amethyst_core::{
+    ecs::{Join, Read, ReadStorage, Resources, SystemData},
+    transform::Transform,
+    Hidden, HiddenPropagate,
+};
+use derivative::Derivative;
+use rendy::{
+    command::{QueueId, RenderPassEncoder},
+    factory::Factory,
+    graph::{
+        render::{PrepareResult, RenderGroup, RenderGroupDesc},
+        GraphContext, NodeBuffer, NodeImage,
+    },
+    hal::{self, device::Device, pso},
+    mesh::{AsVertex, Normal, Position, VertexFormat},
+};
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+/// Draw flat colored outlines around meshes of entities with an `Outline` component.
+///
+/// Back faces are drawn pushed out along the vertex normals, so the pass should
+/// share the depth buffer with the pass drawing the meshes themselves.
+#[derive(Clone, Debug, PartialEq, Derivative)]
+#[derivative(Default(bound = ""))]
+pub struct DrawOutlineDesc {
+    #[derivative(Default(value = "pso::StencilTest::Off"))]
+    stencil_test: pso::StencilTest,
+}
+
+impl DrawOutlineDesc {
+    /// Create instance of `DrawOutline` render group
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Test the stencil buffer, off by default. With `util::stencil_compare` against the
+    /// value written by the pass drawing the meshes, outlines are only drawn around them
+    /// and never over them, whatever their depth.
+    pub fn with_stencil_test(mut self, stencil_test: pso::StencilTest) -> Self {
+        self.stencil_test = stencil_test;
+        self
+    }
+}
+
+impl<B: Backend> RenderGroupDesc<B, Resources> for DrawOutlineDesc {
+    fn build(
+        self,
+        _ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        _queue: QueueId,
+        aux: &Resources,
+        framebuffer_width: u32,
+        framebuffer_height: u32,
+        subpass: hal::pass::Subpass<'_, B>,
+        _buffers: Vec<NodeBuffer>,
+        _images: Vec<NodeImage>,
+    ) -> Result<Box<dyn RenderGroup<B, Resources>>, failure::Error> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("build");
+
+        let env = FlatEnvironmentSub::new(factory)?;
+        let mut vertex_format = vec![Position::vertex(), Normal::vertex()];
+
+        let depth_mode = *aux.fetch::<DepthMode>();
+        let viewport = util::viewport_rect(aux, framebuffer_width, framebuffer_height);
+        let (pipeline, pipeline_layout) = build_outline_pipeline(
+            factory,
+            subpass,
+            viewport,
+            depth_mode,
+            self.stencil_test,
+            &vertex_format,
+            vec![env.raw_layout()],
+        )?;
+
+        vertex_format.sort();
+
+        Ok(Box::new(DrawOutline::<B> {
+            pipeline,
+            pipeline_layout,
+            env,
+            models: DynamicVertex::new(),
+            batches: Default::default(),
+            vertex_format,
+        }))
+    }
+}
+
+#[derive(Debug)]
+pub struct DrawOutline<B: Backend> {
+    pipeline: B::GraphicsPipeline,
+    pipeline_layout: B::PipelineLayout,
+    env: FlatEnvironmentSub<B>,
+    models: DynamicVertex<B, OutlineArgs>,
+    batches: OneLevelBatch<u32, OutlineArgs>,
+    vertex_format: Vec<VertexFormat>,
+}
+
+impl<B: Backend> RenderGroup<B, Resources> for DrawOutline<B> {
+    fn prepare(
+        &mut self,
+        factory: &Factory<B>,
+        _queue: QueueId,
+        index: usize,
+        _subpass: hal::pass::Subpass<'_, B>,
+        resources: &Resources,
+    ) -> PrepareResult {
+        #[cfg(feature = "profiler")]
+        profile_scope!("prepare");
+
+        let (mesh_storage, visibility, hiddens, hiddens_prop, meshes, transforms, outlines) =
+            <(
+                Read<AssetStorage<Mesh>>,
+                Option<Read<Visibility>>,
+                ReadStorage<Hidden>,
+                ReadStorage<HiddenPropagate>,
+                ReadStorage<Handle<Mesh>>,
+                ReadStorage<Transform>,
+                ReadStorage<Outline>,
+            )>::fetch(resources);
+
+        self.env.process(factory, index, resources);
+        self.batches.clear_inner();
+
+        let batches_ref = &mut self.batches;
+        let input = (&meshes, &transforms, &outlines);
+
+        let mut insert = |mesh_id: u32, data: &mut Vec<OutlineArgs>| {
+            if mesh_storage.contains_id(mesh_id) {
+                batches_ref.insert(mesh_id, data.drain(..));
+            }
+        };
+
+        match &visibility {
+            None => (input, !&hiddens, !&hiddens_prop)
+                .join()
+                .map(|((mesh, tform, outline), _, _)| {
+                    (mesh.id(), OutlineArgs::from_object_data(tform, outline))
+                })
+                .for_each_group(&mut insert),
+            Some(visibility) => (input, &visibility.visible_unordered)
+                .join()
+                .map(|((mesh, tform, outline), _)| {
+                    (mesh.id(), OutlineArgs::from_object_data(tform, outline))
+                })
+                .for_each_group(&mut insert),
+        }
+
+        self.batches.prune();
+        self.models.write(
+            factory,
+            index,
+            self.batches.count() as u64,
+            self.batches.data(),
+        );
+
+        PrepareResult::DrawRecord
+    }
+
+    fn draw_inline(
+        &mut self,
+        mut encoder: RenderPassEncoder<'_, B>,
+        index: usize,
+        _subpass: hal::pass::Subpass<'_, B>,
+        resources: &Resources,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("draw");
+
+        if self.batches.count() == 0 {
+            return;
+        }
+
+        let mesh_storage = <Read<'_, AssetStorage<Mesh>>>::fetch(resources);
+        let models_loc = self.vertex_format.len() as u32;
+
+        encoder.bind_graphics_pipeline(&self.pipeline);
+        self.env.bind(index, &self.pipeline_layout, 0, &mut encoder);
+
+        if self.models.bind(index, models_loc, &mut encoder) {
+            for (mesh_id, range) in self.batches.iter() {
+                debug_assert!(mesh_storage.contains_id(*mesh_id));
+                if let Some(mesh) =
+                    B::unwrap_mesh(unsafe { mesh_storage.get_by_id_unchecked(*mesh_id) })
+                {
+                    mesh.bind_and_draw(0, &self.vertex_format, range, &mut encoder)
+                        .unwrap();
+                }
+            }
+        }
+    }
+
+    fn dispose(self: Box<Self>, factory: &mut Factory<B>, _aux: &Resources) {
+        unsafe {
+            factory.device().destroy_graphics_pipeline(self.pipeline);
+            factory
+                .device()
+                .destroy_pipeline_layout(self.pipeline_layout);
+        }
+    }
+}
+
+fn build_outline_pipeline<B: Backend>(
+    factory: &Factory<B>,
+    subpass: hal::pass::Subpass<'_, B>,
+    viewport: pso::Rect,
+    depth_mode: DepthMode,
+    stencil_test: pso::StencilTest,
+    vertex_format: &[VertexFormat],
+    layouts: Vec<&B::DescriptorSetLayout>,
+) -> Result<(B::GraphicsPipeline, B::PipelineLayout), failure::Error> {
+    let pipeline_layout = unsafe {
+        factory
+            .device()
+            .create_pipeline_layout(layouts, None as Option<(_, _)>)
+    }?;
+
+    let vertex_desc = vertex_format
+        .iter()
+        .map(|f| (f.clone(), pso::VertexInputRate::Vertex))
+        .chain(Some((
+            OutlineArgs::vertex(),
+            pso::VertexInputRate::Instance(1),
+        )))
+        .collect::<Vec<_>>();
+
+    let modules = unsafe {
+        util::simple_shader_modules(factory, &super::OUTLINE_VERTEX, &super::OUTLINE_FRAGMENT)
+    };
+    let (shader_vertex, shader_fragment) = match modules {
+        Ok(modules) => modules,
+        Err(e) => {
+            unsafe {
+                factory.device().destroy_pipeline_layout(pipeline_layout);
+            }
+            return Err(e);
+        }
+    };
+
+    let pipes = PipelinesBuilder::new()
+        .with_pipeline(
+            PipelineDescBuilder::new()
+                .with_vertex_desc(&vertex_desc)
+                .with_shaders(util::simple_shader_set(
+                    &shader_vertex,
+                    Some(&shader_fragment),
+                ))
+                .with_layout(&pipeline_layout)
+                .with_subpass(subpass)
+                .with_viewport(viewport)
+                .with_face_culling(pso::Face::FRONT)
+                .with_depth_test(depth_mode.depth_test(pso::DepthTest::On {
+                    fun: pso::Comparison::Less,
+                    write: true,
+                }))
+                .with_stencil_test(stencil_test)
+                .with_blend_targets(vec![pso::ColorBlendDesc(
+                    pso::ColorMask::ALL,
+                    pso::BlendState::Off,
+                )]),
+        )
+        .build(factory, None);
+
+    unsafe {
+        factory.destroy_shader_module(shader_vertex);
+        factory.destroy_shader_module(shader_fragment);
+    }
+
+    match pipes {
+        Err(e) => {
+            unsafe {
+                factory.device().destroy_pipeline_layout(pipeline_layout);
+            }
+            Err(e)
+        }
+        Ok(mut pipes) => Ok((pipes.remove(0), pipeline_layout)),
+    }
+}
@@ -0,0 +1,353 @@
+//! Instanced particles.
+//!
+//! `DrawParticlesDesc` draws every `ParticleEmitter` with one instanced draw call,
+//! each particle a quad facing the camera. Soft particles fade out where they meet
+//! the scene, reading a depth image written by an earlier pass through the group builder:
+//!
+//! ```ignore
+//! DrawParticlesDesc::new().with_soft_particles(0.5).builder().with_image(depth)
+//! ```
+//!
+//! The depth image can't be the depth attachment of the subpass drawing the particles,
+//! usually it is written by a depth prepass such as `DrawDepthPrepassDesc`.
+
+use crate::{
+    particle::{Particle, ParticleEmitter},
+    pipeline::{PipelineDescBuilder, PipelinesBuilder},
+    pod::ParticleArgs,
+    resources::DepthMode,
+    submodules::{
+        gather::CameraGatherer, DynamicUniform, DynamicVertex, FlatEnvironmentSub, SampledImageSub,
+    },
+    types::Backend,
+    util,
+};
+use amethyst_core::{
+    ecs::{Join, Read, ReadStorage, Resources, SystemData},
+    math::Matrix4,
+    Hidden, HiddenPropagate,
+};
+use glsl_layout::*;
+use rendy::{
+    command::{QueueId, RenderPassEncoder},
+    factory::Factory,
+    graph::{
+        render::{PrepareResult, RenderGroup, RenderGroupDesc},
+        GraphContext, ImageAccess, NodeBuffer, NodeImage,
+    },
+    hal::{self, device::Device, image::Filter, pso},
+    mesh::AsVertex,
+};
+use std::ops::Range;
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+/// How particles are blended with the colors behind them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParticleBlend {
+    /// Particles cover what is behind them by their alpha, for smoke and dust.
+    Alpha,
+    /// Particle colors scaled by their alpha add up, for fire, sparks and magic.
+    Additive,
+}
+
+impl Default for ParticleBlend {
+    fn default() -> Self {
+        ParticleBlend::Alpha
+    }
+}
+
+impl ParticleBlend {
+    fn blend_state(self) -> pso::BlendState {
+        match self {
+            ParticleBlend::Alpha => pso::BlendState::ALPHA,
+            ParticleBlend::Additive => pso::BlendState::On {
+                color: pso::BlendOp::Add {
+                    src: pso::Factor::SrcAlpha,
+                    dst: pso::Factor::One,
+                },
+                alpha: pso::BlendOp::Add {
+                    src: pso::Factor::Zero,
+                    dst: pso::Factor::One,
+                },
+            },
+        }
+    }
+}
+
+/// Draw the particles of `ParticleEmitter`s as camera facing quads without lighting.
+///
+/// Particles are depth tested against the scene but write no depth.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DrawParticlesDesc {
+    blend: ParticleBlend,
+    fade_distance: f32,
+}
+
+impl DrawParticlesDesc {
+    /// Create instance of `DrawParticles` render group
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// How particles are blended, `ParticleBlend::Alpha` by default.
+    pub fn with_blend(mut self, blend: ParticleBlend) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    /// Fade particles out over this view space distance in front of the scene,
+    /// reading the depth image given to the group builder.
+    pub fn with_soft_particles(mut self, fade_distance: f32) -> Self {
+        self.fade_distance = fade_distance.max(0.0);
+        self
+    }
+
+    fn soft(&self) -> bool {
+        self.fade_distance > 0.0
+    }
+}
+
+impl<B: Backend> RenderGroupDesc<B, Resources> for DrawParticlesDesc {
+    fn images(&self) -> Vec<ImageAccess> {
+        if self.soft() {
+            vec![util::sampled_image_access()]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn build(
+        self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        queue: QueueId,
+        aux: &Resources,
+        framebuffer_width: u32,
+        framebuffer_height: u32,
+        subpass: hal::pass::Subpass<'_, B>,
+        _buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<Box<dyn RenderGroup<B, Resources>>, failure::Error> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("build");
+
+        let env = FlatEnvironmentSub::new(factory)?;
+        let args = DynamicUniform::new(factory, pso::ShaderStageFlags::FRAGMENT)?;
+        let depth = if self.soft() {
+            SampledImageSub::new(ctx, factory, &images[0], Filter::Nearest)?
+        } else {
+            SampledImageSub::white(factory, queue)?
+        };
+        let vertex = DynamicVertex::new();
+
+        let depth_mode = *aux.fetch::<DepthMode>();
+        let viewport = util::viewport_rect(aux, framebuffer_width, framebuffer_height);
+        let (pipeline, pipeline_layout) = build_particle_pipeline(
+            factory,
+            subpass,
+            viewport,
+            depth_mode,
+            self.blend,
+            vec![env.raw_layout(), args.raw_layout(), depth.raw_layout()],
+        )?;
+
+        Ok(Box::new(DrawParticles::<B> {
+            pipeline,
+            pipeline_layout,
+            env,
+            args,
+            depth,
+            vertex,
+            emitters: Vec::new(),
+            framebuffer_size: [framebuffer_width as f32, framebuffer_height as f32],
+            fade_distance: self.fade_distance,
+            change: Default::default(),
+        }))
+    }
+}
+
+/// Render group drawing `ParticleEmitter`s.
+#[derive(Debug)]
+pub struct DrawParticles<B: Backend> {
+    pipeline: B::GraphicsPipeline,
+    pipeline_layout: B::PipelineLayout,
+    env: FlatEnvironmentSub<B>,
+    args: DynamicUniform<B, ParticleArgs>,
+    depth: SampledImageSub<B>,
+    vertex: DynamicVertex<B, Particle>,
+    emitters: Vec<Range<u32>>,
+    framebuffer_size: [f32; 2],
+    fade_distance: f32,
+    change: util::ChangeDetection,
+}
+
+impl<B: Backend> RenderGroup<B, Resources> for DrawParticles<B> {
+    fn prepare(
+        &mut self,
+        factory: &Factory<B>,
+        _queue: QueueId,
+        index: usize,
+        _subpass: hal::pass::Subpass<'_, B>,
+        resources: &Resources,
+    ) -> PrepareResult {
+        #[cfg(feature = "profiler")]
+        profile_scope!("prepare");
+
+        let (emitters, hiddens, hidden_props) = <(
+            ReadStorage<'_, ParticleEmitter>,
+            ReadStorage<'_, Hidden>,
+            ReadStorage<'_, HiddenPropagate>,
+        )>::fetch(resources);
+
+        self.env.process(factory, index, resources);
+
+        // Scene depth is reconstructed from the depth image, stored as `DepthMode` says.
+        let depth_mode = <Option<Read<'_, DepthMode>>>::fetch(resources)
+            .map_or_else(DepthMode::default, |mode| *mode);
+        let projection = depth_mode.projection(&CameraGatherer::gather(resources).projection);
+        let inv_proj: [[f32; 4]; 4] = projection
+            .try_inverse()
+            .unwrap_or_else(Matrix4::identity)
+            .into();
+        let mut changed = self.args.write(
+            factory,
+            index,
+            ParticleArgs {
+                inv_proj: inv_proj.into(),
+                framebuffer_size: self.framebuffer_size.into(),
+                fade_distance: self.fade_distance,
+            }
+            .std140(),
+        );
+
+        let visible = (&emitters, !&hiddens, !&hidden_props)
+            .join()
+            .map(|(emitter, _, _)| &emitter.particles[..])
+            .filter(|particles| !particles.is_empty())
+            .collect::<Vec<_>>();
+
+        let mut start = 0;
+        let ranges = visible
+            .iter()
+            .map(|particles| {
+                let range = start..start + particles.len() as u32;
+                start = range.end;
+                range
+            })
+            .collect::<Vec<_>>();
+        changed = changed || ranges != self.emitters;
+        self.emitters = ranges;
+
+        {
+            #[cfg(feature = "profiler")]
+            profile_scope!("write");
+
+            self.vertex
+                .write(factory, index, u64::from(start), visible.iter().cloned());
+        }
+
+        self.change.prepare_result(index, changed)
+    }
+
+    fn draw_inline(
+        &mut self,
+        mut encoder: RenderPassEncoder<'_, B>,
+        index: usize,
+        _subpass: hal::pass::Subpass<'_, B>,
+        _resources: &Resources,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("draw");
+
+        if self.emitters.is_empty() {
+            return;
+        }
+
+        let layout = &self.pipeline_layout;
+        encoder.bind_graphics_pipeline(&self.pipeline);
+        self.env.bind(index, layout, 0, &mut encoder);
+        self.args.bind(index, layout, 1, &mut encoder);
+        self.depth.bind(layout, 2, &mut encoder);
+        self.vertex.bind(index, 0, &mut encoder);
+        for range in &self.emitters {
+            encoder.draw(0..4, range.clone());
+        }
+    }
+
+    fn dispose(self: Box<Self>, factory: &mut Factory<B>, _aux: &Resources) {
+        unsafe {
+            factory.device().destroy_graphics_pipeline(self.pipeline);
+            factory
+                .device()
+                .destroy_pipeline_layout(self.pipeline_layout);
+        }
+    }
+}
+
+fn build_particle_pipeline<B: Backend>(
+    factory: &Factory<B>,
+    subpass: hal::pass::Subpass<'_, B>,
+    viewport: pso::Rect,
+    depth_mode: DepthMode,
+    blend: ParticleBlend,
+    layouts: Vec<&B::DescriptorSetLayout>,
+) -> Result<(B::GraphicsPipeline, B::PipelineLayout), failure::Error> {
+    let pipeline_layout = unsafe {
+        factory
+            .device()
+            .create_pipeline_layout(layouts, None as Option<(_, _)>)
+    }?;
+
+    let modules = unsafe {
+        util::simple_shader_modules(factory, &super::PARTICLE_VERTEX, &super::PARTICLE_FRAGMENT)
+    };
+    let (shader_vertex, shader_fragment) = match modules {
+        Ok(modules) => modules,
+        Err(e) => {
+            unsafe {
+                factory.device().destroy_pipeline_layout(pipeline_layout);
+            }
+            return Err(e);
+        }
+    };
+
+    let pipes = PipelinesBuilder::new()
+        .with_pipeline(
+            PipelineDescBuilder::new()
+                .with_vertex_desc(&[(Particle::vertex(), pso::VertexInputRate::Instance(1))])
+                .with_input_assembler(pso::InputAssemblerDesc::new(hal::Primitive::TriangleStrip))
+                .with_shaders(util::simple_shader_set(
+                    &shader_vertex,
+                    Some(&shader_fragment),
+                ))
+                .with_layout(&pipeline_layout)
+                .with_subpass(subpass)
+                .with_viewport(viewport)
+                .with_blend_targets(vec![pso::ColorBlendDesc(
+                    pso::ColorMask::ALL,
+                    blend.blend_state(),
+                )])
+                .with_depth_test(depth_mode.depth_test(pso::DepthTest::On {
+                    fun: pso::Comparison::Less,
+                    write: false,
+                })),
+        )
+        .build(factory, None);
+
+    unsafe {
+        factory.destroy_shader_module(shader_vertex);
+        factory.destroy_shader_module(shader_fragment);
+    }
+
+    match pipes {
+        Err(e) => {
+            unsafe {
+                factory.device().destroy_pipeline_layout(pipeline_layout);
+            }
+            Err(e)
+        }
+        Ok(mut pipes) => Ok((pipes.remove(0), pipeline_layout)),
+    }
+}
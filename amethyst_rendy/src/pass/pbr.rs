@@ -16,6 +16,12 @@ impl<B: Backend> Base3DPassDef<B> for PbrPassDef {
     fn vertex_skinned_shader() -> &'static SpirvShader {
         &super::POS_NORM_TANG_TEX_SKIN_VERTEX
     }
+    fn vertex_color_shader() -> Option<&'static SpirvShader> {
+        Some(&super::POS_NORM_TANG_TEX_COLOR_VERTEX)
+    }
+    fn vertex_color_skinned_shader() -> Option<&'static SpirvShader> {
+        Some(&super::POS_NORM_TANG_TEX_COLOR_SKIN_VERTEX)
+    }
     fn fragment_shader() -> &'static SpirvShader {
         &super::PBR_FRAGMENT
     }
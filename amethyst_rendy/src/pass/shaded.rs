@@ -20,6 +20,12 @@ impl<B: Backend> Base3DPassDef<B> for ShadedPassDef {
     fn vertex_skinned_shader() -> &'static SpirvShader {
         &super::POS_NORM_TEX_SKIN_VERTEX
     }
+    fn vertex_color_shader() -> Option<&'static SpirvShader> {
+        Some(&super::POS_NORM_TEX_COLOR_VERTEX)
+    }
+    fn vertex_color_skinned_shader() -> Option<&'static SpirvShader> {
+        Some(&super::POS_NORM_TEX_COLOR_SKIN_VERTEX)
+    }
     fn fragment_shader() -> &'static SpirvShader {
         &super::SHADED_FRAGMENT
     }
@@ -0,0 +1,300 @@
+//! Shadow map of the primary directional light.
+//!
+//! `DrawShadowDepthDesc` renders the depth of shadow casters as seen by the light
+//! into the depth attachment of its own node, while lit passes built with
+//! `with_shadow_map` sample that image through the group builder:
+//!
+//! ```ignore
+//! DrawShadowDepthDesc::new().builder()
+//! DrawPbrDesc::new().with_shadow_map().builder().with_image(shadow_depth)
+//! ```
+//!
+//! Shadows are only cast while a `ShadowMap` resource exists. The number of cascades
+//! and their split distances come from the `ShadowSettings` resource; each cascade is
+//! drawn into its own horizontal slice of the shadow map.
+
+use crate::{
+    batch::{GroupIterator, OneLevelBatch},
+    pipeline::{PipelineDescBuilder, PipelinesBuilder},
+    pod::{ShadowArgs, VertexArgs},
+    submodules::{gather::ShadowGatherer, DynamicUniform, DynamicVertex},
+    types::{Backend, Mesh},
+    util,
+};
+use amethyst_assets::{AssetStorage, Handle};
+use amethyst_core::{
+    ecs::{Join, Read, ReadStorage, Resources, SystemData},
+    transform::Transform,
+    Hidden, HiddenPropagate,
+};
+use derivative::Derivative;
+use rendy::{
+    command::{QueueId, RenderPassEncoder},
+    factory::Factory,
+    graph::{
+        render::{PrepareResult, RenderGroup, RenderGroupDesc},
+        GraphContext, NodeBuffer, NodeImage,
+    },
+    hal::{self, device::Device, pso},
+    mesh::{AsVertex, Position, VertexFormat},
+    shader::Shader,
+};
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+/// Draw the depth of meshes as seen by the primary directional light.
+///
+/// Renders into a depth attachment only. Entities outside of the camera's view still
+/// cast shadows, so `Visibility` is not used; `Hidden` and `HiddenPropagate` entities are skipped.
+/// Skinned meshes are drawn in their bind pose.
+#[derive(Clone, Debug, PartialEq, Derivative)]
+#[derivative(Default(bound = ""))]
+pub struct DrawShadowDepthDesc;
+
+impl DrawShadowDepthDesc {
+    /// Create instance of `DrawShadowDepth` render group
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<B: Backend> RenderGroupDesc<B, Resources> for DrawShadowDepthDesc {
+    fn colors(&self) -> usize {
+        0
+    }
+
+    fn build(
+        self,
+        _ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        _queue: QueueId,
+        _aux: &Resources,
+        framebuffer_width: u32,
+        framebuffer_height: u32,
+        subpass: hal::pass::Subpass<'_, B>,
+        _buffers: Vec<NodeBuffer>,
+        _images: Vec<NodeImage>,
+    ) -> Result<Box<dyn RenderGroup<B, Resources>>, failure::Error> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("build");
+
+        let args = DynamicUniform::new(factory, pso::ShaderStageFlags::VERTEX)?;
+        let mut vertex_format = vec![Position::vertex()];
+
+        let (pipeline, pipeline_layout) =
+            build_shadow_pipeline(factory, subpass, &vertex_format, vec![args.raw_layout()])?;
+
+        vertex_format.sort();
+
+        Ok(Box::new(DrawShadowDepth::<B> {
+            pipeline,
+            pipeline_layout,
+            args,
+            models: DynamicVertex::new(),
+            batches: Default::default(),
+            vertex_format,
+            framebuffer_width,
+            framebuffer_height,
+            cascade_count: 1,
+        }))
+    }
+}
+
+/// Render group drawing the shadow map of the primary directional light.
+#[derive(Debug)]
+pub struct DrawShadowDepth<B: Backend> {
+    pipeline: B::GraphicsPipeline,
+    pipeline_layout: B::PipelineLayout,
+    args: DynamicUniform<B, ShadowArgs>,
+    models: DynamicVertex<B, VertexArgs>,
+    batches: OneLevelBatch<u32, VertexArgs>,
+    vertex_format: Vec<VertexFormat>,
+    framebuffer_width: u32,
+    framebuffer_height: u32,
+    cascade_count: usize,
+}
+
+impl<B: Backend> RenderGroup<B, Resources> for DrawShadowDepth<B> {
+    fn prepare(
+        &mut self,
+        factory: &Factory<B>,
+        _queue: QueueId,
+        index: usize,
+        _subpass: hal::pass::Subpass<'_, B>,
+        resources: &Resources,
+    ) -> PrepareResult {
+        #[cfg(feature = "profiler")]
+        profile_scope!("prepare");
+
+        let (mesh_storage, hiddens, hiddens_prop, meshes, transforms) = <(
+            Read<AssetStorage<Mesh>>,
+            ReadStorage<Hidden>,
+            ReadStorage<HiddenPropagate>,
+            ReadStorage<Handle<Mesh>>,
+            ReadStorage<Transform>,
+        )>::fetch(resources);
+
+        let shadow = ShadowGatherer::gather(resources);
+        self.args
+            .write(factory, index, ShadowGatherer::args(shadow.as_ref()));
+        self.cascade_count = shadow.as_ref().map_or(1, |s| s.cascade_count);
+        self.batches.clear_inner();
+
+        if shadow.is_some() {
+            let batches_ref = &mut self.batches;
+            (&meshes, &transforms, !&hiddens, !&hiddens_prop)
+                .join()
+                .map(|(mesh, tform, _, _)| {
+                    (mesh.id(), VertexArgs::from_object_data(tform, None, None))
+                })
+                .for_each_group(|mesh_id, data| {
+                    if mesh_storage.contains_id(mesh_id) {
+                        batches_ref.insert(mesh_id, data.drain(..));
+                    }
+                });
+        }
+
+        self.batches.prune();
+        self.models.write(
+            factory,
+            index,
+            self.batches.count() as u64,
+            self.batches.data(),
+        );
+
+        PrepareResult::DrawRecord
+    }
+
+    fn draw_inline(
+        &mut self,
+        mut encoder: RenderPassEncoder<'_, B>,
+        index: usize,
+        _subpass: hal::pass::Subpass<'_, B>,
+        resources: &Resources,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("draw");
+
+        if self.batches.count() == 0 {
+            return;
+        }
+
+        let mesh_storage = <Read<'_, AssetStorage<Mesh>>>::fetch(resources);
+        let models_loc = self.vertex_format.len() as u32;
+
+        encoder.bind_graphics_pipeline(&self.pipeline);
+        self.args
+            .bind(index, &self.pipeline_layout, 0, &mut encoder);
+
+        if !self.models.bind(index, models_loc, &mut encoder) {
+            return;
+        }
+
+        let tile_width = self.framebuffer_width / self.cascade_count as u32;
+        for cascade in 0..self.cascade_count {
+            let rect = pso::Rect {
+                x: (cascade as u32 * tile_width) as i16,
+                y: 0,
+                w: tile_width as i16,
+                h: self.framebuffer_height as i16,
+            };
+            encoder.set_viewports(
+                0,
+                &[pso::Viewport {
+                    rect,
+                    depth: 0.0..1.0,
+                }],
+            );
+            encoder.set_scissors(0, &[rect]);
+            encoder.push_constants(
+                &self.pipeline_layout,
+                pso::ShaderStageFlags::VERTEX,
+                0,
+                &[cascade as u32],
+            );
+
+            for (mesh_id, range) in self.batches.iter() {
+                debug_assert!(mesh_storage.contains_id(*mesh_id));
+                if let Some(mesh) =
+                    B::unwrap_mesh(unsafe { mesh_storage.get_by_id_unchecked(*mesh_id) })
+                {
+                    mesh.bind_and_draw(0, &self.vertex_format, range, &mut encoder)
+                        .unwrap();
+                }
+            }
+        }
+    }
+
+    fn dispose(self: Box<Self>, factory: &mut Factory<B>, _aux: &Resources) {
+        unsafe {
+            factory.device().destroy_graphics_pipeline(self.pipeline);
+            factory
+                .device()
+                .destroy_pipeline_layout(self.pipeline_layout);
+        }
+    }
+}
+
+fn build_shadow_pipeline<B: Backend>(
+    factory: &Factory<B>,
+    subpass: hal::pass::Subpass<'_, B>,
+    vertex_format: &[VertexFormat],
+    layouts: Vec<&B::DescriptorSetLayout>,
+) -> Result<(B::GraphicsPipeline, B::PipelineLayout), failure::Error> {
+    // The cascade index is pushed before drawing each cascade.
+    let pipeline_layout = unsafe {
+        factory
+            .device()
+            .create_pipeline_layout(layouts, vec![(pso::ShaderStageFlags::VERTEX, 0..1)])
+    }?;
+
+    let vertex_desc = vertex_format
+        .iter()
+        .map(|f| (f.clone(), pso::VertexInputRate::Vertex))
+        .chain(Some((
+            VertexArgs::vertex(),
+            pso::VertexInputRate::Instance(1),
+        )))
+        .collect::<Vec<_>>();
+
+    let shader_vertex = match unsafe { super::SHADOW_VERTEX.module(factory) } {
+        Ok(module) => module,
+        Err(e) => {
+            unsafe {
+                factory.device().destroy_pipeline_layout(pipeline_layout);
+            }
+            return Err(e.into());
+        }
+    };
+
+    let pipes = PipelinesBuilder::new()
+        .with_pipeline(
+            PipelineDescBuilder::new()
+                .with_vertex_desc(&vertex_desc)
+                .with_shaders(util::simple_shader_set(&shader_vertex, None))
+                .with_layout(&pipeline_layout)
+                .with_subpass(subpass)
+                .with_face_culling(pso::Face::BACK)
+                .with_depth_test(pso::DepthTest::On {
+                    fun: pso::Comparison::Less,
+                    write: true,
+                })
+                .with_blend_targets(Vec::new()),
+        )
+        .build(factory, None);
+
+    unsafe {
+        factory.destroy_shader_module(shader_vertex);
+    }
+
+    match pipes {
+        Err(e) => {
+            unsafe {
+                factory.device().destroy_pipeline_layout(pipeline_layout);
+            }
+            Err(e)
+        }
+        Ok(mut pipes) => Ok((pipes.remove(0), pipeline_layout)),
+    }
+}
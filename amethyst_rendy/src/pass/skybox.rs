@@ -2,33 +2,48 @@ use crate::{
     palette::Srgb,
     pipeline::{PipelineDescBuilder, PipelinesBuilder},
     pod::IntoPod,
+    resources::DepthMode,
     shape::Shape,
-    submodules::{DynamicUniform, FlatEnvironmentSub},
-    types::Backend,
+    submodules::{DynamicUniform, FlatEnvironmentSub, TextureId, TextureSub},
+    types::{Backend, Texture},
     util,
 };
+use amethyst_assets::Handle;
 use amethyst_core::ecs::{Read, Resources, SystemData};
 use derivative::Derivative;
-use glsl_layout::{vec3, AsStd140};
+use glsl_layout::{boolean, vec3, AsStd140};
 use rendy::{
     command::{QueueId, RenderPassEncoder},
-    factory::Factory,
+    factory::{Factory, ImageState},
     graph::{
         render::{PrepareResult, RenderGroup, RenderGroupDesc},
         GraphContext, NodeBuffer, NodeImage,
     },
-    hal::{self, device::Device, pso},
+    hal::{
+        self,
+        device::Device,
+        image::{Kind, ViewKind},
+        pso,
+    },
     mesh::{AsVertex, Mesh, PosTex},
-    shader::Shader,
+    resource::{DescriptorSet, DescriptorSetLayout, Escape, Handle as RendyHandle},
+    texture::{pixel::Rgba8Srgb, Texture as RendyTexture, TextureBuilder},
 };
 
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 
+/// Resource selecting what `DrawSkybox` draws behind the scene, overriding the settings
+/// the render group was built with.
 #[derive(Clone, Debug, PartialEq)]
 pub struct SkyboxSettings {
-    nadir_color: Srgb,
-    zenith_color: Srgb,
+    /// Color of the sky straight below the camera.
+    pub nadir_color: Srgb,
+    /// Color of the sky straight above the camera.
+    pub zenith_color: Srgb,
+    /// Cubemap drawn instead of the gradient once it is loaded, such as the source of
+    /// the `EnvironmentMap`.
+    pub cubemap: Option<Handle<Texture>>,
 }
 
 impl Default for SkyboxSettings {
@@ -36,6 +51,7 @@ impl Default for SkyboxSettings {
         Self {
             nadir_color: Srgb::new(0.1, 0.3, 0.35),
             zenith_color: Srgb::new(0.75, 1.0, 1.0),
+            cubemap: None,
         }
     }
 }
@@ -44,19 +60,27 @@ impl Default for SkyboxSettings {
 pub struct SkyboxUniform {
     nadir_color: vec3,
     zenith_color: vec3,
+    use_cubemap: boolean,
 }
 
 impl SkyboxSettings {
-    fn uniform(&self) -> <SkyboxUniform as AsStd140>::Std140 {
+    fn uniform(&self, use_cubemap: bool) -> <SkyboxUniform as AsStd140>::Std140 {
         SkyboxUniform {
             nadir_color: self.nadir_color.into_pod(),
             zenith_color: self.zenith_color.into_pod(),
+            use_cubemap: use_cubemap.into(),
         }
         .std140()
     }
 }
 
-/// Draw opaque sprites without lighting.
+/// Draw a sky behind the scene, either a gradient between two colors or a cubemap.
+///
+/// The sky is drawn on the far plane without writing depth, so the group can be added
+/// before or after the opaque groups of a subpass. Drawing it after them saves shading
+/// the pixels covered by geometry.
+///
+/// The `SkyboxSettings` resource is used when present, the settings given here otherwise.
 #[derive(Clone, Debug, PartialEq, Derivative)]
 #[derivative(Default(bound = ""))]
 pub struct DrawSkyboxDesc {
@@ -69,11 +93,23 @@ impl DrawSkyboxDesc {
         Default::default()
     }
 
+    /// Draw a gradient from `nadir_color` below the camera to `zenith_color` above it.
     pub fn with_colors(nadir_color: Srgb, zenith_color: Srgb) -> Self {
         Self {
             default_settings: SkyboxSettings {
                 nadir_color,
                 zenith_color,
+                cubemap: None,
+            },
+        }
+    }
+
+    /// Draw `cubemap`, and the default gradient until it is loaded.
+    pub fn with_cubemap(cubemap: Handle<Texture>) -> Self {
+        Self {
+            default_settings: SkyboxSettings {
+                cubemap: Some(cubemap),
+                ..Default::default()
             },
         }
     }
@@ -85,7 +121,7 @@ impl<B: Backend> RenderGroupDesc<B, Resources> for DrawSkyboxDesc {
         _ctx: &GraphContext<B>,
         factory: &mut Factory<B>,
         queue: QueueId,
-        _resources: &Resources,
+        aux: &Resources,
         framebuffer_width: u32,
         framebuffer_height: u32,
         subpass: hal::pass::Subpass<'_, B>,
@@ -97,16 +133,20 @@ impl<B: Backend> RenderGroupDesc<B, Resources> for DrawSkyboxDesc {
 
         let env = FlatEnvironmentSub::new(factory)?;
         let colors = DynamicUniform::new(factory, pso::ShaderStageFlags::FRAGMENT)?;
+        let textures = TextureSub::new(factory)?;
+        let (fallback_cube, fallback_set) = build_fallback_cube(factory, queue)?;
         let mesh = Shape::Sphere(16, 16)
             .generate::<Vec<PosTex>>(None)
             .build(queue, factory)?;
 
+        let depth_mode = *aux.fetch::<DepthMode>();
+        let viewport = util::viewport_rect(aux, framebuffer_width, framebuffer_height);
         let (pipeline, pipeline_layout) = build_skybox_pipeline(
             factory,
             subpass,
-            framebuffer_width,
-            framebuffer_height,
-            vec![env.raw_layout(), colors.raw_layout()],
+            viewport,
+            depth_mode,
+            vec![env.raw_layout(), colors.raw_layout(), textures.raw_layout()],
         )?;
 
         Ok(Box::new(DrawSkybox::<B> {
@@ -114,6 +154,10 @@ impl<B: Backend> RenderGroupDesc<B, Resources> for DrawSkyboxDesc {
             pipeline_layout,
             env,
             colors,
+            textures,
+            cubemap: None,
+            fallback_cube,
+            fallback_set,
             mesh,
             default_settings: self.default_settings,
         }))
@@ -126,6 +170,11 @@ pub struct DrawSkybox<B: Backend> {
     pipeline_layout: B::PipelineLayout,
     env: FlatEnvironmentSub<B>,
     colors: DynamicUniform<B, SkyboxUniform>,
+    textures: TextureSub<B>,
+    cubemap: Option<TextureId>,
+    // Bound while no cubemap is loaded, the shader needs a valid texture either way.
+    fallback_cube: RendyTexture<B>,
+    fallback_set: Escape<DescriptorSet<B>>,
     mesh: Mesh<B>,
     default_settings: SkyboxSettings,
 }
@@ -142,12 +191,31 @@ impl<B: Backend> RenderGroup<B, Resources> for DrawSkybox<B> {
         #[cfg(feature = "profiler")]
         profile_scope!("prepare");
 
-        let settings = <(Option<Read<'_, SkyboxSettings>>)>::fetch(resources)
-            .map(|s| s.uniform())
-            .unwrap_or_else(|| self.default_settings.uniform());
+        let settings_res = <Option<Read<'_, SkyboxSettings>>>::fetch(resources);
+        let settings = settings_res
+            .as_ref()
+            .map(|s| &**s)
+            .unwrap_or(&self.default_settings);
+
+        let textures = &mut self.textures;
+        textures.maintain(factory, resources);
+        let cubemap = settings.cubemap.as_ref().and_then(|handle| {
+            textures.insert(
+                factory,
+                resources,
+                handle,
+                hal::image::Layout::ShaderReadOnlyOptimal,
+            )
+        });
+        let mut changed = cubemap.map_or(false, |(_, inserted)| inserted);
+        let cubemap = cubemap.map(|(id, _)| id);
+        changed |= cubemap != self.cubemap;
+        self.cubemap = cubemap;
 
         self.env.process(factory, index, resources);
-        let changed = self.colors.write(factory, index, settings);
+        changed |= self
+            .colors
+            .write(factory, index, settings.uniform(cubemap.is_some()));
 
         if changed {
             PrepareResult::DrawRecord
@@ -169,6 +237,18 @@ impl<B: Backend> RenderGroup<B, Resources> for DrawSkybox<B> {
         self.env.bind(index, &self.pipeline_layout, 0, &mut encoder);
         self.colors
             .bind(index, &self.pipeline_layout, 1, &mut encoder);
+        match self.cubemap {
+            Some(cubemap) if self.textures.loaded(cubemap) => {
+                self.textures
+                    .bind(&self.pipeline_layout, 2, cubemap, &mut encoder);
+            }
+            _ => encoder.bind_graphics_descriptor_sets(
+                &self.pipeline_layout,
+                2,
+                Some(self.fallback_set.raw()),
+                std::iter::empty(),
+            ),
+        }
         self.mesh
             .bind(0, &[PosTex::vertex()], &mut encoder)
             .unwrap();
@@ -185,11 +265,48 @@ impl<B: Backend> RenderGroup<B, Resources> for DrawSkybox<B> {
     }
 }
 
+/// A black cubemap, and a descriptor set binding it like a cubemap of `TextureSub`.
+fn build_fallback_cube<B: Backend>(
+    factory: &mut Factory<B>,
+    queue: QueueId,
+) -> Result<(RendyTexture<B>, Escape<DescriptorSet<B>>), failure::Error> {
+    let state = ImageState {
+        queue,
+        stage: pso::PipelineStage::FRAGMENT_SHADER,
+        access: hal::image::Access::SHADER_READ,
+        layout: hal::image::Layout::ShaderReadOnlyOptimal,
+    };
+    let black: Rgba8Srgb = palette::Srgba::new(0.0, 0.0, 0.0, 1.0).into();
+    let cube = TextureBuilder::new()
+        .with_kind(Kind::D2(1, 1, 6, 1))
+        .with_view_kind(ViewKind::Cube)
+        .with_data_width(1)
+        .with_data_height(1)
+        .with_data(vec![black; 6])
+        .build(state, factory)?;
+
+    let layout: RendyHandle<DescriptorSetLayout<B>> =
+        set_layout! {factory, [1] CombinedImageSampler FRAGMENT};
+    let set = factory.create_descriptor_set(layout)?;
+    unsafe {
+        factory.write_descriptor_sets(vec![util::desc_write(
+            set.raw(),
+            0,
+            pso::Descriptor::CombinedImageSampler(
+                cube.view().raw(),
+                hal::image::Layout::ShaderReadOnlyOptimal,
+                cube.sampler().raw(),
+            ),
+        )]);
+    }
+    Ok((cube, set))
+}
+
 fn build_skybox_pipeline<B: Backend>(
     factory: &Factory<B>,
     subpass: hal::pass::Subpass<'_, B>,
-    framebuffer_width: u32,
-    framebuffer_height: u32,
+    viewport: pso::Rect,
+    depth_mode: DepthMode,
     layouts: Vec<&B::DescriptorSetLayout>,
 ) -> Result<(B::GraphicsPipeline, B::PipelineLayout), failure::Error> {
     let pipeline_layout = unsafe {
@@ -198,24 +315,39 @@ fn build_skybox_pipeline<B: Backend>(
             .create_pipeline_layout(layouts, None as Option<(_, _)>)
     }?;
 
-    let shader_vertex = unsafe { super::SKYBOX_VERTEX.module(factory).unwrap() };
-    let shader_fragment = unsafe { super::SKYBOX_FRAGMENT.module(factory).unwrap() };
+    let modules = unsafe {
+        util::simple_shader_modules(factory, &super::SKYBOX_VERTEX, &super::SKYBOX_FRAGMENT)
+    };
+    let (shader_vertex, shader_fragment) = match modules {
+        Ok(modules) => modules,
+        Err(e) => {
+            unsafe {
+                factory.device().destroy_pipeline_layout(pipeline_layout);
+            }
+            return Err(e);
+        }
+    };
+
+    let spec_constants = [pso::SpecializationConstant { id: 0, range: 0..4 }];
+    let spec_data = ((depth_mode == DepthMode::Reversed) as u32).to_ne_bytes();
+    let mut shaders = util::simple_shader_set(&shader_vertex, Some(&shader_fragment));
+    shaders.vertex.specialization = pso::Specialization {
+        constants: &spec_constants,
+        data: &spec_data,
+    };
 
     let pipes = PipelinesBuilder::new()
         .with_pipeline(
             PipelineDescBuilder::new()
                 .with_vertex_desc(&[(PosTex::vertex(), pso::VertexInputRate::Vertex)])
-                .with_shaders(util::simple_shader_set(
-                    &shader_vertex,
-                    Some(&shader_fragment),
-                ))
+                .with_shaders(shaders)
                 .with_layout(&pipeline_layout)
                 .with_subpass(subpass)
-                .with_framebuffer_size(framebuffer_width, framebuffer_height)
-                .with_depth_test(pso::DepthTest::On {
+                .with_viewport(viewport)
+                .with_depth_test(depth_mode.depth_test(pso::DepthTest::On {
                     fun: pso::Comparison::LessEqual,
                     write: false,
-                })
+                }))
                 .with_blend_targets(vec![pso::ColorBlendDesc(
                     pso::ColorMask::ALL,
                     pso::BlendState::Off,
@@ -0,0 +1,350 @@
+//! Screen space ambient occlusion.
+//!
+//! `DrawSsaoDesc` computes ambient occlusion from a depth image produced by an earlier
+//! pass, `DrawSsaoBlurDesc` smooths the result, and the PBR pass darkens its ambient
+//! term with it once built with `DrawPbrDesc::with_ambient_occlusion`. Each group reads
+//! its input image through the group builder:
+//!
+//! ```ignore
+//! DrawSsaoDesc::new().builder().with_image(depth)
+//! DrawSsaoBlurDesc::new().builder().with_image(raw_ao)
+//! DrawPbrDesc::new().with_ambient_occlusion().builder().with_image(blurred_ao)
+//! ```
+//!
+//! The depth image has to be written before the pass drawing the lit scene,
+//! usually by a depth prepass such as `DrawDepthPrepassDesc`.
+
+use super::fullscreen::build_fullscreen_pipeline;
+use crate::{
+    pod::SsaoArgs,
+    resources::DepthMode,
+    submodules::{gather::CameraGatherer, DynamicUniform, SampledImageSub},
+    types::Backend,
+    util,
+};
+use amethyst_core::{
+    ecs::{Read, Resources, SystemData},
+    math::Matrix4,
+};
+use glsl_layout::*;
+use rendy::{
+    command::{QueueId, RenderPassEncoder},
+    factory::Factory,
+    graph::{
+        render::{PrepareResult, RenderGroup, RenderGroupDesc},
+        GraphContext, ImageAccess, NodeBuffer, NodeImage,
+    },
+    hal::{self, device::Device, image::Filter, pso},
+};
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+/// Largest number of samples taken per pixel by `DrawSsao`.
+pub const MAX_SSAO_SAMPLES: u32 = 64;
+
+/// Compute screen space ambient occlusion from a depth image.
+///
+/// Writes the unoccluded fraction into the red channel of its single color attachment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DrawSsaoDesc {
+    radius: f32,
+    samples: u32,
+    intensity: f32,
+    bias: f32,
+}
+
+impl Default for DrawSsaoDesc {
+    fn default() -> Self {
+        DrawSsaoDesc {
+            radius: 0.5,
+            samples: 16,
+            intensity: 1.0,
+            bias: 0.025,
+        }
+    }
+}
+
+impl DrawSsaoDesc {
+    /// Create instance of `DrawSsao` render group
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// View space radius of the hemisphere searched for occluders.
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    /// Number of depth samples per pixel, clamped to `MAX_SSAO_SAMPLES`.
+    pub fn with_samples(mut self, samples: u32) -> Self {
+        self.samples = samples.max(1).min(MAX_SSAO_SAMPLES);
+        self
+    }
+
+    /// Strength of the darkening, where `0.0` disables it.
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Depth offset ignoring occluders this close, to avoid self-occlusion acne.
+    pub fn with_bias(mut self, bias: f32) -> Self {
+        self.bias = bias;
+        self
+    }
+
+    /// View space radius of the hemisphere searched for occluders.
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    /// Number of depth samples per pixel.
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// Strength of the darkening.
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+}
+
+impl<B: Backend> RenderGroupDesc<B, Resources> for DrawSsaoDesc {
+    fn images(&self) -> Vec<ImageAccess> {
+        vec![util::sampled_image_access()]
+    }
+
+    fn depth(&self) -> bool {
+        false
+    }
+
+    fn build(
+        self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        _queue: QueueId,
+        _aux: &Resources,
+        framebuffer_width: u32,
+        framebuffer_height: u32,
+        subpass: hal::pass::Subpass<'_, B>,
+        _buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<Box<dyn RenderGroup<B, Resources>>, failure::Error> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("build");
+
+        let args = DynamicUniform::new(factory, pso::ShaderStageFlags::FRAGMENT)?;
+        let depth = SampledImageSub::new(ctx, factory, &images[0], Filter::Nearest)?;
+
+        let (pipeline, pipeline_layout) = build_fullscreen_pipeline(
+            factory,
+            subpass,
+            framebuffer_width,
+            framebuffer_height,
+            &super::SSAO_FRAGMENT,
+            pso::BlendState::Off,
+            vec![args.raw_layout(), depth.raw_layout()],
+        )?;
+
+        Ok(Box::new(DrawSsao::<B> {
+            pipeline,
+            pipeline_layout,
+            args,
+            depth,
+            settings: self,
+            change: Default::default(),
+        }))
+    }
+}
+
+/// Render group computing screen space ambient occlusion.
+#[derive(Debug)]
+pub struct DrawSsao<B: Backend> {
+    pipeline: B::GraphicsPipeline,
+    pipeline_layout: B::PipelineLayout,
+    args: DynamicUniform<B, SsaoArgs>,
+    depth: SampledImageSub<B>,
+    settings: DrawSsaoDesc,
+    change: util::ChangeDetection,
+}
+
+impl<B: Backend> RenderGroup<B, Resources> for DrawSsao<B> {
+    fn prepare(
+        &mut self,
+        factory: &Factory<B>,
+        _queue: QueueId,
+        index: usize,
+        _subpass: hal::pass::Subpass<'_, B>,
+        resources: &Resources,
+    ) -> PrepareResult {
+        #[cfg(feature = "profiler")]
+        profile_scope!("prepare");
+
+        // Positions are reconstructed from the depth image, stored as `DepthMode` says.
+        let depth_mode = <Option<Read<'_, DepthMode>>>::fetch(resources)
+            .map_or_else(DepthMode::default, |mode| *mode);
+        let projection = depth_mode.projection(&CameraGatherer::gather(resources).projection);
+        let inverse = projection.try_inverse().unwrap_or_else(Matrix4::identity);
+        let proj: [[f32; 4]; 4] = projection.into();
+        let inv_proj: [[f32; 4]; 4] = inverse.into();
+
+        let changed = self.args.write(
+            factory,
+            index,
+            SsaoArgs {
+                proj: proj.into(),
+                inv_proj: inv_proj.into(),
+                sample_count: self.settings.samples as i32,
+                radius: self.settings.radius,
+                intensity: self.settings.intensity,
+                bias: self.settings.bias,
+            }
+            .std140(),
+        );
+
+        self.change.prepare_result(index, changed)
+    }
+
+    fn draw_inline(
+        &mut self,
+        mut encoder: RenderPassEncoder<'_, B>,
+        index: usize,
+        _subpass: hal::pass::Subpass<'_, B>,
+        _resources: &Resources,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("draw");
+
+        encoder.bind_graphics_pipeline(&self.pipeline);
+        self.args
+            .bind(index, &self.pipeline_layout, 0, &mut encoder);
+        self.depth.bind(&self.pipeline_layout, 1, &mut encoder);
+        encoder.draw(0..3, 0..1);
+    }
+
+    fn dispose(self: Box<Self>, factory: &mut Factory<B>, _aux: &Resources) {
+        unsafe {
+            factory.device().destroy_graphics_pipeline(self.pipeline);
+            factory
+                .device()
+                .destroy_pipeline_layout(self.pipeline_layout);
+        }
+    }
+}
+
+/// Blur the noisy output of `DrawSsao` before it is sampled by lighting passes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DrawSsaoBlurDesc;
+
+impl DrawSsaoBlurDesc {
+    /// Create instance of `DrawSsaoBlur` render group
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<B: Backend> RenderGroupDesc<B, Resources> for DrawSsaoBlurDesc {
+    fn images(&self) -> Vec<ImageAccess> {
+        vec![util::sampled_image_access()]
+    }
+
+    fn depth(&self) -> bool {
+        false
+    }
+
+    fn build(
+        self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        _queue: QueueId,
+        _aux: &Resources,
+        framebuffer_width: u32,
+        framebuffer_height: u32,
+        subpass: hal::pass::Subpass<'_, B>,
+        _buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<Box<dyn RenderGroup<B, Resources>>, failure::Error> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("build");
+
+        let input = SampledImageSub::new(ctx, factory, &images[0], Filter::Linear)?;
+
+        let (pipeline, pipeline_layout) = build_fullscreen_pipeline(
+            factory,
+            subpass,
+            framebuffer_width,
+            framebuffer_height,
+            &super::SSAO_BLUR_FRAGMENT,
+            pso::BlendState::Off,
+            vec![input.raw_layout()],
+        )?;
+
+        Ok(Box::new(DrawSsaoBlur::<B> {
+            pipeline,
+            pipeline_layout,
+            input,
+        }))
+    }
+}
+
+/// Render group blurring screen space ambient occlusion.
+#[derive(Debug)]
+pub struct DrawSsaoBlur<B: Backend> {
+    pipeline: B::GraphicsPipeline,
+    pipeline_layout: B::PipelineLayout,
+    input: SampledImageSub<B>,
+}
+
+impl<B: Backend> RenderGroup<B, Resources> for DrawSsaoBlur<B> {
+    fn prepare(
+        &mut self,
+        _factory: &Factory<B>,
+        _queue: QueueId,
+        _index: usize,
+        _subpass: hal::pass::Subpass<'_, B>,
+        _resources: &Resources,
+    ) -> PrepareResult {
+        PrepareResult::DrawRecord
+    }
+
+    fn draw_inline(
+        &mut self,
+        mut encoder: RenderPassEncoder<'_, B>,
+        _index: usize,
+        _subpass: hal::pass::Subpass<'_, B>,
+        _resources: &Resources,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("draw");
+
+        encoder.bind_graphics_pipeline(&self.pipeline);
+        self.input.bind(&self.pipeline_layout, 0, &mut encoder);
+        encoder.draw(0..3, 0..1);
+    }
+
+    fn dispose(self: Box<Self>, factory: &mut Factory<B>, _aux: &Resources) {
+        unsafe {
+            factory.device().destroy_graphics_pipeline(self.pipeline);
+            factory
+                .device()
+                .destroy_pipeline_layout(self.pipeline_layout);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_count_is_clamped() {
+        assert_eq!(DrawSsaoDesc::new().with_samples(0).samples(), 1);
+        assert_eq!(DrawSsaoDesc::new().with_samples(32).samples(), 32);
+        assert_eq!(
+            DrawSsaoDesc::new().with_samples(1000).samples(),
+            MAX_SSAO_SAMPLES
+        );
+    }
+}
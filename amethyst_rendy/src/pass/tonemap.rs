@@ -0,0 +1,164 @@
+//! Tonemapping of a high dynamic range color image.
+//!
+//! The scene is drawn into a floating point color image, which `DrawTonemapDesc`
+//! reads through the group builder to write the final color:
+//!
+//! ```ignore
+//! DrawTonemapDesc::new().builder().with_image(hdr_color)
+//! ```
+
+use super::fullscreen::build_fullscreen_pipeline;
+use crate::{
+    pod::TonemapArgs,
+    resources::{TonemapOperator, TonemapSettings},
+    submodules::{DynamicUniform, SampledImageSub},
+    types::Backend,
+    util,
+};
+use amethyst_core::ecs::{Read, Resources, SystemData};
+use derivative::Derivative;
+use glsl_layout::*;
+use rendy::{
+    command::{QueueId, RenderPassEncoder},
+    factory::Factory,
+    graph::{
+        render::{PrepareResult, RenderGroup, RenderGroupDesc},
+        GraphContext, ImageAccess, NodeBuffer, NodeImage,
+    },
+    hal::{self, device::Device, image::Filter, pso},
+};
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+/// Map a high dynamic range color image to the displayable range.
+///
+/// The operator and exposure come from the `TonemapSettings` resource,
+/// or its defaults when there is none.
+#[derive(Clone, Debug, PartialEq, Derivative)]
+#[derivative(Default(bound = ""))]
+pub struct DrawTonemapDesc;
+
+impl DrawTonemapDesc {
+    /// Create instance of `DrawTonemap` render group
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<B: Backend> RenderGroupDesc<B, Resources> for DrawTonemapDesc {
+    fn images(&self) -> Vec<ImageAccess> {
+        vec![util::sampled_image_access()]
+    }
+
+    fn depth(&self) -> bool {
+        false
+    }
+
+    fn build(
+        self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        _queue: QueueId,
+        _aux: &Resources,
+        framebuffer_width: u32,
+        framebuffer_height: u32,
+        subpass: hal::pass::Subpass<'_, B>,
+        _buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<Box<dyn RenderGroup<B, Resources>>, failure::Error> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("build");
+
+        let args = DynamicUniform::new(factory, pso::ShaderStageFlags::FRAGMENT)?;
+        let input = SampledImageSub::new(ctx, factory, &images[0], Filter::Nearest)?;
+
+        let (pipeline, pipeline_layout) = build_fullscreen_pipeline(
+            factory,
+            subpass,
+            framebuffer_width,
+            framebuffer_height,
+            &super::TONEMAP_FRAGMENT,
+            pso::BlendState::Off,
+            vec![args.raw_layout(), input.raw_layout()],
+        )?;
+
+        Ok(Box::new(DrawTonemap::<B> {
+            pipeline,
+            pipeline_layout,
+            args,
+            input,
+            change: Default::default(),
+        }))
+    }
+}
+
+/// Render group tonemapping a high dynamic range color image.
+#[derive(Debug)]
+pub struct DrawTonemap<B: Backend> {
+    pipeline: B::GraphicsPipeline,
+    pipeline_layout: B::PipelineLayout,
+    args: DynamicUniform<B, TonemapArgs>,
+    input: SampledImageSub<B>,
+    change: util::ChangeDetection,
+}
+
+impl<B: Backend> RenderGroup<B, Resources> for DrawTonemap<B> {
+    fn prepare(
+        &mut self,
+        factory: &Factory<B>,
+        _queue: QueueId,
+        index: usize,
+        _subpass: hal::pass::Subpass<'_, B>,
+        resources: &Resources,
+    ) -> PrepareResult {
+        #[cfg(feature = "profiler")]
+        profile_scope!("prepare");
+
+        let settings = <Option<Read<'_, TonemapSettings>>>::fetch(resources)
+            .map(|settings| settings.clone())
+            .unwrap_or_default();
+
+        let changed = self.args.write(
+            factory,
+            index,
+            TonemapArgs {
+                exposure: settings.exposure,
+                operator: match settings.operator {
+                    TonemapOperator::Clamp => 0,
+                    TonemapOperator::Reinhard => 1,
+                    TonemapOperator::AcesFilmic => 2,
+                },
+            }
+            .std140(),
+        );
+
+        self.change.prepare_result(index, changed)
+    }
+
+    fn draw_inline(
+        &mut self,
+        mut encoder: RenderPassEncoder<'_, B>,
+        index: usize,
+        _subpass: hal::pass::Subpass<'_, B>,
+        _resources: &Resources,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("draw");
+
+        encoder.bind_graphics_pipeline(&self.pipeline);
+        self.args
+            .bind(index, &self.pipeline_layout, 0, &mut encoder);
+        self.input.bind(&self.pipeline_layout, 1, &mut encoder);
+        encoder.draw(0..3, 0..1);
+    }
+
+    fn dispose(self: Box<Self>, factory: &mut Factory<B>, _aux: &Resources) {
+        unsafe {
+            factory.device().destroy_graphics_pipeline(self.pipeline);
+            factory
+                .device()
+                .destroy_pipeline_layout(self.pipeline_layout);
+        }
+    }
+}
@@ -0,0 +1,45 @@
+//! Wireframe rendering of meshes, for debugging geometry.
+//!
+//! Edges are drawn in the color of the entity's `Tint`, white when it has none.
+//! The wireframe can overlay a filled pass when both are in the same subpass and
+//! the wireframe group is added after it.
+
+use super::base_3d::*;
+use crate::{mtl::TexAlbedo, skinning::JointCombined, types::Backend};
+use rendy::{
+    hal::pso,
+    mesh::{AsVertex, Position, TexCoord, VertexFormat},
+    shader::SpirvShader,
+};
+
+#[derive(Debug)]
+pub struct WireframePassDef;
+impl<B: Backend> Base3DPassDef<B> for WireframePassDef {
+    const NAME: &'static str = "Wireframe";
+    type TextureSet = TexAlbedo;
+    fn vertex_shader() -> &'static SpirvShader {
+        &super::POS_TEX_VERTEX
+    }
+    fn vertex_skinned_shader() -> &'static SpirvShader {
+        &super::POS_TEX_SKIN_VERTEX
+    }
+    fn fragment_shader() -> &'static SpirvShader {
+        &super::WIREFRAME_FRAGMENT
+    }
+    fn base_format() -> Vec<VertexFormat> {
+        vec![Position::vertex(), TexCoord::vertex()]
+    }
+    fn skinned_format() -> Vec<VertexFormat> {
+        vec![
+            Position::vertex(),
+            TexCoord::vertex(),
+            JointCombined::vertex(),
+        ]
+    }
+    fn polygon_mode() -> pso::PolygonMode {
+        pso::PolygonMode::Line(1.0)
+    }
+}
+
+pub type DrawWireframeDesc<B> = DrawBase3DDesc<B, WireframePassDef>;
+pub type DrawWireframe<B> = DrawBase3D<B, WireframePassDef>;
@@ -7,9 +7,9 @@ use rendy::{
         pass::Subpass,
         pso::{
             AttributeDesc, BakedStates, BasePipeline, BlendDesc, ColorBlendDesc, DepthStencilDesc,
-            DepthTest, Face, GraphicsPipelineDesc, GraphicsShaderSet, InputAssemblerDesc,
-            Multisampling, PipelineCreationFlags, Rasterizer, Rect, VertexBufferDesc,
-            VertexInputRate, Viewport,
+            DepthTest, Face, GraphicsPipelineDesc, GraphicsShaderSet, InputAssemblerDesc, Location,
+            Multisampling, PipelineCreationFlags, PolygonMode, Rasterizer, Rect, StencilTest,
+            VertexBufferDesc, VertexInputRate, Viewport,
         },
         Primitive,
     },
@@ -164,12 +164,19 @@ impl<'a, B: Backend> PipelineDescBuilder<'a, B> {
         self
     }
     pub fn set_framebuffer_size(&mut self, fb_w: u32, fb_h: u32) {
-        let rect = Rect {
+        self.set_viewport(Rect {
             x: 0,
             y: 0,
             w: fb_w as i16,
             h: fb_h as i16,
-        };
+        });
+    }
+    /// Draw into `rect` only, setting both the viewport and the scissor.
+    pub fn with_viewport(mut self, rect: Rect) -> Self {
+        self.set_viewport(rect);
+        self
+    }
+    pub fn set_viewport(&mut self, rect: Rect) {
         let old_baked_states = self.baked_states.clone();
         self.set_baked_states(BakedStates {
             viewport: Some(Viewport {
@@ -187,6 +194,13 @@ impl<'a, B: Backend> PipelineDescBuilder<'a, B> {
     pub fn set_depth_test(&mut self, depth_test: DepthTest) {
         self.depth_stencil.depth = depth_test;
     }
+    pub fn with_stencil_test(mut self, stencil_test: StencilTest) -> Self {
+        self.set_stencil_test(stencil_test);
+        self
+    }
+    pub fn set_stencil_test(&mut self, stencil_test: StencilTest) {
+        self.depth_stencil.stencil = stencil_test;
+    }
     pub fn with_face_culling(mut self, cull_face: Face) -> Self {
         self.set_face_culling(cull_face);
         self
@@ -194,6 +208,23 @@ impl<'a, B: Backend> PipelineDescBuilder<'a, B> {
     pub fn set_face_culling(&mut self, cull_face: Face) {
         self.rasterizer.cull_face = cull_face;
     }
+    pub fn with_polygon_mode(mut self, polygon_mode: PolygonMode) -> Self {
+        self.set_polygon_mode(polygon_mode);
+        self
+    }
+    pub fn set_polygon_mode(&mut self, polygon_mode: PolygonMode) {
+        self.rasterizer.polygon_mode = polygon_mode;
+    }
+    /// Rasterize polygon edges as lines of the given width.
+    ///
+    /// Widths other than `1.0` require the `wideLines` device feature.
+    pub fn with_line_width(mut self, width: f32) -> Self {
+        self.set_line_width(width);
+        self
+    }
+    pub fn set_line_width(&mut self, width: f32) {
+        self.set_polygon_mode(PolygonMode::Line(width));
+    }
     pub fn with_vertex_desc(mut self, desc: &[(VertexFormat, VertexInputRate)]) -> Self {
         self.set_vertex_desc(desc);
         self
@@ -203,6 +234,23 @@ impl<'a, B: Backend> PipelineDescBuilder<'a, B> {
         self.set_vertex_buffers(vbos);
         self.set_attributes(attrs);
     }
+    pub fn with_vertex_desc_at(
+        mut self,
+        desc: &[(VertexFormat, VertexInputRate)],
+        base_location: Location,
+    ) -> Self {
+        self.set_vertex_desc_at(desc, base_location);
+        self
+    }
+    pub fn set_vertex_desc_at(
+        &mut self,
+        desc: &[(VertexFormat, VertexInputRate)],
+        base_location: Location,
+    ) {
+        let (vbos, attrs) = util::vertex_desc_with_base(desc, base_location);
+        self.set_vertex_buffers(vbos);
+        self.set_attributes(attrs);
+    }
     pub fn with_blend_targets(mut self, targets: Vec<ColorBlendDesc>) -> Self {
         self.set_blend_targets(targets);
         self
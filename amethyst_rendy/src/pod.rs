@@ -1,18 +1,21 @@
 use crate::{
     mtl,
-    resources::Tint as TintComponent,
+    resources::{
+        DitheredFade as DitheredFadeComponent, Outline as OutlineComponent, Tint as TintComponent,
+    },
     sprite::{SpriteRender, SpriteSheet},
     types::Texture,
 };
 use amethyst_assets::{AssetStorage, Handle};
 use amethyst_core::{
+    ecs::Entity,
     math::{convert, Matrix4, Vector4},
     Transform,
 };
 use glsl_layout::*;
 use rendy::{
     hal::format::Format,
-    mesh::{AsAttribute, AsVertex, Model, VertexFormat},
+    mesh::{AsAttribute, AsVertex, Color, Model, VertexFormat},
 };
 
 #[derive(Clone, Copy, Debug, AsStd140)]
@@ -38,6 +41,52 @@ pub struct ViewArgs {
     pub view: mat4,
 }
 
+#[derive(Clone, Copy, Debug, AsStd140)]
+#[repr(C, align(16))]
+pub struct SsaoArgs {
+    pub proj: mat4,
+    pub inv_proj: mat4,
+    pub sample_count: int,
+    pub radius: float,
+    pub intensity: float,
+    pub bias: float,
+}
+
+#[derive(Clone, Copy, Debug, AsStd140)]
+#[repr(C, align(16))]
+pub struct ParticleArgs {
+    pub inv_proj: mat4,
+    pub framebuffer_size: vec2,
+    pub fade_distance: float,
+}
+
+#[derive(Clone, Copy, Debug, AsStd140)]
+#[repr(C, align(16))]
+pub struct BloomArgs {
+    pub direction: vec2,
+    pub threshold: float,
+    pub radius: float,
+    pub intensity: float,
+}
+
+#[derive(Clone, Copy, Debug, AsStd140)]
+#[repr(C, align(16))]
+pub struct TonemapArgs {
+    pub exposure: float,
+    pub operator: int,
+}
+
+#[derive(Clone, Copy, Debug, AsStd140)]
+#[repr(C, align(16))]
+pub struct ShadowArgs {
+    pub light_view_proj: [mat4; 4],
+    pub cascade_splits: vec4,
+    pub camera_forward: vec3,
+    pub bias: float,
+    pub cascade_count: int,
+    pub enabled: int,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, AsStd140)]
 #[repr(C, align(16))]
 pub struct Tint {
@@ -49,31 +98,90 @@ impl AsAttribute for Tint {
     const FORMAT: Format = Format::Rgba32Sfloat;
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, AsStd140)]
+#[repr(C, align(4))]
+pub struct Fade {
+    pub fade: float,
+}
+
+impl AsAttribute for Fade {
+    const NAME: &'static str = "fade";
+    const FORMAT: Format = Format::R32Sfloat;
+}
+
+/// Per instance attributes of 3d meshes.
+///
+/// Packed like `SkinnedVertexArgs`: instances are tightly laid out in the vertex buffer,
+/// and the 88 bytes of attributes are not a multiple of 16.
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
-#[repr(C, align(16))]
+#[repr(C, packed)]
 pub struct VertexArgs {
     pub model: mat4,
     pub tint: vec4,
+    pub fade: float,
+    pub object_id: u32,
 }
 
 impl VertexArgs {
     #[inline]
-    pub fn from_object_data(transform: &Transform, tint: Option<&TintComponent>) -> Self {
+    pub fn from_object_data(
+        transform: &Transform,
+        tint: Option<&TintComponent>,
+        fade: Option<&DitheredFadeComponent>,
+    ) -> Self {
         let model: [[f32; 4]; 4] = convert::<_, Matrix4<f32>>(*transform.global_matrix()).into();
         VertexArgs {
             model: model.into(),
-            tint: tint.map_or([1.0; 4].into(), |t| {
-                let (r, g, b, a) = t.0.into_components();
-                [r, g, b, a].into()
-            }),
+            tint: tint.map_or([1.0; 4].into(), |t| t.0.into_pod()),
+            fade: fade.map_or(1.0, |f| f.fade),
+            object_id: 0,
         }
     }
+
+    /// Identify the drawn object as `entity` in object id attachments.
+    ///
+    /// The id is the index of the entity plus one, leaving `0` for pixels without an object.
+    #[inline]
+    pub fn with_object_id(mut self, entity: Entity) -> Self {
+        self.object_id = ObjectId::from_entity(entity).object_id;
+        self
+    }
 }
 
 impl AsVertex for VertexArgs {
     fn vertex() -> VertexFormat {
-        VertexFormat::new((Model::vertex(), Tint::vertex()))
+        VertexFormat::new((
+            Model::vertex(),
+            Tint::vertex(),
+            Fade::vertex(),
+            ObjectId::vertex(),
+        ))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, AsStd140)]
+#[repr(C, align(4))]
+pub struct ObjectId {
+    pub object_id: u32,
+}
+
+impl ObjectId {
+    /// Id written for `entity`, `0` being left for pixels without an object.
+    pub fn from_entity(entity: Entity) -> Self {
+        ObjectId {
+            object_id: entity.id().wrapping_add(1),
+        }
     }
+
+    /// Index of the entity behind the id, `None` for pixels without an object.
+    pub fn entity_id(self) -> Option<u32> {
+        self.object_id.checked_sub(1)
+    }
+}
+
+impl AsAttribute for ObjectId {
+    const NAME: &'static str = "object_id";
+    const FORMAT: Format = Format::R32Uint;
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, AsStd140)]
@@ -92,12 +200,20 @@ impl AsAttribute for JointsOffset {
 pub struct SkinnedVertexArgs {
     pub model: mat4,
     pub tint: vec4,
+    pub fade: float,
     pub joints_offset: u32,
+    pub object_id: u32,
 }
 
 impl AsVertex for SkinnedVertexArgs {
     fn vertex() -> VertexFormat {
-        VertexFormat::new((Model::vertex(), Tint::vertex(), JointsOffset::vertex()))
+        VertexFormat::new((
+            Model::vertex(),
+            Tint::vertex(),
+            Fade::vertex(),
+            JointsOffset::vertex(),
+            ObjectId::vertex(),
+        ))
     }
 }
 
@@ -106,16 +222,60 @@ impl SkinnedVertexArgs {
     pub fn from_object_data(
         transform: &Transform,
         tint: Option<&TintComponent>,
+        fade: Option<&DitheredFadeComponent>,
         joints_offset: u32,
     ) -> Self {
         let model: [[f32; 4]; 4] = convert::<_, Matrix4<f32>>(*transform.global_matrix()).into();
         SkinnedVertexArgs {
             model: model.into(),
-            tint: tint.map_or([1.0; 4].into(), |t| {
-                let (r, g, b, a) = t.0.into_components();
-                [r, g, b, a].into()
-            }),
+            tint: tint.map_or([1.0; 4].into(), |t| t.0.into_pod()),
+            fade: fade.map_or(1.0, |f| f.fade),
             joints_offset,
+            object_id: 0,
+        }
+    }
+
+    /// Identify the drawn object as `entity` in object id attachments.
+    #[inline]
+    pub fn with_object_id(mut self, entity: Entity) -> Self {
+        self.object_id = ObjectId::from_entity(entity).object_id;
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, AsStd140)]
+#[repr(C, align(4))]
+pub struct OutlineThickness {
+    pub thickness: float,
+}
+
+impl AsAttribute for OutlineThickness {
+    const NAME: &'static str = "outline_thickness";
+    const FORMAT: Format = Format::R32Sfloat;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[repr(C, packed)]
+pub struct OutlineArgs {
+    pub model: mat4,
+    pub color: vec4,
+    pub thickness: float,
+}
+
+impl AsVertex for OutlineArgs {
+    fn vertex() -> VertexFormat {
+        VertexFormat::new((Model::vertex(), Color::vertex(), OutlineThickness::vertex()))
+    }
+}
+
+impl OutlineArgs {
+    #[inline]
+    pub fn from_object_data(transform: &Transform, outline: &OutlineComponent) -> Self {
+        let model: [[f32; 4]; 4] = convert::<_, Matrix4<f32>>(*transform.global_matrix()).into();
+        OutlineArgs {
+            model: model.into(),
+            color: outline.color.into_pod(),
+            thickness: outline.thickness,
         }
     }
 }
@@ -125,6 +285,8 @@ pub struct PointLight {
     pub position: vec3,
     pub color: vec3,
     pub intensity: float,
+    pub radius: float,
+    pub smoothness: float,
 }
 
 #[derive(Clone, Copy, Debug, AsStd140)]
@@ -140,9 +302,19 @@ pub struct SpotLight {
     pub color: vec3,
     pub direction: vec3,
     pub angle: float,
+    pub inner_angle: float,
     pub intensity: float,
     pub range: float,
-    pub smoothness: float,
+}
+
+/// Rectangle centered on `position`, spanning twice `half_width` and `half_height`.
+#[derive(Clone, Copy, Debug, AsStd140)]
+pub struct AreaLight {
+    pub position: vec3,
+    pub color: vec3,
+    pub half_width: vec3,
+    pub intensity: float,
+    pub half_height: vec3,
 }
 
 #[derive(Clone, Copy, Debug, AsStd140)]
@@ -152,20 +324,46 @@ pub struct Environment {
     pub point_light_count: int,
     pub directional_light_count: int,
     pub spot_light_count: int,
+    pub area_light_count: int,
+    pub fog_color: vec3,
+    pub fog_start: float,
+    pub fog_density: float,
+    /// Size of the grid of light tiles, `0` when point lights are not culled per tile.
+    pub light_tile_columns: int,
+    pub light_tile_rows: int,
+    pub lights_per_tile: int,
+    /// Scale of the lit color, from the `Exposure` of the camera.
+    pub exposure: float,
 }
 
 #[derive(Clone, Copy, Debug, AsStd140)]
 #[repr(C, align(16))]
 pub struct Material {
     pub uv_offset: TextureOffset,
+    pub uv_scroll: vec2,
+    pub uv_scale: vec2,
+    pub base_color: vec4,
     pub alpha_cutoff: float,
+    pub emission_factor: vec3,
+    pub emission_intensity: float,
+    pub metallic_factor: float,
+    pub roughness_factor: float,
+    pub double_sided: boolean,
 }
 
 impl Material {
     pub fn from_material(mat: &mtl::Material) -> Self {
         Material {
             uv_offset: TextureOffset::from_offset(&mat.uv_offset),
+            uv_scroll: mat.uv_scroll.into(),
+            uv_scale: mat.uv_scale.into(),
+            base_color: mat.base_color.into_pod(),
             alpha_cutoff: mat.alpha_cutoff,
+            emission_factor: mat.emission_factor.into_pod(),
+            emission_intensity: mat.emission_intensity,
+            metallic_factor: mat.metallic_factor,
+            roughness_factor: mat.roughness_factor,
+            double_sided: mat.double_sided.into(),
         }
     }
 }
@@ -179,6 +377,7 @@ pub struct SpriteArgs {
     pub u_offset: vec2,
     pub v_offset: vec2,
     pub depth: float,
+    pub tint: vec4,
 }
 
 impl AsVertex for SpriteArgs {
@@ -190,6 +389,7 @@ impl AsVertex for SpriteArgs {
             (Format::Rg32Sfloat, "u_offset"),
             (Format::Rg32Sfloat, "v_offset"),
             (Format::R32Sfloat, "depth"),
+            (Format::Rgba32Sfloat, "tint"),
         ))
     }
 }
@@ -200,6 +400,7 @@ impl SpriteArgs {
         sprite_storage: &'a AssetStorage<SpriteSheet>,
         sprite_render: &SpriteRender,
         transform: &Transform,
+        tint: Option<&TintComponent>,
     ) -> Option<(Self, &'a Handle<Texture>)> {
         let sprite_sheet = sprite_storage.get(&sprite_render.sprite_sheet)?;
         if !tex_storage.contains(&sprite_sheet.texture) {
@@ -221,12 +422,17 @@ impl SpriteArgs {
                 u_offset: [sprite.tex_coords.left, sprite.tex_coords.right].into(),
                 v_offset: [sprite.tex_coords.top, sprite.tex_coords.bottom].into(),
                 depth: pos.z,
+                tint: tint.map_or([1.0; 4].into(), |t| t.0.into_pod()),
             },
             &sprite_sheet.texture,
         ))
     }
 }
 
+/// Conversion into the representation uploaded to shaders.
+///
+/// sRGB colors are converted to linear space, where shaders do their lighting and
+/// blending math, so that the sRGB framebuffer encodes them back to the same color.
 pub trait IntoPod<T> {
     fn into_pod(self) -> T;
 }
@@ -247,28 +453,76 @@ impl IntoPod<vec3> for amethyst_core::math::Vector3<f32> {
 
 impl IntoPod<vec3> for palette::Srgb {
     fn into_pod(self) -> vec3 {
-        let (r, g, b) = self.into_components();
+        let (r, g, b) = self.into_linear().into_components();
         [r, g, b].into()
     }
 }
 
 impl IntoPod<[f32; 3]> for palette::Srgb {
     fn into_pod(self) -> [f32; 3] {
-        let (r, g, b) = self.into_components();
+        let (r, g, b) = self.into_linear().into_components();
         [r, g, b]
     }
 }
 
 impl IntoPod<vec4> for palette::Srgba {
     fn into_pod(self) -> vec4 {
-        let (r, g, b, a) = self.into_components();
+        let (r, g, b, a) = self.into_linear().into_components();
         [r, g, b, a].into()
     }
 }
 
 impl IntoPod<[f32; 4]> for palette::Srgba {
     fn into_pod(self) -> [f32; 4] {
-        let (r, g, b, a) = self.into_components();
+        let (r, g, b, a) = self.into_linear().into_components();
         [r, g, b, a]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: [f32; 4], expected: [f32; 4]) {
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            assert!((a - e).abs() < 1e-4, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn srgb_colors_are_uploaded_linear() {
+        let color: [f32; 3] = palette::Srgb::new(0.5, 1.0, 0.0).into_pod();
+        assert_close(
+            [color[0], color[1], color[2], 1.0],
+            [0.214_041, 1.0, 0.0, 1.0],
+        );
+
+        // Alpha is not gamma encoded.
+        let color: [f32; 4] = palette::Srgba::new(0.5, 0.5, 0.5, 0.5).into_pod();
+        assert_close(color, [0.214_041, 0.214_041, 0.214_041, 0.5]);
+    }
+
+    #[test]
+    fn instance_args_match_their_vertex_stride() {
+        assert_eq!(
+            std::mem::size_of::<VertexArgs>(),
+            VertexArgs::vertex().stride as usize
+        );
+        assert_eq!(
+            std::mem::size_of::<SkinnedVertexArgs>(),
+            SkinnedVertexArgs::vertex().stride as usize
+        );
+    }
+
+    #[test]
+    fn object_ids_leave_zero_for_no_object() {
+        use amethyst_core::ecs::{Builder, World};
+
+        let mut world = World::new();
+        let entity = world.create_entity().build();
+        let id = ObjectId::from_entity(entity);
+        assert_eq!(id.object_id, entity.id() + 1);
+        assert_eq!(id.entity_id(), Some(entity.id()));
+        assert_eq!(ObjectId { object_id: 0 }.entity_id(), None);
+    }
+}
@@ -0,0 +1,279 @@
+//! Offscreen color targets sampled as textures.
+//!
+//! A `RenderTarget` reserves a `Handle<Texture>` that materials use like any loaded
+//! texture, to show mirrors, monitors or portals. The graph creator sizes an image with
+//! `RenderTarget::kind`, draws a camera into it, and hands it over to the texture with
+//! a `RenderTargetTextureDesc` group added to the node drawing those materials:
+//!
+//! ```ignore
+//! let target = targets.get("monitor").unwrap();
+//! let monitor = graph_builder.create_image(target.kind(), 1, Format::Rgba8Srgb, Some(clear));
+//! let monitor_depth = graph_builder.create_image(target.kind(), 1, depth_format, Some(clear_depth));
+//! graph_builder.add_node(
+//!     SubpassBuilder::new()
+//!         .with_group(DrawShadedDesc::new().with_camera(monitor_camera).builder())
+//!         .with_color(monitor)
+//!         .with_depth_stencil(monitor_depth)
+//!         .into_pass(),
+//! );
+//! graph_builder.add_node(
+//!     SubpassBuilder::new()
+//!         .with_group(
+//!             RenderTargetTextureDesc::new(target.texture().clone())
+//!                 .builder()
+//!                 .with_image(monitor),
+//!         )
+//!         .with_group(DrawShadedDesc::new().builder())
+//!         .with_color(color)
+//!         .with_depth_stencil(depth)
+//!         .into_pass(),
+//! );
+//! ```
+//!
+//! Graph creators keep a copy of the `RenderTargets` resource and rebuild the graph
+//! when it changes, so resolution changes made through `RenderTargets::set_resolution`
+//! resize the images.
+
+use crate::{
+    types::{Backend, RenderTargetView, Texture},
+    util,
+};
+use amethyst_assets::{AssetStorage, Handle};
+use amethyst_core::ecs::{Resources, SystemData, Write};
+use fnv::FnvHashMap;
+use rendy::{
+    command::{QueueId, RenderPassEncoder},
+    factory::Factory,
+    graph::{
+        render::{PrepareResult, RenderGroup, RenderGroupDesc},
+        GraphContext, ImageAccess, NodeBuffer, NodeImage,
+    },
+    hal::{
+        self,
+        image::{Filter, Kind, SamplerInfo, WrapMode},
+    },
+};
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+/// Offscreen color target whose image is sampled through a `Texture` handle.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderTarget {
+    texture: Handle<Texture>,
+    width: u32,
+    height: u32,
+}
+
+impl RenderTarget {
+    /// Reserve the texture of a `width` by `height` target.
+    ///
+    /// Materials using the texture are skipped until the render graph draws the target.
+    pub fn new(storage: &mut AssetStorage<Texture>, width: u32, height: u32) -> Self {
+        RenderTarget {
+            texture: storage.insert(Texture::render_target()),
+            width: width.max(1),
+            height: height.max(1),
+        }
+    }
+
+    /// Texture sampling the target, for the texture slots of a `Material`.
+    pub fn texture(&self) -> &Handle<Texture> {
+        &self.texture
+    }
+
+    /// Width of the target in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the target in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Kind of the graph image to create for the target.
+    pub fn kind(&self) -> Kind {
+        Kind::D2(self.width, self.height, 1, 1)
+    }
+
+    /// Change the resolution, applied when the render graph is next rebuilt.
+    pub fn set_resolution(&mut self, width: u32, height: u32) {
+        self.width = width.max(1);
+        self.height = height.max(1);
+    }
+}
+
+/// Render targets by name, read by graph creators to size their images.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RenderTargets {
+    targets: FnvHashMap<String, RenderTarget>,
+}
+
+impl RenderTargets {
+    /// Register a `width` by `height` target under `name`, replacing any target
+    /// of that name, and return its texture.
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        storage: &mut AssetStorage<Texture>,
+        width: u32,
+        height: u32,
+    ) -> Handle<Texture> {
+        let target = RenderTarget::new(storage, width, height);
+        let texture = target.texture.clone();
+        self.targets.insert(name.into(), target);
+        texture
+    }
+
+    /// Target registered under `name`.
+    pub fn get(&self, name: &str) -> Option<&RenderTarget> {
+        self.targets.get(name)
+    }
+
+    /// Change the resolution of the target registered under `name`.
+    ///
+    /// Returns `false` when there is no such target.
+    pub fn set_resolution(&mut self, name: &str, width: u32, height: u32) -> bool {
+        self.targets
+            .get_mut(name)
+            .map(|target| target.set_resolution(width, height))
+            .is_some()
+    }
+
+    /// Remove the target registered under `name`.
+    pub fn remove(&mut self, name: &str) -> Option<RenderTarget> {
+        self.targets.remove(name)
+    }
+
+    /// Iterate over the registered targets and their names.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &RenderTarget)> {
+        self.targets
+            .iter()
+            .map(|(name, target)| (name.as_str(), target))
+    }
+}
+
+/// Hand the image passed to the group builder over to a render target texture.
+///
+/// Draws nothing. Added to the node drawing the materials that sample the texture,
+/// so that the render graph finishes drawing the image and makes it readable first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderTargetTextureDesc {
+    texture: Handle<Texture>,
+    filter: Filter,
+}
+
+impl RenderTargetTextureDesc {
+    /// Replace the texture behind `texture`, usually `RenderTarget::texture`.
+    pub fn new(texture: Handle<Texture>) -> Self {
+        RenderTargetTextureDesc {
+            texture,
+            filter: Filter::Linear,
+        }
+    }
+
+    /// Filter used to sample the image, `Filter::Linear` by default.
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+}
+
+impl<B: Backend> RenderGroupDesc<B, Resources> for RenderTargetTextureDesc {
+    fn images(&self) -> Vec<ImageAccess> {
+        vec![util::sampled_image_access()]
+    }
+
+    fn depth(&self) -> bool {
+        false
+    }
+
+    fn build(
+        self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        _queue: QueueId,
+        aux: &Resources,
+        _framebuffer_width: u32,
+        _framebuffer_height: u32,
+        _subpass: hal::pass::Subpass<'_, B>,
+        _buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<Box<dyn RenderGroup<B, Resources>>, failure::Error> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("build");
+
+        let view = util::node_image_view(ctx, factory, &images[0])?;
+        let sampler = factory.get_sampler(SamplerInfo::new(self.filter, WrapMode::Clamp))?;
+
+        let mut storage = <Write<'_, AssetStorage<Texture>>>::fetch(aux);
+        if !storage.contains(&self.texture) {
+            return Err(failure::format_err!(
+                "Render target texture {:?} is not in the texture storage",
+                self.texture
+            ));
+        }
+        // Materials sampling the previous image are rebuilt along with the graph.
+        storage.replace(
+            &self.texture,
+            B::wrap_render_target(RenderTargetView::new(view, sampler)),
+        );
+
+        Ok(Box::new(RenderTargetTexture))
+    }
+}
+
+/// Render group keeping a render target image readable by the node it belongs to.
+#[derive(Debug)]
+pub struct RenderTargetTexture;
+
+impl<B: Backend> RenderGroup<B, Resources> for RenderTargetTexture {
+    fn prepare(
+        &mut self,
+        _factory: &Factory<B>,
+        _queue: QueueId,
+        _index: usize,
+        _subpass: hal::pass::Subpass<'_, B>,
+        _resources: &Resources,
+    ) -> PrepareResult {
+        PrepareResult::DrawReuse
+    }
+
+    fn draw_inline(
+        &mut self,
+        _encoder: RenderPassEncoder<'_, B>,
+        _index: usize,
+        _subpass: hal::pass::Subpass<'_, B>,
+        _resources: &Resources,
+    ) {
+    }
+
+    fn dispose(self: Box<Self>, _factory: &mut Factory<B>, _aux: &Resources) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolution_changes_are_tracked() {
+        let mut storage = AssetStorage::<Texture>::new();
+        let mut targets = RenderTargets::default();
+        let texture = targets.register("monitor", &mut storage, 256, 0);
+        let before = targets.clone();
+
+        let target = targets.get("monitor").unwrap();
+        assert_eq!(target.texture(), &texture);
+        assert_eq!(target.kind(), Kind::D2(256, 1, 1, 1));
+        assert!(storage.contains(&texture));
+
+        assert!(targets.set_resolution("monitor", 512, 512));
+        assert!(!targets.set_resolution("mirror", 512, 512));
+        assert_ne!(targets, before);
+        assert_eq!(
+            targets.get("monitor").unwrap().kind(),
+            Kind::D2(512, 512, 1, 1)
+        );
+    }
+}
@@ -1,11 +1,23 @@
 //! `amethyst` rendering ecs resources
 //!
 
-use amethyst_assets::PrefabData;
-use amethyst_core::ecs::{Component, DenseVecStorage, Entity, Write};
+use crate::{camera::Orthographic, pod::IntoPod, types::Texture};
+use amethyst_assets::{Handle, PrefabData};
+use amethyst_core::{
+    ecs::{Component, DenseVecStorage, Entity, Write},
+    math::{Matrix4, Point3, Vector3},
+};
 use amethyst_error::Error;
+use rendy::hal::{
+    command::{ClearDepthStencil, ClearValue},
+    pso::{Comparison, DepthTest},
+    window::PresentMode,
+};
 
 /// The ambient color of a scene
+///
+/// Added to the lighting of every lit surface, regardless of the lights around it.
+/// Defaults to black, so surfaces not hit by any light stay dark.
 #[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
 pub struct AmbientColor(#[serde(with = "crate::serde_shim::srgba")] pub palette::Srgba);
 
@@ -31,6 +43,63 @@ impl<'a> PrefabData<'a> for AmbientColor {
     }
 }
 
+/// Distance fog of a scene, blending lit surfaces into its color the further they are
+/// from the camera.
+///
+/// The fog starts `start` units away from the camera and thickens exponentially with
+/// `density`. Without this resource, or with a `density` of zero, no fog is applied.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Fog {
+    /// Color distant surfaces fade into, usually matching the clear color or sky.
+    #[serde(with = "crate::serde_shim::srgb")]
+    pub color: palette::Srgb,
+    /// Distance from the camera at which the fog starts.
+    pub start: f32,
+    /// How quickly the fog thickens past `start`, per unit of distance.
+    pub density: f32,
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Fog {
+            color: palette::Srgb::new(0.5, 0.5, 0.5),
+            start: 0.0,
+            density: 0.0,
+        }
+    }
+}
+
+impl<'a> PrefabData<'a> for Fog {
+    type SystemData = Write<'a, Fog>;
+    type Result = ();
+
+    fn add_to_entity(
+        &self,
+        _: Entity,
+        fog: &mut Self::SystemData,
+        _: &[Entity],
+        _: &[Entity],
+    ) -> Result<(), Error> {
+        **fog = self.clone();
+        Ok(())
+    }
+}
+
+/// Image based lighting of a scene, added by the PBR pass on top of the analytic lights.
+///
+/// The textures are used as-is, so they have to be prefiltered offline or by the application.
+/// Without this resource no image based lighting is applied.
+#[derive(Clone, Debug)]
+pub struct EnvironmentMap {
+    /// Cubemap of the diffuse irradiance arriving from each direction.
+    pub irradiance: Handle<Texture>,
+    /// Cubemap of the specular radiance, prefiltered for increasing roughness along its mip levels.
+    pub specular: Handle<Texture>,
+    /// Split sum BRDF lookup table, indexed by the cosine of the view angle and the roughness.
+    pub brdf_lut: Handle<Texture>,
+}
+
 /// A single object tinting applied in multiplicative mode (modulation)
 #[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
 pub struct Tint(#[serde(with = "crate::serde_shim::srgba")] pub palette::Srgba);
@@ -45,3 +114,583 @@ impl Into<[f32; 4]> for Tint {
         [r, g, b, a]
     }
 }
+
+/// Fades an opaque object out by discarding a screen space dither pattern of its fragments.
+///
+/// Unlike blending, this keeps depth writes and needs no sorting, which makes it suitable
+/// for level of detail crossfades. Transparent passes ignore it.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct DitheredFade {
+    /// Visible fraction of the fragments, from `0.0` (invisible) to `1.0` (fully drawn).
+    pub fade: f32,
+}
+
+impl Default for DitheredFade {
+    fn default() -> Self {
+        DitheredFade { fade: 1.0 }
+    }
+}
+
+impl Component for DitheredFade {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Draws a flat colored outline around the entity's mesh using `DrawOutlineDesc`.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Outline {
+    /// Color of the outline.
+    #[serde(with = "crate::serde_shim::srgba")]
+    pub color: palette::Srgba,
+    /// Distance the outline extends past the surface, in model space units.
+    pub thickness: f32,
+}
+
+impl Component for Outline {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Area covered by the shadow map of the primary directional light.
+///
+/// The shadow map is a depth image of the render graph, written by `DrawShadowDepthDesc`
+/// and sampled by passes built with `with_shadow_map`. Shadows are only cast while this
+/// resource exists; it decides where the light's view is placed each frame.
+/// The primary light is the one marked with `PrimaryDirectionalLight`, or else the first
+/// entity with a directional `Light`.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ShadowMap {
+    /// Half the width and height of the square covered around the camera, in world units.
+    pub half_extent: f32,
+    /// Depth of the covered volume along the light direction, centered on the camera.
+    pub depth: f32,
+    /// Depth offset avoiding self-shadowing acne.
+    pub bias: f32,
+}
+
+impl Default for ShadowMap {
+    fn default() -> Self {
+        ShadowMap {
+            half_extent: 20.0,
+            depth: 100.0,
+            bias: 0.005,
+        }
+    }
+}
+
+impl ShadowMap {
+    /// Projection and view of a light shining along `direction`, centered on `center`.
+    pub fn light_view_proj(&self, direction: Vector3<f32>, center: Vector3<f32>) -> Matrix4<f32> {
+        self.cascade_view_proj(direction, center, self.half_extent)
+    }
+
+    /// Like `light_view_proj`, covering a square `half_extent` units from `center`.
+    ///
+    /// The covered depth grows to enclose that square when it is wider than `depth`.
+    pub fn cascade_view_proj(
+        &self,
+        direction: Vector3<f32>,
+        center: Vector3<f32>,
+        half_extent: f32,
+    ) -> Matrix4<f32> {
+        let depth = self.depth.max(half_extent * 2.0);
+        let direction = direction
+            .try_normalize(std::f32::EPSILON)
+            .unwrap_or_else(|| -Vector3::y());
+        let up = if direction.y.abs() > 0.99 {
+            Vector3::z()
+        } else {
+            Vector3::y()
+        };
+        let target = Point3::from(center);
+        let eye = target - direction * (depth * 0.5);
+        let view = Matrix4::look_at_rh(&eye, &target, &up);
+        let projection = Orthographic::new(
+            -half_extent,
+            half_extent,
+            -half_extent,
+            half_extent,
+            0.0,
+            depth,
+        );
+        projection.as_matrix() * view
+    }
+}
+
+/// Largest number of cascades of the directional shadow map.
+pub const MAX_SHADOW_CASCADES: usize = 4;
+
+/// Cascades of the shadow map cast by the primary directional light, read every frame.
+///
+/// Each cascade covers the part of the camera's view between the previous split distance
+/// and its own, and takes an equal share of the shadow map width, nearest on the left.
+/// A single cascade covers the `ShadowMap` square around the camera instead.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ShadowSettings {
+    /// View space distances from the camera at which each cascade ends, nearest first.
+    /// The number of cascades is the number of splits, from 1 to `MAX_SHADOW_CASCADES`.
+    pub cascade_splits: Vec<f32>,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        ShadowSettings {
+            cascade_splits: vec![50.0],
+        }
+    }
+}
+
+impl ShadowSettings {
+    /// Number of cascades rendered into the shadow map.
+    pub fn cascade_count(&self) -> usize {
+        self.cascade_splits.len().max(1).min(MAX_SHADOW_CASCADES)
+    }
+}
+
+/// Bounding sphere of the part of a camera's view between the view space distances
+/// `near` and `far`, as its center in view space and its radius.
+///
+/// Returns `None` when `projection` can not be inverted.
+pub fn view_slice_bounds(
+    projection: &Matrix4<f32>,
+    near: f32,
+    far: f32,
+) -> Option<(Point3<f32>, f32)> {
+    let inverse = projection.try_inverse()?;
+    let corners = [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)]
+        .iter()
+        .flat_map(|&(x, y)| {
+            // Edges of the view volume are straight, so points at other distances
+            // are interpolated between the near and far plane.
+            let front = inverse.transform_point(&Point3::new(x, y, 0.0));
+            let back = inverse.transform_point(&Point3::new(x, y, 1.0));
+            let at = move |distance: f32| {
+                let t = (distance + front.z) / (front.z - back.z);
+                front + (back - front) * t
+            };
+            vec![at(near), at(far)]
+        })
+        .collect::<Vec<_>>();
+
+    let center = Point3::from(
+        corners
+            .iter()
+            .fold(Vector3::zeros(), |sum, corner| sum + corner.coords)
+            / corners.len() as f32,
+    );
+    let radius = corners
+        .iter()
+        .map(|corner| (corner - center).norm())
+        .fold(0.0, f32::max);
+    Some((center, radius))
+}
+
+/// Curve mapping high dynamic range colors into the displayable range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum TonemapOperator {
+    /// Clip every channel above `1.0`.
+    Clamp,
+    /// `color / (color + 1)`, keeping hues but washing out highlights.
+    Reinhard,
+    /// Approximation of the ACES filmic curve, with a contrasty toe and soft shoulder.
+    AcesFilmic,
+}
+
+/// Settings of the tonemapping done by `DrawTonemapDesc`, read every frame.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct TonemapSettings {
+    /// Curve applied to the exposed color.
+    pub operator: TonemapOperator,
+    /// Linear scale applied to the color before the curve.
+    pub exposure: f32,
+}
+
+impl Default for TonemapSettings {
+    fn default() -> Self {
+        TonemapSettings {
+            operator: TonemapOperator::AcesFilmic,
+            exposure: 1.0,
+        }
+    }
+}
+
+/// Settings of the bloom chain made of `DrawBloomExtractDesc`, `DrawBloomBlurDesc`
+/// and `DrawBloomDesc`, read every frame.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct BloomSettings {
+    /// Brightness above which pixels bloom, in linear color.
+    pub threshold: f32,
+    /// Width of the blur in texels of the blurred image, at most 16.
+    pub radius: f32,
+    /// Scale of the bloom added back onto the scene.
+    pub intensity: f32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        BloomSettings {
+            threshold: 1.0,
+            radius: 4.0,
+            intensity: 0.5,
+        }
+    }
+}
+
+/// Values the color and depth images of the render graph are cleared to every frame.
+///
+/// Read by graph creators when creating those images. Clear values are baked into the
+/// render passes, so changing this resource rebuilds the render graph.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ClearColor {
+    /// Background color, converted like every other color handed to the shaders.
+    #[serde(with = "crate::serde_shim::srgba")]
+    pub color: palette::Srgba,
+    /// Depth the depth image is cleared to, the far plane at `1.0`.
+    pub depth: f32,
+}
+
+impl Default for ClearColor {
+    fn default() -> Self {
+        ClearColor {
+            color: palette::Srgba::new(0.0, 0.0, 0.0, 1.0),
+            depth: 1.0,
+        }
+    }
+}
+
+impl ClearColor {
+    /// Clear value of a color image.
+    pub fn color_value(&self) -> ClearValue {
+        let color: [f32; 4] = self.color.into_pod();
+        ClearValue::Color(color.into())
+    }
+
+    /// Clear value of a depth-stencil image stored as `mode`, with the stencil cleared to zero.
+    pub fn depth_value(&self, mode: DepthMode) -> ClearValue {
+        ClearValue::DepthStencil(ClearDepthStencil(mode.depth(self.depth), 0))
+    }
+}
+
+/// How depth is stored in the depth image shared by the passes drawing through the camera.
+///
+/// The mode applies to the whole render graph, so that every pass sharing a depth image
+/// agrees on it: the 3D, sprite, outline, skybox and debug line passes flip their depth
+/// tests, and the projection handed to their shaders is flipped along. Graph creators
+/// clear the depth image with `ClearColor::depth_value`. Changing this resource rebuilds
+/// the render graph.
+///
+/// Shadow maps have depth images of their own and always use `DepthMode::Standard`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum DepthMode {
+    /// The near plane is at `0.0` and the far plane at `1.0`.
+    Standard,
+    /// The near plane is at `1.0` and the far plane at `0.0`.
+    ///
+    /// Floating point depth images then keep their precision far from the camera,
+    /// for large view distances.
+    Reversed,
+}
+
+impl Default for DepthMode {
+    fn default() -> Self {
+        DepthMode::Standard
+    }
+}
+
+impl DepthMode {
+    /// Value stored for `depth`, given with the far plane at `1.0`.
+    pub fn depth(self, depth: f32) -> f32 {
+        match self {
+            DepthMode::Standard => depth,
+            DepthMode::Reversed => 1.0 - depth,
+        }
+    }
+
+    /// Comparison to use in place of `fun`, written for `DepthMode::Standard`.
+    pub fn comparison(self, fun: Comparison) -> Comparison {
+        match (self, fun) {
+            (DepthMode::Standard, fun) => fun,
+            (DepthMode::Reversed, Comparison::Less) => Comparison::Greater,
+            (DepthMode::Reversed, Comparison::LessEqual) => Comparison::GreaterEqual,
+            (DepthMode::Reversed, Comparison::Greater) => Comparison::Less,
+            (DepthMode::Reversed, Comparison::GreaterEqual) => Comparison::LessEqual,
+            (DepthMode::Reversed, fun) => fun,
+        }
+    }
+
+    /// Depth test to use in place of `test`, written for `DepthMode::Standard`.
+    pub fn depth_test(self, test: DepthTest) -> DepthTest {
+        match test {
+            DepthTest::On { fun, write } => DepthTest::On {
+                fun: self.comparison(fun),
+                write,
+            },
+            DepthTest::Off => DepthTest::Off,
+        }
+    }
+
+    /// Projection to hand to the shaders in place of `projection`, a camera projection
+    /// mapping the far plane to `1.0`.
+    pub fn projection(self, projection: &Matrix4<f32>) -> Matrix4<f32> {
+        match self {
+            DepthMode::Standard => *projection,
+            #[rustfmt::skip]
+            DepthMode::Reversed => Matrix4::new(
+                1.0, 0.0, 0.0, 0.0,
+                0.0, 1.0, 0.0, 0.0,
+                0.0, 0.0, -1.0, 1.0,
+                0.0, 0.0, 0.0, 1.0,
+            ) * projection,
+        }
+    }
+}
+
+/// Lights of one kind found in the world and uploaded to the shaders.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LightCount {
+    /// Enabled lights found in the world.
+    pub gathered: usize,
+    /// Lights uploaded to the shaders, at most the light limit of the pass.
+    pub uploaded: usize,
+}
+
+impl LightCount {
+    /// Lights dropped for exceeding the light limit of the pass.
+    pub fn dropped(&self) -> usize {
+        self.gathered.saturating_sub(self.uploaded)
+    }
+}
+
+/// Lights gathered by the lit 3D passes during the last frame, to diagnose lights
+/// dropped past `LightLimits`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LightStats {
+    /// Point lights.
+    pub point: LightCount,
+    /// Directional lights.
+    pub directional: LightCount,
+    /// Spot lights.
+    pub spot: LightCount,
+    /// Area lights.
+    pub area: LightCount,
+}
+
+/// Draws issued by the 3D passes during the last frame, to check how well meshes are
+/// batched into instanced draws.
+///
+/// Counted while preparing each frame, even when recorded command buffers are reused.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    /// Draw calls.
+    pub draw_calls: usize,
+    /// Instances drawn by all draw calls.
+    pub instances: usize,
+    /// Triangles drawn, counting every mesh as a triangle list.
+    pub triangles: usize,
+}
+
+impl RenderStats {
+    /// Count a draw of `instances` instances of a mesh with `mesh_len` vertices, or
+    /// indices if it is indexed.
+    pub fn record_draw(&mut self, mesh_len: u32, instances: u32) {
+        self.draw_calls += 1;
+        self.instances += instances as usize;
+        self.triangles += (mesh_len / 3) as usize * instances as usize;
+    }
+}
+
+pub use amethyst_window::{AspectRatioLock, RequireHardwareGpu, SampleCount, SrgbSurface, Vsync};
+
+/// Maps a `Vsync` setting to swapchain present modes.
+///
+/// Changing the `Vsync` resource rebuilds the render graph, recreating the swapchain with
+/// the present mode graph creators pick with `util::select_present_mode`.
+pub trait VsyncPresentModes {
+    /// Present modes matching this setting, from most to least preferred.
+    fn present_modes(self) -> &'static [PresentMode];
+
+    /// Priority of a present mode for this setting, higher is better.
+    /// Suitable for `PresentBuilder::with_present_modes_priority`.
+    fn present_mode_priority(self, mode: PresentMode) -> Option<usize>;
+
+    /// Select the present mode that will actually be applied out of those supported by the surface.
+    ///
+    /// Falls back to `Fifo`, which every surface supports, with a warning.
+    fn select_present_mode(self, supported: &[PresentMode]) -> PresentMode;
+}
+
+impl VsyncPresentModes for Vsync {
+    fn present_modes(self) -> &'static [PresentMode] {
+        if self.0 {
+            &[PresentMode::Fifo, PresentMode::Relaxed]
+        } else {
+            &[
+                PresentMode::Mailbox,
+                PresentMode::Immediate,
+                PresentMode::Relaxed,
+                PresentMode::Fifo,
+            ]
+        }
+    }
+
+    fn present_mode_priority(self, mode: PresentMode) -> Option<usize> {
+        let modes = self.present_modes();
+        modes
+            .iter()
+            .position(|&m| m == mode)
+            .map(|index| modes.len() - index)
+    }
+
+    fn select_present_mode(self, supported: &[PresentMode]) -> PresentMode {
+        let preferred = self.present_modes()[0];
+        let mode = self
+            .present_modes()
+            .iter()
+            .cloned()
+            .find(|mode| supported.contains(mode))
+            .unwrap_or(PresentMode::Fifo);
+        if mode == preferred {
+            log::info!("Vsync {}, using present mode {:?}", self.0, mode);
+        } else {
+            log::warn!(
+                "Present mode {:?} is not supported, using {:?} instead",
+                preferred,
+                mode
+            );
+        }
+        mode
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn light_count_reports_dropped_lights() {
+        let count = LightCount {
+            gathered: 142,
+            uploaded: 128,
+        };
+        assert_eq!(count.dropped(), 14);
+        assert_eq!(LightCount::default().dropped(), 0);
+    }
+
+    #[test]
+    fn render_stats_count_instanced_triangles() {
+        let mut stats = RenderStats::default();
+        stats.record_draw(36, 10);
+        stats.record_draw(6, 1);
+        assert_eq!(stats.draw_calls, 2);
+        assert_eq!(stats.instances, 11);
+        assert_eq!(stats.triangles, 122);
+    }
+
+    #[test]
+    fn vsync_selects_fifo() {
+        let supported = [
+            PresentMode::Immediate,
+            PresentMode::Mailbox,
+            PresentMode::Fifo,
+        ];
+        assert_eq!(
+            Vsync(true).select_present_mode(&supported),
+            PresentMode::Fifo
+        );
+    }
+
+    #[test]
+    fn vsync_off_prefers_mailbox() {
+        let supported = [
+            PresentMode::Immediate,
+            PresentMode::Mailbox,
+            PresentMode::Fifo,
+        ];
+        assert_eq!(
+            Vsync(false).select_present_mode(&supported),
+            PresentMode::Mailbox
+        );
+        assert_eq!(
+            Vsync(false).select_present_mode(&[PresentMode::Fifo, PresentMode::Immediate]),
+            PresentMode::Immediate
+        );
+    }
+
+    #[test]
+    fn vsync_off_falls_back_to_fifo() {
+        assert_eq!(
+            Vsync(false).select_present_mode(&[PresentMode::Fifo]),
+            PresentMode::Fifo
+        );
+    }
+
+    #[test]
+    fn shadow_map_centers_on_camera() {
+        let map = ShadowMap::default();
+        let center = Vector3::new(3.0, 1.0, -2.0);
+        let light_view_proj = map.light_view_proj(Vector3::new(0.3, -1.0, 0.2), center);
+
+        let clip = light_view_proj * Point3::from(center).to_homogeneous();
+        assert!(clip.x.abs() < 1e-4);
+        assert!(clip.y.abs() < 1e-4);
+        assert!((clip.z - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn view_slice_encloses_frustum_corners() {
+        let projection = Orthographic::new(-2.0, 2.0, -1.0, 1.0, 0.1, 100.0);
+        let (center, radius) = view_slice_bounds(projection.as_matrix(), 10.0, 20.0).unwrap();
+
+        assert!((center - Point3::new(0.0, 0.0, -15.0)).norm() < 1e-3);
+        let corner = Vector3::new(2.0, 1.0, 5.0).norm();
+        assert!((radius - corner).abs() < 1e-3);
+    }
+
+    #[test]
+    fn reversed_depth_swaps_near_and_far() {
+        let projection = Orthographic::new(-1.0, 1.0, -1.0, 1.0, 0.1, 100.0);
+        let depth = |mode: DepthMode, z: f32| {
+            let clip =
+                mode.projection(projection.as_matrix()) * Point3::new(0.0, 0.0, z).to_homogeneous();
+            clip.z / clip.w
+        };
+
+        assert!(depth(DepthMode::Standard, -0.1).abs() < 1e-4);
+        assert!((depth(DepthMode::Reversed, -0.1) - 1.0).abs() < 1e-4);
+        assert!(depth(DepthMode::Reversed, -100.0).abs() < 1e-4);
+        assert_eq!(DepthMode::Reversed.depth(1.0), 0.0);
+        assert_eq!(
+            DepthMode::Reversed.comparison(Comparison::LessEqual),
+            Comparison::GreaterEqual
+        );
+        assert_eq!(
+            DepthMode::Reversed.comparison(Comparison::Equal),
+            Comparison::Equal
+        );
+    }
+
+    #[test]
+    fn cascade_count_is_clamped() {
+        let mut settings = ShadowSettings::default();
+        assert_eq!(settings.cascade_count(), 1);
+        settings.cascade_splits = vec![5.0, 10.0, 20.0, 40.0, 80.0];
+        assert_eq!(settings.cascade_count(), MAX_SHADOW_CASCADES);
+        settings.cascade_splits.clear();
+        assert_eq!(settings.cascade_count(), 1);
+    }
+
+    #[test]
+    fn priority_follows_preference() {
+        let vsync = Vsync(false);
+        assert!(
+            vsync.present_mode_priority(PresentMode::Mailbox)
+                > vsync.present_mode_priority(PresentMode::Immediate)
+        );
+        assert_eq!(
+            Vsync(true).present_mode_priority(PresentMode::Mailbox),
+            None
+        );
+    }
+}
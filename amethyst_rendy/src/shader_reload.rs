@@ -0,0 +1,181 @@
+//! Runtime reloading of shaders, enabled with the `shader-hot-reload` feature in debug builds.
+//!
+//! Insert a `ShaderHotReload` resource listing SPIR-V files to load in place of the shaders
+//! embedded in the passes. `RenderingSystem` checks the files every frame and rebuilds the
+//! render graph when one of them changes, so every pipeline using the shader is created
+//! again from the new file:
+//!
+//! ```ignore
+//! let mut reload = ShaderHotReload::new();
+//! reload.watch(PbrPassDef::fragment_shader(), "amethyst_rendy/compiled/fragment/pbr.frag.spv");
+//! world.add_resource(reload);
+//! ```
+//!
+//! Files that fail to load are reported and the embedded shader is used instead. In release
+//! builds the embedded shaders are always used.
+
+use amethyst_core::ecs::{Read, Resources, SystemData};
+use fnv::FnvHashMap;
+use rendy::shader::{Shader, SpirvShader};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// First word of every SPIR-V module.
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+#[derive(Debug)]
+struct WatchedShader {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+/// Shaders loaded from files instead of the SPIR-V embedded in the passes.
+///
+/// Shaders are identified by the embedded `SpirvShader` they replace, such as the one
+/// returned by `Base3DPassDef::fragment_shader`.
+#[derive(Debug, Default)]
+pub struct ShaderHotReload {
+    shaders: FnvHashMap<usize, WatchedShader>,
+}
+
+impl ShaderHotReload {
+    /// Creates a `ShaderHotReload` watching no file.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Load the SPIR-V file at `path` in place of the embedded `shader`, with the same stage
+    /// and entry point, and reload it whenever the file changes.
+    pub fn watch(&mut self, shader: &'static SpirvShader, path: impl Into<PathBuf>) {
+        let path = path.into();
+        let modified = modified(&path);
+        self.shaders
+            .insert(key(shader), WatchedShader { path, modified });
+    }
+
+    /// Go back to the embedded `shader`.
+    pub fn unwatch(&mut self, shader: &'static SpirvShader) {
+        self.shaders.remove(&key(shader));
+    }
+
+    /// Check the watched files, returning whether any of them changed since the last check.
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        for watched in self.shaders.values_mut() {
+            let modified = modified(&watched.path);
+            if modified != watched.modified {
+                log::info!("Reloading shader `{}`", watched.path.display());
+                watched.modified = modified;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// The shader loaded from the file watched for `shader`, if there is one.
+    pub fn load(&self, shader: &SpirvShader) -> Option<SpirvShader> {
+        let watched = self.shaders.get(&key(shader))?;
+        match read_spirv(&watched.path) {
+            Ok(spirv) => Some(SpirvShader::new(spirv, shader.stage(), shader.entry())),
+            Err(e) => {
+                log::error!(
+                    "Failed to load shader `{}`, using the embedded one: {}",
+                    watched.path.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+}
+
+/// The shader loaded in place of `shader` by the `ShaderHotReload` resource, if any.
+/// Always `None` in release builds.
+pub fn reloaded_shader(res: &Resources, shader: &SpirvShader) -> Option<SpirvShader> {
+    if !cfg!(debug_assertions) {
+        return None;
+    }
+    <Option<Read<'_, ShaderHotReload>>>::fetch(res).and_then(|reload| reload.load(shader))
+}
+
+fn key(shader: &SpirvShader) -> usize {
+    shader as *const SpirvShader as usize
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+fn read_spirv(path: &Path) -> Result<Vec<u8>, String> {
+    let spirv = fs::read(path).map_err(|e| e.to_string())?;
+    if spirv.len() < 4 || spirv.len() % 4 != 0 {
+        return Err("not a SPIR-V module".to_string());
+    }
+    let magic = [spirv[0], spirv[1], spirv[2], spirv[3]];
+    if u32::from_le_bytes(magic) != SPIRV_MAGIC && u32::from_be_bytes(magic) != SPIRV_MAGIC {
+        return Err("not a SPIR-V module".to_string());
+    }
+    Ok(spirv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rendy::hal::pso::ShaderStageFlags;
+
+    fn spirv_file(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "amethyst_shader_reload_{}_{}.spv",
+            std::process::id(),
+            name
+        ));
+        fs::write(&path, SPIRV_MAGIC.to_le_bytes()).unwrap();
+        path
+    }
+
+    #[test]
+    fn watched_shader_is_loaded_from_its_file() {
+        let embedded: &'static SpirvShader = Box::leak(Box::new(SpirvShader::new(
+            Vec::new(),
+            ShaderStageFlags::FRAGMENT,
+            "main",
+        )));
+        let other = SpirvShader::new(Vec::new(), ShaderStageFlags::FRAGMENT, "main");
+        let path = spirv_file("load");
+
+        let mut reload = ShaderHotReload::new();
+        reload.watch(embedded, &path);
+        let loaded = reload.load(embedded).unwrap();
+        assert_eq!(&*loaded.spirv().unwrap(), &SPIRV_MAGIC.to_le_bytes()[..]);
+        assert_eq!(loaded.stage(), ShaderStageFlags::FRAGMENT);
+        assert!(reload.load(&other).is_none());
+
+        fs::write(&path, b"not spirv").unwrap();
+        assert!(reload.load(embedded).is_none());
+
+        reload.unwatch(embedded);
+        assert!(reload.load(embedded).is_none());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn poll_reports_changed_files() {
+        let embedded: &'static SpirvShader = Box::leak(Box::new(SpirvShader::new(
+            Vec::new(),
+            ShaderStageFlags::VERTEX,
+            "main",
+        )));
+        let path = spirv_file("poll");
+
+        let mut reload = ShaderHotReload::new();
+        reload.watch(embedded, &path);
+        assert!(!reload.poll());
+
+        fs::remove_file(&path).unwrap();
+        assert!(reload.poll());
+        assert!(!reload.poll());
+    }
+}
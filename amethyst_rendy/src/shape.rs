@@ -1,4 +1,4 @@
-use crate::types::Mesh;
+use crate::types::{Mesh, MeshData};
 use amethyst_assets::{AssetStorage, Handle, Loader, PrefabData, Progress, ProgressCounter};
 use amethyst_core::{
     ecs::prelude::{Entity, Read, ReadExpect, WriteStorage},
@@ -76,7 +76,7 @@ where
     ) -> Result<bool, Error> {
         let (loader, _, mesh_storage) = system_data;
         self.handle = Some(loader.load_from_data(
-            self.shape.generate::<V>(self.shape_scale).into(),
+            self.shape.generate_data::<V>(self.shape_scale),
             progress,
             &mesh_storage,
         ));
@@ -168,7 +168,7 @@ impl Shape {
     {
         upload
             .loader
-            .load_from_data(self.generate::<V>(scale).into(), progress, &upload.storage)
+            .load_from_data(self.generate_data::<V>(scale), progress, &upload.storage)
     }
 
     /// Generate `MeshBuilder` for the `Shape`
@@ -191,6 +191,27 @@ impl Shape {
         V::from(&self.generate_internal(scale)).into()
     }
 
+    /// Generate `MeshData` for the `Shape`, with bounds enclosing the generated vertices
+    ///
+    /// ### Parameters:
+    ///
+    /// - `scale`: Scale the shape by the given amounts along the x, y, z axes
+    ///
+    /// ### Type parameters:
+    ///
+    /// `V`: Vertex format to use, must to be one of:
+    ///     * `Vec<PosTex>`
+    ///     * `Vec<PosNormTex>`
+    ///     * `Vec<PosNormTangTex>`
+    ///     * `ComboMeshCreator`
+    pub fn generate_data<V>(&self, scale: Option<(f32, f32, f32)>) -> MeshData
+    where
+        V: FromShape + Into<MeshBuilder<'static>>,
+    {
+        let shape = self.generate_internal(scale);
+        MeshData::with_positions(V::from(&shape).into(), shape.0.iter().map(|v| v.0))
+    }
+
     /// Generate vertices for the `Shape`, in format `V`
     ///
     /// ### Parameters:
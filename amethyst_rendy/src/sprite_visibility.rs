@@ -1,6 +1,6 @@
 use crate::{
     camera::{ActiveCamera, Camera},
-    transparent::Transparent,
+    transparent::{RenderOrder, Transparent},
 };
 use amethyst_core::{
     ecs::prelude::{Entities, Entity, Join, Read, ReadStorage, System, Write},
@@ -24,8 +24,8 @@ pub struct SpriteVisibility {
     pub visible_ordered: Vec<Entity>,
 }
 
-/// Determines what entities to be drawn. Will also sort transparent entities back to front based on
-/// position on the Z axis.
+/// Determines what entities to be drawn. Will also sort transparent entities by their `RenderOrder`,
+/// then back to front based on position on the Z axis.
 ///
 /// The sprite render pass should draw all sprites without semi-transparent pixels, then draw the
 /// sprites with semi-transparent pixels from far to near.
@@ -43,6 +43,7 @@ pub struct SpriteVisibilitySortingSystem {
 struct Internals {
     entity: Entity,
     transparent: bool,
+    order: RenderOrder,
     centroid: Point3<Float>,
     camera_distance: Float,
     from_camera: Vector3<Float>,
@@ -64,12 +65,23 @@ impl<'a> System<'a> for SpriteVisibilitySortingSystem {
         Option<Read<'a, ActiveCamera>>,
         ReadStorage<'a, Camera>,
         ReadStorage<'a, Transparent>,
+        ReadStorage<'a, RenderOrder>,
         ReadStorage<'a, Transform>,
     );
 
     fn run(
         &mut self,
-        (entities, mut visibility, hidden, hidden_prop, active, camera, transparent, transform): Self::SystemData,
+        (
+            entities,
+            mut visibility,
+            hidden,
+            hidden_prop,
+            active,
+            camera,
+            transparent,
+            render_order,
+            transform,
+        ): Self::SystemData,
     ) {
         #[cfg(feature = "profiler")]
         profile_scope!("run");
@@ -98,6 +110,7 @@ impl<'a> System<'a> for SpriteVisibilitySortingSystem {
                 .map(|(entity, centroid)| Internals {
                     entity,
                     transparent: transparent.contains(entity),
+                    order: render_order.get(entity).cloned().unwrap_or_default(),
                     centroid,
                     camera_distance: (centroid.z - camera_centroid.z).abs(),
                     from_camera: centroid - camera_centroid,
@@ -119,9 +132,11 @@ impl<'a> System<'a> for SpriteVisibilitySortingSystem {
         // Note: Smaller Z values are placed first, so that semi-transparent sprite colors blend
         // correctly.
         self.transparent.sort_by(|a, b| {
-            b.camera_distance
-                .partial_cmp(&a.camera_distance)
-                .unwrap_or(Ordering::Equal)
+            a.order.cmp(&b.order).then_with(|| {
+                b.camera_distance
+                    .partial_cmp(&a.camera_distance)
+                    .unwrap_or(Ordering::Equal)
+            })
         });
 
         visibility.visible_ordered.clear();
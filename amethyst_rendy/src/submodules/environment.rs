@@ -1,51 +1,214 @@
 use crate::{
-    light::Light,
+    light::{Light, LightDisabled, PointLight, PrimaryDirectionalLight},
     pod::{self, IntoPod},
     rendy::{
-        command::RenderPassEncoder,
-        factory::Factory,
-        hal::{self, adapter::PhysicalDevice, device::Device, pso::Descriptor},
+        command::{QueueId, RenderPassEncoder},
+        factory::{Factory, ImageState},
+        hal::{
+            self,
+            device::Device,
+            image::{Kind, ViewKind},
+            pso::{Descriptor, SpecializationConstant},
+        },
         memory::Write as _,
-        resource::{Buffer, DescriptorSet, DescriptorSetLayout, Escape, Handle as RendyHandle},
+        resource::{
+            Buffer, BufferInfo, DescriptorSet, DescriptorSetLayout, Escape, Handle as RendyHandle,
+        },
+        texture::{pixel::Rgba8Srgb, Texture as RendyTexture, TextureBuilder},
     },
-    submodules::gather::{AmbientGatherer, CameraGatherer},
-    types::Backend,
+    resources::{EnvironmentMap, LightStats},
+    submodules::{
+        gather::{
+            directional_lights, AmbientGatherer, CameraGatherer, FogGatherer, ShadowGatherer,
+        },
+        LightTiling,
+    },
+    system::RenderedFrames,
+    types::{Backend, Texture},
     util::{self, TapCountIter},
 };
+use amethyst_assets::AssetStorage;
 use amethyst_core::{
-    ecs::{Join, ReadStorage, Resources, SystemData},
-    math::{convert, Vector3},
+    ecs::{Entity, Join, Read, ReadStorage, Resources, SystemData, Write},
+    math::{convert, Matrix4, Vector3},
     transform::Transform,
 };
+use derivative::Derivative;
 use glsl_layout::*;
+use std::sync::{Arc, Mutex};
 
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 
-const MAX_POINT_LIGHTS: usize = 128;
-const MAX_DIR_LIGHTS: usize = 16;
-const MAX_SPOT_LIGHTS: usize = 128;
+/// Largest number of each kind of light uploaded by `EnvironmentSub`.
+///
+/// Lights past a limit are silently dropped. The lighting shaders size their
+/// light arrays through specialization constants 0 to 3, see `specialization_data`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LightLimits {
+    /// Maximum number of point lights
+    pub point: usize,
+    /// Maximum number of directional lights
+    pub directional: usize,
+    /// Maximum number of spot lights
+    pub spot: usize,
+    /// Maximum number of area lights
+    pub area: usize,
+}
 
-#[derive(Debug)]
+impl Default for LightLimits {
+    fn default() -> Self {
+        LightLimits {
+            point: 128,
+            directional: 16,
+            spot: 128,
+            area: 16,
+        }
+    }
+}
+
+/// Specialization constants of the light array sizes in `LightLimits::specialization_data`.
+pub const LIGHT_LIMIT_CONSTANTS: [SpecializationConstant; 4] = [
+    SpecializationConstant { id: 0, range: 0..4 },
+    SpecializationConstant { id: 1, range: 4..8 },
+    SpecializationConstant {
+        id: 2,
+        range: 8..12,
+    },
+    SpecializationConstant {
+        id: 3,
+        range: 12..16,
+    },
+];
+
+impl LightLimits {
+    /// Raw specialization data for `LIGHT_LIMIT_CONSTANTS`.
+    pub fn specialization_data(&self) -> Vec<u8> {
+        [self.point, self.directional, self.spot, self.area]
+            .iter()
+            .flat_map(|&limit| (limit as u32).to_ne_bytes().to_vec())
+            .collect()
+    }
+}
+
+/// Camera and light data bound as a single descriptor set.
+///
+/// Clones share the same buffers, so render groups holding clones of one
+/// `EnvironmentSub` upload the data only once per frame.
+///
+/// The camera is shared by clones as well, see `with_camera`.
+///
+/// The textures of the `EnvironmentMap` resource are bound at bindings 6 to 8.
+/// Without one, black textures are bound instead, which adds no image based lighting.
+///
+/// The point light lists of `LightCulling` are bound at binding 9, see `with_light_tiles`,
+/// and the area lights at binding 10.
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""), Clone(bound = ""))]
 pub struct EnvironmentSub<B: Backend> {
     layout: RendyHandle<DescriptorSetLayout<B>>,
-    per_image: Vec<PerImageEnvironmentSub<B>>,
+    limits: LightLimits,
+    camera: Option<Entity>,
+    light_tiles: Option<(RendyHandle<Buffer<B>>, LightTiling)>,
+    fallback: Arc<FallbackEnvironmentMap<B>>,
+    per_image: Arc<Mutex<Vec<PerImageEnvironmentSub<B>>>>,
+    // Sets of the shared images, bound without locking them while drawing.
+    sets: Vec<RendyHandle<DescriptorSet<B>>>,
+}
+
+#[derive(Debug)]
+struct FallbackEnvironmentMap<B: Backend> {
+    cube: RendyTexture<B>,
+    lut: RendyTexture<B>,
+    // Bound in place of light tiles, never read by the shaders.
+    light_tiles: Escape<Buffer<B>>,
 }
 
 #[derive(Debug)]
 struct PerImageEnvironmentSub<B: Backend> {
     buffer: Option<Escape<Buffer<B>>>,
-    set: Escape<DescriptorSet<B>>,
+    set: RendyHandle<DescriptorSet<B>>,
+    frame: Option<u64>,
+    // Ids and versions of the bound environment map textures
+    environment_map: Option<[(u32, u32); 3]>,
 }
 
 impl<B: Backend> EnvironmentSub<B> {
-    pub fn new(factory: &Factory<B>) -> Result<Self, failure::Error> {
+    pub fn new(factory: &mut Factory<B>, queue: QueueId) -> Result<Self, failure::Error> {
+        Self::with_limits(factory, queue, LightLimits::default())
+    }
+
+    pub fn with_limits(
+        factory: &mut Factory<B>,
+        queue: QueueId,
+        limits: LightLimits,
+    ) -> Result<Self, failure::Error> {
         Ok(Self {
-            layout: set_layout! {factory, [1] UniformBuffer VERTEX, [4] UniformBuffer FRAGMENT},
-            per_image: Vec::new(),
+            layout: set_layout! {
+                factory,
+                [1] UniformBuffer VERTEX,
+                [5] UniformBuffer FRAGMENT,
+                [3] CombinedImageSampler FRAGMENT,
+                [1] StorageBuffer FRAGMENT,
+                [1] UniformBuffer FRAGMENT
+            },
+            limits,
+            camera: None,
+            light_tiles: None,
+            fallback: Arc::new(FallbackEnvironmentMap::new(factory, queue)?),
+            per_image: Arc::new(Mutex::new(Vec::new())),
+            sets: Vec::new(),
         })
     }
 
+    /// View through `camera` instead of the active camera, to draw a separate view
+    /// such as a `RenderTarget`. Set it before cloning, as clones upload the data only once.
+    pub fn with_camera(mut self, camera: Option<Entity>) -> Self {
+        self.camera = camera;
+        self
+    }
+
+    /// Only light fragments with the point lights listed for their screen tile in `buffer`,
+    /// written by a `LightCulling` node with the same `tiling`, instead of every point light.
+    ///
+    /// The tiles cover the view of the active camera over the whole framebuffer, so they are
+    /// ignored while viewing through another camera. Set them before the environment is
+    /// first processed, as clones share the descriptor sets.
+    pub fn with_light_tiles(mut self, buffer: RendyHandle<Buffer<B>>, tiling: LightTiling) -> Self {
+        self.light_tiles = Some((buffer, tiling));
+        self
+    }
+
+    /// Environment with the layout and limits of this one, uploading its own data
+    /// as seen from `camera`, such as the view of one split-screen player.
+    ///
+    /// Light tiles are not carried over, every point light is iterated instead.
+    pub fn for_camera(&self, camera: Option<Entity>) -> Self {
+        Self {
+            layout: self.layout.clone(),
+            limits: self.limits,
+            camera,
+            light_tiles: None,
+            fallback: self.fallback.clone(),
+            per_image: Arc::new(Mutex::new(Vec::new())),
+            sets: Vec::new(),
+        }
+    }
+
+    /// Change the camera viewed through, see `with_camera`.
+    pub fn set_camera(&mut self, camera: Option<Entity>) {
+        self.camera = camera;
+    }
+
+    pub fn limits(&self) -> LightLimits {
+        self.limits
+    }
+
+    /// Camera viewed through instead of the active camera, if any.
+    pub fn camera(&self) -> Option<Entity> {
+        self.camera
+    }
+
     pub fn raw_layout(&self) -> &B::DescriptorSetLayout {
         self.layout.raw()
     }
@@ -54,14 +217,39 @@ impl<B: Backend> EnvironmentSub<B> {
         #[cfg(feature = "profiler")]
         profile_scope!("process");
 
+        let frame = <Option<Read<'_, RenderedFrames>>>::fetch(res).map(|frames| frames.0);
+        let mut per_image = self.per_image.lock().unwrap();
         let this_image = {
-            while self.per_image.len() <= index {
-                self.per_image
-                    .push(PerImageEnvironmentSub::new(factory, &self.layout));
+            while per_image.len() <= index {
+                let light_tiles = self
+                    .light_tiles
+                    .as_ref()
+                    .map_or(&*self.fallback.light_tiles, |(buffer, _)| &**buffer);
+                per_image.push(PerImageEnvironmentSub::new(
+                    factory,
+                    &self.layout,
+                    &self.fallback,
+                    light_tiles,
+                ));
             }
-            &mut self.per_image[index]
+            // Sets never change once created, so only new ones are copied.
+            let known = self.sets.len();
+            self.sets
+                .extend(per_image[known..].iter().map(|image| image.set.clone()));
+            &mut per_image[index]
         };
-        this_image.process(factory, res)
+
+        // Another render group sharing this environment already uploaded it this frame.
+        if !first_in_frame(&mut this_image.frame, frame) {
+            return false;
+        }
+        this_image.process_environment_map(factory, res, &self.fallback);
+        let tiling = self
+            .light_tiles
+            .as_ref()
+            .filter(|_| self.camera.is_none())
+            .map(|&(_, tiling)| tiling);
+        this_image.process(factory, res, &self.limits, tiling, self.camera)
     }
 
     #[inline]
@@ -72,52 +260,178 @@ impl<B: Backend> EnvironmentSub<B> {
         set_id: u32,
         encoder: &mut RenderPassEncoder<'_, B>,
     ) {
-        self.per_image[index].bind(pipeline_layout, set_id, encoder);
+        encoder.bind_graphics_descriptor_sets(
+            pipeline_layout,
+            set_id,
+            Some(self.sets[index].raw()),
+            std::iter::empty(),
+        );
+    }
+}
+
+impl<B: Backend> FallbackEnvironmentMap<B> {
+    fn new(factory: &mut Factory<B>, queue: QueueId) -> Result<Self, failure::Error> {
+        let state = ImageState {
+            queue,
+            stage: hal::pso::PipelineStage::FRAGMENT_SHADER,
+            access: hal::image::Access::SHADER_READ,
+            layout: hal::image::Layout::ShaderReadOnlyOptimal,
+        };
+        let black = || -> Rgba8Srgb { palette::Srgba::new(0.0, 0.0, 0.0, 1.0).into() };
+
+        let cube = TextureBuilder::new()
+            .with_kind(Kind::D2(1, 1, 6, 1))
+            .with_view_kind(ViewKind::Cube)
+            .with_data_width(1)
+            .with_data_height(1)
+            .with_data(vec![black(); 6])
+            .build(state, factory)?;
+        let lut = TextureBuilder::new()
+            .with_kind(Kind::D2(1, 1, 1, 1))
+            .with_view_kind(ViewKind::D2)
+            .with_data_width(1)
+            .with_data_height(1)
+            .with_data(vec![black()])
+            .build(state, factory)?;
+        let light_tiles = factory.create_buffer(
+            BufferInfo {
+                size: 16,
+                usage: hal::buffer::Usage::STORAGE,
+            },
+            rendy::memory::Data,
+        )?;
+
+        Ok(Self {
+            cube,
+            lut,
+            light_tiles,
+        })
+    }
+
+    fn descriptors(&self) -> [Descriptor<'_, B>; 3] {
+        let layout = hal::image::Layout::ShaderReadOnlyOptimal;
+        let desc = |texture: &RendyTexture<B>| {
+            Descriptor::CombinedImageSampler(texture.view().raw(), layout, texture.sampler().raw())
+        };
+        [desc(&self.cube), desc(&self.cube), desc(&self.lut)]
     }
 }
 
 impl<B: Backend> PerImageEnvironmentSub<B> {
-    fn new(factory: &Factory<B>, layout: &RendyHandle<DescriptorSetLayout<B>>) -> Self {
+    fn new(
+        factory: &Factory<B>,
+        layout: &RendyHandle<DescriptorSetLayout<B>>,
+        fallback: &FallbackEnvironmentMap<B>,
+        light_tiles: &Buffer<B>,
+    ) -> Self {
+        let set = Escape::share(factory.create_descriptor_set(layout.clone()).unwrap());
+        Self::write_environment_map(factory, &set, fallback.descriptors());
+        unsafe {
+            factory.write_descriptor_sets(Some(util::desc_write(
+                set.raw(),
+                9,
+                Descriptor::Buffer(light_tiles.raw(), None..None),
+            )));
+        }
         Self {
             buffer: None,
-            set: factory.create_descriptor_set(layout.clone()).unwrap(),
+            set,
+            frame: None,
+            environment_map: None,
         }
     }
 
-    #[inline]
-    fn bind(
-        &self,
-        pipeline_layout: &B::PipelineLayout,
-        set_id: u32,
-        encoder: &mut RenderPassEncoder<'_, B>,
+    fn write_environment_map(
+        factory: &Factory<B>,
+        set: &DescriptorSet<B>,
+        [irradiance, specular, brdf_lut]: [Descriptor<'_, B>; 3],
     ) {
-        encoder.bind_graphics_descriptor_sets(
-            pipeline_layout,
-            set_id,
-            Some(self.set.raw()),
-            std::iter::empty(),
-        );
+        use util::desc_write;
+        let set = set.raw();
+        unsafe {
+            factory.write_descriptor_sets(vec![
+                desc_write(set, 6, irradiance),
+                desc_write(set, 7, specular),
+                desc_write(set, 8, brdf_lut),
+            ]);
+        }
     }
 
-    fn process(&mut self, factory: &Factory<B>, res: &Resources) -> bool {
-        let align = factory
-            .physical()
-            .limits()
-            .min_uniform_buffer_offset_alignment;
+    fn process_environment_map(
+        &mut self,
+        factory: &Factory<B>,
+        res: &Resources,
+        fallback: &FallbackEnvironmentMap<B>,
+    ) {
+        let (environment_map, tex_storage) = <(
+            Option<Read<'_, EnvironmentMap>>,
+            Read<'_, AssetStorage<Texture>>,
+        )>::fetch(res);
+
+        let layout = hal::image::Layout::ShaderReadOnlyOptimal;
+        let loaded = environment_map.as_ref().and_then(|map| {
+            let mut key = [(0, 0); 3];
+            let mut descs = Vec::with_capacity(3);
+            for (i, handle) in [&map.irradiance, &map.specular, &map.brdf_lut]
+                .iter()
+                .enumerate()
+            {
+                let (texture, version) = tex_storage.get_with_version(handle)?;
+                key[i] = (handle.id(), *version);
+                descs.push(util::texture_desc(texture, layout)?);
+            }
+            Some((key, descs))
+        });
+
+        match loaded {
+            Some((key, mut descs)) => {
+                if self.environment_map != Some(key) {
+                    let brdf_lut = descs.pop().unwrap();
+                    let specular = descs.pop().unwrap();
+                    let irradiance = descs.pop().unwrap();
+                    Self::write_environment_map(
+                        factory,
+                        &self.set,
+                        [irradiance, specular, brdf_lut],
+                    );
+                    self.environment_map = Some(key);
+                }
+            }
+            None => {
+                if self.environment_map.take().is_some() {
+                    Self::write_environment_map(factory, &self.set, fallback.descriptors());
+                }
+            }
+        }
+    }
+
+    fn process(
+        &mut self,
+        factory: &Factory<B>,
+        res: &Resources,
+        limits: &LightLimits,
+        tiling: Option<LightTiling>,
+        camera: Option<Entity>,
+    ) -> bool {
+        let align = util::ubo_offset_align(factory);
 
         let projview_size = util::align_size::<pod::ViewArgs>(align, 1);
         let env_buf_size = util::align_size::<pod::Environment>(align, 1);
-        let plight_buf_size = util::align_size::<pod::PointLight>(align, MAX_POINT_LIGHTS);
-        let dlight_buf_size = util::align_size::<pod::DirectionalLight>(align, MAX_DIR_LIGHTS);
-        let slight_buf_size = util::align_size::<pod::SpotLight>(align, MAX_SPOT_LIGHTS);
+        let plight_buf_size = util::align_size::<pod::PointLight>(align, limits.point);
+        let dlight_buf_size = util::align_size::<pod::DirectionalLight>(align, limits.directional);
+        let slight_buf_size = util::align_size::<pod::SpotLight>(align, limits.spot);
+        let shadow_buf_size = util::align_size::<pod::ShadowArgs>(align, 1);
+        let alight_buf_size = util::align_size::<pod::AreaLight>(align, limits.area);
 
         let projview_range = 0..projview_size;
         let env_range = util::next_range(&projview_range, env_buf_size);
         let plight_range = util::next_range(&env_range, plight_buf_size);
         let dlight_range = util::next_range(&plight_range, dlight_buf_size);
         let slight_range = util::next_range(&dlight_range, slight_buf_size);
+        let shadow_range = util::next_range(&slight_range, shadow_buf_size);
+        let alight_range = util::next_range(&shadow_range, alight_buf_size);
 
-        let whole_range = 0..slight_range.end;
+        let whole_range = 0..alight_range.end;
 
         let new_buffer = util::ensure_buffer(
             &factory,
@@ -138,6 +452,8 @@ impl<B: Backend> PerImageEnvironmentSub<B> {
                 let desc_plight = Descriptor::Buffer(buffer, opt_range(plight_range.clone()));
                 let desc_dlight = Descriptor::Buffer(buffer, opt_range(dlight_range.clone()));
                 let desc_slight = Descriptor::Buffer(buffer, opt_range(slight_range.clone()));
+                let desc_shadow = Descriptor::Buffer(buffer, opt_range(shadow_range.clone()));
+                let desc_alight = Descriptor::Buffer(buffer, opt_range(alight_range.clone()));
 
                 unsafe {
                     factory.write_descriptor_sets(vec![
@@ -146,6 +462,8 @@ impl<B: Backend> PerImageEnvironmentSub<B> {
                         desc_write(env_set, 2, desc_plight),
                         desc_write(env_set, 3, desc_dlight),
                         desc_write(env_set, 4, desc_slight),
+                        desc_write(env_set, 5, desc_shadow),
+                        desc_write(env_set, 10, desc_alight),
                     ]);
                 }
             }
@@ -153,60 +471,67 @@ impl<B: Backend> PerImageEnvironmentSub<B> {
             let CameraGatherer {
                 camera_position,
                 projview,
-            } = CameraGatherer::gather(res);
+                exposure,
+                ..
+            } = CameraGatherer::gather_for(res, camera);
 
             let mut mapped = buffer.map(factory, whole_range.clone()).unwrap();
             let mut writer = unsafe { mapped.write::<u8>(factory, whole_range.clone()).unwrap() };
             let dst_slice = unsafe { writer.slice() };
 
+            let (fog_color, fog_start, fog_density) = FogGatherer::gather(res);
             let mut env = pod::Environment {
                 ambient_color: AmbientGatherer::gather(res),
                 camera_position,
                 point_light_count: 0,
                 directional_light_count: 0,
                 spot_light_count: 0,
+                area_light_count: 0,
+                fog_color,
+                fog_start,
+                fog_density,
+                light_tile_columns: tiling.map_or(0, |tiling| tiling.columns as i32),
+                light_tile_rows: tiling.map_or(0, |tiling| tiling.rows as i32),
+                lights_per_tile: tiling.map_or(0, |tiling| tiling.lights_per_tile as i32),
+                exposure,
             }
             .std140();
 
-            let (lights, transforms) =
-                <(ReadStorage<'_, Light>, ReadStorage<'_, Transform>)>::fetch(res);
+            let (lights, primary, disabled, transforms) = <(
+                ReadStorage<'_, Light>,
+                ReadStorage<'_, PrimaryDirectionalLight>,
+                ReadStorage<'_, LightDisabled>,
+                ReadStorage<'_, Transform>,
+            )>::fetch(res);
 
-            let point_lights = (&lights, &transforms)
-                .join()
-                .filter_map(|(light, transform)| match light {
-                    Light::Point(light) => Some(
-                        pod::PointLight {
-                            position: convert::<_, Vector3<f32>>(
-                                transform.global_matrix().column(3).xyz(),
-                            )
-                            .into_pod(),
-                            color: light.color.into_pod(),
-                            intensity: light.intensity,
-                        }
-                        .std140(),
-                    ),
-                    _ => None,
+            let point_lights = point_lights(&lights, &transforms, &disabled)
+                .map(|(position, light)| {
+                    pod::PointLight {
+                        position: position.into_pod(),
+                        color: light.color.into_pod(),
+                        intensity: light.intensity,
+                        radius: light.radius,
+                        smoothness: light.smoothness,
+                    }
+                    .std140()
                 })
-                .take(MAX_POINT_LIGHTS);
+                .take(limits.point);
 
-            let dir_lights = lights
-                .join()
-                .filter_map(|light| match light {
-                    Light::Directional(ref light) => Some(
-                        pod::DirectionalLight {
-                            color: light.color.into_pod(),
-                            intensity: light.intensity,
-                            direction: light.direction.into_pod(),
-                        }
-                        .std140(),
-                    ),
-                    _ => None,
+            let dir_lights = directional_lights(&lights, &primary, &disabled)
+                .into_iter()
+                .map(|light| {
+                    pod::DirectionalLight {
+                        color: light.color.into_pod(),
+                        intensity: light.intensity,
+                        direction: light.direction.into_pod(),
+                    }
+                    .std140()
                 })
-                .take(MAX_DIR_LIGHTS);
+                .take(limits.directional);
 
-            let spot_lights = (&lights, &transforms)
+            let spot_lights = (&lights, &transforms, !&disabled)
                 .join()
-                .filter_map(|(light, transform)| {
+                .filter_map(|(light, transform, _)| {
                     if let Light::Spot(ref light) = *light {
                         Some(
                             pod::SpotLight {
@@ -217,9 +542,9 @@ impl<B: Backend> PerImageEnvironmentSub<B> {
                                 color: light.color.into_pod(),
                                 direction: light.direction.into_pod(),
                                 angle: light.angle.cos(),
+                                inner_angle: light.inner_cone_angle().cos(),
                                 intensity: light.intensity,
                                 range: light.range,
-                                smoothness: light.smoothness,
                             }
                             .std140(),
                         )
@@ -227,7 +552,39 @@ impl<B: Backend> PerImageEnvironmentSub<B> {
                         None
                     }
                 })
-                .take(MAX_SPOT_LIGHTS);
+                .take(limits.spot);
+
+            let area_lights = (&lights, &transforms, !&disabled)
+                .join()
+                .filter_map(|(light, transform, _)| match light {
+                    Light::Area(light) => {
+                        let model = convert::<_, Matrix4<f32>>(*transform.global_matrix());
+                        Some(
+                            pod::AreaLight {
+                                position: model.column(3).xyz().into_pod(),
+                                color: light.color.into_pod(),
+                                half_width: (model.column(0).xyz() * light.width * 0.5).into_pod(),
+                                intensity: light.intensity,
+                                half_height: (model.column(1).xyz() * light.height * 0.5)
+                                    .into_pod(),
+                            }
+                            .std140(),
+                        )
+                    }
+                    _ => None,
+                })
+                .take(limits.area);
+
+            let mut stats = LightStats::default();
+            for (light, _) in (&lights, !&disabled).join() {
+                match light {
+                    Light::Point(_) => stats.point.gathered += 1,
+                    Light::Directional(_) => stats.directional.gathered += 1,
+                    Light::Spot(_) => stats.spot.gathered += 1,
+                    Light::Area(_) => stats.area.gathered += 1,
+                    _ => {}
+                }
+            }
 
             use util::{usize_range, write_into_slice};
             write_into_slice(
@@ -242,10 +599,71 @@ impl<B: Backend> PerImageEnvironmentSub<B> {
                 &mut dst_slice[usize_range(slight_range)],
                 spot_lights.tap_count(&mut env.spot_light_count),
             );
+            write_into_slice(
+                &mut dst_slice[usize_range(alight_range)],
+                area_lights.tap_count(&mut env.area_light_count),
+            );
+            stats.point.uploaded = env.point_light_count as usize;
+            stats.directional.uploaded = env.directional_light_count as usize;
+            stats.spot.uploaded = env.spot_light_count as usize;
+            stats.area.uploaded = env.area_light_count as usize;
+            if let Some(mut light_stats) = <Option<Write<'_, LightStats>>>::fetch(res) {
+                *light_stats = stats;
+            }
+
             write_into_slice(&mut dst_slice[usize_range(projview_range)], Some(projview));
             write_into_slice(&mut dst_slice[usize_range(env_range)], Some(env));
+            write_into_slice(
+                &mut dst_slice[usize_range(shadow_range)],
+                Some(ShadowGatherer::args(ShadowGatherer::gather(res).as_ref())),
+            );
         }
 
         return new_buffer;
     }
 }
+
+/// Enabled point lights with their world position, in the order they are uploaded.
+///
+/// `LightCulling` refers to lights by their index in this order.
+pub(crate) fn point_lights<'a>(
+    lights: &'a ReadStorage<'_, Light>,
+    transforms: &'a ReadStorage<'_, Transform>,
+    disabled: &'a ReadStorage<'_, LightDisabled>,
+) -> impl Iterator<Item = (Vector3<f32>, &'a PointLight)> + 'a {
+    (lights, transforms, !disabled)
+        .join()
+        .filter_map(|(light, transform, _)| match light {
+            Light::Point(light) => Some((
+                convert::<_, Vector3<f32>>(transform.global_matrix().column(3).xyz()),
+                light,
+            )),
+            _ => None,
+        })
+}
+
+/// Record that an image is uploaded in `frame`, returning `false` when it already was.
+///
+/// Without a frame number every call uploads.
+fn first_in_frame(uploaded: &mut Option<u64>, frame: Option<u64>) -> bool {
+    if frame.is_some() && *uploaded == frame {
+        return false;
+    }
+    *uploaded = frame;
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clones_upload_once_per_frame() {
+        let mut uploaded = None;
+        assert!(first_in_frame(&mut uploaded, Some(1)));
+        assert!(!first_in_frame(&mut uploaded, Some(1)));
+        assert!(first_in_frame(&mut uploaded, Some(2)));
+        assert!(first_in_frame(&mut uploaded, None));
+        assert!(first_in_frame(&mut uploaded, None));
+    }
+}
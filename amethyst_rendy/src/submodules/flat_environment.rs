@@ -4,7 +4,7 @@ use crate::{
     submodules::{gather::CameraGatherer, uniform::DynamicUniform},
     types::Backend,
 };
-use amethyst_core::ecs::Resources;
+use amethyst_core::ecs::{Entity, Resources};
 
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
@@ -12,15 +12,23 @@ use thread_profiler::profile_scope;
 #[derive(Debug)]
 pub struct FlatEnvironmentSub<B: Backend> {
     uniform: DynamicUniform<B, ViewArgs>,
+    camera: Option<Entity>,
 }
 
 impl<B: Backend> FlatEnvironmentSub<B> {
     pub fn new(factory: &Factory<B>) -> Result<Self, failure::Error> {
         Ok(Self {
             uniform: DynamicUniform::new(factory, rendy::hal::pso::ShaderStageFlags::VERTEX)?,
+            camera: None,
         })
     }
 
+    /// View through `camera` instead of the active camera.
+    pub fn with_camera(mut self, camera: Option<Entity>) -> Self {
+        self.camera = camera;
+        self
+    }
+
     pub fn raw_layout(&self) -> &B::DescriptorSetLayout {
         self.uniform.raw_layout()
     }
@@ -28,7 +36,7 @@ impl<B: Backend> FlatEnvironmentSub<B> {
     pub fn process(&mut self, factory: &Factory<B>, index: usize, res: &Resources) {
         #[cfg(feature = "profiler")]
         profile_scope!("process");
-        let projview = CameraGatherer::gather(res).projview;
+        let projview = CameraGatherer::gather_for(res, self.camera).projview;
         self.uniform.write(factory, index, projview);
     }
 
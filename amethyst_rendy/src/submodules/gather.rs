@@ -1,11 +1,15 @@
 use crate::{
-    camera::{ActiveCamera, Camera},
+    camera::{ActiveCamera, Camera, Exposure},
+    light::{DirectionalLight, Light, LightDisabled, PrimaryDirectionalLight},
     pod::{self, IntoPod},
-    resources::AmbientColor,
+    resources::{
+        view_slice_bounds, AmbientColor, DepthMode, Fog, ShadowMap, ShadowSettings,
+        MAX_SHADOW_CASCADES,
+    },
 };
 use amethyst_core::{
-    ecs::{Join, Read, ReadExpect, ReadStorage, Resources, SystemData},
-    math::{convert, Matrix4, Vector3},
+    ecs::{Entities, Entity, Join, Read, ReadExpect, ReadStorage, Resources, SystemData},
+    math::{convert, Matrix4, Point3, Vector3},
     transform::Transform,
 };
 use amethyst_window::ScreenDimensions;
@@ -18,53 +22,84 @@ type Std140<T> = <T as AsStd140>::Std140;
 
 pub struct CameraGatherer {
     pub camera_position: vec3,
+    /// Projection and view handed to the shaders, with the projection following `DepthMode`.
     pub projview: Std140<pod::ViewArgs>,
+    /// Projection of the camera, mapping the far plane to `1.0` whatever the `DepthMode`.
+    pub projection: Matrix4<f32>,
+    pub view: Matrix4<f32>,
+    /// `Exposure` of the camera, `1.0` without one.
+    pub exposure: f32,
 }
 
 impl CameraGatherer {
     pub fn gather(res: &Resources) -> Self {
+        Self::gather_for(res, None)
+    }
+
+    /// View through `camera` instead of the active camera, falling back to it
+    /// when `camera` is `None` or has no `Camera` component.
+    pub fn gather_for(res: &Resources, camera: Option<Entity>) -> Self {
         #[cfg(feature = "profiler")]
         profile_scope!("gather_cameras");
 
-        let (active_camera, cameras, transforms, dimensions) = <(
-            Option<Read<'_, ActiveCamera>>,
-            ReadStorage<'_, Camera>,
-            ReadStorage<'_, Transform>,
-            ReadExpect<'_, ScreenDimensions>,
-        )>::fetch(res);
+        let (entities, active_camera, cameras, transforms, exposures, dimensions, depth_mode) =
+            <(
+                Entities<'_>,
+                Option<Read<'_, ActiveCamera>>,
+                ReadStorage<'_, Camera>,
+                ReadStorage<'_, Transform>,
+                ReadStorage<'_, Exposure>,
+                ReadExpect<'_, ScreenDimensions>,
+                Option<Read<'_, DepthMode>>,
+            )>::fetch(res);
 
         let defcam = Camera::standard_2d(dimensions.width(), dimensions.height());
         let identity = Transform::default();
 
-        let (camera, transform) = active_camera
-            .as_ref()
-            .and_then(|ac| {
-                cameras
-                    .get(ac.entity)
-                    .map(|camera| (camera, transforms.get(ac.entity).unwrap_or(&identity)))
+        let (entity, camera, transform) = camera
+            .filter(|entity| cameras.contains(*entity))
+            .or_else(|| active_camera.as_ref().map(|ac| ac.entity))
+            .and_then(|entity| {
+                cameras.get(entity).map(|camera| {
+                    (
+                        Some(entity),
+                        camera,
+                        transforms.get(entity).unwrap_or(&identity),
+                    )
+                })
             })
             .unwrap_or_else(|| {
-                (&cameras, &transforms)
+                (&entities, &cameras, &transforms)
                     .join()
                     .next()
-                    .unwrap_or((&defcam, &identity))
+                    .map(|(entity, camera, transform)| (Some(entity), camera, transform))
+                    .unwrap_or((None, &defcam, &identity))
             });
+        let exposure = entity
+            .and_then(|entity| exposures.get(entity))
+            .map_or(1.0, |exposure| exposure.0);
 
         let camera_position =
             convert::<_, Vector3<f32>>(transform.global_matrix().column(3).xyz()).into_pod();
 
-        let proj: [[f32; 4]; 4] = (*camera.as_matrix()).into();
-        let view: [[f32; 4]; 4] = convert::<_, Matrix4<f32>>(transform.view_matrix()).into();
+        let projection = *camera.as_matrix();
+        let view = convert::<_, Matrix4<f32>>(transform.view_matrix());
+        let depth_mode = depth_mode.map_or_else(DepthMode::default, |mode| *mode);
+        let proj: [[f32; 4]; 4] = depth_mode.projection(&projection).into();
+        let view_raw: [[f32; 4]; 4] = view.into();
 
         let projview = pod::ViewArgs {
             proj: proj.into(),
-            view: view.into(),
+            view: view_raw.into(),
         }
         .std140();
 
         Self {
             camera_position,
             projview,
+            projection,
+            view,
+            exposure,
         }
     }
 }
@@ -73,9 +108,148 @@ pub struct AmbientGatherer;
 impl AmbientGatherer {
     pub fn gather(res: &Resources) -> vec3 {
         let ambient_color = <Option<Read<'_, AmbientColor>>>::fetch(res);
-        ambient_color.map_or([0.0, 0.0, 0.0].into(), |c| {
-            let (r, g, b, _) = c.0.into_components();
-            [r, g, b].into()
+        // Converted the same way as light colors, so both share one color space.
+        ambient_color.map_or([0.0, 0.0, 0.0].into(), |c| c.0.color.into_pod())
+    }
+}
+
+/// Enabled directional lights, starting with the primary one.
+///
+/// The shaders shadow the first uploaded directional light, so it must be the one
+/// `ShadowGatherer` places the shadow map for.
+pub(crate) fn directional_lights<'a>(
+    lights: &'a ReadStorage<'_, Light>,
+    primary: &ReadStorage<'_, PrimaryDirectionalLight>,
+    disabled: &ReadStorage<'_, LightDisabled>,
+) -> Vec<&'a DirectionalLight> {
+    let mut primary_index = None;
+    let mut directional = Vec::new();
+    for (light, is_primary, _) in (lights, primary.maybe(), !disabled).join() {
+        if let Light::Directional(light) = light {
+            if is_primary.is_some() && primary_index.is_none() {
+                primary_index = Some(directional.len());
+            }
+            directional.push(light);
+        }
+    }
+    if let Some(index) = primary_index {
+        let light = directional.remove(index);
+        directional.insert(0, light);
+    }
+    directional
+}
+
+pub struct FogGatherer;
+impl FogGatherer {
+    /// Color, start and density of the fog, with a density of zero when there is none.
+    pub fn gather(res: &Resources) -> (vec3, f32, f32) {
+        let fog = <Option<Read<'_, Fog>>>::fetch(res);
+        fog.map_or(([0.0, 0.0, 0.0].into(), 0.0, 0.0), |fog| {
+            (fog.color.into_pod(), fog.start, fog.density.max(0.0))
+        })
+    }
+}
+
+/// Light space of the shadow map cast by the primary directional light.
+pub struct ShadowGatherer {
+    /// Light space of each cascade, only the first `cascade_count` are used.
+    pub light_view_proj: [Matrix4<f32>; MAX_SHADOW_CASCADES],
+    pub cascade_splits: [f32; MAX_SHADOW_CASCADES],
+    pub cascade_count: usize,
+    pub camera_forward: Vector3<f32>,
+    pub bias: f32,
+}
+
+impl ShadowGatherer {
+    /// Returns `None` when there is no `ShadowMap` resource or no directional light.
+    pub fn gather(res: &Resources) -> Option<Self> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("gather_shadows");
+
+        let (shadow_map, settings, lights, primary, disabled) = <(
+            Option<Read<'_, ShadowMap>>,
+            Option<Read<'_, ShadowSettings>>,
+            ReadStorage<'_, Light>,
+            ReadStorage<'_, PrimaryDirectionalLight>,
+            ReadStorage<'_, LightDisabled>,
+        )>::fetch(res);
+        let shadow_map = shadow_map?;
+        let settings = settings.map(|s| s.clone()).unwrap_or_default();
+
+        let direction = directional_lights(&lights, &primary, &disabled)
+            .first()?
+            .direction;
+
+        let camera = CameraGatherer::gather(res);
+        let camera_position: [f32; 3] = *camera.camera_position.as_ref();
+        let camera_position = Vector3::from(camera_position);
+        let inverse_view = camera.view.try_inverse().unwrap_or_else(Matrix4::identity);
+        let camera_forward = -inverse_view
+            .column(2)
+            .xyz()
+            .try_normalize(std::f32::EPSILON)
+            .unwrap_or_else(Vector3::z);
+
+        let cascade_count = settings.cascade_count();
+        let mut cascade_splits = [std::f32::MAX; MAX_SHADOW_CASCADES];
+        let mut light_view_proj = [Matrix4::identity(); MAX_SHADOW_CASCADES];
+
+        if cascade_count == 1 {
+            light_view_proj[0] = shadow_map.light_view_proj(direction, camera_position);
+        } else {
+            // Cascades start at the camera's near plane.
+            let mut near = camera
+                .projection
+                .try_inverse()
+                .map_or(0.0, |inverse| -inverse.transform_point(&Point3::origin()).z);
+            for cascade in 0..cascade_count {
+                let far = settings.cascade_splits[cascade];
+                cascade_splits[cascade] = far;
+                light_view_proj[cascade] = match view_slice_bounds(&camera.projection, near, far) {
+                    Some((center, radius)) => shadow_map.cascade_view_proj(
+                        direction,
+                        inverse_view.transform_point(&center).coords,
+                        radius,
+                    ),
+                    None => shadow_map.light_view_proj(direction, camera_position),
+                };
+                near = far;
+            }
+        }
+
+        Some(Self {
+            light_view_proj,
+            cascade_splits,
+            cascade_count,
+            camera_forward,
+            bias: shadow_map.bias,
         })
     }
+
+    /// Shader arguments of a gathered shadow, disabled when there is none.
+    pub fn args(shadow: Option<&Self>) -> Std140<pod::ShadowArgs> {
+        let mut light_view_proj: [mat4; MAX_SHADOW_CASCADES] =
+            [[[0.0; 4]; 4].into(); MAX_SHADOW_CASCADES];
+        let mut cascade_splits = [std::f32::MAX; MAX_SHADOW_CASCADES];
+        let mut camera_forward = [0.0, 0.0, -1.0];
+
+        if let Some(shadow) = shadow {
+            for (dst, src) in light_view_proj.iter_mut().zip(&shadow.light_view_proj) {
+                let matrix: [[f32; 4]; 4] = (*src).into();
+                *dst = matrix.into();
+            }
+            cascade_splits = shadow.cascade_splits;
+            camera_forward = shadow.camera_forward.into();
+        }
+
+        pod::ShadowArgs {
+            light_view_proj,
+            cascade_splits: cascade_splits.into(),
+            camera_forward: camera_forward.into(),
+            bias: shadow.map_or(0.0, |s| s.bias),
+            cascade_count: shadow.map_or(1, |s| s.cascade_count as i32),
+            enabled: shadow.is_some() as i32,
+        }
+        .std140()
+    }
 }
@@ -0,0 +1,464 @@
+use crate::{
+    light::{Light, LightDisabled},
+    rendy::{
+        command::{
+            CommandBuffer, CommandPool, Compute, ExecutableState, Family, Fence, MultiShot,
+            PendingState, Queue, SimultaneousUse, Submission, Submit,
+        },
+        factory::Factory,
+        frame::Frames,
+        graph::{
+            gfx_acquire_barriers, gfx_release_barriers, BufferAccess, GraphContext, Node,
+            NodeBuffer, NodeDesc, NodeImage,
+        },
+        hal::{self, device::Device, pso},
+        memory::Write as _,
+        resource::{Buffer, BufferInfo, DescriptorSet, DescriptorSetLayout, Escape, Handle},
+        shader::{Shader, SpirvShader},
+    },
+    submodules::{environment::point_lights, gather::CameraGatherer, LightLimits},
+    types::Backend,
+    util::{self, TapCountIter},
+};
+use amethyst_core::{
+    ecs::{ReadStorage, Resources, SystemData},
+    math::Point3,
+    transform::Transform,
+};
+use derivative::Derivative;
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+lazy_static::lazy_static! {
+    static ref LIGHT_CULLING_COMPUTE: SpirvShader = SpirvShader::new(
+        include_bytes!("../../compiled/compute/light_culling.comp.spv").to_vec(),
+        pso::ShaderStageFlags::COMPUTE,
+        "main",
+    );
+}
+
+/// Side in pixels of the square screen tiles point lights are assigned to.
+pub const LIGHT_TILE_SIZE: u32 = 16;
+
+/// Tiles along each side of a workgroup of the culling shader.
+const WORKGROUP_SIZE: u32 = 8;
+
+/// Grid of screen tiles covering a framebuffer, each listing the point lights reaching it.
+///
+/// The lists are stored one after the other in a buffer of `buffer_size` bytes, each made
+/// of the number of lights followed by room for `lights_per_tile` light indices.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LightTiling {
+    /// Width in pixels of the covered framebuffer.
+    pub width: u32,
+    /// Height in pixels of the covered framebuffer.
+    pub height: u32,
+    /// Number of tiles along the width.
+    pub columns: u32,
+    /// Number of tiles along the height.
+    pub rows: u32,
+    /// Largest number of lights listed for a tile.
+    pub lights_per_tile: u32,
+}
+
+impl LightTiling {
+    /// Tiles covering a framebuffer of `width` by `height` pixels, listing up to 64 lights each.
+    pub fn new(width: u32, height: u32) -> Self {
+        let tiles = |pixels: u32| ((pixels + LIGHT_TILE_SIZE - 1) / LIGHT_TILE_SIZE).max(1);
+        LightTiling {
+            width,
+            height,
+            columns: tiles(width),
+            rows: tiles(height),
+            lights_per_tile: 64,
+        }
+    }
+
+    /// List up to `count` lights per tile. Lights past it are dropped from the tile.
+    pub fn with_lights_per_tile(mut self, count: u32) -> Self {
+        self.lights_per_tile = count.max(1);
+        self
+    }
+
+    /// Number of tiles covering the framebuffer.
+    pub fn tile_count(&self) -> u32 {
+        self.columns * self.rows
+    }
+
+    /// Size in bytes of the buffer holding the light lists of every tile.
+    pub fn buffer_size(&self) -> u64 {
+        util::byte_size::<u32>((self.tile_count() * (self.lights_per_tile + 1)) as usize)
+    }
+}
+
+/// Whether the queue family the render graph runs on supports compute, which
+/// `LightCullingDesc` needs. Inserted by `RenderingSystem`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LightCullingSupported(pub bool);
+
+/// Arguments of the culling shader, followed by the view space bounds of the lights.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct CullingArgs {
+    projection: [[f32; 4]; 4],
+    framebuffer_size: [u32; 2],
+    tile_count: [u32; 2],
+    light_count: u32,
+    lights_per_tile: u32,
+    _pad: [u32; 2],
+}
+
+/// Assigns point lights to screen tiles with a compute shader, so that lighting shaders
+/// only iterate over the lights reaching each fragment instead of every point light.
+///
+/// The node writes the lists into the buffer passed to its builder, created with the
+/// `buffer_size` of its `LightTiling`. Render groups built `with_light_tiles` read it
+/// through their `EnvironmentSub`:
+///
+/// ```ignore
+/// let tiling = LightTiling::new(width, height);
+/// let tiles = graph_builder.create_buffer(tiling.buffer_size());
+/// let culling = graph_builder.add_node(
+///     LightCullingDesc::new(tiling).builder().with_buffer(tiles),
+/// );
+/// let pass = graph_builder.add_node(
+///     SubpassBuilder::new()
+///         .with_group(DrawPbrDesc::new().with_light_tiles(tiling).builder().with_buffer(tiles))
+///         .with_color(color)
+///         .with_depth_stencil(depth)
+///         .into_pass()
+///         .with_dependency(culling),
+/// );
+/// ```
+///
+/// Without this node the lighting shaders keep iterating over every point light, which is
+/// also how split-screen views and groups drawing another camera are lit.
+///
+/// Building the node fails on devices whose graphics queue can't run compute shaders.
+/// Graph creators check the `LightCullingSupported` resource first, and otherwise leave the
+/// node out and build the groups without `with_light_tiles`. The groups then bind a small
+/// placeholder buffer in place of the tiles and iterate over every point light:
+///
+/// ```ignore
+/// if res.try_fetch::<LightCullingSupported>().map_or(false, |supported| supported.0) {
+///     // Add the culling node and the groups reading its tiles as above.
+/// } else {
+///     // Add `DrawPbrDesc::new().builder()` without light tiles.
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct LightCullingDesc {
+    tiling: LightTiling,
+    max_point_lights: usize,
+}
+
+impl LightCullingDesc {
+    /// Cull lights for the tiles of `tiling`.
+    pub fn new(tiling: LightTiling) -> Self {
+        LightCullingDesc {
+            tiling,
+            max_point_lights: LightLimits::default().point,
+        }
+    }
+
+    /// Cull at most `count` point lights, 128 by default.
+    /// It has to match the point light limit of the environments reading the tiles.
+    pub fn with_max_point_lights(mut self, count: usize) -> Self {
+        self.max_point_lights = count.max(1);
+        self
+    }
+
+    fn args_size(&self) -> u64 {
+        util::byte_size::<CullingArgs>(1) + util::byte_size::<[f32; 4]>(self.max_point_lights)
+    }
+}
+
+impl<B: Backend> NodeDesc<B, Resources> for LightCullingDesc {
+    type Node = LightCulling<B>;
+
+    fn buffers(&self) -> Vec<BufferAccess> {
+        vec![BufferAccess {
+            access: hal::buffer::Access::SHADER_WRITE,
+            usage: hal::buffer::Usage::STORAGE,
+            stages: pso::PipelineStage::COMPUTE_SHADER,
+        }]
+    }
+
+    fn build<'a>(
+        self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        family: &mut Family<B>,
+        _queue: usize,
+        _aux: &Resources,
+        buffers: Vec<NodeBuffer>,
+        _images: Vec<NodeImage>,
+    ) -> Result<Self::Node, failure::Error> {
+        assert_eq!(buffers.len(), 1);
+        if !family.capability().supports_compute() {
+            return Err(failure::format_err!(
+                "Light culling needs a queue with compute capability, \
+                 check `LightCullingSupported` before adding the node"
+            ));
+        }
+        let tiles = ctx
+            .get_buffer(buffers[0].id)
+            .expect("Light tile buffer is not created by the graph");
+
+        let layout: Handle<DescriptorSetLayout<B>> =
+            set_layout! {factory, [2] StorageBuffer COMPUTE};
+        let pipeline_layout = unsafe {
+            factory
+                .device()
+                .create_pipeline_layout(Some(layout.raw()), None as Option<(_, _)>)
+        }?;
+        let pipeline = unsafe {
+            match LIGHT_CULLING_COMPUTE.module(factory) {
+                Ok(module) => {
+                    let pipeline = factory.device().create_compute_pipeline(
+                        &pso::ComputePipelineDesc::new(
+                            pso::EntryPoint {
+                                entry: "main",
+                                module: &module,
+                                specialization: pso::Specialization::default(),
+                            },
+                            &pipeline_layout,
+                        ),
+                        None,
+                    );
+                    factory.destroy_shader_module(module);
+                    pipeline.map_err(failure::Error::from)
+                }
+                Err(e) => Err(e.into()),
+            }
+        };
+        let pipeline = match pipeline {
+            Ok(pipeline) => pipeline,
+            Err(e) => {
+                unsafe {
+                    factory.device().destroy_pipeline_layout(pipeline_layout);
+                }
+                return Err(e);
+            }
+        };
+
+        let mut pool = factory
+            .create_command_pool(family)?
+            .with_capability::<Compute>()
+            .expect("Family capability is checked above");
+
+        let groups = |tiles: u32| (tiles + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+        let frames = ctx.frames_in_flight() as usize;
+        let mut per_frame = Vec::with_capacity(frames);
+        for command_buffer in pool.allocate_buffers(frames) {
+            let args = factory.create_buffer(
+                BufferInfo {
+                    size: self.args_size(),
+                    usage: hal::buffer::Usage::STORAGE,
+                },
+                rendy::memory::Dynamic,
+            )?;
+            let set = factory.create_descriptor_set(layout.clone())?;
+            unsafe {
+                factory.write_descriptor_sets(vec![
+                    util::desc_write(
+                        set.raw(),
+                        0,
+                        pso::Descriptor::Buffer(args.raw(), None..None),
+                    ),
+                    util::desc_write(
+                        set.raw(),
+                        1,
+                        pso::Descriptor::Buffer(tiles.raw(), None..None),
+                    ),
+                ]);
+            }
+
+            // The arguments change every frame, but not the commands.
+            let mut recording = command_buffer.begin(MultiShot(SimultaneousUse), ());
+            unsafe {
+                let mut encoder = recording.encoder();
+                let (stages, barriers) = gfx_acquire_barriers(ctx, &buffers, None);
+                if !barriers.is_empty() {
+                    encoder.pipeline_barrier(stages, hal::memory::Dependencies::empty(), barriers);
+                }
+                encoder.bind_compute_pipeline(&pipeline);
+                encoder.bind_compute_descriptor_sets(
+                    &pipeline_layout,
+                    0,
+                    Some(set.raw()),
+                    std::iter::empty(),
+                );
+                encoder.dispatch(groups(self.tiling.columns), groups(self.tiling.rows), 1);
+                let (stages, barriers) = gfx_release_barriers(ctx, &buffers, None);
+                if !barriers.is_empty() {
+                    encoder.pipeline_barrier(stages, hal::memory::Dependencies::empty(), barriers);
+                }
+            }
+            let (submit, command_buffer) = recording.finish().submit();
+
+            per_frame.push(PerFrameLightCulling {
+                args,
+                set,
+                submit,
+                command_buffer,
+            });
+        }
+
+        Ok(LightCulling {
+            tiling: self.tiling,
+            max_point_lights: self.max_point_lights,
+            pipeline,
+            pipeline_layout,
+            pool,
+            per_frame,
+        })
+    }
+}
+
+/// Node built from `LightCullingDesc`.
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""))]
+pub struct LightCulling<B: Backend> {
+    tiling: LightTiling,
+    max_point_lights: usize,
+    pipeline: B::ComputePipeline,
+    pipeline_layout: B::PipelineLayout,
+    #[derivative(Debug = "ignore")]
+    pool: CommandPool<B, Compute>,
+    per_frame: Vec<PerFrameLightCulling<B>>,
+}
+
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""))]
+struct PerFrameLightCulling<B: Backend> {
+    args: Escape<Buffer<B>>,
+    set: Escape<DescriptorSet<B>>,
+    #[derivative(Debug = "ignore")]
+    submit: Submit<B, SimultaneousUse>,
+    #[derivative(Debug = "ignore")]
+    command_buffer:
+        CommandBuffer<B, Compute, PendingState<ExecutableState<MultiShot<SimultaneousUse>>>>,
+}
+
+impl<B: Backend> PerFrameLightCulling<B> {
+    fn write_args(
+        &mut self,
+        factory: &Factory<B>,
+        res: &Resources,
+        tiling: &LightTiling,
+        max_point_lights: usize,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("write_args");
+
+        let CameraGatherer {
+            projection, view, ..
+        } = CameraGatherer::gather(res);
+        let (lights, transforms, disabled) = <(
+            ReadStorage<'_, Light>,
+            ReadStorage<'_, Transform>,
+            ReadStorage<'_, LightDisabled>,
+        )>::fetch(res);
+
+        // Unbounded lights get a negative radius and reach every tile.
+        let bounds = point_lights(&lights, &transforms, &disabled)
+            .map(|(position, light)| {
+                let center = view.transform_point(&Point3::from(position));
+                let radius = if light.radius > 0.0 {
+                    light.radius
+                } else {
+                    -1.0
+                };
+                [center.x, center.y, center.z, radius]
+            })
+            .take(max_point_lights);
+
+        let args_range = 0..util::byte_size::<CullingArgs>(1);
+        let bounds_range =
+            util::next_range(&args_range, util::byte_size::<[f32; 4]>(max_point_lights));
+        let mut light_count = 0u32;
+
+        let mut mapped = self.args.map(factory, 0..bounds_range.end).unwrap();
+        let mut writer = unsafe { mapped.write::<u8>(factory, 0..bounds_range.end).unwrap() };
+        let dst_slice = unsafe { writer.slice() };
+        util::write_into_slice(
+            &mut dst_slice[util::usize_range(bounds_range)],
+            bounds.tap_count(&mut light_count),
+        );
+        util::write_into_slice(
+            &mut dst_slice[util::usize_range(args_range)],
+            Some(CullingArgs {
+                projection: projection.into(),
+                framebuffer_size: [tiling.width, tiling.height],
+                tile_count: [tiling.columns, tiling.rows],
+                light_count,
+                lights_per_tile: tiling.lights_per_tile,
+                _pad: [0; 2],
+            }),
+        );
+    }
+}
+
+impl<B: Backend> Node<B, Resources> for LightCulling<B> {
+    type Capability = Compute;
+    type Desc = LightCullingDesc;
+
+    unsafe fn run<'a>(
+        &mut self,
+        _ctx: &GraphContext<B>,
+        factory: &Factory<B>,
+        queue: &mut Queue<B>,
+        aux: &Resources,
+        frames: &Frames<B>,
+        waits: &[(&'a B::Semaphore, pso::PipelineStage)],
+        signals: &[&'a B::Semaphore],
+        fence: Option<&mut Fence<B>>,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("light_culling");
+
+        // The graph waits for the frame that last used this slot before running again.
+        let slot = frames.next().index() as usize % self.per_frame.len();
+        let this_frame = &mut self.per_frame[slot];
+        this_frame.write_args(factory, aux, &self.tiling, self.max_point_lights);
+
+        queue.submit(
+            Some(
+                Submission::new()
+                    .submits(Some(&this_frame.submit))
+                    .wait(waits.iter().cloned())
+                    .signal(signals.iter()),
+            ),
+            fence,
+        );
+    }
+
+    unsafe fn dispose(mut self, factory: &mut Factory<B>, _aux: &Resources) {
+        let pool = &mut self.pool;
+        for frame in self.per_frame.drain(..) {
+            pool.free_buffers(Some(frame.command_buffer.mark_complete()));
+        }
+        factory.destroy_command_pool(self.pool);
+        factory.device().destroy_compute_pipeline(self.pipeline);
+        factory
+            .device()
+            .destroy_pipeline_layout(self.pipeline_layout);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_cover_partial_edges() {
+        let tiling = LightTiling::new(1280, 721).with_lights_per_tile(31);
+        assert_eq!((tiling.columns, tiling.rows), (80, 46));
+        assert_eq!(tiling.buffer_size(), 80 * 46 * 32 * 4);
+
+        let empty = LightTiling::new(0, 0);
+        assert_eq!(empty.tile_count(), 1);
+    }
+}
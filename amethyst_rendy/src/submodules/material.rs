@@ -4,7 +4,7 @@ use crate::{
     rendy::{
         command::RenderPassEncoder,
         factory::Factory,
-        hal::{self, adapter::PhysicalDevice, device::Device, pso::Descriptor},
+        hal::{self, device::Device, pso::Descriptor},
         memory::Write as _,
         resource::{
             Buffer, BufferInfo, DescriptorSet, DescriptorSetLayout, Escape, Handle as RendyHandle,
@@ -16,6 +16,7 @@ use crate::{
 use amethyst_assets::{AssetStorage, Handle};
 use amethyst_core::ecs::{Read, Resources, SystemData};
 use glsl_layout::*;
+use smallvec::SmallVec;
 
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
@@ -119,6 +120,13 @@ impl<B: Backend> SlottedBuffer<B> {
     }
 }
 
+/// Versions of a material asset and its textures, as written to its descriptor set.
+#[derive(Debug, PartialEq)]
+struct MaterialVersions {
+    material: u32,
+    textures: SmallVec<[u32; 6]>,
+}
+
 #[derive(Debug)]
 enum MaterialState<B: Backend> {
     Unloaded {
@@ -128,10 +136,12 @@ enum MaterialState<B: Backend> {
         set: Escape<DescriptorSet<B>>,
         slot: usize,
         generation: u32,
+        handle: Handle<Material>,
+        versions: MaterialVersions,
     },
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct MaterialId(u32);
 
 #[derive(Debug)]
@@ -159,11 +169,7 @@ impl<B: Backend, T: for<'a> StaticTextureSet<'a>> MaterialSub<B, T> {
     }
 
     fn create_buffer(factory: &Factory<B>) -> Result<SlottedBuffer<B>, failure::Error> {
-        let align = factory
-            .physical()
-            .limits()
-            .min_uniform_buffer_offset_alignment;
-        let material_step = util::align_size::<pod::Material>(align, 1);
+        let material_step = util::align_size::<pod::Material>(util::ubo_offset_align(factory), 1);
         SlottedBuffer::new(factory, material_step, 1024, hal::buffer::Usage::UNIFORM)
     }
 
@@ -171,8 +177,130 @@ impl<B: Backend, T: for<'a> StaticTextureSet<'a>> MaterialSub<B, T> {
         self.layout.raw()
     }
 
-    pub fn maintain(&mut self) {
-        self.generation += self.generation.wrapping_add(1);
+    /// Rewrite the descriptor sets of materials used during the last frame whose asset
+    /// or textures changed since they were written, and start a new frame.
+    pub fn maintain(&mut self, factory: &Factory<B>, res: &Resources) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("maintain");
+
+        let (mat_storage, tex_storage) = <(
+            Read<'_, AssetStorage<Material>>,
+            Read<'_, AssetStorage<Texture>>,
+        )>::fetch(res);
+
+        for state in self.materials.iter_mut() {
+            let unload = match state {
+                MaterialState::Loaded {
+                    set,
+                    slot,
+                    generation,
+                    handle,
+                    versions,
+                } if *generation == self.generation => {
+                    match Self::versions(&mat_storage, &tex_storage, handle) {
+                        Some((_, ref new_versions)) if new_versions == versions => false,
+                        Some((mat, new_versions)) => {
+                            if new_versions.textures == versions.textures {
+                                // The set already points at the slot and textures, which
+                                // keeps per frame changes like scrolling cheap.
+                                Self::write_material(factory, &mut self.buffers, *slot, mat);
+                            } else {
+                                Self::write_set(
+                                    factory,
+                                    &mut self.buffers,
+                                    set,
+                                    *slot,
+                                    mat,
+                                    &tex_storage,
+                                );
+                            }
+                            *versions = new_versions;
+                            false
+                        }
+                        None => {
+                            self.allocator.release(*slot);
+                            true
+                        }
+                    }
+                }
+                _ => false,
+            };
+            if unload {
+                *state = MaterialState::Unloaded {
+                    generation: self.generation,
+                };
+            }
+        }
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    /// The material behind `handle` and the versions of it and its textures, if they
+    /// are all loaded.
+    fn versions<'a>(
+        mat_storage: &'a AssetStorage<Material>,
+        tex_storage: &AssetStorage<Texture>,
+        handle: &Handle<Material>,
+    ) -> Option<(&'a Material, MaterialVersions)> {
+        let (mat, version) = mat_storage.get_with_version(handle)?;
+        let textures = T::textures(mat)
+            .map(|t| {
+                let (tex, version) = tex_storage.get_with_version(t)?;
+                util::texture_desc::<B>(tex, hal::image::Layout::ShaderReadOnlyOptimal)?;
+                Some(*version)
+            })
+            .collect::<Option<SmallVec<_>>>()?;
+        Some((
+            mat,
+            MaterialVersions {
+                material: *version,
+                textures,
+            },
+        ))
+    }
+
+    /// Write `mat` to its slot of the uniform buffers.
+    fn write_material(
+        factory: &Factory<B>,
+        buffers: &mut [SlottedBuffer<B>],
+        slot: usize,
+        mat: &Material,
+    ) {
+        let pod = pod::Material::from_material(mat).std140();
+        buffers[slot / 1024].write(factory, slot % 1024, util::slice_as_bytes(&[pod]));
+    }
+
+    /// Write `mat` to its slot of the uniform buffers, and both to `set`.
+    fn write_set(
+        factory: &Factory<B>,
+        buffers: &mut [SlottedBuffer<B>],
+        set: &DescriptorSet<B>,
+        slot: usize,
+        mat: &Material,
+        tex_storage: &AssetStorage<Texture>,
+    ) {
+        use util::{desc_write, texture_desc};
+
+        Self::write_material(factory, buffers, slot, mat);
+        let buf_desc = buffers[slot / 1024].descriptor(slot % 1024);
+
+        unsafe {
+            let set = set.raw();
+
+            let tex_descs = T::textures(mat).enumerate().map(|(i, t)| {
+                desc_write(
+                    set,
+                    (i + 1) as u32,
+                    texture_desc(
+                        tex_storage.get(t).unwrap(),
+                        hal::image::Layout::ShaderReadOnlyOptimal,
+                    )
+                    .unwrap(),
+                )
+            });
+
+            let desc_iter = std::iter::once(desc_write(set, 0, buf_desc)).chain(tex_descs);
+            factory.write_descriptor_sets(desc_iter);
+        }
     }
 
     fn collect_unused(&mut self) {
@@ -200,61 +328,30 @@ impl<B: Backend, T: for<'a> StaticTextureSet<'a>> MaterialSub<B, T> {
         #[cfg(feature = "profiler")]
         profile_scope!("try_insert");
 
-        use util::{desc_write, slice_as_bytes, texture_desc};
         let (mat_storage, tex_storage) = <(
             Read<'_, AssetStorage<Material>>,
             Read<'_, AssetStorage<Texture>>,
         )>::fetch(res);
 
-        let mat = mat_storage.get(handle)?;
-
-        if T::textures(mat).any(|t| {
-            !tex_storage
-                .get(t)
-                .map_or(false, |tex| B::unwrap_texture(tex).is_some())
-        }) {
-            return None;
-        }
-
-        let pod = pod::Material::from_material(&mat).std140();
+        let (mat, versions) = Self::versions(&mat_storage, &tex_storage, handle)?;
 
         if self.allocator.would_overflow() {
             self.collect_unused();
         }
 
         let slot = self.allocator.reserve();
-        let buf_num = slot / 1024;
-        let buf_slot = slot % 1024;
-
-        while self.buffers.len() <= buf_num {
+        while self.buffers.len() <= slot / 1024 {
             self.buffers.push(Self::create_buffer(factory).unwrap());
         }
-        self.buffers[buf_num].write(factory, buf_slot, slice_as_bytes(&[pod]));
         let set = factory.create_descriptor_set(self.layout.clone()).unwrap();
-        let buf_desc = self.buffers[buf_num].descriptor(buf_slot);
-
-        unsafe {
-            let set = set.raw();
+        Self::write_set(factory, &mut self.buffers, &set, slot, mat, &tex_storage);
 
-            let tex_descs = T::textures(mat).enumerate().map(|(i, t)| {
-                desc_write(
-                    set,
-                    (i + 1) as u32,
-                    texture_desc(
-                        tex_storage.get(t).unwrap(),
-                        hal::image::Layout::ShaderReadOnlyOptimal,
-                    )
-                    .unwrap(),
-                )
-            });
-
-            let desc_iter = std::iter::once(desc_write(set, 0, buf_desc)).chain(tex_descs);
-            factory.write_descriptor_sets(desc_iter);
-        }
         Some(MaterialState::Loaded {
             set,
             slot,
             generation: self.generation,
+            handle: handle.clone(),
+            versions,
         })
     }
 
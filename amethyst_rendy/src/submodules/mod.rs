@@ -1,6 +1,8 @@
 mod environment;
 mod flat_environment;
+mod light_culling;
 mod material;
+mod sampled_image;
 mod skinning;
 mod texture;
 mod uniform;
@@ -10,7 +12,9 @@ pub mod gather;
 
 pub use environment::*;
 pub use flat_environment::*;
+pub use light_culling::*;
 pub use material::*;
+pub use sampled_image::*;
 pub use skinning::*;
 pub use texture::*;
 pub use uniform::*;
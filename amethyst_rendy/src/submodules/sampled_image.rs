@@ -0,0 +1,125 @@
+use crate::{
+    rendy::{
+        command::{QueueId, RenderPassEncoder},
+        factory::{Factory, ImageState},
+        graph::{GraphContext, NodeImage},
+        hal::{
+            self,
+            image::{Filter, SamplerInfo, WrapMode},
+            pso::Descriptor,
+        },
+        resource::{
+            DescriptorSet, DescriptorSetLayout, Escape, Handle as RendyHandle, ImageView, Sampler,
+        },
+        texture::Texture,
+    },
+    types::Backend,
+    util,
+};
+
+#[derive(Debug)]
+enum ImageSource<B: Backend> {
+    Node {
+        view: Escape<ImageView<B>>,
+        sampler: RendyHandle<Sampler<B>>,
+    },
+    Texture(Texture<B>),
+}
+
+/// Single sampled image bound as its own descriptor set.
+///
+/// Used to read images produced by earlier graph nodes, such as depth or
+/// screen space ambient occlusion, from a fragment shader.
+#[derive(Debug)]
+pub struct SampledImageSub<B: Backend> {
+    layout: RendyHandle<DescriptorSetLayout<B>>,
+    set: Escape<DescriptorSet<B>>,
+    source: ImageSource<B>,
+}
+
+impl<B: Backend> SampledImageSub<B> {
+    /// Sample an image of the render graph.
+    ///
+    /// Depth-stencil images are viewed through their depth aspect only.
+    pub fn new(
+        ctx: &GraphContext<B>,
+        factory: &Factory<B>,
+        image: &NodeImage,
+        filter: Filter,
+    ) -> Result<Self, failure::Error> {
+        let view = util::node_image_view(ctx, factory, image)?;
+        let sampler = factory.get_sampler(SamplerInfo::new(filter, WrapMode::Clamp))?;
+
+        Self::with_source(factory, ImageSource::Node { view, sampler }, image.layout)
+    }
+
+    /// Sample a single opaque white texel, for shaders expecting an image
+    /// that was not provided.
+    pub fn white(factory: &mut Factory<B>, queue: QueueId) -> Result<Self, failure::Error> {
+        use palette::LinSrgba;
+        use rendy::texture::palette::load_from_linear_rgba;
+
+        let layout = hal::image::Layout::ShaderReadOnlyOptimal;
+        let texture = load_from_linear_rgba(LinSrgba::new(1.0, 1.0, 1.0, 1.0)).build(
+            ImageState {
+                queue,
+                stage: hal::pso::PipelineStage::FRAGMENT_SHADER,
+                access: hal::image::Access::SHADER_READ,
+                layout,
+            },
+            factory,
+        )?;
+
+        Self::with_source(factory, ImageSource::Texture(texture), layout)
+    }
+
+    fn with_source(
+        factory: &Factory<B>,
+        source: ImageSource<B>,
+        layout: hal::image::Layout,
+    ) -> Result<Self, failure::Error> {
+        let set_layout: RendyHandle<DescriptorSetLayout<B>> =
+            set_layout! {factory, [1] CombinedImageSampler FRAGMENT};
+        let set = factory.create_descriptor_set(set_layout.clone())?;
+
+        let desc = match &source {
+            ImageSource::Node { view, sampler } => {
+                Descriptor::CombinedImageSampler(view.raw(), layout, sampler.raw())
+            }
+            ImageSource::Texture(texture) => Descriptor::CombinedImageSampler(
+                texture.view().raw(),
+                layout,
+                texture.sampler().raw(),
+            ),
+        };
+
+        unsafe {
+            factory.write_descriptor_sets(Some(util::desc_write(set.raw(), 0, desc)));
+        }
+
+        Ok(Self {
+            layout: set_layout,
+            set,
+            source,
+        })
+    }
+
+    pub fn raw_layout(&self) -> &B::DescriptorSetLayout {
+        self.layout.raw()
+    }
+
+    #[inline]
+    pub fn bind(
+        &self,
+        pipeline_layout: &B::PipelineLayout,
+        set_id: u32,
+        encoder: &mut RenderPassEncoder<'_, B>,
+    ) {
+        encoder.bind_graphics_descriptor_sets(
+            pipeline_layout,
+            set_id,
+            Some(self.set.raw()),
+            std::iter::empty(),
+        );
+    }
+}
@@ -28,7 +28,7 @@ enum TextureState<B: Backend> {
     },
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct TextureId(u32);
 
 #[derive(Debug)]
@@ -92,7 +92,7 @@ impl<B: Backend> TextureSub<B> {
                 _ => {}
             }
         }
-        self.generation += self.generation.wrapping_add(1);
+        self.generation = self.generation.wrapping_add(1);
     }
 
     fn try_insert(
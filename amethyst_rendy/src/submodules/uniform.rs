@@ -71,7 +71,7 @@ where
         let mut mapped = this_image.map(factory);
         let mut writer = unsafe {
             mapped
-                .write::<u8>(factory.device(), 0..std::mem::size_of::<T::Std140>() as u64)
+                .write::<u8>(factory.device(), 0..util::byte_size::<T::Std140>(1))
                 .unwrap()
         };
         let slice = unsafe { writer.slice() };
@@ -100,7 +100,7 @@ where
         let buffer = factory
             .create_buffer(
                 BufferInfo {
-                    size: std::mem::size_of::<T::Std140>() as u64,
+                    size: util::byte_size::<T::Std140>(1),
                     usage: hal::buffer::Usage::UNIFORM,
                 },
                 rendy::memory::Dynamic,
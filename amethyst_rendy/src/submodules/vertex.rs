@@ -14,22 +14,41 @@ use core::{marker::PhantomData, ops::Range};
 #[derive(Debug)]
 pub struct DynamicVertex<B: Backend, T: 'static> {
     per_image: Vec<PerImageDynamicVertex<B>>,
+    growth: util::BufferGrowth,
+    shrink_after: Option<u32>,
     marker: PhantomData<T>,
 }
 
 #[derive(Debug)]
 struct PerImageDynamicVertex<B: Backend> {
     buffer: Option<Escape<Buffer<B>>>,
+    // Consecutive writes using at most half of the buffer.
+    low_usage_frames: u32,
 }
 
 impl<B: Backend, T: 'static> DynamicVertex<B, T> {
     pub fn new() -> Self {
         Self {
             per_image: Vec::new(),
+            growth: util::BufferGrowth::default(),
+            shrink_after: None,
             marker: PhantomData,
         }
     }
 
+    /// Size buffers with `growth` when they are too small, powers of two by default.
+    pub fn with_growth(mut self, growth: util::BufferGrowth) -> Self {
+        self.growth = growth;
+        self
+    }
+
+    /// Reallocate smaller buffers once `frames` writes in a row would fit in half of them.
+    /// Buffers never shrink by default.
+    pub fn with_shrink_after(mut self, frames: Option<u32>) -> Self {
+        self.shrink_after = frames;
+        self
+    }
+
     pub fn write<I>(
         &mut self,
         factory: &Factory<B>,
@@ -52,8 +71,10 @@ impl<B: Backend, T: 'static> DynamicVertex<B, T> {
             &mut self.per_image[index]
         };
 
-        let buf_size = max_num_items * core::mem::size_of::<T>() as u64;
-        if let Some((allocated, mut mapped)) = this_image.map(factory, 0..buf_size) {
+        let buf_size = max_num_items * util::byte_size::<T>(1);
+        if let Some((allocated, mut mapped)) =
+            this_image.map(factory, 0..buf_size, self.growth, self.shrink_after)
+        {
             let mut writer = unsafe { mapped.write::<u8>(factory.device(), 0..buf_size).unwrap() };
             let mut slice = unsafe { writer.slice() };
 
@@ -85,16 +106,40 @@ impl<B: Backend, T: 'static> DynamicVertex<B, T> {
 
 impl<B: Backend> PerImageDynamicVertex<B> {
     fn new() -> Self {
-        Self { buffer: None }
+        Self {
+            buffer: None,
+            low_usage_frames: 0,
+        }
     }
 
-    fn ensure(&mut self, factory: &Factory<B>, max_size: u64) -> bool {
-        util::ensure_buffer(
+    fn ensure(
+        &mut self,
+        factory: &Factory<B>,
+        max_size: u64,
+        growth: util::BufferGrowth,
+        shrink_after: Option<u32>,
+    ) -> bool {
+        if let (Some(frames), Some(buffer)) = (shrink_after, &self.buffer) {
+            if growth.grown_size(max_size) * 2 <= buffer.size() {
+                self.low_usage_frames += 1;
+                if self.low_usage_frames >= frames {
+                    self.buffer = None;
+                }
+            } else {
+                self.low_usage_frames = 0;
+            }
+        }
+        if self.buffer.is_none() {
+            self.low_usage_frames = 0;
+        }
+
+        util::ensure_buffer_with_growth(
             &factory,
             &mut self.buffer,
             hal::buffer::Usage::VERTEX,
             rendy::memory::Dynamic,
             max_size,
+            growth,
         )
         .unwrap()
     }
@@ -103,8 +148,10 @@ impl<B: Backend> PerImageDynamicVertex<B> {
         &'a mut self,
         factory: &Factory<B>,
         range: Range<u64>,
+        growth: util::BufferGrowth,
+        shrink_after: Option<u32>,
     ) -> Option<(bool, MappedRange<'a, B>)> {
-        let alloc = self.ensure(factory, range.end);
+        let alloc = self.ensure(factory, range.end, growth, shrink_after);
         if let Some(buffer) = &mut self.buffer {
             Some((alloc, buffer.map(factory.device(), range).unwrap()))
         } else {
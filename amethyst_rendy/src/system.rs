@@ -1,14 +1,21 @@
 //! Renderer system
 use crate::{
-    camera::{ActiveCamera, Camera},
+    camera::{ActiveCamera, Camera, Exposure},
+    capture::{CaptureFrame, CapturedFrame},
     debug_drawing::DebugLinesComponent,
-    light::Light,
+    light::{Light, LightDisabled, PrimaryDirectionalLight},
     mtl::{Material, MaterialDefaults},
-    resources::Tint,
+    particle::ParticleEmitter,
+    resources::{
+        AspectRatioLock, ClearColor, DepthMode, DitheredFade, LightStats, Outline, RenderStats,
+        RequireHardwareGpu, SampleCount, SrgbSurface, Tint, Vsync,
+    },
     skinning::JointTransforms,
     sprite::SpriteRender,
-    transparent::Transparent,
+    submodules::LightCullingSupported,
+    transparent::{RenderOrder, Transparent},
     types::{Backend, Mesh, Texture},
+    util,
     visibility::Visibility,
 };
 use amethyst_assets::{
@@ -17,17 +24,23 @@ use amethyst_assets::{
 use amethyst_core::{
     components::Transform,
     ecs::{Read, ReadExpect, ReadStorage, Resources, RunNow, SystemData, Write, WriteExpect},
+    shrev::EventChannel,
     timing::Time,
     Hidden, HiddenPropagate,
 };
-use palette::{LinSrgba, Srgba};
+use amethyst_window::ScreenDimensions;
+use palette::{LinSrgba, Srgb, Srgba};
 use rendy::{
     command::{Families, QueueId},
-    factory::{Factory, ImageState},
+    factory::{
+        BasicDevicesConfigure, BasicHeapsConfigure, DevicesConfigure, Factory, ImageState,
+        OneGraphicsQueue,
+    },
     graph::{Graph, GraphBuilder},
+    hal::{self, adapter::AdapterInfo},
     texture::palette::{load_from_linear_rgba, load_from_srgba},
 };
-use std::sync::Arc;
+use std::{cell::RefCell, rc::Rc, sync::Arc};
 
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
@@ -41,6 +54,31 @@ pub trait GraphCreator<B: Backend> {
     fn builder(&mut self, factory: &mut Factory<B>, res: &Resources) -> GraphBuilder<B, Resources>;
 }
 
+/// Frames in a row the render graph may fail to build before the renderer gives up.
+const MAX_RECOVERY_ATTEMPTS: u32 = 3;
+
+/// Sent through the `EventChannel<RendererRecovered>` resource when a frame is drawn again
+/// after the render graph failed to build, such as when the swapchain can't be created for
+/// a surface invalidated by a driver reset or by leaving exclusive fullscreen.
+///
+/// The graph is built again every frame from the current `ScreenDimensions` until it
+/// succeeds. A swapchain going out of date while frames are drawn is recreated by the
+/// present node itself. Losing the device is not recovered from, as nothing created by
+/// the factory can be used anymore, and panics with the error instead.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RendererRecovered {
+    /// Number of times the graph failed to build before it drew a frame.
+    pub attempts: u32,
+    /// Description of the first failure.
+    pub error: String,
+}
+
+/// Frames the render graph ran, counting the one being prepared.
+///
+/// Render groups sharing data through clones upload it once per frame by it.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct RenderedFrames(pub(crate) u64);
+
 pub struct RenderingSystem<B, G>
 where
     B: Backend,
@@ -49,6 +87,17 @@ where
     graph: Option<Graph<B, Resources>>,
     families: Option<Families<B>>,
     graph_creator: G,
+    vsync: Vsync,
+    samples: SampleCount,
+    srgb: SrgbSurface,
+    clear_color: ClearColor,
+    depth_mode: DepthMode,
+    aspect_ratio_lock: AspectRatioLock,
+    minimized: bool,
+    // Failed builds since the last frame drawn, and the first error.
+    failure: Option<(u32, String)>,
+    #[cfg(feature = "gpu-profiler")]
+    gpu_profiler: Option<crate::gpu_profiler::GpuProfiler<B>>,
 }
 
 impl<B, G> RenderingSystem<B, G>
@@ -61,6 +110,16 @@ where
             graph: None,
             families: None,
             graph_creator,
+            vsync: Vsync::default(),
+            samples: SampleCount::default(),
+            srgb: SrgbSurface::default(),
+            clear_color: ClearColor::default(),
+            depth_mode: DepthMode::default(),
+            aspect_ratio_lock: AspectRatioLock::default(),
+            minimized: false,
+            failure: None,
+            #[cfg(feature = "gpu-profiler")]
+            gpu_profiler: None,
         }
     }
 }
@@ -81,17 +140,38 @@ type SetupData<'a> = (
     ReadStorage<'a, Handle<Texture>>,
     ReadStorage<'a, Handle<Material>>,
     ReadStorage<'a, Tint>,
+    ReadStorage<'a, Outline>,
+    ReadStorage<'a, DitheredFade>,
     ReadStorage<'a, Light>,
-    ReadStorage<'a, Camera>,
+    (
+        ReadStorage<'a, LightDisabled>,
+        ReadStorage<'a, PrimaryDirectionalLight>,
+    ),
+    (ReadStorage<'a, Camera>, ReadStorage<'a, Exposure>),
     ReadStorage<'a, Hidden>,
     ReadStorage<'a, HiddenPropagate>,
     ReadStorage<'a, DebugLinesComponent>,
     ReadStorage<'a, Transparent>,
+    ReadStorage<'a, RenderOrder>,
     ReadStorage<'a, Transform>,
-    ReadStorage<'a, SpriteRender>,
+    (
+        ReadStorage<'a, SpriteRender>,
+        ReadStorage<'a, ParticleEmitter>,
+    ),
     Option<Read<'a, Visibility>>,
     Option<Read<'a, ActiveCamera>>,
     ReadStorage<'a, JointTransforms>,
+    (Read<'a, Vsync>, Read<'a, AspectRatioLock>),
+    Read<'a, SampleCount>,
+    Read<'a, SrgbSurface>,
+    Read<'a, ClearColor>,
+    Read<'a, DepthMode>,
+    (Read<'a, LightStats>, Read<'a, RenderStats>),
+    (
+        Read<'a, CaptureFrame>,
+        Read<'a, EventChannel<CapturedFrame>>,
+        Read<'a, EventChannel<RendererRecovered>>,
+    ),
 );
 
 // struct MeshProcessor<B: Backend>(PhantomData<B>);
@@ -122,8 +202,9 @@ where
                 #[cfg(feature = "profiler")]
                 profile_scope!("process_mesh");
 
-                b.0.build(*queue_id, &mut factory)
-                    .map(B::wrap_mesh)
+                b.builder
+                    .build(*queue_id, &mut factory)
+                    .map(|mesh| B::wrap_mesh(mesh).with_bounds(b.bounds))
                     .map(ProcessingState::Loaded)
                     .map_err(|e| e.compat().into())
             },
@@ -169,7 +250,7 @@ where
         );
     }
 
-    fn rebuild_graph(&mut self, res: &Resources) {
+    fn rebuild_graph(&mut self, res: &Resources) -> Result<(), failure::Error> {
         #[cfg(feature = "profiler")]
         profile_scope!("rebuild_graph");
 
@@ -181,6 +262,17 @@ where
             graph.dispose(&mut *factory, res);
         }
 
+        // The disposed graph is done with its queries, groups of the new graph register
+        // their scopes again.
+        #[cfg(feature = "gpu-profiler")]
+        {
+            if let Some(mut timestamps) =
+                res.try_fetch_mut::<crate::gpu_profiler::GpuTimestamps<B>>()
+            {
+                timestamps.clear();
+            }
+        }
+
         let builder = {
             #[cfg(feature = "profiler")]
             profile_scope!("run_graph_creator");
@@ -190,21 +282,67 @@ where
         let graph = {
             #[cfg(feature = "profiler")]
             profile_scope!("build_graph");
-            builder
-                .build(&mut factory, self.families.as_mut().unwrap(), res)
-                .unwrap()
+            builder.build(&mut factory, self.families.as_mut().unwrap(), res)?
         };
 
         self.graph = Some(graph);
+        Ok(())
+    }
+
+    /// Count a failed build of the graph, which is built again next frame.
+    ///
+    /// Panics when the device is lost or the graph failed to build too many times in a row.
+    fn record_failure(&mut self, error: failure::Error) {
+        if is_device_lost(&error) {
+            panic!(
+                "Graphics device lost, the renderer can't continue: {}",
+                error
+            );
+        }
+        let attempts = match self.failure.take() {
+            Some((attempts, first)) => {
+                self.failure = Some((attempts + 1, first));
+                attempts + 1
+            }
+            None => {
+                self.failure = Some((1, error.to_string()));
+                1
+            }
+        };
+        if attempts > MAX_RECOVERY_ATTEMPTS {
+            panic!(
+                "Failed to build the render graph {} times in a row: {}",
+                attempts, error
+            );
+        }
+        log::warn!("Failed to build the render graph, retrying: {}", error);
     }
 
     fn run_graph(&mut self, res: &Resources) {
+        // Render groups add up their draws while preparing the frame.
+        *res.fetch_mut::<RenderStats>() = RenderStats::default();
+        res.fetch_mut::<RenderedFrames>().0 += 1;
+
         let mut factory = res.fetch_mut::<Factory<B>>();
         factory.maintain(self.families.as_mut().unwrap());
+        #[cfg(feature = "gpu-profiler")]
+        {
+            let queue = *res.fetch::<QueueId>();
+            if let Some(profiler) = &mut self.gpu_profiler {
+                profiler.maintain(&factory, self.families.as_mut().unwrap(), queue, res);
+            }
+        }
+
         self.graph
             .as_mut()
             .unwrap()
-            .run(&mut factory, self.families.as_mut().unwrap(), res)
+            .run(&mut factory, self.families.as_mut().unwrap(), res);
+
+        if let Some((attempts, error)) = self.failure.take() {
+            log::info!("Renderer recovered after {} failed builds", attempts);
+            res.fetch_mut::<EventChannel<RendererRecovered>>()
+                .single_write(RendererRecovered { attempts, error });
+        }
     }
 }
 
@@ -216,25 +354,136 @@ where
     fn run_now(&mut self, res: &'a Resources) {
         self.asset_loading(SystemData::fetch(res));
 
+        // A minimized window can have no area, which no swapchain or viewport can match.
+        // Nothing is drawn until it is restored, and the graph is rebuilt then, as the
+        // swapchain may have been invalidated meanwhile.
+        if has_no_area(res) {
+            if !self.minimized {
+                log::debug!("Window has no area, rendering is paused");
+                self.minimized = true;
+            }
+            return;
+        }
+        let restored = std::mem::replace(&mut self.minimized, false);
+
         let rebuild = self.graph_creator.rebuild(res);
-        if self.graph.is_none() || rebuild {
-            self.rebuild_graph(res);
+
+        // Switching present mode requires the swapchain to be recreated.
+        let vsync = *res.fetch::<Vsync>();
+        let vsync_changed = vsync != self.vsync;
+        self.vsync = vsync;
+
+        // Attachments and pipelines are created for a fixed sample count. A newly requested
+        // count is lowered to one the device supports before graph creators read it.
+        let samples = {
+            let mut samples = res.fetch_mut::<SampleCount>();
+            if *samples != self.samples {
+                let factory = res.fetch::<Factory<B>>();
+                samples.0 = util::select_sample_count(&factory, samples.0);
+            }
+            *samples
+        };
+        let samples_changed = samples != self.samples;
+        self.samples = samples;
+
+        // The swapchain format is picked when the graph is built.
+        let srgb = *res.fetch::<SrgbSurface>();
+        let srgb_changed = srgb != self.srgb;
+        self.srgb = srgb;
+
+        // Clear values are part of the render passes.
+        let clear_color = *res.fetch::<ClearColor>();
+        let clear_color_changed = clear_color != self.clear_color;
+        self.clear_color = clear_color;
+
+        // Depth tests of every pass sharing the depth image follow the depth mode.
+        let depth_mode = *res.fetch::<DepthMode>();
+        let depth_mode_changed = depth_mode != self.depth_mode;
+        self.depth_mode = depth_mode;
+
+        // Viewports are baked into the pipelines of the scene passes.
+        let aspect_ratio_lock = *res.fetch::<AspectRatioLock>();
+        let aspect_ratio_lock_changed = aspect_ratio_lock != self.aspect_ratio_lock;
+        self.aspect_ratio_lock = aspect_ratio_lock;
+
+        // Pipelines are created again from the files of shaders that changed.
+        #[cfg(all(feature = "shader-hot-reload", debug_assertions))]
+        let shaders_changed = res
+            .try_fetch_mut::<crate::shader_reload::ShaderHotReload>()
+            .map_or(false, |mut reload| reload.poll());
+        #[cfg(not(all(feature = "shader-hot-reload", debug_assertions)))]
+        let shaders_changed = false;
+
+        if self.graph.is_none()
+            || restored
+            || shaders_changed
+            || rebuild
+            || vsync_changed
+            || samples_changed
+            || srgb_changed
+            || clear_color_changed
+            || depth_mode_changed
+            || aspect_ratio_lock_changed
+        {
+            if let Err(e) = self.rebuild_graph(res) {
+                self.record_failure(e);
+                return;
+            }
         }
         self.run_graph(res);
     }
 
     fn setup(&mut self, res: &mut Resources) {
-        let config: rendy::factory::Config = Default::default();
+        let picked = Rc::new(RefCell::new(None));
+        let config = rendy::factory::Config {
+            devices: PickedAdapter(picked.clone()),
+            heaps: BasicHeapsConfigure,
+            queues: OneGraphicsQueue,
+        };
         let (factory, families): (Factory<B>, _) = rendy::factory::init(config).unwrap();
+        if let Some(info) = picked.borrow().as_ref() {
+            let require_hardware = res
+                .try_fetch::<RequireHardwareGpu>()
+                .map_or(false, |require| require.0);
+            check_adapter(info, require_hardware);
+        }
 
         let queue_id = QueueId {
             family: families.family_by_index(0).id(),
             index: 0,
         };
+        res.insert(LightCullingSupported(
+            families.family_by_index(0).capability().supports_compute(),
+        ));
+
+        #[cfg(feature = "gpu-profiler")]
+        {
+            use crate::gpu_profiler::{GpuProfiler, GpuProfilerStats, GpuTimestamps};
+            match (
+                GpuTimestamps::new(&factory),
+                GpuProfiler::new(&factory, queue_id),
+            ) {
+                (Ok(timestamps), Ok(profiler)) => {
+                    res.insert(timestamps);
+                    self.gpu_profiler = Some(profiler);
+                }
+                (timestamps, profiler) => {
+                    log::error!("GPU profiler unavailable, render groups are not timed");
+                    if let Ok(timestamps) = timestamps {
+                        timestamps.dispose(&factory);
+                    }
+                    if let Ok(profiler) = profiler {
+                        profiler.dispose(&factory);
+                    }
+                }
+            }
+            res.insert(GpuProfilerStats::default());
+        }
 
         self.families = Some(families);
         res.insert(factory);
         res.insert(queue_id);
+        res.insert(RenderedFrames::default());
         AssetLoadingData::<B>::setup(res);
         SetupData::setup(res);
 
@@ -262,6 +511,69 @@ where
     }
 }
 
+/// Picks the adapter rendy picks by default, keeping its description to report it.
+#[derive(Debug)]
+struct PickedAdapter(Rc<RefCell<Option<AdapterInfo>>>);
+
+impl DevicesConfigure for PickedAdapter {
+    fn pick<B: hal::Backend>(&self, adapters: &[hal::Adapter<B>]) -> usize {
+        let index = BasicDevicesConfigure.pick(adapters);
+        *self.0.borrow_mut() = Some(adapters[index].info.clone());
+        index
+    }
+}
+
+/// Log the adapter rendering is done with, and warn when it is a software one.
+///
+/// # Panics
+///
+/// Panics on a software adapter when `require_hardware` is set.
+fn check_adapter(info: &AdapterInfo, require_hardware: bool) {
+    log::info!(
+        "Rendering with adapter {} ({:?})",
+        info.name,
+        info.device_type
+    );
+    if !util::is_software_adapter(info) {
+        return;
+    }
+    let message = format!(
+        "No hardware GPU adapter was found, rendering on the software adapter {}. \
+         Expect very low frame rates. Check that the graphics drivers of the GPU are installed \
+         and support the backend this game was built with.",
+        info.name
+    );
+    if require_hardware {
+        log::error!("{}", message);
+        panic!("{} `DisplayConfig::require_hardware_gpu` is set.", message);
+    }
+    log::warn!("{}", message);
+}
+
+/// Whether `error` comes from the device being lost.
+fn is_device_lost(error: &failure::Error) -> bool {
+    use hal::{device, window};
+    error.iter_chain().any(|cause| {
+        cause.downcast_ref::<device::DeviceLost>().is_some()
+            || match cause.downcast_ref::<device::OomOrDeviceLost>() {
+                Some(device::OomOrDeviceLost::DeviceLost(_)) => true,
+                _ => false,
+            }
+            || match cause.downcast_ref::<window::CreationError>() {
+                Some(window::CreationError::DeviceLost(_)) => true,
+                _ => false,
+            }
+    })
+}
+
+/// Whether the window is too small to be drawn to, as when it is minimized on Windows.
+fn has_no_area(res: &Resources) -> bool {
+    res.try_fetch::<ScreenDimensions>()
+        .map_or(false, |dimensions| {
+            dimensions.width() < 1.0 || dimensions.height() < 1.0
+        })
+}
+
 fn create_default_mat<B: Backend>(res: &mut Resources) -> Material {
     use crate::mtl::TextureOffset;
 
@@ -269,7 +581,7 @@ fn create_default_mat<B: Backend>(res: &mut Resources) -> Material {
 
     let loader = res.fetch::<Loader>();
 
-    let albedo = load_from_srgba(Srgba::new(0.5, 0.5, 0.5, 1.0));
+    let albedo = load_from_srgba(Srgba::new(1.0, 1.0, 1.0, 1.0));
     let emission = load_from_srgba(Srgba::new(0.0, 0.0, 0.0, 0.0));
     let normal = load_from_linear_rgba(LinSrgba::new(0.5, 0.5, 1.0, 1.0));
     let metallic_roughness = load_from_linear_rgba(LinSrgba::new(0.0, 0.5, 0.0, 0.0));
@@ -288,11 +600,33 @@ fn create_default_mat<B: Backend>(res: &mut Resources) -> Material {
     Material {
         alpha_cutoff: 0.01,
         albedo,
+        base_color: Srgba::new(1.0, 1.0, 1.0, 1.0),
         emission,
+        emission_factor: Srgb::new(1.0, 1.0, 1.0),
+        emission_intensity: 1.0,
         normal,
         metallic_roughness,
+        metallic_factor: 1.0,
+        roughness_factor: 1.0,
         ambient_occlusion,
         cavity,
         uv_offset: TextureOffset::default(),
+        uv_scroll: [0.0, 0.0],
+        uv_scale: [1.0, 1.0],
+        double_sided: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_loss_is_told_apart() {
+        assert!(is_device_lost(&hal::device::DeviceLost.into()));
+        assert!(is_device_lost(
+            &hal::window::CreationError::DeviceLost(hal::device::DeviceLost).into()
+        ));
+        assert!(!is_device_lost(&failure::format_err!("Surface is lost")));
     }
 }
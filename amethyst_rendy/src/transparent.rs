@@ -1,5 +1,9 @@
 use amethyst_assets::PrefabData;
-use amethyst_core::ecs::{prelude::Component, storage::NullStorage, Entity, WriteStorage};
+use amethyst_core::ecs::{
+    prelude::{Component, DenseVecStorage},
+    storage::NullStorage,
+    Entity, WriteStorage,
+};
 use amethyst_error::Error;
 
 /// Transparent mesh component
@@ -25,3 +29,42 @@ impl<'a> PrefabData<'a> for Transparent {
         Ok(())
     }
 }
+
+/// Forces the place of a `Transparent` entity in the drawing order, before its depth is
+/// considered. Entities with a higher order are drawn later, over the others.
+///
+/// Entities without this component have an order of `0`, so a positive order draws an
+/// entity after every unordered one, such as a label placed on a decal of the same plane.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct RenderOrder(pub i32);
+
+impl Component for RenderOrder {
+    type Storage = DenseVecStorage<Self>;
+}
+
+impl<'a> PrefabData<'a> for RenderOrder {
+    type SystemData = WriteStorage<'a, RenderOrder>;
+    type Result = ();
+
+    fn add_to_entity(
+        &self,
+        entity: Entity,
+        storage: &mut Self::SystemData,
+        _: &[Entity],
+        _: &[Entity],
+    ) -> Result<(), Error> {
+        storage.insert(entity, *self)?;
+        Ok(())
+    }
+}
@@ -1,12 +1,15 @@
 use amethyst_assets::{Asset, Handle};
-use amethyst_core::ecs::DenseVecStorage;
+use amethyst_core::{ecs::DenseVecStorage, math::Vector3};
+use rendy::resource::{Escape, Handle as RendyHandle, ImageView, Sampler};
 use serde::{Deserialize, Serialize};
 
 pub trait Backend: rendy::hal::Backend {
     fn unwrap_mesh(mesh: &Mesh) -> Option<&rendy::mesh::Mesh<Self>>;
     fn unwrap_texture(texture: &Texture) -> Option<&rendy::texture::Texture<Self>>;
+    fn unwrap_render_target(texture: &Texture) -> Option<&RenderTargetView<Self>>;
     fn wrap_mesh(mesh: rendy::mesh::Mesh<Self>) -> Mesh;
     fn wrap_texture(texture: rendy::texture::Texture<Self>) -> Texture;
+    fn wrap_render_target(target: RenderTargetView<Self>) -> Texture;
 }
 
 macro_rules! impl_backends {
@@ -24,21 +27,19 @@ macro_rules! impl_backends {
             )*
         }
 
-        /// Mesh wrapper.
         #[derive(Debug)]
-        pub enum Mesh {
+        enum BackendMesh {
             $(
                 #[cfg(feature = $feature)]
                 $variant(rendy::mesh::Mesh<$backend>),
             )*
         }
 
-        /// Texture wrapper.
         #[derive(Debug)]
-        pub enum Texture {
+        enum BackendTexture {
             $(
                 #[cfg(feature = $feature)]
-                $variant(rendy::texture::Texture<$backend>),
+                $variant(TextureSource<$backend>),
             )*
         }
 
@@ -48,7 +49,7 @@ macro_rules! impl_backends {
                 #[inline]
                 #[allow(irrefutable_let_patterns)]
                 fn unwrap_mesh(mesh: &Mesh) -> Option<&rendy::mesh::Mesh<Self>> {
-                    if let Mesh::$variant(inner) = mesh {
+                    if let BackendMesh::$variant(inner) = &mesh.inner {
                         Some(inner)
                     } else {
                         None
@@ -57,7 +58,20 @@ macro_rules! impl_backends {
                 #[inline]
                 #[allow(irrefutable_let_patterns)]
                 fn unwrap_texture(texture: &Texture) -> Option<&rendy::texture::Texture<Self>> {
-                    if let Texture::$variant(inner) = texture {
+                    if let Some(BackendTexture::$variant(TextureSource::Loaded(inner))) =
+                        &texture.inner
+                    {
+                        Some(inner)
+                    } else {
+                        None
+                    }
+                }
+                #[inline]
+                #[allow(irrefutable_let_patterns)]
+                fn unwrap_render_target(texture: &Texture) -> Option<&RenderTargetView<Self>> {
+                    if let Some(BackendTexture::$variant(TextureSource::Target(inner))) =
+                        &texture.inner
+                    {
                         Some(inner)
                     } else {
                         None
@@ -65,11 +79,22 @@ macro_rules! impl_backends {
                 }
                 #[inline]
                 fn wrap_mesh(mesh: rendy::mesh::Mesh<Self>) -> Mesh {
-                    Mesh::$variant(mesh)
+                    Mesh {
+                        inner: BackendMesh::$variant(mesh),
+                        bounds: None,
+                    }
                 }
                 #[inline]
                 fn wrap_texture(texture: rendy::texture::Texture<Self>) -> Texture {
-                    Texture::$variant(texture)
+                    Texture {
+                        inner: Some(BackendTexture::$variant(TextureSource::Loaded(texture))),
+                    }
+                }
+                #[inline]
+                fn wrap_render_target(target: RenderTargetView<Self>) -> Texture {
+                    Texture {
+                        inner: Some(BackendTexture::$variant(TextureSource::Target(target))),
+                    }
                 }
             }
         )*
@@ -106,12 +131,99 @@ impl Backend for rendy::empty::Backend {
     fn unwrap_texture(_: &Texture) -> Option<&rendy::texture::Texture<Self>> {
         None
     }
+    fn unwrap_render_target(_: &Texture) -> Option<&RenderTargetView<Self>> {
+        None
+    }
     fn wrap_mesh(_: rendy::mesh::Mesh<Self>) -> Mesh {
         unimplemented!()
     }
     fn wrap_texture(_: rendy::texture::Texture<Self>) -> Texture {
         unimplemented!()
     }
+    fn wrap_render_target(_: RenderTargetView<Self>) -> Texture {
+        unimplemented!()
+    }
+}
+
+/// Texture wrapper.
+#[derive(Debug)]
+pub struct Texture {
+    // `None` until the render graph draws a render target texture.
+    inner: Option<BackendTexture>,
+}
+
+impl Texture {
+    /// Placeholder for the texture of a render target, which can be sampled once
+    /// the render graph replaces it by the image it draws into.
+    ///
+    /// Materials using it are skipped until then.
+    pub fn render_target() -> Self {
+        Texture { inner: None }
+    }
+}
+
+#[derive(Debug)]
+enum TextureSource<B: rendy::hal::Backend> {
+    Loaded(rendy::texture::Texture<B>),
+    Target(RenderTargetView<B>),
+}
+
+/// Image of the render graph sampled through a `Texture`, see `RenderTarget`.
+#[derive(Debug)]
+pub struct RenderTargetView<B: rendy::hal::Backend> {
+    view: Escape<ImageView<B>>,
+    sampler: RendyHandle<Sampler<B>>,
+}
+
+impl<B: rendy::hal::Backend> RenderTargetView<B> {
+    /// Sample `view` with `sampler`.
+    pub fn new(view: Escape<ImageView<B>>, sampler: RendyHandle<Sampler<B>>) -> Self {
+        RenderTargetView { view, sampler }
+    }
+
+    /// View of the render graph image.
+    pub fn view(&self) -> &ImageView<B> {
+        &self.view
+    }
+
+    /// Sampler used to read the image.
+    pub fn sampler(&self) -> &Sampler<B> {
+        &self.sampler
+    }
+}
+
+/// Mesh wrapper.
+#[derive(Debug)]
+pub struct Mesh {
+    inner: BackendMesh,
+    bounds: Option<(Vector3<f32>, Vector3<f32>)>,
+}
+
+impl Mesh {
+    /// Axis-aligned box enclosing the vertex positions in model space, as `(min, max)`.
+    ///
+    /// Computed once when the mesh is loaded, `None` for meshes loaded without positions.
+    pub fn bounds(&self) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        self.bounds
+    }
+
+    pub(crate) fn with_bounds(mut self, bounds: Option<(Vector3<f32>, Vector3<f32>)>) -> Self {
+        self.bounds = bounds;
+        self
+    }
+}
+
+/// Axis-aligned box enclosing `positions` as `(min, max)`, `None` when there are none.
+pub fn position_bounds(
+    positions: impl IntoIterator<Item = [f32; 3]>,
+) -> Option<(Vector3<f32>, Vector3<f32>)> {
+    positions.into_iter().fold(None, |bounds, position| {
+        let position = Vector3::from(position);
+        Some(match bounds {
+            None => (position, position),
+            Some((min, max)) => (min.inf(&position), max.sup(&position)),
+        })
+    })
 }
 
 impl Asset for Mesh {
@@ -126,17 +238,39 @@ impl Asset for Texture {
     type HandleStorage = DenseVecStorage<Handle<Self>>;
 }
 
+/// Data of a `Mesh` asset.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct MeshData(
-    #[serde(deserialize_with = "deserialize_data")] pub rendy::mesh::MeshBuilder<'static>,
-);
+pub struct MeshData {
+    /// Vertex and index data uploaded to the GPU.
+    #[serde(deserialize_with = "deserialize_data")]
+    pub builder: rendy::mesh::MeshBuilder<'static>,
+    /// Bounds of the vertex positions, as returned by `Mesh::bounds`.
+    #[serde(default)]
+    pub bounds: Option<(Vector3<f32>, Vector3<f32>)>,
+}
+
+impl MeshData {
+    /// Mesh data with bounds enclosing `positions`, the position attribute of `builder`.
+    pub fn with_positions(
+        builder: rendy::mesh::MeshBuilder<'static>,
+        positions: impl IntoIterator<Item = [f32; 3]>,
+    ) -> Self {
+        MeshData {
+            builder,
+            bounds: position_bounds(positions),
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TextureData(pub rendy::texture::TextureBuilder<'static>);
 
 impl From<rendy::mesh::MeshBuilder<'static>> for MeshData {
     fn from(builder: rendy::mesh::MeshBuilder<'static>) -> Self {
-        Self(builder)
+        MeshData {
+            builder,
+            bounds: None,
+        }
     }
 }
 
@@ -152,3 +286,18 @@ where
 {
     Ok(rendy::mesh::MeshBuilder::deserialize(deserializer)?.into_owned())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds_enclose_positions() {
+        let bounds = position_bounds(vec![[1.0, -2.0, 0.5], [-1.0, 3.0, 0.0], [0.0, 0.0, 2.0]]);
+        assert_eq!(
+            bounds,
+            Some((Vector3::new(-1.0, -2.0, 0.0), Vector3::new(1.0, 3.0, 2.0)))
+        );
+        assert_eq!(position_bounds(vec![]), None);
+    }
+}
@@ -1,5 +1,11 @@
-use crate::types::{Backend, Texture};
-use amethyst_core::num::PrimInt;
+use crate::{
+    resources::{AspectRatioLock, Vsync, VsyncPresentModes},
+    types::{Backend, Texture},
+};
+use amethyst_core::{
+    ecs::{Read, Resources, SystemData},
+    num::PrimInt,
+};
 use core::{
     hash::Hash,
     iter::{DoubleEndedIterator, ExactSizeIterator, FusedIterator},
@@ -9,11 +15,19 @@ use derivative::Derivative;
 use glsl_layout::*;
 use rendy::{
     factory::Factory,
-    graph::render::PrepareResult,
-    hal::{self, buffer::Usage, format, pso},
+    graph::{render::PrepareResult, GraphContext, ImageAccess, NodeImage},
+    hal::{
+        self,
+        adapter::{AdapterInfo, DeviceType, PhysicalDevice},
+        buffer::Usage,
+        format, pso,
+        window::PresentMode,
+    },
     memory::MemoryUsage,
     mesh::VertexFormat,
-    resource::{BufferInfo, Escape},
+    resource::{BufferInfo, Escape, ImageView, ImageViewInfo},
+    shader::{Shader, SpirvShader},
+    wsi::Surface,
 };
 use smallvec::SmallVec;
 
@@ -35,18 +49,75 @@ pub fn usize_range(range: Range<u64>) -> Range<usize> {
     range.start as usize..range.end as usize
 }
 
+/// How buffers grow when their contents outgrow them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BufferGrowth {
+    /// Round sizes up to a power of two, so that slowly growing contents don't reallocate
+    /// every frame. Up to half of the buffer can be left unused.
+    PowerOfTwo,
+    /// Round sizes up to a multiple of this many bytes.
+    Increment(u64),
+    /// Allocate exactly the requested size, reallocating whenever the contents grow.
+    Exact,
+}
+
+impl Default for BufferGrowth {
+    fn default() -> Self {
+        BufferGrowth::PowerOfTwo
+    }
+}
+
+impl BufferGrowth {
+    /// Size of a buffer allocated to hold `min_size` bytes.
+    pub fn grown_size(self, min_size: u64) -> u64 {
+        match self {
+            BufferGrowth::PowerOfTwo => min_size.next_power_of_two(),
+            BufferGrowth::Increment(increment) => {
+                let increment = increment.max(1);
+                ((min_size + increment - 1) / increment) * increment
+            }
+            BufferGrowth::Exact => min_size,
+        }
+    }
+}
+
+/// Make sure `buffer` holds at least `min_size` bytes, replacing it with a new buffer
+/// when it is missing or too small. New buffers are rounded up to a power of two, so
+/// that slowly growing contents don't reallocate every frame.
+///
+/// Returns whether a new buffer was created, in which case descriptors pointing to the
+/// old buffer need to be written again. The contents of the old buffer are not copied.
 pub fn ensure_buffer<B: Backend>(
     factory: &Factory<B>,
     buffer: &mut Option<Escape<rendy::resource::Buffer<B>>>,
     usage: Usage,
     memory_usage: impl MemoryUsage,
     min_size: u64,
+) -> Result<bool, failure::Error> {
+    ensure_buffer_with_growth(
+        factory,
+        buffer,
+        usage,
+        memory_usage,
+        min_size,
+        BufferGrowth::PowerOfTwo,
+    )
+}
+
+/// Like `ensure_buffer`, sizing new buffers with `growth`.
+pub fn ensure_buffer_with_growth<B: Backend>(
+    factory: &Factory<B>,
+    buffer: &mut Option<Escape<rendy::resource::Buffer<B>>>,
+    usage: Usage,
+    memory_usage: impl MemoryUsage,
+    min_size: u64,
+    growth: BufferGrowth,
 ) -> Result<bool, failure::Error> {
     #[cfg(feature = "profiler")]
     profile_scope!("ensure_buffer");
 
     if buffer.as_ref().map(|b| b.size()).unwrap_or(0) < min_size {
-        let new_size = min_size.next_power_of_two();
+        let new_size = growth.grown_size(min_size);
         let new_buffer = factory.create_buffer(
             BufferInfo {
                 size: new_size,
@@ -61,14 +132,285 @@ pub fn ensure_buffer<B: Backend>(
     }
 }
 
+/// Size in bytes of `len` values of `T` laid out next to each other.
+#[inline]
+pub fn byte_size<T>(len: usize) -> u64 {
+    (core::mem::size_of::<T>() * len) as u64
+}
+
+/// Alignment required by the device for the offsets of uniform buffer descriptors,
+/// to be passed to `align_size`.
+#[inline]
+pub fn ubo_offset_align<B: Backend>(factory: &Factory<B>) -> u64 {
+    factory
+        .physical()
+        .limits()
+        .min_uniform_buffer_offset_alignment
+}
+
+/// Size in bytes of an array of `array_len` uniform values of `T` in std140 layout,
+/// rounded up to a multiple of `align`.
+///
+/// Uniform data sharing a buffer starts at offsets that are multiples of the device's
+/// `ubo_offset_align`, so each part of the buffer is sized with this function.
 pub fn align_size<T: AsStd140>(align: u64, array_len: usize) -> u64
 where
     T::Std140: Sized,
 {
-    let size = (core::mem::size_of::<T::Std140>() * array_len) as u64;
+    let size = byte_size::<T::Std140>(array_len);
     ((size + align - 1) / align) * align
 }
 
+/// Stencil test replacing the stencil value with `reference` wherever a fragment is drawn,
+/// so that a later pass can compare against it with `stencil_compare`.
+pub fn stencil_write(reference: pso::StencilValue) -> pso::StencilTest {
+    stencil_test(pso::Comparison::Always, pso::StencilOp::Replace, reference)
+}
+
+/// Stencil test only drawing fragments where `reference` compares with `fun` to the stored
+/// stencil value, which is kept as is. `Comparison::NotEqual` draws where a pass built with
+/// `stencil_write` of the same reference did not, such as outlines around an object.
+pub fn stencil_compare(fun: pso::Comparison, reference: pso::StencilValue) -> pso::StencilTest {
+    stencil_test(fun, pso::StencilOp::Keep, reference)
+}
+
+fn stencil_test(
+    fun: pso::Comparison,
+    op_pass: pso::StencilOp,
+    reference: pso::StencilValue,
+) -> pso::StencilTest {
+    let face = pso::StencilFace {
+        fun,
+        mask_read: pso::State::Static(!0),
+        mask_write: pso::State::Static(!0),
+        op_fail: pso::StencilOp::Keep,
+        op_depth_fail: pso::StencilOp::Keep,
+        op_pass,
+        reference: pso::State::Static(reference),
+    };
+    pso::StencilTest::On {
+        front: face,
+        back: face,
+    }
+}
+
+/// Depth formats considered by `select_depth_format`, from most to least preferred.
+pub const DEPTH_FORMAT_PRIORITY: [format::Format; 4] = [
+    format::Format::D32Sfloat,
+    format::Format::D24UnormS8Uint,
+    format::Format::D32SfloatS8Uint,
+    format::Format::D16Unorm,
+];
+
+/// The first depth format `supported` accepts, in the order of `select_depth_format`.
+pub fn first_depth_format(
+    preferred: Option<format::Format>,
+    stencil: bool,
+    supported: impl Fn(format::Format) -> bool,
+) -> Option<format::Format> {
+    preferred
+        .into_iter()
+        .chain(DEPTH_FORMAT_PRIORITY.iter().cloned())
+        .filter(|format| {
+            let aspects = format.surface_desc().aspects;
+            aspects.contains(format::Aspects::DEPTH)
+                && (!stencil || aspects.contains(format::Aspects::STENCIL))
+        })
+        .find(|&format| supported(format))
+}
+
+/// Select a depth format that the device supports as an optimally tiled depth-stencil attachment.
+///
+/// `preferred` is tried first, followed by `DEPTH_FORMAT_PRIORITY`. When `stencil` is set,
+/// only formats with a stencil aspect are considered. The same format must be used for
+/// every pass sharing a depth image, so select it once per graph.
+pub fn select_depth_format<B: Backend>(
+    factory: &Factory<B>,
+    preferred: Option<format::Format>,
+    stencil: bool,
+) -> Option<format::Format> {
+    let selected = first_depth_format(preferred, stencil, |format| {
+        factory
+            .physical()
+            .format_properties(Some(format))
+            .optimal_tiling
+            .contains(format::ImageFeature::DEPTH_STENCIL_ATTACHMENT)
+    });
+
+    match (preferred, selected) {
+        (Some(preferred), Some(format)) if preferred != format => log::warn!(
+            "Depth format {:?} is not supported, falling back to {:?}",
+            preferred,
+            format
+        ),
+        (_, Some(format)) => log::info!("Using depth format {:?}", format),
+        (_, None) => log::error!("No supported depth format found"),
+    }
+    selected
+}
+
+/// Whether an adapter renders on the CPU, either reported as such or known by the name
+/// of its driver, such as llvmpipe or SwiftShader.
+pub fn is_software_adapter(info: &AdapterInfo) -> bool {
+    if let DeviceType::Cpu = info.device_type {
+        return true;
+    }
+    let name = info.name.to_lowercase();
+    ["llvmpipe", "softpipe", "swiftshader", "basic render driver"]
+        .iter()
+        .any(|driver| name.contains(driver))
+}
+
+/// Select the swapchain format of `surface`, sRGB or linear as `srgb` asks.
+///
+/// Fragment shaders output linear colors. An sRGB format encodes them for display, which
+/// is what the passes are written for. A linear format presents them as written, for
+/// games applying gamma correction themselves. Formats of the other kind are used, with
+/// a warning, when the surface supports none of the requested kind.
+pub fn select_surface_format<B: Backend>(
+    factory: &Factory<B>,
+    surface: &Surface<B>,
+    srgb: bool,
+) -> format::Format {
+    let wanted = if srgb {
+        format::ChannelType::Srgb
+    } else {
+        format::ChannelType::Unorm
+    };
+    let fallback = factory.get_surface_format(surface);
+    let (_, formats, ..) = factory.get_surface_compatibility(surface);
+
+    let selected = match formats {
+        // The surface takes any format.
+        None if srgb => Some(format::Format::Bgra8Srgb),
+        None => Some(format::Format::Bgra8Unorm),
+        Some(formats) => {
+            // Formats laid out like the default one are tried first.
+            let layout = fallback.base_format().0;
+            let candidates = || {
+                formats
+                    .iter()
+                    .cloned()
+                    .filter(|format| format.base_format().1 == wanted)
+            };
+            candidates()
+                .find(|format| format.base_format().0 == layout)
+                .or_else(|| candidates().next())
+        }
+    };
+
+    match selected {
+        Some(format) => {
+            log::info!("Using surface format {:?}", format);
+            format
+        }
+        None => {
+            log::warn!(
+                "Surface has no {:?} format, falling back to {:?}",
+                wanted,
+                fallback
+            );
+            fallback
+        }
+    }
+}
+
+/// Select the present mode of `surface` for the `vsync` setting, logging the mode picked.
+///
+/// Falls back to `Fifo` with a warning when the surface supports no mode matching the
+/// setting. Graph creators apply it to the present node, so that the mode changes when the
+/// `Vsync` resource does and the graph is rebuilt:
+///
+/// ```ignore
+/// let present_mode = util::select_present_mode(factory, &surface, *res.fetch::<Vsync>());
+/// PresentNode::builder(factory, surface, color)
+///     .with_present_modes_priority(util::present_mode_priority(present_mode))
+/// ```
+pub fn select_present_mode<B: Backend>(
+    factory: &Factory<B>,
+    surface: &Surface<B>,
+    vsync: Vsync,
+) -> PresentMode {
+    let (_, _, supported, ..) = factory.get_surface_compatibility(surface);
+    vsync.select_present_mode(&supported)
+}
+
+/// Present mode priority for `PresentBuilder::with_present_modes_priority` accepting
+/// only `mode`, such as the one returned by `select_present_mode`.
+pub fn present_mode_priority(mode: PresentMode) -> impl Fn(PresentMode) -> Option<usize> {
+    move |candidate| if candidate == mode { Some(1) } else { None }
+}
+
+/// Lower `requested` to the largest sample count in the `supported` bit mask not exceeding it.
+///
+/// Bit `n` of the mask stands for `2^n` samples, single sampling is always available.
+pub fn clamp_sample_count(
+    requested: hal::image::NumSamples,
+    supported: hal::image::NumSamples,
+) -> hal::image::NumSamples {
+    let supported = supported | 1;
+    (0..8)
+        .rev()
+        .map(|bit| 1 << bit)
+        .find(|&count| count <= requested && supported & count != 0)
+        .unwrap_or(1)
+}
+
+/// Select the sample count used for multisampled color and depth attachments,
+/// clamping `requested` to a count the device supports for both.
+pub fn select_sample_count<B: Backend>(
+    factory: &Factory<B>,
+    requested: hal::image::NumSamples,
+) -> hal::image::NumSamples {
+    let limits = factory.physical().limits();
+    let supported = limits.framebuffer_color_samples_count & limits.framebuffer_depth_samples_count;
+    let samples = clamp_sample_count(requested, supported);
+    if samples != requested {
+        log::warn!(
+            "{} samples per pixel are not supported, using {} instead",
+            requested,
+            samples
+        );
+    }
+    samples
+}
+
+/// Create the vertex and fragment modules of a pipeline.
+///
+/// The vertex module is destroyed again when the fragment module fails to compile.
+pub unsafe fn simple_shader_modules<B: Backend>(
+    factory: &Factory<B>,
+    vertex: &SpirvShader,
+    fragment: &SpirvShader,
+) -> Result<(B::ShaderModule, B::ShaderModule), failure::Error> {
+    let vertex = vertex.module(factory)?;
+    match fragment.module(factory) {
+        Ok(fragment) => Ok((vertex, fragment)),
+        Err(e) => {
+            factory.destroy_shader_module(vertex);
+            Err(e.into())
+        }
+    }
+}
+
+/// Create the module of `shader`, or of the file replacing it while shaders are
+/// hot-reloaded, see `shader_reload`.
+pub unsafe fn shader_module<B: Backend>(
+    factory: &Factory<B>,
+    res: &Resources,
+    shader: &SpirvShader,
+) -> Result<B::ShaderModule, failure::Error> {
+    #[cfg(feature = "shader-hot-reload")]
+    {
+        if let Some(reloaded) = crate::shader_reload::reloaded_shader(res, shader) {
+            return reloaded.module(factory).map_err(Into::into);
+        }
+    }
+    #[cfg(not(feature = "shader-hot-reload"))]
+    let _ = res;
+    shader.module(factory).map_err(Into::into)
+}
+
 pub fn simple_shader_set<'a, B: Backend>(
     vertex: &'a B::ShaderModule,
     fragment: Option<&'a B::ShaderModule>,
@@ -76,6 +418,103 @@ pub fn simple_shader_set<'a, B: Backend>(
     simple_shader_set_ext(vertex, fragment, None, None, None)
 }
 
+/// Like `simple_shader_set`, with specialization constants applied to both shaders.
+pub fn specialized_shader_set<'a, B: Backend>(
+    vertex: &'a B::ShaderModule,
+    fragment: Option<&'a B::ShaderModule>,
+    vertex_specialization: pso::Specialization<'a>,
+    fragment_specialization: pso::Specialization<'a>,
+) -> pso::GraphicsShaderSet<'a, B> {
+    let mut set = simple_shader_set(vertex, fragment);
+    set.vertex.specialization = vertex_specialization;
+    if let Some(entry) = set.fragment.as_mut() {
+        entry.specialization = fragment_specialization;
+    }
+    set
+}
+
+/// Specialization constants chosen when the pipelines of a pass are built, to select
+/// shader variants such as feature toggles without compiling separate SPIR-V.
+///
+/// Shaders declare them as `layout(constant_id = N) const`, constants a shader doesn't
+/// declare are ignored.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SpecConstants {
+    constants: Vec<(u32, [u8; 4])>,
+}
+
+impl SpecConstants {
+    /// Create an empty set of constants, leaving shaders with their default values.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Set the `uint` constant `id`.
+    pub fn with_u32(self, id: u32, value: u32) -> Self {
+        self.with_bytes(id, value.to_ne_bytes())
+    }
+
+    /// Set the `int` constant `id`.
+    pub fn with_i32(self, id: u32, value: i32) -> Self {
+        self.with_bytes(id, value.to_ne_bytes())
+    }
+
+    /// Set the `float` constant `id`.
+    pub fn with_f32(self, id: u32, value: f32) -> Self {
+        self.with_bytes(id, value.to_bits().to_ne_bytes())
+    }
+
+    /// Set the `bool` constant `id`.
+    pub fn with_bool(self, id: u32, value: bool) -> Self {
+        self.with_u32(id, value as u32)
+    }
+
+    fn with_bytes(mut self, id: u32, bytes: [u8; 4]) -> Self {
+        match self
+            .constants
+            .iter_mut()
+            .find(|(constant, _)| *constant == id)
+        {
+            Some(constant) => constant.1 = bytes,
+            None => self.constants.push((id, bytes)),
+        }
+        self
+    }
+
+    /// Whether no constant is set.
+    pub fn is_empty(&self) -> bool {
+        self.constants.is_empty()
+    }
+
+    /// Whether the constant `id` is set.
+    pub fn contains(&self, id: u32) -> bool {
+        self.constants.iter().any(|(constant, _)| *constant == id)
+    }
+
+    /// Constants and raw data of a `pso::Specialization` setting these constants after
+    /// those of `base`. Constants sharing an id with one of `base` are skipped.
+    pub fn append_to(
+        &self,
+        base: &[pso::SpecializationConstant],
+        base_data: &[u8],
+    ) -> (Vec<pso::SpecializationConstant>, Vec<u8>) {
+        let mut constants = base.to_vec();
+        let mut data = base_data.to_vec();
+        for (id, bytes) in &self.constants {
+            if base.iter().any(|constant| constant.id == *id) {
+                continue;
+            }
+            let start = data.len() as u16;
+            constants.push(pso::SpecializationConstant {
+                id: *id,
+                range: start..start + 4,
+            });
+            data.extend_from_slice(bytes);
+        }
+        (constants, data)
+    }
+}
+
 pub fn simple_shader_set_ext<'a, B: Backend>(
     vertex: &'a B::ShaderModule,
     fragment: Option<&'a B::ShaderModule>,
@@ -102,6 +541,15 @@ pub fn simple_shader_set_ext<'a, B: Backend>(
 
 pub fn vertex_desc(
     formats: &[(VertexFormat, pso::VertexInputRate)],
+) -> (Vec<pso::VertexBufferDesc>, Vec<pso::AttributeDesc>) {
+    vertex_desc_with_base(formats, 0)
+}
+
+/// Build vertex buffer and attribute descriptions with attribute locations starting at
+/// `base_location`, for shaders that reserve the lower locations.
+pub fn vertex_desc_with_base(
+    formats: &[(VertexFormat, pso::VertexInputRate)],
+    base_location: pso::Location,
 ) -> (Vec<pso::VertexBufferDesc>, Vec<pso::AttributeDesc>) {
     let mut vertex_buffers = Vec::with_capacity(formats.len());
     let mut attributes = Vec::with_capacity(formats.len());
@@ -109,7 +557,7 @@ pub fn vertex_desc(
     let mut sorted: SmallVec<[_; 16]> = formats.iter().enumerate().collect();
     sorted.sort_unstable_by(|a, b| a.1.cmp(&b.1));
 
-    let mut loc_offset = 0;
+    let mut loc_offset = base_location;
     for (loc_base, (format, rate)) in sorted {
         push_vertex_desc(
             format.gfx_vertex_input_desc(*rate),
@@ -168,8 +616,104 @@ pub fn texture_desc<'a, B: Backend>(
     texture: &'a Texture,
     layout: hal::image::Layout,
 ) -> Option<pso::Descriptor<'a, B>> {
-    B::unwrap_texture(texture).map(|inner| {
-        pso::Descriptor::CombinedImageSampler(inner.view().raw(), layout, inner.sampler().raw())
+    if let Some(inner) = B::unwrap_texture(texture) {
+        Some(pso::Descriptor::CombinedImageSampler(
+            inner.view().raw(),
+            layout,
+            inner.sampler().raw(),
+        ))
+    } else {
+        B::unwrap_render_target(texture).map(|target| {
+            pso::Descriptor::CombinedImageSampler(
+                target.view().raw(),
+                layout,
+                target.sampler().raw(),
+            )
+        })
+    }
+}
+
+/// Access to a graph image sampled by a fragment shader.
+pub fn sampled_image_access() -> ImageAccess {
+    ImageAccess {
+        access: hal::image::Access::SHADER_READ,
+        usage: hal::image::Usage::SAMPLED,
+        layout: hal::image::Layout::ShaderReadOnlyOptimal,
+        stages: pso::PipelineStage::FRAGMENT_SHADER,
+    }
+}
+
+/// Rectangle covering a whole `width` by `height` framebuffer.
+pub fn framebuffer_rect(width: u32, height: u32) -> pso::Rect {
+    pso::Rect {
+        x: 0,
+        y: 0,
+        w: width as i16,
+        h: height as i16,
+    }
+}
+
+/// Rectangle of a `width` by `height` framebuffer the scene is drawn into, keeping the
+/// ratio of the `AspectRatioLock` resource. The whole framebuffer without one.
+pub fn viewport_rect(res: &Resources, width: u32, height: u32) -> pso::Rect {
+    let (x, y, w, h) = <Option<Read<'_, AspectRatioLock>>>::fetch(res)
+        .map_or((0, 0, width, height), |lock| lock.viewport(width, height));
+    pso::Rect {
+        x: x as i16,
+        y: y as i16,
+        w: w as i16,
+        h: h as i16,
+    }
+}
+
+/// View of a graph image sampled by a fragment shader.
+///
+/// Depth-stencil images are viewed through their depth aspect only.
+pub fn node_image_view<B: Backend>(
+    ctx: &GraphContext<B>,
+    factory: &Factory<B>,
+    image: &NodeImage,
+) -> Result<Escape<ImageView<B>>, failure::Error> {
+    let handle = ctx
+        .get_image(image.id)
+        .ok_or_else(|| failure::format_err!("Graph image {:?} is missing", image.id))?;
+
+    let mut range = image.range.clone();
+    if range.aspects.contains(format::Aspects::DEPTH) {
+        range.aspects = format::Aspects::DEPTH;
+    }
+
+    let view = factory.create_image_view(
+        handle.clone(),
+        ImageViewInfo {
+            view_kind: hal::image::ViewKind::D2,
+            format: handle.format(),
+            swizzle: format::Swizzle::NO,
+            range,
+        },
+    )?;
+    Ok(view)
+}
+
+/// Rasterizer multisampling for pipelines drawing into attachments with `samples` samples
+/// per pixel, or `None` for single sampled attachments.
+///
+/// Alpha to coverage turns fragment alpha into a sample mask, and has no effect without
+/// multisampling, where shaders are left to alpha test on their own.
+pub fn multisampling(
+    samples: hal::image::NumSamples,
+    alpha_coverage: bool,
+) -> Option<pso::Multisampling> {
+    if samples <= 1 {
+        return None;
+    }
+
+    Some(pso::Multisampling {
+        rasterization_samples: samples,
+        sample_shading: None,
+        sample_mask: !0,
+        alpha_coverage,
+        alpha_to_one: false,
     })
 }
 
@@ -322,3 +866,152 @@ impl ChangeDetection {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rendy::mesh::{AsVertex, PosTex};
+
+    #[test]
+    fn spec_constants_follow_base_constants() {
+        let base = [pso::SpecializationConstant { id: 0, range: 0..4 }];
+        let constants = SpecConstants::new()
+            .with_bool(4, true)
+            .with_u32(0, 7)
+            .with_f32(5, 0.5)
+            .with_bool(4, false);
+        let (constants, data) = constants.append_to(&base, &8u32.to_ne_bytes());
+
+        assert_eq!(
+            constants
+                .iter()
+                .map(|constant| (constant.id, constant.range.clone()))
+                .collect::<Vec<_>>(),
+            vec![(0, 0..4), (4, 4..8), (5, 8..12)]
+        );
+        assert_eq!(&data[0..4], &8u32.to_ne_bytes());
+        assert_eq!(&data[4..8], &0u32.to_ne_bytes());
+        assert_eq!(&data[8..12], &0.5f32.to_bits().to_ne_bytes());
+    }
+
+    #[test]
+    fn software_adapters_are_detected() {
+        let adapter = |name: &str, device_type| AdapterInfo {
+            name: name.to_string(),
+            vendor: 0,
+            device: 0,
+            device_type,
+        };
+        assert!(is_software_adapter(&adapter("Cpu device", DeviceType::Cpu)));
+        assert!(is_software_adapter(&adapter(
+            "llvmpipe (LLVM 8.0, 256 bits)",
+            DeviceType::Other
+        )));
+        assert!(!is_software_adapter(&adapter(
+            "GeForce GTX 1060",
+            DeviceType::DiscreteGpu
+        )));
+    }
+
+    #[test]
+    fn vertex_desc_locations_start_at_base() {
+        let formats = [(PosTex::vertex(), pso::VertexInputRate::Vertex)];
+        let (_, attributes) = vertex_desc(&formats);
+        let (buffers, based) = vertex_desc_with_base(&formats, 4);
+
+        assert_eq!(buffers.len(), 1);
+        assert_eq!(
+            attributes.iter().map(|a| a.location).collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+        assert_eq!(
+            based.iter().map(|a| a.location).collect::<Vec<_>>(),
+            vec![4, 5]
+        );
+    }
+
+    #[test]
+    fn viewport_keeps_locked_aspect() {
+        let mut res = Resources::new();
+        assert_eq!(viewport_rect(&res, 800, 600), framebuffer_rect(800, 600));
+
+        res.insert(AspectRatioLock(Some((16, 9))));
+        let rect = |x, y, w, h| pso::Rect { x, y, w, h };
+        assert_eq!(viewport_rect(&res, 800, 600), rect(0, 75, 800, 450));
+        assert_eq!(viewport_rect(&res, 2000, 900), rect(200, 0, 1600, 900));
+    }
+
+    #[test]
+    fn buffer_growth_rounds_up() {
+        assert_eq!(BufferGrowth::PowerOfTwo.grown_size(300), 512);
+        assert_eq!(BufferGrowth::Increment(256).grown_size(300), 512);
+        assert_eq!(BufferGrowth::Increment(256).grown_size(256), 256);
+        assert_eq!(BufferGrowth::Exact.grown_size(300), 300);
+    }
+
+    #[test]
+    fn stencil_compare_keeps_stored_value() {
+        match stencil_compare(pso::Comparison::NotEqual, 1) {
+            pso::StencilTest::On { front, back } => {
+                assert_eq!(front, back);
+                assert_eq!(front.fun, pso::Comparison::NotEqual);
+                assert_eq!(front.op_pass, pso::StencilOp::Keep);
+                assert_eq!(front.reference, pso::State::Static(1));
+            }
+            pso::StencilTest::Off => panic!("stencil test is off"),
+        }
+    }
+
+    #[test]
+    fn depth_formats_fall_back_in_priority_order() {
+        use hal::format::Format;
+
+        let all = |_| true;
+        assert_eq!(
+            first_depth_format(None, false, all),
+            Some(Format::D32Sfloat)
+        );
+        assert_eq!(
+            first_depth_format(Some(Format::D16Unorm), false, all),
+            Some(Format::D16Unorm)
+        );
+        assert_eq!(
+            first_depth_format(None, true, all),
+            Some(Format::D24UnormS8Uint)
+        );
+
+        let no_d32 = |format| format != Format::D32Sfloat && format != Format::D24UnormS8Uint;
+        assert_eq!(
+            first_depth_format(Some(Format::D32Sfloat), false, no_d32),
+            Some(Format::D32SfloatS8Uint)
+        );
+        assert_eq!(
+            first_depth_format(Some(Format::Rgba8Unorm), true, no_d32),
+            Some(Format::D32SfloatS8Uint)
+        );
+        assert_eq!(
+            first_depth_format(None, false, |format| format == Format::D16Unorm),
+            Some(Format::D16Unorm)
+        );
+        assert_eq!(first_depth_format(None, true, |_| false), None);
+    }
+
+    #[test]
+    fn sample_count_is_clamped_to_supported() {
+        assert_eq!(clamp_sample_count(4, 0b0111), 4);
+        assert_eq!(clamp_sample_count(8, 0b0111), 4);
+        assert_eq!(clamp_sample_count(6, 0b1111), 4);
+        assert_eq!(clamp_sample_count(2, 0), 1);
+        assert_eq!(clamp_sample_count(0, 0b0111), 1);
+    }
+
+    #[test]
+    fn alpha_coverage_requires_multisampling() {
+        assert!(multisampling(1, true).is_none());
+
+        let ms = multisampling(4, true).unwrap();
+        assert_eq!(ms.rasterization_samples, 4);
+        assert!(ms.alpha_coverage);
+        assert!(!multisampling(4, false).unwrap().alpha_coverage);
+    }
+}
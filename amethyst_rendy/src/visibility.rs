@@ -1,13 +1,13 @@
 use crate::{
     camera::{ActiveCamera, Camera},
-    transparent::Transparent,
+    transparent::{RenderOrder, Transparent},
 };
 use amethyst_core::{
     ecs::prelude::{
         Component, DenseVecStorage, Entities, Entity, Join, Read, ReadExpect, ReadStorage, System,
         Write,
     },
-    math::{self as na, convert, distance_squared, Matrix4, Point3, RealField, Vector4},
+    math::{self as na, convert, Matrix4, Point3, RealField, Vector4},
     num::One,
     Float, Hidden, HiddenPropagate, Transform,
 };
@@ -31,7 +31,11 @@ pub struct Visibility {
 }
 
 /// Determine what entities are visible to the camera, and which are not. Will also sort transparent
-/// entities back to front based on distance from camera.
+/// entities by their `RenderOrder`, then back to front based on their depth in camera space,
+/// keeping the order of entities at equal depth.
+///
+/// Entities are tested against the camera frustum using their `BoundingSphere`.
+/// Entities without one are always considered visible, and hidden entities never are.
 ///
 /// Note that this should run after `Transform` has been updated for the current frame, and
 /// before rendering occurs.
@@ -80,6 +84,7 @@ impl Component for BoundingSphere {
 struct Internals {
     entity: Entity,
     transparent: bool,
+    order: RenderOrder,
     centroid: Point3<Float>,
     camera_distance: Float,
 }
@@ -103,6 +108,7 @@ impl<'a> System<'a> for VisibilitySortingSystem {
         Option<Read<'a, ActiveCamera>>,
         ReadStorage<'a, Camera>,
         ReadStorage<'a, Transparent>,
+        ReadStorage<'a, RenderOrder>,
         ReadStorage<'a, Transform>,
         ReadStorage<'a, BoundingSphere>,
         ReadExpect<'a, ScreenDimensions>,
@@ -118,6 +124,7 @@ impl<'a> System<'a> for VisibilitySortingSystem {
             active,
             camera,
             transparent,
+            render_order,
             transform,
             bound,
             dimensions,
@@ -126,7 +133,6 @@ impl<'a> System<'a> for VisibilitySortingSystem {
         #[cfg(feature = "profiler")]
         profile_scope!("run");
 
-        let origin = Point3::origin();
         let defcam = Camera::standard_2d(dimensions.width(), dimensions.height());
         let identity = Transform::default();
 
@@ -136,11 +142,8 @@ impl<'a> System<'a> for VisibilitySortingSystem {
             .or_else(|| camera_join.next())
             .unwrap_or((&defcam, &identity));
 
-        let camera_centroid = camera_transform.global_matrix().transform_point(&origin);
-        let frustum = Frustum::new(
-            convert::<_, Matrix4<Float>>(*camera.as_matrix())
-                * camera_transform.global_matrix().try_inverse().unwrap(),
-        );
+        let view = camera_transform.global_matrix().try_inverse().unwrap();
+        let frustum = Frustum::new(convert::<_, Matrix4<Float>>(*camera.as_matrix()) * view);
 
         self.centroids.clear();
         self.centroids.extend(
@@ -153,21 +156,24 @@ impl<'a> System<'a> for VisibilitySortingSystem {
             )
                 .join()
                 .map(|(entity, transform, sphere, _, _)| {
-                    let pos = sphere.map_or(&origin, |s| &s.center);
                     let matrix = transform.global_matrix();
                     (
                         entity,
-                        matrix.transform_point(&pos),
-                        sphere.map_or(na::one(), |s| s.radius)
-                            * matrix[(0, 0)].max(matrix[(1, 1)]).max(matrix[(2, 2)]),
+                        centroid(transform, sphere),
+                        sphere.map(|s| {
+                            s.radius * matrix[(0, 0)].max(matrix[(1, 1)]).max(matrix[(2, 2)])
+                        }),
                     )
                 })
-                .filter(|(_, centroid, radius)| frustum.check_sphere(centroid, *radius))
+                .filter(|(_, centroid, radius)| {
+                    radius.map_or(true, |radius| frustum.check_sphere(centroid, radius))
+                })
                 .map(|(entity, centroid, _)| Internals {
                     entity,
                     transparent: transparent.contains(entity),
+                    order: render_order.get(entity).cloned().unwrap_or_default(),
                     centroid,
-                    camera_distance: distance_squared(&centroid, &camera_centroid),
+                    camera_distance: camera_depth(&view, &centroid),
                 }),
         );
         self.transparent.clear();
@@ -175,9 +181,7 @@ impl<'a> System<'a> for VisibilitySortingSystem {
             .extend(self.centroids.iter().filter(|c| c.transparent).cloned());
 
         self.transparent.sort_by(|a, b| {
-            b.camera_distance
-                .partial_cmp(&a.camera_distance)
-                .unwrap_or(Ordering::Equal)
+            back_to_front((&a.order, a.camera_distance), (&b.order, b.camera_distance))
         });
 
         visibility.visible_unordered.clear();
@@ -195,6 +199,40 @@ impl<'a> System<'a> for VisibilitySortingSystem {
     }
 }
 
+/// Center of the `BoundingSphere` of an entity in world space, or its origin without one.
+pub(crate) fn centroid(transform: &Transform, sphere: Option<&BoundingSphere>) -> Point3<Float> {
+    let origin = Point3::origin();
+    let pos = sphere.map_or(&origin, |s| &s.center);
+    transform.global_matrix().transform_point(pos)
+}
+
+/// Entities of `objects`, given as `(entity, order, centroid)`, in the order
+/// `VisibilitySortingSystem` gives transparent entities seen through the `view` matrix.
+///
+/// Used to order the transparent entities of split-screen views, which aren't culled.
+pub(crate) fn sort_for_view(
+    view: &Matrix4<Float>,
+    objects: &[(Entity, RenderOrder, Point3<Float>)],
+) -> Vec<Entity> {
+    let mut sorted = objects
+        .iter()
+        .map(|(entity, order, centroid)| (*entity, order, camera_depth(view, centroid)))
+        .collect::<Vec<_>>();
+    sorted.sort_by(|a, b| back_to_front((a.1, a.2), (b.1, b.2)));
+    sorted.into_iter().map(|(entity, _, _)| entity).collect()
+}
+
+/// Depth of `centroid` in the camera space of `view`, growing away from the camera.
+fn camera_depth(view: &Matrix4<Float>, centroid: &Point3<Float>) -> Float {
+    -view.transform_point(centroid).z
+}
+
+/// Order `(order, depth)` pairs by render order, then from the farthest to the closest.
+fn back_to_front(a: (&RenderOrder, Float), b: (&RenderOrder, Float)) -> Ordering {
+    a.0.cmp(b.0)
+        .then_with(|| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal))
+}
+
 #[derive(Debug)]
 struct Frustum {
     planes: [Vector4<Float>; 6],
@@ -232,3 +270,156 @@ impl Frustum {
         return true;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst_core::{
+        ecs::{
+            prelude::{Builder, Dispatcher, DispatcherBuilder, World},
+            EntityBuilder,
+        },
+        SystemBundle, TransformBundle,
+    };
+    use std::f32::consts::PI;
+
+    /// A world with a 3D camera at the origin, looking down -Z, and the systems updating
+    /// `Visibility`.
+    fn setup() -> (World, Dispatcher<'static, 'static>) {
+        let mut world = World::new();
+        world.add_resource(ScreenDimensions::new(800, 600, 1.0));
+
+        let mut builder = DispatcherBuilder::new();
+        TransformBundle::new()
+            .build(&mut builder)
+            .expect("Failed to add the transform systems");
+        builder.add(
+            VisibilitySortingSystem::new(),
+            "visibility_sorting_system",
+            &["transform_system"],
+        );
+        let mut dispatcher = builder.build();
+        dispatcher.setup(&mut world.res);
+
+        world
+            .create_entity()
+            .with(Camera::standard_3d(800.0, 600.0))
+            .with(Transform::default())
+            .build();
+        (world, dispatcher)
+    }
+
+    fn object_at(world: &mut World, x: f32, y: f32, z: f32) -> EntityBuilder<'_> {
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(x, y, z);
+        world.create_entity().with(transform)
+    }
+
+    fn point(z: f32) -> Point3<Float> {
+        Point3::new(Float::from(0.0), Float::from(0.0), Float::from(z))
+    }
+
+    #[test]
+    fn split_screen_views_sort_back_to_front_per_camera() {
+        let mut world = World::new();
+        let near = world.create_entity().build();
+        let middle = world.create_entity().build();
+        let far = world.create_entity().build();
+        let objects = [
+            (middle, RenderOrder::default(), point(0.0)),
+            (near, RenderOrder::default(), point(5.0)),
+            (far, RenderOrder::default(), point(-5.0)),
+        ];
+
+        // One camera on each side of the objects, facing each other.
+        let mut front = Transform::default();
+        front.set_translation_xyz(0.0, 0.0, 10.0);
+        let mut back = Transform::default();
+        back.set_translation_xyz(0.0, 0.0, -10.0);
+        back.set_rotation_y_axis(PI);
+
+        assert_eq!(
+            sort_for_view(&front.view_matrix(), &objects),
+            vec![far, middle, near]
+        );
+        assert_eq!(
+            sort_for_view(&back.view_matrix(), &objects),
+            vec![near, middle, far]
+        );
+    }
+
+    #[test]
+    fn transparent_entities_sort_by_render_order_then_back_to_front() {
+        let (mut world, mut dispatcher) = setup();
+        let near = object_at(&mut world, 0.0, 0.0, -2.0)
+            .with(Transparent)
+            .build();
+        let far = object_at(&mut world, 0.0, 0.0, -8.0)
+            .with(Transparent)
+            .build();
+        let middle = object_at(&mut world, 0.0, 0.0, -5.0)
+            .with(Transparent)
+            .build();
+        let farthest_on_top = object_at(&mut world, 0.0, 0.0, -9.0)
+            .with(Transparent)
+            .with(RenderOrder(1))
+            .build();
+        let nearest_below = object_at(&mut world, 0.0, 0.0, -1.0)
+            .with(Transparent)
+            .with(RenderOrder(-1))
+            .build();
+        let opaque = object_at(&mut world, 0.0, 0.0, -3.0).build();
+
+        dispatcher.dispatch(&world.res);
+
+        let visibility = world.read_resource::<Visibility>();
+        assert_eq!(
+            visibility.visible_ordered,
+            vec![nearest_below, far, middle, near, farthest_on_top]
+        );
+        assert!(visibility.visible_unordered.contains(opaque.id()));
+        assert!(!visibility.visible_unordered.contains(near.id()));
+    }
+
+    #[test]
+    fn transparent_entities_sort_by_camera_space_depth() {
+        let (mut world, mut dispatcher) = setup();
+        // Farther from the camera than `deep`, but closer along its view direction.
+        let wide = object_at(&mut world, 2.0, 0.0, -3.0)
+            .with(Transparent)
+            .build();
+        let deep = object_at(&mut world, 0.0, 0.0, -3.5)
+            .with(Transparent)
+            .build();
+
+        dispatcher.dispatch(&world.res);
+
+        let visibility = world.read_resource::<Visibility>();
+        assert_eq!(visibility.visible_ordered, vec![deep, wide]);
+    }
+
+    #[test]
+    fn entities_without_bounding_sphere_are_always_visible() {
+        let (mut world, mut dispatcher) = setup();
+        // All of them are behind the camera.
+        let opaque = object_at(&mut world, 0.0, 0.0, 5.0).build();
+        let transparent = object_at(&mut world, 0.0, 0.0, 5.0)
+            .with(Transparent)
+            .build();
+        let culled = object_at(&mut world, 0.0, 0.0, 5.0)
+            .with(BoundingSphere::origin(1.0))
+            .build();
+        let culled_transparent = object_at(&mut world, 0.0, 0.0, 5.0)
+            .with(Transparent)
+            .with(BoundingSphere::origin(1.0))
+            .build();
+
+        dispatcher.dispatch(&world.res);
+
+        let visibility = world.read_resource::<Visibility>();
+        assert!(visibility.visible_unordered.contains(opaque.id()));
+        assert!(!visibility.visible_unordered.contains(culled.id()));
+        assert_eq!(visibility.visible_ordered, vec![transparent]);
+        assert!(!visibility.visible_ordered.contains(&culled_transparent));
+    }
+}
@@ -25,11 +25,11 @@ use amethyst_rendy::{
             pso::{self, ShaderStageFlags},
         },
         mesh::{AsVertex, VertexFormat},
-        shader::{Shader, SpirvShader},
+        shader::SpirvShader,
         texture::palette::load_from_srgba,
     },
     resources::Tint,
-    simple_shader_set,
+    simple_shader_modules, simple_shader_set,
     submodules::{DynamicUniform, DynamicVertex, TextureId, TextureSub},
     types::{Backend, Texture},
     ChangeDetection,
@@ -427,8 +427,16 @@ fn build_ui_pipeline<B: Backend>(
             .create_pipeline_layout(layouts, None as Option<(_, _)>)
     }?;
 
-    let shader_vertex = unsafe { UI_VERTEX.module(factory).unwrap() };
-    let shader_fragment = unsafe { UI_FRAGMENT.module(factory).unwrap() };
+    let modules = unsafe { simple_shader_modules(factory, &UI_VERTEX, &UI_FRAGMENT) };
+    let (shader_vertex, shader_fragment) = match modules {
+        Ok(modules) => modules,
+        Err(e) => {
+            unsafe {
+                factory.device().destroy_pipeline_layout(pipeline_layout);
+            }
+            return Err(e);
+        }
+    };
 
     let pipes = PipelinesBuilder::new()
         .with_pipeline(
@@ -202,7 +202,7 @@ impl<'a> System<'a> for TextEditingMouseSystem {
                         event: WindowEvent::CursorMoved { position, .. },
                         ..
                     } => {
-                        let hidpi = screen_dimensions.hidpi_factor() as f32;
+                        let hidpi = screen_dimensions.window_hidpi_factor() as f32;
                         self.mouse_position = (
                             position.x as f32 * hidpi,
                             (screen_dimensions.height() - position.y as f32) * hidpi,
@@ -0,0 +1,173 @@
+//! Utility keeping the projection of cameras in line with the window's aspect ratio
+
+use amethyst_assets::PrefabData;
+use amethyst_core::ecs::{
+    Component, Entities, Entity, Join, NullStorage, Read, ReadExpect, ReadStorage, Resources,
+    System, SystemData, WriteStorage,
+};
+use amethyst_derive::PrefabData;
+use amethyst_error::Error;
+use amethyst_rendy::camera::{ActiveCamera, Camera, Orthographic};
+use amethyst_window::ScreenDimensions;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{auto_fov::AutoFov, ortho_camera::CameraOrtho};
+
+/// Marker component keeping `CameraResizeSystem` away from a camera whose projection
+/// is managed by hand.
+///
+/// Cameras with an `AutoFov` or `CameraOrtho` component are left alone as well.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PrefabData, Serialize)]
+#[prefab(Component)]
+pub struct FixedProjection;
+
+impl Component for FixedProjection {
+    type Storage = NullStorage<Self>;
+}
+
+/// Change the projection of `camera` to match `aspect`, the width of the view divided by
+/// its height.
+///
+/// Perspective projections keep their vertical FOV, near and far planes. Orthographic
+/// projections keep their vertical extent and center, and get wider or narrower.
+pub fn fit_aspect_ratio(camera: &mut Camera, aspect: f32) {
+    if !aspect.is_finite() || aspect <= 0.0 {
+        return;
+    }
+
+    if let Some(perspective) = camera.projection_mut().as_perspective_mut() {
+        perspective.set_aspect(aspect);
+        return;
+    }
+
+    let resized = camera.projection().as_orthographic().map(|ortho| {
+        let center = (ortho.left() + ortho.right()) * 0.5;
+        let mut half_width = (ortho.top() - ortho.bottom()).abs() * aspect * 0.5;
+        // Keeps a mirrored projection mirrored.
+        if ortho.right() < ortho.left() {
+            half_width = -half_width;
+        }
+        Orthographic::new(
+            center - half_width,
+            center + half_width,
+            ortho.bottom(),
+            ortho.top(),
+            ortho.near(),
+            ortho.far(),
+        )
+    });
+    if let Some(ortho) = resized {
+        camera.set_projection(ortho.into());
+    }
+}
+
+/// System that updates the projection of cameras when the window is resized, so that
+/// the view is not stretched. See `fit_aspect_ratio` for how projections change.
+///
+/// By default only the camera drawn by the renderer is updated: the `ActiveCamera`, or
+/// the first camera when there is none. Cameras tagged with `FixedProjection`, `AutoFov`
+/// or `CameraOrtho` are skipped.
+///
+/// Cameras are also updated on the first run, to fit cameras created before the window
+/// size was known.
+pub struct CameraResizeSystem {
+    last_dimensions: ScreenDimensions,
+    all_cameras: bool,
+}
+
+impl CameraResizeSystem {
+    /// Update the camera drawn by the renderer.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Update every camera, for split-screen or render to texture setups.
+    pub fn all_cameras() -> Self {
+        CameraResizeSystem {
+            all_cameras: true,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for CameraResizeSystem {
+    fn default() -> Self {
+        Self {
+            last_dimensions: ScreenDimensions::new(0, 0, 0.0),
+            all_cameras: false,
+        }
+    }
+}
+
+impl<'a> System<'a> for CameraResizeSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, ScreenDimensions>,
+        Option<Read<'a, ActiveCamera>>,
+        WriteStorage<'a, Camera>,
+        ReadStorage<'a, FixedProjection>,
+        ReadStorage<'a, AutoFov>,
+        ReadStorage<'a, CameraOrtho>,
+    );
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+    }
+
+    fn run(
+        &mut self,
+        (entities, screen, active, mut cameras, fixed, auto_fovs, orthos): Self::SystemData,
+    ) {
+        if self.last_dimensions == *screen {
+            return;
+        }
+        self.last_dimensions = screen.clone();
+
+        // Matches the camera picked by the renderer.
+        let drawn: Option<Entity> = active
+            .as_ref()
+            .map(|active| active.entity)
+            .filter(|entity| cameras.contains(*entity))
+            .or_else(|| (&entities, &cameras).join().next().map(|(e, _)| e));
+
+        let aspect = screen.aspect_ratio();
+        for (entity, camera, _, _, _) in
+            (&entities, &mut cameras, !&fixed, !&auto_fovs, !&orthos).join()
+        {
+            if self.all_cameras || Some(entity) == drawn {
+                fit_aspect_ratio(camera, aspect);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn perspective_keeps_fov() {
+        let mut camera = Camera::perspective(1.0, 1.2, 0.1, 100.0);
+        fit_aspect_ratio(&mut camera, 2.0);
+
+        let perspective = camera.projection().as_perspective().unwrap();
+        assert!((perspective.aspect() - 2.0).abs() < 1e-6);
+        assert!((perspective.fovy() - 1.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn orthographic_widens_around_center() {
+        let mut camera = Camera::orthographic(0.0, 4.0, -1.0, 1.0, 0.1, 100.0);
+        fit_aspect_ratio(&mut camera, 3.0);
+
+        let ortho = camera.projection().as_orthographic().unwrap();
+        assert!((ortho.left() + 1.0).abs() < 1e-4);
+        assert!((ortho.right() - 5.0).abs() < 1e-4);
+        assert!((ortho.top() - 1.0).abs() < 1e-4);
+
+        fit_aspect_ratio(&mut camera, 0.0);
+        let unchanged = camera.projection().as_orthographic().unwrap();
+        assert!((unchanged.right() - 5.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,211 @@
+//! Utility to keep a target's on-screen size constant while the camera's FOV changes
+
+use amethyst_core::{
+    ecs::{
+        Component, Entities, Entity, HashMapStorage, Join, Read, Resources, System, SystemData,
+        WriteStorage,
+    },
+    math::Vector3,
+    timing::Time,
+    transform::Transform,
+    Float,
+};
+use amethyst_rendy::camera::Camera;
+
+/// Height of the view frustum at `distance` from a camera with the vertical FOV `fovy`.
+pub fn frustum_height(distance: f32, fovy: f32) -> f32 {
+    2.0 * distance * (fovy * 0.5).tan()
+}
+
+/// Distance at which the view frustum of a camera with the vertical FOV `fovy` is
+/// `frustum_height` tall.
+pub fn distance_for_fov(frustum_height: f32, fovy: f32) -> f32 {
+    frustum_height * 0.5 / (fovy * 0.5).tan()
+}
+
+/// A component making a perspective camera dolly towards or away from a target as its FOV
+/// changes, so that the target keeps the same size on screen.
+///
+/// The camera is moved along its own view direction. Both the camera and the target
+/// are expected to have no parent, as their local translations are used.
+#[derive(Clone, Debug)]
+pub struct DollyZoom {
+    /// The entity whose projected size is held constant
+    target: Entity,
+
+    /// The height of the view frustum at the target's distance
+    frustum_height: f32,
+
+    /// The vertical FOV being animated to, with the speed in radians per second
+    goal: Option<(f32, f32)>,
+}
+
+impl DollyZoom {
+    /// Creates a dolly zoom keeping a view frustum `frustum_height` tall at the target.
+    pub fn new(target: Entity, frustum_height: f32) -> Self {
+        DollyZoom {
+            target,
+            frustum_height,
+            goal: None,
+        }
+    }
+
+    /// Creates a dolly zoom holding the framing of a camera with the vertical FOV `fovy`,
+    /// currently `distance` away from the target.
+    pub fn from_distance(target: Entity, distance: f32, fovy: f32) -> Self {
+        Self::new(target, frustum_height(distance, fovy))
+    }
+
+    /// The entity whose projected size is held constant
+    pub fn target(&self) -> Entity {
+        self.target
+    }
+
+    /// The height of the view frustum at the target's distance
+    pub fn frustum_height(&self) -> f32 {
+        self.frustum_height
+    }
+
+    /// The vertical FOV currently being animated to, if any
+    pub fn goal_fov(&self) -> Option<f32> {
+        self.goal.map(|(fov, _)| fov)
+    }
+
+    /// Sets the entity whose projected size is held constant
+    pub fn set_target(&mut self, target: Entity) {
+        self.target = target;
+    }
+
+    /// Sets the height of the view frustum at the target's distance
+    pub fn set_frustum_height(&mut self, frustum_height: f32) {
+        self.frustum_height = frustum_height;
+    }
+
+    /// Animates the camera's vertical FOV to `fovy`, changing by `speed` radians per second.
+    ///
+    /// This function panics if `speed` is not larger than zero.
+    pub fn animate_to(&mut self, fovy: f32, speed: f32) {
+        assert!(
+            speed > 0.0,
+            format!("`speed` should be larger than 0, but `{}` given", speed)
+        );
+        self.goal = Some((fovy, speed));
+    }
+
+    /// Distance from the target at which it keeps its size for the vertical FOV `fovy`
+    pub fn distance_for_fov(&self, fovy: f32) -> f32 {
+        distance_for_fov(self.frustum_height, fovy)
+    }
+
+    /// Advances the FOV animation by `delta_seconds`, returning the new vertical FOV.
+    fn step(&mut self, fovy: f32, delta_seconds: f32) -> f32 {
+        match self.goal {
+            None => fovy,
+            Some((goal, speed)) => {
+                let max_delta = speed * delta_seconds;
+                let delta = goal - fovy;
+                if delta.abs() <= max_delta {
+                    self.goal = None;
+                    goal
+                } else {
+                    fovy + max_delta * delta.signum()
+                }
+            }
+        }
+    }
+}
+
+impl Component for DollyZoom {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// System that animates the FOV of cameras with a `DollyZoom` component and moves them so
+/// their target keeps the same projected size.
+///
+/// Changes to the FOV made by other systems are picked up as well, so the FOV may also be
+/// driven directly through `Perspective::set_fov`.
+#[derive(Debug, Default)]
+pub struct DollyZoomSystem;
+
+impl<'a> System<'a> for DollyZoomSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        WriteStorage<'a, DollyZoom>,
+        WriteStorage<'a, Camera>,
+        WriteStorage<'a, Transform>,
+    );
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+    }
+
+    fn run(
+        &mut self,
+        (entities, time, mut dollies, mut cameras, mut transforms): Self::SystemData,
+    ) {
+        for (entity, dolly, camera) in (&entities, &mut dollies, &mut cameras).join() {
+            let perspective = match camera.projection_mut().as_perspective_mut() {
+                Some(perspective) => perspective,
+                None => continue,
+            };
+
+            let fovy = dolly.step(perspective.fovy(), time.delta_seconds());
+            perspective.set_fov(fovy);
+
+            let target = match transforms.get(dolly.target) {
+                Some(transform) => *transform.translation(),
+                None => continue,
+            };
+
+            if let Some(transform) = transforms.get_mut(entity) {
+                let forward = transform.rotation() * -Vector3::z();
+                let distance = Float::from(dolly.distance_for_fov(fovy));
+                *transform.translation_mut() = target - forward * distance;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst_core::{
+        ecs::{Builder, World},
+        math::Vector4,
+    };
+    use amethyst_rendy::camera::Perspective;
+
+    fn projected_height(fovy: f32, distance: f32, height: f32) -> f32 {
+        let projection = Perspective::new(1.0, fovy, 0.1, 1000.0);
+        let top = projection.as_matrix() * Vector4::new(0.0, height * 0.5, -distance, 1.0);
+        (top.y / top.w).abs() * 2.0
+    }
+
+    #[test]
+    fn projected_size_stays_constant() {
+        let height = frustum_height(10.0, 1.0);
+        let expected = projected_height(1.0, 10.0, 2.0);
+
+        for &fovy in &[0.2, 0.5, 1.3, 2.0] {
+            let distance = distance_for_fov(height, fovy);
+            let projected = projected_height(fovy, distance, 2.0);
+            assert!((projected - expected).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn fov_animation_reaches_goal() {
+        let target = World::new().create_entity().build();
+        let mut dolly = DollyZoom::new(target, 1.0);
+        dolly.animate_to(1.0, 0.5);
+
+        let fovy = dolly.step(0.5, 0.5);
+        assert!((fovy - 0.75).abs() < 1e-6);
+        assert_eq!(dolly.goal_fov(), Some(1.0));
+
+        let fovy = dolly.step(fovy, 1.0);
+        assert_eq!(fovy, 1.0);
+        assert_eq!(dolly.goal_fov(), None);
+    }
+}
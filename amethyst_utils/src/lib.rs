@@ -6,10 +6,13 @@ pub use self::app_root_dir::*;
 
 pub mod app_root_dir;
 pub mod auto_fov;
+pub mod camera_resize;
 pub mod circular_buffer;
+pub mod dolly_zoom;
 pub mod fps_counter;
 pub mod ortho_camera;
 pub mod removal;
 pub mod scene;
 pub mod tag;
 pub mod time_destroy;
+pub mod uv_scroll;
@@ -0,0 +1,109 @@
+//! Utility scrolling the textures of materials over time
+
+use std::collections::HashSet;
+
+use amethyst_assets::{AssetStorage, Handle, PrefabData};
+use amethyst_core::{
+    ecs::{
+        Component, DenseVecStorage, Entity, Join, Read, ReadStorage, Resources, System, SystemData,
+        Write, WriteStorage,
+    },
+    timing::Time,
+};
+use amethyst_derive::PrefabData;
+use amethyst_error::Error;
+use amethyst_rendy::Material;
+
+use serde::{Deserialize, Serialize};
+
+/// Component scrolling the textures of the entity's material, at `velocity` texture
+/// coordinates per second.
+///
+/// The `uv_scroll` of the material asset is changed, so every entity sharing the material
+/// scrolls along. When several entities with a `UvScroll` share a material, the velocity
+/// of one of them is used.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PrefabData, Serialize)]
+#[prefab(Component)]
+pub struct UvScroll {
+    /// Texture coordinates scrolled per second, along U and V.
+    pub velocity: [f32; 2],
+}
+
+impl UvScroll {
+    /// Creates a scroll moving `u` and `v` texture coordinates per second.
+    pub fn new(u: f32, v: f32) -> Self {
+        UvScroll { velocity: [u, v] }
+    }
+}
+
+impl Component for UvScroll {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Move `offset` by `velocity` during `delta_seconds`, wrapped to `[0, 1)` so that it keeps
+/// its precision on long runs. Texture samplers repeating the texture show no difference.
+pub fn scroll_offset(offset: [f32; 2], velocity: [f32; 2], delta_seconds: f32) -> [f32; 2] {
+    let wrap = |value: f32| value - value.floor();
+    [
+        wrap(offset[0] + velocity[0] * delta_seconds),
+        wrap(offset[1] + velocity[1] * delta_seconds),
+    ]
+}
+
+/// System advancing the `uv_scroll` of the materials of entities with a `UvScroll`
+/// component, every frame.
+///
+/// Materials are replaced in their storage, which has the renderer upload the new offset.
+#[derive(Debug, Default)]
+pub struct UvScrollSystem {
+    scrolled: HashSet<u32>,
+}
+
+impl UvScrollSystem {
+    /// Creates a new `UvScrollSystem`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<'a> System<'a> for UvScrollSystem {
+    type SystemData = (
+        Read<'a, Time>,
+        ReadStorage<'a, UvScroll>,
+        ReadStorage<'a, Handle<Material>>,
+        Write<'a, AssetStorage<Material>>,
+    );
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+    }
+
+    fn run(&mut self, (time, scrolls, handles, mut materials): Self::SystemData) {
+        let delta_seconds = time.delta_seconds();
+        self.scrolled.clear();
+        for (scroll, handle) in (&scrolls, &handles).join() {
+            if scroll.velocity == [0.0, 0.0] || !self.scrolled.insert(handle.id()) {
+                continue;
+            }
+            if let Some(material) = materials.get(handle) {
+                let material = Material {
+                    uv_scroll: scroll_offset(material.uv_scroll, scroll.velocity, delta_seconds),
+                    ..material.clone()
+                };
+                materials.replace(handle, material);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offset_wraps_around() {
+        let offset = scroll_offset([0.9, 0.1], [0.5, -0.5], 0.4);
+        assert!((offset[0] - 0.1).abs() < 1e-5);
+        assert!((offset[1] - 0.9).abs() < 1e-5);
+    }
+}
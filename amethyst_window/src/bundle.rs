@@ -1,4 +1,4 @@
-use crate::{DisplayConfig, EventsLoopSystem, WindowSystem};
+use crate::{DisplayConfig, EventsLoopSystem, HeadlessWindowSystem, WindowSystem};
 use amethyst_config::Config;
 use amethyst_core::{bundle::SystemBundle, shred::DispatcherBuilder};
 use amethyst_error::Error;
@@ -6,12 +6,27 @@ use winit::EventsLoop;
 
 pub struct WindowBundle {
     config: DisplayConfig,
+    headless: bool,
 }
 
 impl WindowBundle {
     /// Builds a new window bundle from a loaded `DisplayConfig`.
     pub fn from_config(config: DisplayConfig) -> Self {
-        WindowBundle { config }
+        WindowBundle {
+            config,
+            headless: false,
+        }
+    }
+
+    /// Builds a window bundle that never opens a window or polls window events,
+    /// for dedicated servers and tests running without a display.
+    ///
+    /// Only the `ScreenDimensions` resource is provided, sized after `config.dimensions`.
+    pub fn headless(config: DisplayConfig) -> Self {
+        WindowBundle {
+            config,
+            headless: true,
+        }
     }
 
     /// Builds a new window bundle by loading the `DisplayConfig` from `path`.
@@ -23,6 +38,15 @@ impl WindowBundle {
 
 impl<'a, 'b> SystemBundle<'a, 'b> for WindowBundle {
     fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<(), Error> {
+        if self.headless {
+            builder.add(
+                HeadlessWindowSystem::from_config(&self.config),
+                "window",
+                &[],
+            );
+            return Ok(());
+        }
+
         let event_loop = EventsLoop::new();
         builder.add(
             WindowSystem::from_config(&event_loop, self.config),
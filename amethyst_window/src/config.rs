@@ -1,32 +1,90 @@
 use std::path::PathBuf;
 
-use log::error;
-use serde::{Deserialize, Serialize};
+use log::warn;
+use serde::{Deserialize, Deserializer, Serialize};
 use winit::{Icon, WindowAttributes, WindowBuilder};
 
 use crate::monitor::{MonitorIdent, MonitorsAccess};
 
+/// How the window covers the screen.
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum DisplayMode {
+    /// A regular window, sized by `DisplayConfig::dimensions`.
+    Windowed,
+    /// Fullscreen on a monitor, through the fullscreen support of the platform.
+    Fullscreen(MonitorIdent),
+    /// A window without decorations covering a whole monitor. The desktop resolution
+    /// is kept, so switching to other windows doesn't flicker.
+    BorderlessFullscreen(MonitorIdent),
+}
+
+impl Default for DisplayMode {
+    fn default() -> Self {
+        DisplayMode::Windowed
+    }
+}
+
+/// The variants of `DisplayMode` along with the values of the former `fullscreen` key.
+#[derive(Deserialize)]
+enum DisplayModeCompat {
+    Windowed,
+    Fullscreen(MonitorIdent),
+    BorderlessFullscreen(MonitorIdent),
+    None,
+    Some(MonitorIdent),
+}
+
+fn deserialize_display_mode<'de, D>(deserializer: D) -> Result<DisplayMode, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match DisplayModeCompat::deserialize(deserializer)? {
+        DisplayModeCompat::Windowed | DisplayModeCompat::None => DisplayMode::Windowed,
+        DisplayModeCompat::Fullscreen(monitor) | DisplayModeCompat::Some(monitor) => {
+            DisplayMode::Fullscreen(monitor)
+        }
+        DisplayModeCompat::BorderlessFullscreen(monitor) => {
+            DisplayMode::BorderlessFullscreen(monitor)
+        }
+    })
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct DisplayConfig {
     /// Name of the application window.
     #[serde(default = "default_title")]
     pub title: String,
-    /// Enables fullscreen mode on specific monitor when set.
-    /// Defaults to `None`, which means fullscreen is off.
+    /// Whether the window is a regular window or covers a whole monitor.
+    /// Defaults to `DisplayMode::Windowed`.
+    ///
+    /// Also read from the `fullscreen` key it replaced, where `Some(monitor)` stands for
+    /// `Fullscreen(monitor)` and `None` for `Windowed`.
+    #[serde(
+        default,
+        alias = "fullscreen",
+        deserialize_with = "deserialize_display_mode"
+    )]
+    pub display_mode: DisplayMode,
+    /// Monitor the window is centered on when it opens in windowed mode.
+    /// Falls back to the primary monitor when that monitor is not connected.
+    /// Defaults to `None`, which leaves the placement to the platform.
     #[serde(default)]
-    pub fullscreen: Option<MonitorIdent>,
+    pub monitor: Option<MonitorIdent>,
     /// Current window dimensions, measured in pixels (px).
     #[serde(default)]
     pub dimensions: Option<(u32, u32)>,
-    /// Minimum window dimensions, measured in pixels (px).
+    /// Minimum window dimensions, measured in pixels (px). The user cannot resize the
+    /// window below them.
     #[serde(default)]
     pub min_dimensions: Option<(u32, u32)>,
-    /// Maximum window dimensions, measured in pixels (px).
+    /// Maximum window dimensions, measured in pixels (px). The user cannot resize the
+    /// window above them.
     #[serde(default)]
     pub max_dimensions: Option<(u32, u32)>,
     #[serde(default = "default_visibility")]
     pub visibility: bool,
-    /// A path to the icon used for the window.
+    /// A path to the icon used for the window, an image file decoded to RGBA.
+    /// If it fails to load, a warning is logged and the default icon is kept.
     /// If `loaded_icon` is present, this will be ignored.
     #[serde(default)]
     pub icon: Option<PathBuf>,
@@ -36,13 +94,15 @@ pub struct DisplayConfig {
     /// Whether the window should have borders and bars.
     #[serde(default = "default_decorations")]
     pub decorations: bool,
-    /// Whether the window should be maximized upon creation.
+    /// Whether the window should be maximized upon creation. Ignored when the window
+    /// is not `resizable`.
     #[serde(default)]
     pub maximized: bool,
     /// Enable multitouch on iOS.
     #[serde(default)]
     pub multitouch: bool,
-    /// Whether the window is resizable or not.
+    /// Whether the window is resizable or not. A window that is not resizable also
+    /// loses its maximize button on Windows and macOS, and is fixed to its size on X11.
     #[serde(default = "default_resizable")]
     pub resizable: bool,
     /// Whether the the window should be transparent. If this is true, writing
@@ -50,6 +110,37 @@ pub struct DisplayConfig {
     /// window.
     #[serde(default)]
     pub transparent: bool,
+    /// Whether presentation waits for vertical blank, capping the frame rate to the
+    /// display refresh rate. Disabling it allows tearing in exchange for lower latency.
+    /// Defaults to `true`.
+    #[serde(default = "default_vsync")]
+    pub vsync: bool,
+    /// Number of samples per pixel for multisample anti-aliasing, where `1` disables it.
+    /// Unsupported counts are lowered to the closest count the device supports.
+    /// Defaults to `1`.
+    #[serde(default = "default_samples")]
+    pub samples: u8,
+    /// Whether the swapchain uses an sRGB format when the surface supports one. Shaders
+    /// output linear colors, which such a swapchain encodes for display. Disable it to
+    /// present the colors as written, for games doing their own gamma correction.
+    /// Defaults to `true`.
+    #[serde(default = "default_srgb")]
+    pub srgb: bool,
+    /// Scale factor between physical and logical pixels used in place of the one reported
+    /// by the platform, so `ScreenDimensions` reports logical sizes the game controls.
+    /// `Some(1.0)` disables DPI scaling, which keeps pixel art at a fixed size on every
+    /// monitor. Defaults to `None`, which respects the scale factor of the platform.
+    #[serde(default)]
+    pub hidpi_factor: Option<f64>,
+    /// Width to height ratio kept by the area the scene is drawn into, such as `(16, 9)`.
+    /// Windows of another shape show bars around that area instead of stretching it.
+    /// Defaults to `None`, drawing over the whole window.
+    #[serde(default)]
+    pub aspect_ratio_lock: Option<(u32, u32)>,
+    /// Whether the renderer refuses to start on a software adapter instead of warning
+    /// about it. Defaults to `false`.
+    #[serde(default)]
+    pub require_hardware_gpu: bool,
 
     /// A programmatically loaded window icon; not present in serialization.
     /// Takes precedence over `icon`.
@@ -83,7 +174,8 @@ impl Default for DisplayConfig {
     fn default() -> Self {
         DisplayConfig {
             title: default_title(),
-            fullscreen: None,
+            display_mode: DisplayMode::Windowed,
+            monitor: None,
             dimensions: None,
             min_dimensions: None,
             max_dimensions: None,
@@ -95,6 +187,12 @@ impl Default for DisplayConfig {
             multitouch: false,
             resizable: default_resizable(),
             transparent: false,
+            vsync: default_vsync(),
+            samples: default_samples(),
+            srgb: default_srgb(),
+            hidpi_factor: None,
+            aspect_ratio_lock: None,
+            require_hardware_gpu: false,
             loaded_icon: None,
         }
     }
@@ -116,23 +214,46 @@ fn default_resizable() -> bool {
     true
 }
 
+fn default_vsync() -> bool {
+    true
+}
+
+fn default_samples() -> u8 {
+    1
+}
+
+fn default_srgb() -> bool {
+    true
+}
+
 impl DisplayConfig {
     /// Creates a `winit::WindowBuilder` using the values set in the `DisplayConfig`.
     ///
-    /// The `MonitorsAccess` is needed to configure a fullscreen window.
+    /// The `MonitorsAccess` is needed to configure a window covering a monitor.
     pub fn to_window_builder(self, monitors: &impl MonitorsAccess) -> WindowBuilder {
+        let (fullscreen, borderless) = match &self.display_mode {
+            DisplayMode::Windowed => (None, None),
+            DisplayMode::Fullscreen(ident) => (Some(ident.monitor_id(monitors)), None),
+            DisplayMode::BorderlessFullscreen(ident) => (None, Some(ident.monitor_id(monitors))),
+        };
         let attrs = WindowAttributes {
-            dimensions: self.dimensions.map(Into::into),
+            dimensions: borderless
+                .map(|monitor| {
+                    monitor
+                        .get_dimensions()
+                        .to_logical(monitor.get_hidpi_factor())
+                })
+                .or_else(|| self.dimensions.map(Into::into)),
             max_dimensions: self.max_dimensions.map(Into::into),
             min_dimensions: self.min_dimensions.map(Into::into),
             title: self.title,
-            maximized: self.maximized,
+            maximized: self.maximized && self.resizable && borderless.is_none(),
             visible: self.visibility,
             transparent: self.transparent,
-            decorations: self.decorations,
+            decorations: self.decorations && borderless.is_none(),
             always_on_top: self.always_on_top,
             window_icon: None,
-            fullscreen: self.fullscreen.map(|ident| ident.monitor_id(monitors)),
+            fullscreen,
             resizable: self.resizable,
             multitouch: self.multitouch,
         };
@@ -146,8 +267,8 @@ impl DisplayConfig {
             let icon = match Icon::from_path(&icon) {
                 Ok(x) => Some(x),
                 Err(e) => {
-                    error!(
-                        "Failed to load window icon from `{}`: {}",
+                    warn!(
+                        "Failed to load window icon from `{}`, keeping the default icon: {}",
                         icon.display(),
                         e
                     );
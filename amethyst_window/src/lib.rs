@@ -6,9 +6,12 @@ mod system;
 
 pub use crate::{
     bundle::WindowBundle,
-    config::DisplayConfig,
-    monitor::{MonitorIdent, MonitorsAccess},
-    resources::ScreenDimensions,
-    system::{EventsLoopSystem, WindowSystem},
+    config::{DisplayConfig, DisplayMode},
+    monitor::{MonitorIdent, MonitorsAccess, VideoMode},
+    resources::{
+        AspectRatioLock, CursorIcon, CursorState, RequireHardwareGpu, SampleCount,
+        ScreenDimensions, SrgbSurface, Vsync, WindowCommand, WindowEvent,
+    },
+    system::{EventsLoopSystem, HeadlessWindowSystem, WindowSystem},
 };
-pub use winit::{Icon, Window};
+pub use winit::{Icon, MouseCursor, Window};
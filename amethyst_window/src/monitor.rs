@@ -6,6 +6,14 @@ use winit::{AvailableMonitorsIter, EventsLoop, MonitorId, Window};
 pub trait MonitorsAccess {
     fn iter(&self) -> AvailableMonitorsIter;
     fn primary(&self) -> MonitorId;
+
+    /// Identifiers of all available monitors, for example to list them in an options menu.
+    fn available_monitors(&self) -> Vec<MonitorIdent> {
+        self.iter()
+            .enumerate()
+            .filter_map(|(i, m)| m.get_name().map(|name| MonitorIdent(i as u16, name)))
+            .collect()
+    }
 }
 
 impl MonitorsAccess for EventsLoop {
@@ -26,6 +34,15 @@ impl MonitorsAccess for Window {
     }
 }
 
+/// A resolution and refresh rate a monitor can display.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Deserialize, Serialize)]
+pub struct VideoMode {
+    /// Resolution in physical pixels.
+    pub dimensions: (u32, u32),
+    /// Refresh rate in hertz, `None` when the platform doesn't report it.
+    pub refresh_rate: Option<u16>,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
 pub struct MonitorIdent(u16, String);
 
@@ -63,6 +80,23 @@ impl MonitorIdent {
             .and_then(|(i, m)| m.get_name().map(|name| Self(i as u16, name)))
     }
 
+    /// Human-readable name of the monitor.
+    pub fn name(&self) -> &str {
+        &self.1
+    }
+
+    /// Video modes supported by the monitor.
+    ///
+    /// Only the current mode of the monitor is known to winit, so it is the only one
+    /// returned, without a refresh rate.
+    pub fn video_modes(&self, monitors: &impl MonitorsAccess) -> Vec<VideoMode> {
+        let (width, height): (f64, f64) = self.monitor_id(monitors).get_dimensions().into();
+        vec![VideoMode {
+            dimensions: (width as u32, height as u32),
+            refresh_rate: None,
+        }]
+    }
+
     /// Select a monitor that matches this identifier most closely.
     pub fn monitor_id(&self, monitors: &impl MonitorsAccess) -> MonitorId {
         monitors
@@ -1,3 +1,8 @@
+use crate::config::DisplayMode;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use winit::MouseCursor;
+
 /// World resource that stores screen dimensions.
 #[derive(Debug, PartialEq, Clone)]
 pub struct ScreenDimensions {
@@ -10,6 +15,9 @@ pub struct ScreenDimensions {
     /// The ratio between the backing framebuffer resolution and the window size in screen pixels.
     /// This is typically one for a normal display and two for a retina display.
     hidpi: f64,
+    /// The scale factor reported by the platform, which differs from `hidpi` when
+    /// `DisplayConfig::hidpi_factor` overrides it.
+    pub(crate) window_hidpi: f64,
     pub(crate) dirty: bool,
 }
 
@@ -21,20 +29,33 @@ impl ScreenDimensions {
             h: h as f64,
             aspect_ratio: w as f32 / h as f32,
             hidpi,
+            window_hidpi: hidpi,
             dirty: false,
         }
     }
 
-    /// Returns the current logical width of the window.
+    /// Returns the current physical width of the window in pixels.
     pub fn width(&self) -> f32 {
         self.w as f32
     }
 
-    /// Returns the current logical height of the window.
+    /// Returns the current physical height of the window in pixels.
     pub fn height(&self) -> f32 {
         self.h as f32
     }
 
+    /// Returns the current logical width of the window, the physical width divided by
+    /// the hidpi factor.
+    pub fn logical_width(&self) -> f32 {
+        (self.w / self.hidpi) as f32
+    }
+
+    /// Returns the current logical height of the window, the physical height divided by
+    /// the hidpi factor.
+    pub fn logical_height(&self) -> f32 {
+        (self.h / self.hidpi) as f32
+    }
+
     /// Returns the current aspect ratio of the window.
     pub fn aspect_ratio(&self) -> f32 {
         self.aspect_ratio
@@ -42,10 +63,25 @@ impl ScreenDimensions {
 
     /// Returns the ratio between the backing framebuffer resolution and the window size in screen pixels.
     /// This is typically one for a normal display and two for a retina display.
+    ///
+    /// `WindowSystem` keeps it up to date every frame, including when the window moves
+    /// to a monitor with a different DPI without being resized.
+    ///
+    /// When `DisplayConfig::hidpi_factor` is set, this is the configured factor instead
+    /// of the one reported by the platform.
     pub fn hidpi_factor(&self) -> f64 {
         self.hidpi
     }
 
+    /// Returns the scale factor the platform reports for the window, regardless of any
+    /// override from `DisplayConfig::hidpi_factor`.
+    ///
+    /// Logical positions in winit events are measured with this factor, so use it to
+    /// convert them to physical pixels.
+    pub fn window_hidpi_factor(&self) -> f64 {
+        self.window_hidpi
+    }
+
     /// Updates the width and height of the screen and recomputes the aspect
     /// ratio.
     ///
@@ -66,3 +102,204 @@ impl ScreenDimensions {
         self.hidpi = factor;
     }
 }
+
+/// World resource that selects the mouse cursor shown while it hovers the window.
+///
+/// `WindowSystem` applies any change to this resource on its next run.
+#[derive(Debug, PartialEq, Clone)]
+pub enum CursorIcon {
+    /// One of the cursors provided by the platform.
+    Standard(MouseCursor),
+    /// A custom cursor loaded from an image file.
+    ///
+    /// Platforms that cannot display custom cursors show `fallback` instead.
+    Image {
+        /// Path to the cursor image.
+        path: PathBuf,
+        /// Standard cursor used when the image can't be applied.
+        fallback: MouseCursor,
+    },
+    /// A custom cursor built from pixel data, such as an image asset loaded by the game.
+    ///
+    /// Platforms that cannot display custom cursors show `fallback` instead.
+    Rgba {
+        /// Pixels in row-major order with 8 bits per RGBA channel.
+        rgba: Vec<u8>,
+        /// Width of the image in pixels.
+        width: u32,
+        /// Height of the image in pixels.
+        height: u32,
+        /// Pixel of the image that sits at the pointer position.
+        hotspot: (u32, u32),
+        /// Standard cursor used when the image can't be applied.
+        fallback: MouseCursor,
+    },
+}
+
+impl Default for CursorIcon {
+    fn default() -> Self {
+        CursorIcon::Standard(MouseCursor::Default)
+    }
+}
+
+/// World resource reflecting the cursor grab and visibility applied by `WindowSystem`.
+///
+/// Change them with `WindowCommand::SetCursorGrab` and `WindowCommand::SetCursorVisible`.
+/// The grab is released while the window is unfocused and restored once it regains focus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CursorState {
+    pub(crate) grab_requested: bool,
+    pub(crate) grabbed: bool,
+    pub(crate) visible: bool,
+}
+
+impl Default for CursorState {
+    fn default() -> Self {
+        CursorState {
+            grab_requested: false,
+            grabbed: false,
+            visible: true,
+        }
+    }
+}
+
+impl CursorState {
+    /// Whether the cursor is currently confined to the window.
+    pub fn grabbed(&self) -> bool {
+        self.grabbed
+    }
+
+    /// Whether the game asked for the cursor to be confined, even if the
+    /// grab is released because the window is unfocused.
+    pub fn grab_requested(&self) -> bool {
+        self.grab_requested
+    }
+
+    /// Whether the cursor is shown while it hovers the window.
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+}
+
+/// World resource selecting whether presentation waits for vertical blank.
+///
+/// Initialized from `DisplayConfig::vsync` by `WindowSystem`, the renderer
+/// picks the swapchain present mode from it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Vsync(pub bool);
+
+impl Default for Vsync {
+    fn default() -> Self {
+        Vsync(true)
+    }
+}
+
+/// World resource holding the number of samples per pixel for multisample anti-aliasing.
+///
+/// Initialized from `DisplayConfig::samples` by `WindowSystem`. The renderer lowers it
+/// to the largest count the device supports, then graph creators use it for the
+/// attachments and passes drawing the scene. The graph is rebuilt when it changes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SampleCount(pub u8);
+
+impl Default for SampleCount {
+    fn default() -> Self {
+        SampleCount(1)
+    }
+}
+
+/// World resource selecting whether the swapchain uses an sRGB format.
+///
+/// Initialized from `DisplayConfig::srgb` by `WindowSystem`. Graph creators pick the
+/// swapchain format from it, the renderer rebuilds the graph when it changes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SrgbSurface(pub bool);
+
+impl Default for SrgbSurface {
+    fn default() -> Self {
+        SrgbSurface(true)
+    }
+}
+
+/// World resource refusing to render on software adapters.
+///
+/// Initialized from `DisplayConfig::require_hardware_gpu` by `WindowSystem`. The renderer
+/// logs the adapter it picks at startup and warns when it is a software one, which renders
+/// at a few frames per second. When this is `true`, it panics with that message instead.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequireHardwareGpu(pub bool);
+
+/// World resource locking the aspect ratio of the area the renderer draws the scene into.
+///
+/// Initialized from `DisplayConfig::aspect_ratio_lock` by `WindowSystem`. When set to a
+/// `(width, height)` ratio, scene passes draw into the largest centered area of that ratio,
+/// leaving bars of the clear color on the sides of a window of another shape. The window
+/// still resizes freely and `ScreenDimensions` reports its whole size, so cameras should
+/// take the ratio of their projection from `aspect_ratio` when it is locked.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AspectRatioLock(pub Option<(u32, u32)>);
+
+impl AspectRatioLock {
+    /// Width divided by height of the locked ratio, if any.
+    pub fn aspect_ratio(&self) -> Option<f32> {
+        match self.0 {
+            Some((width, height)) if width > 0 && height > 0 => Some(width as f32 / height as f32),
+            _ => None,
+        }
+    }
+
+    /// Area drawn in a `width` by `height` framebuffer, as `(x, y, width, height)` in pixels.
+    /// The whole framebuffer when unlocked.
+    pub fn viewport(&self, width: u32, height: u32) -> (u32, u32, u32, u32) {
+        let (ratio_width, ratio_height) = match self.0 {
+            Some((w, h)) if w > 0 && h > 0 => (u64::from(w), u64::from(h)),
+            _ => return (0, 0, width, height),
+        };
+        let fit_width = (u64::from(height) * ratio_width / ratio_height) as u32;
+        if fit_width <= width {
+            ((width - fit_width) / 2, 0, fit_width, height)
+        } else {
+            let fit_height = (u64::from(width) * ratio_height / ratio_width) as u32;
+            (0, (height - fit_height) / 2, width, fit_height)
+        }
+    }
+}
+
+/// Request to change the window at runtime.
+///
+/// Write these to the `EventChannel<WindowCommand>` resource, `WindowSystem`
+/// applies them in order on its next run.
+#[derive(Debug, PartialEq, Clone)]
+pub enum WindowCommand {
+    /// Switch between windowed, fullscreen and borderless fullscreen modes.
+    ///
+    /// Going back to `DisplayMode::Windowed` restores the size and position the window had
+    /// before leaving it. A monitor that is no longer connected resolves to the closest match,
+    /// or the primary one.
+    SetDisplayMode(DisplayMode),
+    /// Change the title of the window.
+    SetTitle(String),
+    /// Confine the cursor to the window, or release it.
+    SetCursorGrab(bool),
+    /// Show or hide the cursor while it hovers the window.
+    SetCursorVisible(bool),
+    /// Replace the `CursorIcon` resource, changing the cursor shown over the window.
+    SetCursorIcon(CursorIcon),
+}
+
+/// Change of the window reported by `WindowSystem` through the `EventChannel<WindowEvent>` resource.
+///
+/// Unlike the raw `winit::WindowEvent`s, these are only sent once the
+/// `ScreenDimensions` resource reflects the change.
+#[derive(Debug, PartialEq, Clone)]
+pub enum WindowEvent {
+    /// The size or hidpi factor of the window changed, sent once per actual change.
+    WindowResized {
+        /// New width of the window in pixels (px).
+        width: f64,
+        /// New height of the window in pixels (px).
+        height: f64,
+        /// New ratio between the framebuffer resolution and the window size in screen pixels.
+        hidpi_factor: f64,
+    },
+}
@@ -1,15 +1,45 @@
-use crate::{config::DisplayConfig, resources::ScreenDimensions};
+use crate::{
+    config::{DisplayConfig, DisplayMode},
+    resources::{
+        AspectRatioLock, CursorIcon, CursorState, RequireHardwareGpu, SampleCount,
+        ScreenDimensions, SrgbSurface, Vsync, WindowCommand, WindowEvent,
+    },
+};
 use amethyst_config::Config;
 use amethyst_core::{
-    ecs::{Resources, RunNow, System, SystemData, Write, WriteExpect},
-    shrev::EventChannel,
+    ecs::{Read, Resources, RunNow, System, SystemData, Write, WriteExpect},
+    shrev::{EventChannel, ReaderId},
 };
+use log::warn;
 use std::{path::Path, sync::Arc};
-use winit::{Event, EventsLoop, Window};
+use winit::{
+    dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize},
+    Event, EventsLoop, MonitorId, Window,
+};
 
 /// System for opening and managing the window.
 pub struct WindowSystem {
     window: Arc<Window>,
+    cursor: Option<CursorIcon>,
+    command_reader: Option<ReaderId<WindowCommand>>,
+    event_reader: Option<ReaderId<Event>>,
+    focused: bool,
+    vsync: Option<Vsync>,
+    samples: Option<SampleCount>,
+    srgb: Option<SrgbSurface>,
+    aspect_ratio_lock: Option<AspectRatioLock>,
+    require_hardware_gpu: Option<RequireHardwareGpu>,
+    hidpi_override: Option<f64>,
+    display: DisplayState,
+}
+
+/// Display mode of the window, and what is needed to go back to windowed mode.
+#[derive(Debug, Default)]
+struct DisplayState {
+    mode: DisplayMode,
+    decorations: bool,
+    windowed_position: Option<LogicalPosition>,
+    windowed_size: Option<LogicalSize>,
 }
 
 impl WindowSystem {
@@ -18,20 +48,156 @@ impl WindowSystem {
     }
 
     pub fn from_config(events_loop: &EventsLoop, config: DisplayConfig) -> Self {
+        let vsync = Vsync(config.vsync);
+        let samples = SampleCount(config.samples.max(1));
+        let srgb = SrgbSurface(config.srgb);
+        let aspect_ratio_lock = AspectRatioLock(config.aspect_ratio_lock);
+        let require_hardware_gpu = RequireHardwareGpu(config.require_hardware_gpu);
+        let hidpi_override = config.hidpi_factor.filter(|factor| {
+            let valid = factor.is_finite() && *factor > 0.0;
+            if !valid {
+                warn!(
+                    "Ignoring invalid hidpi factor {} in the display config, using the factor of the platform",
+                    factor
+                );
+            }
+            valid
+        });
+        let display = DisplayState {
+            mode: config.display_mode.clone(),
+            decorations: config.decorations,
+            windowed_position: None,
+            windowed_size: config.dimensions.map(Into::into),
+        };
+        let monitor = config.monitor.clone();
         let window = config
             .to_window_builder(events_loop)
             .build(events_loop)
             .unwrap();
-        Self::new(window)
+        match &display.mode {
+            DisplayMode::Windowed => {
+                if let Some(ident) = monitor {
+                    center_on_monitor(&window, &ident.monitor_id(events_loop));
+                }
+            }
+            DisplayMode::BorderlessFullscreen(ident) => {
+                cover_monitor(&window, &ident.monitor_id(events_loop));
+            }
+            DisplayMode::Fullscreen(_) => {}
+        }
+        Self {
+            vsync: Some(vsync),
+            samples: Some(samples),
+            srgb: Some(srgb),
+            aspect_ratio_lock: Some(aspect_ratio_lock),
+            require_hardware_gpu: Some(require_hardware_gpu),
+            hidpi_override,
+            display,
+            ..Self::new(window)
+        }
     }
 
     pub fn new(window: Window) -> Self {
         Self {
             window: Arc::new(window),
+            cursor: None,
+            command_reader: None,
+            event_reader: None,
+            focused: true,
+            vsync: None,
+            samples: None,
+            srgb: None,
+            aspect_ratio_lock: None,
+            require_hardware_gpu: None,
+            hidpi_override: None,
+            display: DisplayState {
+                decorations: true,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn apply_command(
+        window: &Window,
+        focused: bool,
+        command: &WindowCommand,
+        display: &mut DisplayState,
+        cursor: &mut CursorIcon,
+        cursor_state: &mut CursorState,
+    ) {
+        match command {
+            WindowCommand::SetDisplayMode(mode) => set_display_mode(window, display, mode),
+            WindowCommand::SetTitle(title) => window.set_title(title),
+            WindowCommand::SetCursorGrab(grab) => {
+                cursor_state.grab_requested = *grab;
+                if focused {
+                    cursor_state.grabbed = grab_cursor(window, *grab);
+                }
+            }
+            WindowCommand::SetCursorVisible(visible) => {
+                cursor_state.visible = *visible;
+                window.hide_cursor(!visible);
+            }
+            WindowCommand::SetCursorIcon(icon) => *cursor = icon.clone(),
+        }
+    }
+
+    fn manage_focus(&self, cursor_state: &mut CursorState) {
+        if self.focused {
+            // Some platforms drop the grab and hidden cursor while the window is unfocused.
+            self.window.hide_cursor(!cursor_state.visible);
+            if cursor_state.grab_requested {
+                cursor_state.grabbed = grab_cursor(&self.window, true);
+            }
+        } else if cursor_state.grabbed {
+            cursor_state.grabbed = grab_cursor(&self.window, false);
+        }
+    }
+
+    fn manage_cursor(&mut self, cursor: &CursorIcon) {
+        if self.cursor.as_ref() == Some(cursor) {
+            return;
+        }
+
+        match cursor {
+            CursorIcon::Standard(icon) => self.window.set_cursor(*icon),
+            CursorIcon::Image { path, fallback } => {
+                // winit has no way to build a cursor from an image yet.
+                warn!(
+                    "Custom cursor images are not supported on this platform, using {:?} instead of {:?}",
+                    fallback, path
+                );
+                self.window.set_cursor(*fallback);
+            }
+            CursorIcon::Rgba {
+                rgba,
+                width,
+                height,
+                fallback,
+                ..
+            } => {
+                if rgba.len() != (width * height * 4) as usize {
+                    warn!(
+                        "Cursor image of {}x{} pixels has {} bytes of RGBA data, expected {}",
+                        width,
+                        height,
+                        rgba.len(),
+                        width * height * 4
+                    );
+                }
+                // winit has no way to build a cursor from pixel data yet.
+                warn!(
+                    "Custom cursor images are not supported on this platform, using {:?} instead",
+                    fallback
+                );
+                self.window.set_cursor(*fallback);
+            }
         }
+        self.cursor = Some(cursor.clone());
     }
 
-    fn manage_dimensions(&mut self, mut screen_dimensions: &mut ScreenDimensions) {
+    /// Returns whether the dimensions or hidpi factor of the window changed.
+    fn manage_dimensions(&mut self, mut screen_dimensions: &mut ScreenDimensions) -> bool {
         let width = screen_dimensions.w;
         let height = screen_dimensions.h;
 
@@ -41,10 +207,15 @@ impl WindowSystem {
             screen_dimensions.dirty = false;
         }
 
-        let hidpi = self.window.get_hidpi_factor();
+        // The physical size always comes from the factor of the platform, the override only
+        // changes what the game considers a logical pixel.
+        let window_hidpi = self.window.get_hidpi_factor();
+        let hidpi = self.hidpi_override.unwrap_or(window_hidpi);
+        let mut changed = hidpi != screen_dimensions.hidpi_factor()
+            || window_hidpi != screen_dimensions.window_hidpi;
 
         if let Some(size) = self.window.get_inner_size() {
-            let (window_width, window_height): (f64, f64) = size.to_physical(hidpi).into();
+            let (window_width, window_height): (f64, f64) = size.to_physical(window_hidpi).into();
 
             // Send window size changes to the resource
             if (window_width, window_height) != (width, height) {
@@ -53,17 +224,140 @@ impl WindowSystem {
                 // We don't need to send the updated size of the window back to the window itself,
                 // so set dirty to false.
                 screen_dimensions.dirty = false;
+                changed = true;
             }
         }
         screen_dimensions.update_hidpi_factor(hidpi);
+        screen_dimensions.window_hidpi = window_hidpi;
+        changed
     }
 }
 
+/// Returns whether the cursor is grabbed afterwards.
+fn grab_cursor(window: &Window, grab: bool) -> bool {
+    match window.grab_cursor(grab) {
+        Ok(()) => grab,
+        Err(err) => {
+            warn!("Failed to set cursor grab to {}: {}", grab, err);
+            !grab
+        }
+    }
+}
+
+fn center_on_monitor(window: &Window, monitor: &MonitorId) {
+    let origin = monitor.get_position();
+    let area = monitor.get_dimensions();
+    let size = window
+        .get_outer_size()
+        .map(|size| size.to_physical(window.get_hidpi_factor()))
+        .unwrap_or_else(|| PhysicalSize::new(0.0, 0.0));
+
+    let position = PhysicalPosition::new(
+        origin.x + (area.width - size.width).max(0.0) / 2.0,
+        origin.y + (area.height - size.height).max(0.0) / 2.0,
+    );
+    window.set_position(position.to_logical(monitor.get_hidpi_factor()));
+}
+
+fn set_display_mode(window: &Window, display: &mut DisplayState, mode: &DisplayMode) {
+    if *mode == display.mode {
+        return;
+    }
+    if display.mode == DisplayMode::Windowed {
+        display.windowed_position = window.get_position();
+        display.windowed_size = window.get_inner_size();
+    }
+
+    match mode {
+        DisplayMode::Windowed => {
+            window.set_fullscreen(None);
+            window.set_decorations(display.decorations);
+            if let Some(size) = display.windowed_size {
+                window.set_inner_size(size);
+            }
+            if let Some(position) = display.windowed_position {
+                window.set_position(position);
+            }
+        }
+        DisplayMode::Fullscreen(ident) => {
+            window.set_decorations(display.decorations);
+            window.set_fullscreen(Some(ident.monitor_id(window)));
+        }
+        DisplayMode::BorderlessFullscreen(ident) => {
+            window.set_fullscreen(None);
+            window.set_decorations(false);
+            cover_monitor(window, &ident.monitor_id(window));
+        }
+    }
+    display.mode = mode.clone();
+}
+
+/// Move and resize the window over the whole area of `monitor`.
+fn cover_monitor(window: &Window, monitor: &MonitorId) {
+    let hidpi = monitor.get_hidpi_factor();
+    window.set_position(monitor.get_position().to_logical(hidpi));
+    window.set_inner_size(monitor.get_dimensions().to_logical(hidpi));
+}
+
 impl<'a> System<'a> for WindowSystem {
-    type SystemData = WriteExpect<'a, ScreenDimensions>;
+    type SystemData = (
+        WriteExpect<'a, ScreenDimensions>,
+        Write<'a, CursorIcon>,
+        Write<'a, CursorState>,
+        Read<'a, EventChannel<WindowCommand>>,
+        Read<'a, EventChannel<Event>>,
+        Write<'a, EventChannel<WindowEvent>>,
+    );
 
-    fn run(&mut self, mut screen_dimesnions: Self::SystemData) {
-        self.manage_dimensions(&mut screen_dimesnions);
+    fn run(
+        &mut self,
+        (
+            mut screen_dimesnions,
+            mut cursor,
+            mut cursor_state,
+            commands,
+            winit_events,
+            mut events,
+        ): Self::SystemData,
+    ) {
+        let focus = self.event_reader.as_mut().and_then(|reader| {
+            winit_events
+                .read(reader)
+                .filter_map(|event| match event {
+                    Event::WindowEvent {
+                        event: winit::WindowEvent::Focused(focused),
+                        ..
+                    } => Some(*focused),
+                    _ => None,
+                })
+                .last()
+        });
+        if let Some(focused) = focus {
+            if focused != self.focused {
+                self.focused = focused;
+                self.manage_focus(&mut cursor_state);
+            }
+        }
+        if let Some(reader) = self.command_reader.as_mut() {
+            for command in commands.read(reader) {
+                Self::apply_command(
+                    &self.window,
+                    self.focused,
+                    command,
+                    &mut self.display,
+                    &mut cursor,
+                    &mut cursor_state,
+                );
+            }
+        }
+        if self.manage_dimensions(&mut screen_dimesnions) {
+            events.single_write(WindowEvent::WindowResized {
+                width: screen_dimesnions.w,
+                height: screen_dimesnions.h,
+                hidpi_factor: screen_dimesnions.hidpi_factor(),
+            });
+        }
+        self.manage_cursor(&cursor);
     }
     fn setup(&mut self, res: &mut Resources) {
         let (width, height) = self
@@ -71,9 +365,120 @@ impl<'a> System<'a> for WindowSystem {
             .get_inner_size()
             .expect("Window closed during initialization!")
             .into();
-        let hidpi = self.window.get_hidpi_factor();
-        res.insert(ScreenDimensions::new(width, height, hidpi));
+        let window_hidpi = self.window.get_hidpi_factor();
+        let mut screen_dimensions =
+            ScreenDimensions::new(width, height, self.hidpi_override.unwrap_or(window_hidpi));
+        screen_dimensions.window_hidpi = window_hidpi;
+        res.insert(screen_dimensions);
         res.insert(self.window.clone());
+        res.entry().or_insert_with(CursorIcon::default);
+        if let Some(vsync) = self.vsync.take() {
+            res.insert(vsync);
+        }
+        if let Some(samples) = self.samples.take() {
+            res.insert(samples);
+        }
+        if let Some(srgb) = self.srgb.take() {
+            res.insert(srgb);
+        }
+        if let Some(aspect_ratio_lock) = self.aspect_ratio_lock.take() {
+            res.insert(aspect_ratio_lock);
+        }
+        if let Some(require_hardware_gpu) = self.require_hardware_gpu.take() {
+            res.insert(require_hardware_gpu);
+        }
+        Self::SystemData::setup(res);
+        self.command_reader = Some(
+            res.fetch_mut::<EventChannel<WindowCommand>>()
+                .register_reader(),
+        );
+        self.event_reader = Some(res.fetch_mut::<EventChannel<Event>>().register_reader());
+    }
+}
+
+/// Dimensions of the screen when running headless without configured dimensions.
+const DEFAULT_HEADLESS_DIMENSIONS: (u32, u32) = (1024, 768);
+
+/// Stand-in for `WindowSystem` that never opens a window.
+///
+/// Provides the same resources and event channels, so systems using them keep working
+/// without a display. Resizes requested through `ScreenDimensions` are applied immediately
+/// and reported as `WindowEvent::WindowResized`. `WindowCommand`s update the `CursorIcon`
+/// and `CursorState` resources, while titles and display modes have nothing to apply to.
+pub struct HeadlessWindowSystem {
+    dimensions: (u32, u32),
+    hidpi: f64,
+    command_reader: Option<ReaderId<WindowCommand>>,
+}
+
+impl HeadlessWindowSystem {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            dimensions: (width, height),
+            hidpi: 1.0,
+            command_reader: None,
+        }
+    }
+
+    pub fn from_config(config: &DisplayConfig) -> Self {
+        let (width, height) = config.dimensions.unwrap_or(DEFAULT_HEADLESS_DIMENSIONS);
+        Self {
+            hidpi: config
+                .hidpi_factor
+                .filter(|factor| factor.is_finite() && *factor > 0.0)
+                .unwrap_or(1.0),
+            ..Self::new(width, height)
+        }
+    }
+}
+
+impl<'a> System<'a> for HeadlessWindowSystem {
+    type SystemData = (
+        WriteExpect<'a, ScreenDimensions>,
+        Write<'a, CursorIcon>,
+        Write<'a, CursorState>,
+        Read<'a, EventChannel<WindowCommand>>,
+        Write<'a, EventChannel<WindowEvent>>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            mut screen_dimensions,
+            mut cursor,
+            mut cursor_state,
+            commands,
+            mut events,
+        ): Self::SystemData,
+    ) {
+        if let Some(reader) = self.command_reader.as_mut() {
+            for command in commands.read(reader) {
+                match command {
+                    WindowCommand::SetCursorGrab(grab) => cursor_state.grab_requested = *grab,
+                    WindowCommand::SetCursorVisible(visible) => cursor_state.visible = *visible,
+                    WindowCommand::SetCursorIcon(icon) => *cursor = icon.clone(),
+                    WindowCommand::SetDisplayMode(_) | WindowCommand::SetTitle(_) => {}
+                }
+            }
+        }
+        if screen_dimensions.dirty {
+            screen_dimensions.dirty = false;
+            events.single_write(WindowEvent::WindowResized {
+                width: screen_dimensions.w,
+                height: screen_dimensions.h,
+                hidpi_factor: screen_dimensions.hidpi_factor(),
+            });
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        let (width, height) = self.dimensions;
+        res.insert(ScreenDimensions::new(width, height, self.hidpi));
+        Self::SystemData::setup(res);
+        self.command_reader = Some(
+            res.fetch_mut::<EventChannel<WindowCommand>>()
+                .register_reader(),
+        );
     }
 }
 
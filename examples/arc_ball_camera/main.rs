@@ -26,7 +26,7 @@ use amethyst::{
             mesh::{Normal, Position, TexCoord},
         },
         types::DefaultBackend,
-        GraphCreator, RenderingSystem,
+        util, GraphCreator, RenderingSystem,
     },
     utils::{application_root_dir, scene::BasicScenePrefab},
     window::{ScreenDimensions, Window, WindowBundle},
@@ -130,6 +130,7 @@ fn main() -> Result<(), Error> {
 struct ExampleGraph {
     dimensions: Option<ScreenDimensions>,
     surface_format: Option<Format>,
+    depth_format: Option<Format>,
     dirty: bool,
 }
 
@@ -176,10 +177,14 @@ impl GraphCreator<DefaultBackend> for ExampleGraph {
             Some(ClearValue::Color([0.34, 0.36, 0.52, 1.0].into())),
         );
 
+        let depth_format = *self.depth_format.get_or_insert_with(|| {
+            util::select_depth_format(factory, None, false)
+                .expect("Device has no usable depth format")
+        });
         let depth = graph_builder.create_image(
             window_kind,
             1,
-            Format::D32Sfloat,
+            depth_format,
             Some(ClearValue::DepthStencil(ClearDepthStencil(1.0, 0))),
         );
 
@@ -25,7 +25,7 @@ use amethyst::{
             texture::palette::load_from_srgba,
         },
         types::{DefaultBackend, Mesh, MeshData},
-        GraphCreator, RenderingSystem,
+        util, GraphCreator, RenderingSystem,
     },
     utils::application_root_dir,
     window::{ScreenDimensions, Window, WindowBundle},
@@ -167,7 +167,6 @@ fn initialise_camera(world: &mut World) {
 fn initialise_lights(world: &mut World) {
     let light: Light = PointLight {
         intensity: 100.0,
-        radius: 1.0,
         color: Srgb::new(1.0, 1.0, 1.0),
         ..Default::default()
     }
@@ -184,6 +183,7 @@ fn initialise_lights(world: &mut World) {
 struct ExampleGraph {
     dimensions: Option<ScreenDimensions>,
     surface_format: Option<Format>,
+    depth_format: Option<Format>,
     dirty: bool,
 }
 
@@ -229,10 +229,14 @@ impl GraphCreator<DefaultBackend> for ExampleGraph {
             Some(ClearValue::Color([0.34, 0.36, 0.52, 1.0].into())),
         );
 
+        let depth_format = *self.depth_format.get_or_insert_with(|| {
+            util::select_depth_format(factory, None, false)
+                .expect("Device has no usable depth format")
+        });
         let depth = graph_builder.create_image(
             window_kind,
             1,
-            Format::D32Sfloat,
+            depth_format,
             Some(ClearValue::DepthStencil(ClearDepthStencil(1.0, 0))),
         );
 
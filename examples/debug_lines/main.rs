@@ -29,7 +29,7 @@ use amethyst::{
             },
         },
         types::DefaultBackend,
-        Backend, GraphCreator, RenderingSystem,
+        util, Backend, GraphCreator, RenderingSystem,
     },
     utils::application_root_dir,
     window::{ScreenDimensions, Window, WindowBundle},
@@ -213,6 +213,7 @@ fn main() -> amethyst::Result<()> {
 struct ExampleGraph {
     dimensions: Option<ScreenDimensions>,
     surface_format: Option<Format>,
+    depth_format: Option<Format>,
     dirty: bool,
 }
 
@@ -251,10 +252,14 @@ impl<B: Backend> GraphCreator<B> for ExampleGraph {
             Some(ClearValue::Color([0.34, 0.36, 0.52, 1.0].into())),
         );
 
+        let depth_format = *self.depth_format.get_or_insert_with(|| {
+            util::select_depth_format(factory, None, false)
+                .expect("Device has no usable depth format")
+        });
         let depth = graph_builder.create_image(
             window_kind,
             1,
-            Format::D32Sfloat,
+            depth_format,
             Some(ClearValue::DepthStencil(ClearDepthStencil(1.0, 0))),
         );
 
@@ -23,7 +23,7 @@ use amethyst::{
         },
         shape::Shape,
         types::{DefaultBackend, Texture},
-        GraphCreator, Mesh, RenderingSystem,
+        util, GraphCreator, Mesh, RenderingSystem,
     },
     utils::application_root_dir,
     window::{ScreenDimensions, Window, WindowBundle},
@@ -39,7 +39,7 @@ impl SimpleState for Example {
         let mat_defaults = world.read_resource::<MaterialDefaults>().0.clone();
 
         println!("Load mesh");
-        let (mesh, albedo) = {
+        let (mesh, albedo, metallic_roughness) = {
             let mesh = world.exec(|loader: AssetLoaderSystemData<'_, Mesh>| {
                 loader.load_from_data(
                     Shape::Sphere(32, 32)
@@ -54,8 +54,15 @@ impl SimpleState for Example {
                     (),
                 )
             });
+            // White, so the factors of each material set its metallic and roughness alone.
+            let metallic_roughness = world.exec(|loader: AssetLoaderSystemData<'_, Texture>| {
+                loader.load_from_data(
+                    load_from_linear_rgba(LinSrgba::new(1.0, 1.0, 1.0, 1.0)).into(),
+                    (),
+                )
+            });
 
-            (mesh, albedo)
+            (mesh, albedo, metallic_roughness)
         };
 
         println!("Create spheres");
@@ -67,27 +74,18 @@ impl SimpleState for Example {
                 let mut pos = Transform::default();
                 pos.set_translation_xyz(2.0f32 * (i - 2) as f32, 2.0f32 * (j - 2) as f32, 0.0);
 
-                let mtl = world.exec(
-                    |(mtl_loader, tex_loader): (
-                        AssetLoaderSystemData<'_, Material>,
-                        AssetLoaderSystemData<'_, Texture>,
-                    )| {
-                        let metallic_roughness = tex_loader.load_from_data(
-                            load_from_linear_rgba(LinSrgba::new(0.0, roughness, metallic, 0.0))
-                                .into(),
-                            (),
-                        );
-
-                        mtl_loader.load_from_data(
-                            Material {
-                                albedo: albedo.clone(),
-                                metallic_roughness,
-                                ..mat_defaults.clone()
-                            },
-                            (),
-                        )
-                    },
-                );
+                let mtl = world.exec(|mtl_loader: AssetLoaderSystemData<'_, Material>| {
+                    mtl_loader.load_from_data(
+                        Material {
+                            albedo: albedo.clone(),
+                            metallic_roughness: metallic_roughness.clone(),
+                            metallic_factor: metallic,
+                            roughness_factor: roughness,
+                            ..mat_defaults.clone()
+                        },
+                        (),
+                    )
+                });
 
                 world
                     .create_entity()
@@ -173,6 +171,7 @@ fn main() -> amethyst::Result<()> {
 struct ExampleGraph {
     dimensions: Option<ScreenDimensions>,
     surface_format: Option<Format>,
+    depth_format: Option<Format>,
     dirty: bool,
 }
 
@@ -219,10 +218,14 @@ impl GraphCreator<DefaultBackend> for ExampleGraph {
             Some(ClearValue::Color([0.34, 0.36, 0.52, 1.0].into())),
         );
 
+        let depth_format = *self.depth_format.get_or_insert_with(|| {
+            util::select_depth_format(factory, None, false)
+                .expect("Device has no usable depth format")
+        });
         let depth = graph_builder.create_image(
             window_kind,
             1,
-            Format::D32Sfloat,
+            depth_format,
             Some(ClearValue::DepthStencil(ClearDepthStencil(1.0, 0))),
         );
 
@@ -24,7 +24,7 @@ use amethyst::{
         },
         sprite::SpriteSheet,
         types::DefaultBackend,
-        GraphCreator, RenderingSystem,
+        util, GraphCreator, RenderingSystem,
     },
     ui::{DrawUiDesc, UiBundle},
     utils::application_root_dir,
@@ -171,6 +171,7 @@ impl ScoreBoard {
 struct ExampleGraph {
     dimensions: Option<ScreenDimensions>,
     surface_format: Option<Format>,
+    depth_format: Option<Format>,
     dirty: bool,
 }
 
@@ -226,10 +227,14 @@ impl GraphCreator<DefaultBackend> for ExampleGraph {
             Some(ClearValue::Color([0.34, 0.36, 0.52, 1.0].into())),
         );
 
+        let depth_format = *self.depth_format.get_or_insert_with(|| {
+            util::select_depth_format(factory, None, false)
+                .expect("Device has no usable depth format")
+        });
         let depth = graph_builder.create_image(
             window_kind,
             1,
-            Format::D32Sfloat,
+            depth_format,
             Some(ClearValue::DepthStencil(ClearDepthStencil(1.0, 0))),
         );
 
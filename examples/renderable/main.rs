@@ -24,6 +24,7 @@ use amethyst::{
     },
     prelude::*,
     renderer::{
+        blit::BlitDesc,
         camera::Camera,
         light::Light,
         palette::{Srgb, Srgba},
@@ -37,9 +38,9 @@ use amethyst::{
             hal::{format::Format, image},
             mesh::{Normal, Position, TexCoord},
         },
-        resources::AmbientColor,
+        resources::{AmbientColor, ClearColor, DepthMode, SampleCount, SrgbSurface, Vsync},
         types::DefaultBackend,
-        GraphCreator, RenderingSystem,
+        util, GraphCreator, RenderingSystem,
     },
     ui::{DrawUiDesc, UiBundle, UiCreator, UiFinder, UiText},
     utils::{
@@ -221,7 +222,12 @@ fn main() -> Result<(), Error> {
         .with_thread_local(RenderingSystem::<DefaultBackend, _>::new(
             ExampleGraph::default(),
         ));
-    let mut game = Application::build(resources_directory, Loading::default())?.build(game_data)?;
+    let mut game = Application::build(resources_directory, Loading::default())?
+        .with_resource(ClearColor {
+            color: Srgba::new(0.34, 0.36, 0.52, 1.0),
+            depth: 1.0,
+        })
+        .build(game_data)?;
     game.run();
     Ok(())
 }
@@ -325,7 +331,7 @@ impl<'a> System<'a> for ExampleSystem {
 #[derive(Default)]
 struct ExampleGraph {
     dimensions: Option<ScreenDimensions>,
-    surface_format: Option<Format>,
+    depth_format: Option<Format>,
     dirty: bool,
 }
 
@@ -347,48 +353,76 @@ impl GraphCreator<DefaultBackend> for ExampleGraph {
         factory: &mut Factory<DefaultBackend>,
         res: &Resources,
     ) -> GraphBuilder<DefaultBackend, Resources> {
-        use amethyst::renderer::rendy::{
-            graph::present::PresentNode,
-            hal::command::{ClearDepthStencil, ClearValue},
-        };
+        use amethyst::renderer::rendy::graph::present::PresentNode;
 
         self.dirty = false;
+        let clear = *res.fetch::<ClearColor>();
         let window = <ReadExpect<'_, std::sync::Arc<Window>>>::fetch(res);
         let surface = factory.create_surface(&window);
-        // cache surface format to speed things up
-        let surface_format = *self
-            .surface_format
-            .get_or_insert_with(|| factory.get_surface_format(&surface));
+        // Shaders output linear colors, encoded for display by an sRGB swapchain.
+        let surface_format =
+            util::select_surface_format(factory, &surface, res.fetch::<SrgbSurface>().0);
         let dimensions = self.dimensions.as_ref().unwrap();
-        let window_kind =
-            image::Kind::D2(dimensions.width() as u32, dimensions.height() as u32, 1, 1);
+        let (width, height) = (dimensions.width() as u32, dimensions.height() as u32);
+        let window_kind = image::Kind::D2(width, height, 1, 1);
+        // Already lowered to a sample count the device supports by the renderer.
+        let samples = res.fetch::<SampleCount>().0;
+        let scene_kind = image::Kind::D2(width, height, 1, samples);
 
         let mut graph_builder = GraphBuilder::new();
-        let color = graph_builder.create_image(
-            window_kind,
-            1,
-            surface_format,
-            Some(ClearValue::Color([0.34, 0.36, 0.52, 1.0].into())),
-        );
+        let color =
+            graph_builder.create_image(window_kind, 1, surface_format, Some(clear.color_value()));
+        // A multisampled scene is resolved into the presented image before drawing the UI.
+        let scene_color = if samples > 1 {
+            graph_builder.create_image(scene_kind, 1, surface_format, Some(clear.color_value()))
+        } else {
+            color
+        };
 
+        let depth_format = *self.depth_format.get_or_insert_with(|| {
+            util::select_depth_format(factory, None, false)
+                .expect("Device has no usable depth format")
+        });
         let depth = graph_builder.create_image(
-            window_kind,
+            scene_kind,
             1,
-            Format::D32Sfloat,
-            Some(ClearValue::DepthStencil(ClearDepthStencil(1.0, 0))),
+            depth_format,
+            Some(clear.depth_value(*res.fetch::<DepthMode>())),
         );
 
-        let pass = graph_builder.add_node(
+        let scene = graph_builder.add_node(
             SubpassBuilder::new()
-                .with_group(DrawShadedDesc::new().builder())
-                .with_group(DrawUiDesc::new().builder())
-                .with_color(color)
+                .with_group(DrawShadedDesc::new().with_samples(samples).builder())
+                .with_color(scene_color)
                 .with_depth_stencil(depth)
                 .into_pass(),
         );
+        let scene = if samples > 1 {
+            graph_builder.add_node(
+                BlitDesc::new()
+                    .builder()
+                    .with_image(scene_color)
+                    .with_image(color)
+                    .with_dependency(scene),
+            )
+        } else {
+            scene
+        };
+        let ui = graph_builder.add_node(
+            SubpassBuilder::new()
+                .with_group(DrawUiDesc::new().builder())
+                .with_color(color)
+                .into_pass()
+                .with_dependency(scene),
+        );
 
-        let _present = graph_builder
-            .add_node(PresentNode::builder(factory, surface, color).with_dependency(pass));
+        // Toggling `Vsync` rebuilds the graph, selecting the present mode again.
+        let present_mode = util::select_present_mode(factory, &surface, *res.fetch::<Vsync>());
+        let _present = graph_builder.add_node(
+            PresentNode::builder(factory, surface, color)
+                .with_present_modes_priority(util::present_mode_priority(present_mode))
+                .with_dependency(ui),
+        );
 
         graph_builder
     }
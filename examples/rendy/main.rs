@@ -57,6 +57,7 @@ use amethyst_rendy::{
     system::{GraphCreator, RenderingSystem},
     transparent::Transparent,
     types::{Backend, DefaultBackend, Mesh, Texture},
+    util,
     visibility::{BoundingSphere, VisibilitySortingSystem},
 };
 use std::{path::Path, sync::Arc};
@@ -187,7 +188,7 @@ impl SimpleState for Example {
             },
         );
 
-        let (mesh, albedo) = {
+        let (mesh, albedo, metallic_roughness) = {
             let mesh = world.exec(|loader: AssetLoaderSystemData<'_, Mesh>| {
                 loader.load_from_data(
                     Shape::Sphere(16, 16)
@@ -202,8 +203,15 @@ impl SimpleState for Example {
                     self.progress.as_mut().unwrap(),
                 )
             });
+            // White, so the factors of each material set its metallic and roughness alone.
+            let metallic_roughness = world.exec(|loader: AssetLoaderSystemData<'_, Texture>| {
+                loader.load_from_data(
+                    load_from_linear_rgba(LinSrgba::new(1.0, 1.0, 1.0, 1.0)).into(),
+                    self.progress.as_mut().unwrap(),
+                )
+            });
 
-            (mesh, albedo)
+            (mesh, albedo, metallic_roughness)
         };
 
         println!("Create spheres");
@@ -222,27 +230,18 @@ impl SimpleState for Example {
                 let roughness = i as f32 / 9.0;
                 let metallic = j as f32 / 9.0;
 
-                let mtl = world.exec(
-                    |(mtl_loader, tex_loader): (
-                        AssetLoaderSystemData<'_, Material>,
-                        AssetLoaderSystemData<'_, Texture>,
-                    )| {
-                        let metallic_roughness = tex_loader.load_from_data(
-                            load_from_linear_rgba(LinSrgba::new(0.0, roughness, metallic, 0.0))
-                                .into(),
-                            self.progress.as_mut().unwrap(),
-                        );
-
-                        mtl_loader.load_from_data(
-                            Material {
-                                albedo: albedo.clone(),
-                                metallic_roughness,
-                                ..mat_defaults.clone()
-                            },
-                            self.progress.as_mut().unwrap(),
-                        )
-                    },
-                );
+                let mtl = world.exec(|mtl_loader: AssetLoaderSystemData<'_, Material>| {
+                    mtl_loader.load_from_data(
+                        Material {
+                            albedo: albedo.clone(),
+                            metallic_roughness: metallic_roughness.clone(),
+                            metallic_factor: metallic,
+                            roughness_factor: roughness,
+                            ..mat_defaults.clone()
+                        },
+                        self.progress.as_mut().unwrap(),
+                    )
+                });
                 mtls.push(mtl);
             }
         }
@@ -611,6 +610,7 @@ struct ExampleGraph {
     dimensions: Option<ScreenDimensions>,
     last_mode: RenderMode,
     surface_format: Option<Format>,
+    depth_format: Option<Format>,
     dirty: bool,
 }
 
@@ -663,10 +663,14 @@ impl<B: Backend> GraphCreator<B> for ExampleGraph {
             Some(ClearValue::Color([0.34, 0.36, 0.52, 1.0].into())),
         );
 
+        let depth_format = *self.depth_format.get_or_insert_with(|| {
+            util::select_depth_format(factory, None, false)
+                .expect("Device has no usable depth format")
+        });
         let depth = graph_builder.create_image(
             window_kind,
             1,
-            Format::D32Sfloat,
+            depth_format,
             Some(ClearValue::DepthStencil(ClearDepthStencil(1.0, 0))),
         );
 